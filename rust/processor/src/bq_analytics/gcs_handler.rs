@@ -18,7 +18,7 @@ pub async fn upload_parquet_to_gcs(
     buffer: Vec<u8>,
     table_name: &str,
     bucket_name: &str,
-    bucket_root: &Path,
+    object_name: &Path,
     processor_name: String,
 ) -> Result<(), ParquetProcessorError> {
     if buffer.is_empty() {
@@ -28,24 +28,6 @@ pub async fn upload_parquet_to_gcs(
         ));
     }
 
-    let now = chrono::Utc::now();
-    let start_of_month = now
-        .with_day(1)
-        .unwrap()
-        .with_hour(0)
-        .unwrap()
-        .with_minute(0)
-        .unwrap()
-        .with_second(0)
-        .unwrap()
-        .with_nanosecond(0)
-        .unwrap();
-    let highwater_s = start_of_month.timestamp_millis();
-    let highwater_ms = now.timestamp_millis();
-    let counter = 0; // THIS NEED TO BE REPLACED OR REIMPLEMENTED WITH AN ACTUAL LOGIC TO ENSURE FILE UNIQUENESS.
-    let object_name: PathBuf =
-        generate_parquet_file_path(bucket_root, table_name, highwater_s, highwater_ms, counter);
-
     let file_name = object_name.to_str().unwrap().to_owned();
     let upload_type: UploadType = UploadType::Simple(Media::new(file_name.clone()));
 
@@ -101,6 +83,26 @@ pub async fn upload_parquet_to_gcs(
     }
 }
 
+/// Where the next file of the table goes, by the month and the millisecond it's uploaded in
+pub fn next_parquet_file_path(bucket_root: &Path, table_name: &str) -> PathBuf {
+    let now = chrono::Utc::now();
+    let start_of_month = now
+        .with_day(1)
+        .unwrap()
+        .with_hour(0)
+        .unwrap()
+        .with_minute(0)
+        .unwrap()
+        .with_second(0)
+        .unwrap()
+        .with_nanosecond(0)
+        .unwrap();
+    let highwater_s = start_of_month.timestamp_millis();
+    let highwater_ms = now.timestamp_millis();
+    let counter = 0; // THIS NEED TO BE REPLACED OR REIMPLEMENTED WITH AN ACTUAL LOGIC TO ENSURE FILE UNIQUENESS.
+    generate_parquet_file_path(bucket_root, table_name, highwater_s, highwater_ms, counter)
+}
+
 fn generate_parquet_file_path(
     gcs_bucket_root: &Path,
     table: &str,
@@ -1,6 +1,9 @@
 use super::ParquetProcessingResult;
 use crate::{
-    bq_analytics::gcs_handler::upload_parquet_to_gcs,
+    bq_analytics::{
+        gcs_handler::{next_parquet_file_path, upload_parquet_to_gcs},
+        manifest::{recover_manifest, write_manifest, ParquetManifest},
+    },
     gap_detectors::ProcessingResult,
     utils::{
         counters::{PARQUET_HANDLER_CURRENT_BUFFER_SIZE, PARQUET_STRUCT_SIZE},
@@ -10,9 +13,14 @@ use crate::{
 use ahash::AHashMap;
 use allocative::Allocative;
 use anyhow::{Context, Result};
+use bytes::Bytes;
 use google_cloud_storage::client::Client as GCSClient;
 use parquet::{
-    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    column::writer::ColumnCloseResult,
+    file::{
+        properties::WriterProperties, reader::FileReader, serialized_reader::SerializedFileReader,
+        writer::SerializedFileWriter,
+    },
     record::RecordWriter,
     schema::types::Type,
 };
@@ -71,6 +79,12 @@ where
     pub max_buffer_size: usize,
     pub last_upload_time: Instant,
     pub processor_name: String,
+    // Top level columns to keep in the uploaded files, all of them if None
+    pub included_fields: Option<Vec<String>>,
+    // Files uploaded for the table, read on startup by `recover`
+    pub manifest: ParquetManifest,
+    // Versions up to this one are already in a complete file and aren't written again
+    pub last_complete_version: Option<i64>,
 }
 fn create_new_writer(schema: Arc<Type>) -> Result<SerializedFileWriter<Vec<u8>>> {
     let props = WriterProperties::builder()
@@ -81,6 +95,99 @@ fn create_new_writer(schema: Arc<Type>) -> Result<SerializedFileWriter<Vec<u8>>>
     SerializedFileWriter::new(Vec::new(), schema, props_arc).context("Failed to create new writer")
 }
 
+/// Makes sure every included field is a top level column of the parquet schema.
+pub fn validate_included_fields(schema: &Type, included_fields: &[String]) -> Result<()> {
+    let field_names: Vec<&str> = schema.get_fields().iter().map(|f| f.name()).collect();
+    let unknown_fields: Vec<&str> = included_fields
+        .iter()
+        .map(|field| field.as_str())
+        .filter(|field| !field_names.contains(field))
+        .collect();
+    if !unknown_fields.is_empty() {
+        anyhow::bail!(
+            "Unknown included fields {:?} for parquet schema {}",
+            unknown_fields,
+            schema.name()
+        );
+    }
+    if included_fields.is_empty() {
+        anyhow::bail!("No included fields for parquet schema {}", schema.name());
+    }
+    Ok(())
+}
+
+/// Makes sure every table in `included_fields` is one of the processor's `tables`.
+pub fn validate_included_tables(
+    included_fields: &AHashMap<String, Vec<String>>,
+    tables: &[&str],
+) -> Result<()> {
+    let mut unknown_tables: Vec<&str> = included_fields
+        .keys()
+        .map(|table| table.as_str())
+        .filter(|table| !tables.contains(table))
+        .collect();
+    if !unknown_tables.is_empty() {
+        unknown_tables.sort();
+        anyhow::bail!(
+            "Unknown included_fields tables {:?}, the processor writes {:?}",
+            unknown_tables,
+            tables
+        );
+    }
+    Ok(())
+}
+
+/// Rewrites a parquet file keeping only the included top level columns. Column chunks are copied
+/// over as is, so nothing gets decoded or compressed again.
+pub fn project_parquet_columns(
+    buffer: Vec<u8>,
+    schema: &Type,
+    included_fields: &[String],
+) -> Result<Vec<u8>> {
+    let bytes = Bytes::from(buffer);
+    let reader =
+        SerializedFileReader::new(bytes.clone()).context("Failed to read parquet buffer")?;
+
+    let projected_fields = schema
+        .get_fields()
+        .iter()
+        .filter(|field| included_fields.iter().any(|f| f == field.name()))
+        .cloned()
+        .collect();
+    let projected_schema = Type::group_type_builder(schema.name())
+        .with_fields(projected_fields)
+        .build()
+        .context("Failed to build projected schema")?;
+    let mut writer = create_new_writer(Arc::new(projected_schema))?;
+
+    for row_group in reader.metadata().row_groups() {
+        let mut row_group_writer = writer.next_row_group().context("Failed to get row group")?;
+        for column in row_group.columns() {
+            if !included_fields
+                .iter()
+                .any(|f| f == &column.column_path().parts()[0])
+            {
+                continue;
+            }
+            row_group_writer
+                .append_column(&bytes, ColumnCloseResult {
+                    bytes_written: column.compressed_size() as u64,
+                    rows_written: row_group.num_rows() as u64,
+                    metadata: column.clone(),
+                    bloom_filter: None,
+                    column_index: None,
+                    offset_index: None,
+                })
+                .context("Failed to copy column chunk")?;
+        }
+        row_group_writer
+            .close()
+            .context("Failed to close row group")?;
+    }
+
+    writer.into_inner().context("Failed to get inner buffer")
+}
+
 impl<ParquetType> ParquetHandler<ParquetType>
 where
     ParquetType: Allocative + GetTimeStamp + HasVersion + HasParquetSchema + 'static + NamedTable,
@@ -104,7 +211,11 @@ where
         upload_interval: Duration,
         max_buffer_size: usize,
         processor_name: String,
+        included_fields: Option<Vec<String>>,
     ) -> Result<Self> {
+        if let Some(included_fields) = &included_fields {
+            validate_included_fields(&schema, included_fields)?;
+        }
         // had to append unique id to avoid concurrent write issues
         let writer = create_new_writer(schema.clone())?;
 
@@ -121,9 +232,27 @@ where
             max_buffer_size,
             last_upload_time: Instant::now(),
             processor_name,
+            included_fields,
+            manifest: ParquetManifest::default(),
+            last_complete_version: None,
         })
     }
 
+    /// Reads the table's manifest and drops the files that weren't confirmed, before anything is
+    /// uploaded
+    pub async fn recover(&mut self, gcs_client: &GCSClient) -> Result<()> {
+        let manifest = recover_manifest(
+            gcs_client,
+            &self.bucket_name,
+            &PathBuf::from(&self.bucket_root),
+            ParquetType::TABLE_NAME,
+        )
+        .await?;
+        self.last_complete_version = manifest.last_complete_version();
+        self.manifest = manifest;
+        Ok(())
+    }
+
     pub async fn handle(
         &mut self,
         gcs_client: &GCSClient,
@@ -145,15 +274,19 @@ where
             self.last_upload_time = Instant::now();
         }
 
+        let mut already_uploaded = vec![];
         for parquet_struct in parquet_structs {
-            let size_of_struct = allocative::size_of_unique(&parquet_struct);
-            PARQUET_STRUCT_SIZE
-                .with_label_values(&[&processor_name, ParquetType::TABLE_NAME])
-                .set(size_of_struct as i64);
-            self.buffer_size_bytes += size_of_struct;
-            self.buffer.push(parquet_struct);
-
-            if self.buffer_size_bytes >= self.max_buffer_size {
+            if self
+                .last_complete_version
+                .is_some_and(|version| parquet_struct.version() <= version)
+            {
+                already_uploaded.push(parquet_struct);
+                continue;
+            }
+            // Only upload between versions, so that a version is never split across files
+            if self.buffer_size_bytes >= self.max_buffer_size
+                && self.buffer.last().map(|s| s.version()) != Some(parquet_struct.version())
+            {
                 debug!(
                     table_name = ParquetType::TABLE_NAME,
                     buffer_size = self.buffer_size_bytes,
@@ -166,6 +299,16 @@ where
                 }
                 self.last_upload_time = Instant::now();
             }
+
+            let size_of_struct = allocative::size_of_unique(&parquet_struct);
+            PARQUET_STRUCT_SIZE
+                .with_label_values(&[&processor_name, ParquetType::TABLE_NAME])
+                .set(size_of_struct as i64);
+            self.buffer_size_bytes += size_of_struct;
+            self.buffer.push(parquet_struct);
+        }
+        if !already_uploaded.is_empty() {
+            self.skip_uploaded_structs(&already_uploaded).await;
         }
 
         PARQUET_HANDLER_CURRENT_BUFFER_SIZE
@@ -175,6 +318,34 @@ where
         Ok(())
     }
 
+    /// Counts structs that a previous run already uploaded as processed, so the gap detector can
+    /// move past their versions
+    async fn skip_uploaded_structs(&self, structs: &[ParquetType]) {
+        let start_version = structs.first().unwrap().version();
+        let last = structs.last().unwrap();
+        let end_version = last.version();
+        debug!(
+            table_name = ParquetType::TABLE_NAME,
+            start_version = start_version,
+            end_version = end_version,
+            "Skipping versions that are already in uploaded files."
+        );
+        let parquet_processing_result = ParquetProcessingResult {
+            start_version,
+            end_version,
+            last_transaction_timestamp: Some(naive_datetime_to_timestamp(last.get_timestamp())),
+            txn_version_to_struct_count: None,
+            parquet_processed_structs: Some(build_parquet_processed_transactions(structs)),
+            table_name: ParquetType::TABLE_NAME.to_string(),
+        };
+        self.gap_detector_sender
+            .send(ProcessingResult::ParquetProcessingResult(
+                parquet_processing_result,
+            ))
+            .await
+            .expect("[Parser] Failed to send versions to gap detector");
+    }
+
     async fn upload_buffer(&mut self, gcs_client: &GCSClient) -> Result<()> {
         // This is to cover the case when interval duration has passed but buffer is empty
         if self.buffer.is_empty() {
@@ -230,19 +401,50 @@ where
         let upload_buffer = old_writer
             .into_inner()
             .context("Failed to get inner buffer")?;
+        let upload_buffer = match &self.included_fields {
+            Some(included_fields) => {
+                project_parquet_columns(upload_buffer, &self.schema, included_fields)?
+            },
+            None => upload_buffer,
+        };
 
         let bucket_root = PathBuf::from(&self.bucket_root);
+        let object_name = next_parquet_file_path(&bucket_root, ParquetType::TABLE_NAME);
+        let file_name = object_name.to_str().unwrap().to_owned();
+
+        // The file is recorded before it's uploaded, so that it's deleted on startup if the
+        // processor stops before it's confirmed
+        self.manifest
+            .add_pending(start_version, end_version, file_name.clone());
+        write_manifest(
+            gcs_client,
+            &self.bucket_name,
+            &bucket_root,
+            ParquetType::TABLE_NAME,
+            &self.manifest,
+        )
+        .await?;
 
         upload_parquet_to_gcs(
             gcs_client,
             upload_buffer,
             ParquetType::TABLE_NAME,
             &self.bucket_name,
-            &bucket_root,
+            &object_name,
             self.processor_name.clone(),
         )
         .await?;
 
+        self.manifest.complete(&file_name);
+        write_manifest(
+            gcs_client,
+            &self.bucket_name,
+            &bucket_root,
+            ParquetType::TABLE_NAME,
+            &self.manifest,
+        )
+        .await?;
+
         self.buffer_size_bytes = 0;
 
         let parquet_processing_result = ParquetProcessingResult {
@@ -285,3 +487,147 @@ fn build_parquet_processed_transactions<ParquetType: NamedTable + HasVersion>(
     }
     txn_version_to_struct_count_for_gap_detector
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocative_derive::Allocative;
+    use google_cloud_storage::client::ClientConfig as GcsClientConfig;
+    use parquet::record::RowAccessor;
+    use parquet_derive::ParquetRecordWriter;
+
+    #[derive(Allocative, Debug, Default, ParquetRecordWriter)]
+    struct TestRow {
+        txn_version: i64,
+        token_name: String,
+        token_properties: String,
+    }
+
+    impl NamedTable for TestRow {
+        const TABLE_NAME: &'static str = "test_rows";
+    }
+
+    impl HasVersion for TestRow {
+        fn version(&self) -> i64 {
+            self.txn_version
+        }
+    }
+
+    impl GetTimeStamp for TestRow {
+        fn get_timestamp(&self) -> chrono::NaiveDateTime {
+            chrono::NaiveDateTime::default()
+        }
+    }
+
+    fn row(txn_version: i64) -> TestRow {
+        TestRow {
+            txn_version,
+            ..TestRow::default()
+        }
+    }
+
+    fn write_rows(rows: &[TestRow]) -> Vec<u8> {
+        let mut writer = create_new_writer(TestRow::schema()).unwrap();
+        let mut row_group_writer = writer.next_row_group().unwrap();
+        rows.write_to_row_group(&mut row_group_writer).unwrap();
+        row_group_writer.close().unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_validate_included_fields() {
+        let schema = TestRow::schema();
+        assert!(validate_included_fields(&schema, &["txn_version".to_string()]).is_ok());
+        assert!(validate_included_fields(&schema, &["token_uri".to_string()]).is_err());
+        assert!(validate_included_fields(&schema, &[]).is_err());
+    }
+
+    #[test]
+    fn test_validate_included_tables() {
+        let included_fields = AHashMap::from([("events".to_string(), vec![])]);
+        assert!(validate_included_tables(&included_fields, &["events"]).is_ok());
+        assert!(validate_included_tables(&included_fields, &["transactions"]).is_err());
+        assert!(validate_included_tables(&AHashMap::new(), &["transactions"]).is_ok());
+    }
+
+    #[test]
+    fn test_project_parquet_columns() {
+        let rows = vec![
+            TestRow {
+                txn_version: 1,
+                token_name: "a".to_string(),
+                token_properties: "{}".to_string(),
+            },
+            TestRow {
+                txn_version: 2,
+                token_name: "b".to_string(),
+                token_properties: "{}".to_string(),
+            },
+        ];
+        let included_fields = vec!["token_name".to_string(), "txn_version".to_string()];
+        let projected =
+            project_parquet_columns(write_rows(&rows), &TestRow::schema(), &included_fields)
+                .unwrap();
+
+        let reader = SerializedFileReader::new(Bytes::from(projected)).unwrap();
+        let metadata = reader.metadata();
+        let field_names: Vec<&str> = metadata
+            .file_metadata()
+            .schema()
+            .get_fields()
+            .iter()
+            .map(|field| field.name())
+            .collect();
+        // Schema order is kept regardless of the order fields are listed in
+        assert_eq!(field_names, vec!["txn_version", "token_name"]);
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+
+        let versions: Vec<i64> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_long(0).unwrap())
+            .collect();
+        assert_eq!(versions, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_skips_uploaded_versions() {
+        let (gap_detector_sender, gap_detector_receiver) = kanal::bounded_async(1);
+        let mut handler = ParquetHandler::new(
+            "bucket".to_string(),
+            "root".to_string(),
+            gap_detector_sender,
+            TestRow::schema(),
+            Duration::from_secs(3600),
+            usize::MAX,
+            "test_processor".to_string(),
+            None,
+        )
+        .unwrap();
+        handler.last_complete_version = Some(10);
+        // Nothing gets uploaded, so the client is never called
+        let gcs_client = GCSClient::new(GcsClientConfig::default());
+
+        handler
+            .handle(&gcs_client, ParquetDataGeneric {
+                data: vec![row(9), row(10), row(10), row(11)],
+            })
+            .await
+            .unwrap();
+        let buffered: Vec<i64> = handler.buffer.iter().map(|row| row.version()).collect();
+        assert_eq!(buffered, vec![11]);
+
+        // The skipped versions are still reported to the gap detector as processed
+        let ProcessingResult::ParquetProcessingResult(result) =
+            gap_detector_receiver.recv().await.unwrap()
+        else {
+            panic!("Expected a parquet processing result");
+        };
+        assert_eq!((result.start_version, result.end_version), (9, 10));
+        assert_eq!(
+            result.parquet_processed_structs,
+            Some(AHashMap::from([(9, 1), (10, 2)]))
+        );
+        assert!(gap_detector_receiver.is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Records which version ranges of a table have been uploaded to which parquet files, in
+//! `<bucket_root>/<table>/_manifest.json` next to the files.
+//!
+//! A file is added as pending before it's uploaded and marked complete once the upload succeeds.
+//! On startup, pending files are deleted, since the processor crashed before confirming them, and
+//! their versions get written again. Versions up to the end of the last complete file are already
+//! in the bucket and are skipped when the processor replays them from its checkpoint, which can
+//! be behind the table's files since it waits for every table of the processor. This relies on
+//! versions reaching the handler in order, which is what files' version ranges assume too.
+//!
+//!
+//! Every complete file stays listed. Files completed one after the other are merged into one
+//! entry covering all of their versions, so the manifest grows by a file name per upload rather
+//! than by a whole entry.
+
+use crate::bq_analytics::ParquetProcessorError;
+use anyhow::Context;
+use google_cloud_storage::{
+    client::Client as GCSClient,
+    http::{
+        objects::{
+            delete::DeleteObjectRequest,
+            download::Range,
+            get::GetObjectRequest,
+            upload::{Media, UploadObjectRequest, UploadType},
+        },
+        Error as StorageError,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+pub const MANIFEST_FILE_NAME: &str = "_manifest.json";
+const TIMEOUT_SECONDS: u64 = 60;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestFileStatus {
+    Pending,
+    Complete,
+}
+
+/// Parquet files and the versions in them, both inclusive. A pending entry is a single file, a
+/// complete one the files uploaded in a row, in version order.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ManifestEntry {
+    pub start_version: i64,
+    pub end_version: i64,
+    pub file_names: Vec<String>,
+    pub status: ManifestFileStatus,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ParquetManifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+impl ParquetManifest {
+    /// Adds a file that's about to be uploaded
+    pub fn add_pending(&mut self, start_version: i64, end_version: i64, file_name: String) {
+        self.files.push(ManifestEntry {
+            start_version,
+            end_version,
+            file_names: vec![file_name],
+            status: ManifestFileStatus::Pending,
+        });
+    }
+
+    /// Marks an uploaded file as complete and merges it with the complete entries next to it.
+    /// Returns false if it isn't pending.
+    pub fn complete(&mut self, file_name: &str) -> bool {
+        let Some(entry) = self.files.iter_mut().find(|entry| {
+            entry.status == ManifestFileStatus::Pending && entry.file_names == [file_name]
+        }) else {
+            return false;
+        };
+        entry.status = ManifestFileStatus::Complete;
+        self.files.dedup_by(|next, previous| {
+            if previous.status != ManifestFileStatus::Complete
+                || next.status != ManifestFileStatus::Complete
+            {
+                return false;
+            }
+            previous.end_version = previous.end_version.max(next.end_version);
+            previous.file_names.append(&mut next.file_names);
+            true
+        });
+        true
+    }
+
+    /// Removes and returns the files that were never confirmed
+    pub fn take_pending(&mut self) -> Vec<ManifestEntry> {
+        let (pending, complete) = std::mem::take(&mut self.files)
+            .into_iter()
+            .partition(|entry| entry.status == ManifestFileStatus::Pending);
+        self.files = complete;
+        pending
+    }
+
+    /// The last version that's already in a complete file, if any
+    pub fn last_complete_version(&self) -> Option<i64> {
+        self.files
+            .iter()
+            .filter(|entry| entry.status == ManifestFileStatus::Complete)
+            .map(|entry| entry.end_version)
+            .max()
+    }
+}
+
+pub fn manifest_path(bucket_root: &Path, table_name: &str) -> String {
+    bucket_root
+        .join(table_name)
+        .join(MANIFEST_FILE_NAME)
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn is_not_found(err: &StorageError) -> bool {
+    matches!(err, StorageError::Response(response) if response.code == 404)
+}
+
+/// Reads the table's manifest, or an empty one if the table has none yet
+pub async fn read_manifest(
+    client: &GCSClient,
+    bucket_name: &str,
+    bucket_root: &Path,
+    table_name: &str,
+) -> Result<ParquetManifest, ParquetProcessorError> {
+    let request = GetObjectRequest {
+        bucket: bucket_name.to_string(),
+        object: manifest_path(bucket_root, table_name),
+        ..Default::default()
+    };
+    let result = timeout(
+        Duration::from_secs(TIMEOUT_SECONDS),
+        client.download_object(&request, &Range::default()),
+    )
+    .await
+    .map_err(ParquetProcessorError::TimeoutError)?;
+    match result {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse manifest {}", request.object))?),
+        Err(e) if is_not_found(&e) => Ok(ParquetManifest::default()),
+        Err(e) => Err(ParquetProcessorError::StorageError(e)),
+    }
+}
+
+pub async fn write_manifest(
+    client: &GCSClient,
+    bucket_name: &str,
+    bucket_root: &Path,
+    table_name: &str,
+    manifest: &ParquetManifest,
+) -> Result<(), ParquetProcessorError> {
+    let body = serde_json::to_vec(manifest).context("Failed to serialize manifest")?;
+    let mut media = Media::new(manifest_path(bucket_root, table_name));
+    media.content_type = "application/json".into();
+    let request = UploadObjectRequest {
+        bucket: bucket_name.to_string(),
+        ..Default::default()
+    };
+    timeout(
+        Duration::from_secs(TIMEOUT_SECONDS),
+        client.upload_object(&request, body, &UploadType::Simple(media)),
+    )
+    .await
+    .map_err(ParquetProcessorError::TimeoutError)?
+    .map_err(ParquetProcessorError::StorageError)?;
+    Ok(())
+}
+
+/// Reads the table's manifest on startup and deletes the files it never confirmed, so their
+/// versions can be written again. Returns the cleaned up manifest.
+pub async fn recover_manifest(
+    client: &GCSClient,
+    bucket_name: &str,
+    bucket_root: &Path,
+    table_name: &str,
+) -> Result<ParquetManifest, ParquetProcessorError> {
+    let mut manifest = read_manifest(client, bucket_name, bucket_root, table_name).await?;
+    let pending = manifest.take_pending();
+    for entry in &pending {
+        for file_name in &entry.file_names {
+            warn!(
+                table_name = table_name,
+                file_name = file_name,
+                start_version = entry.start_version,
+                end_version = entry.end_version,
+                "Deleting parquet file that wasn't confirmed before the processor stopped",
+            );
+            let request = DeleteObjectRequest {
+                bucket: bucket_name.to_string(),
+                object: file_name.clone(),
+                ..Default::default()
+            };
+            if let Err(e) = client.delete_object(&request).await {
+                // The upload may not have started
+                if !is_not_found(&e) {
+                    return Err(ParquetProcessorError::StorageError(e));
+                }
+            }
+        }
+    }
+    if !pending.is_empty() {
+        write_manifest(client, bucket_name, bucket_root, table_name, &manifest).await?;
+    }
+    info!(
+        table_name = table_name,
+        files = manifest.files.len(),
+        deleted_files = pending.len(),
+        last_complete_version = manifest.last_complete_version(),
+        "Read parquet manifest",
+    );
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let mut manifest = ParquetManifest::default();
+        assert_eq!(manifest.last_complete_version(), None);
+
+        manifest.add_pending(1, 10, "table/1/1_0.parquet".to_string());
+        assert!(manifest.complete("table/1/1_0.parquet"));
+        assert!(!manifest.complete("table/1/1_0.parquet"));
+        manifest.add_pending(11, 20, "table/1/2_0.parquet".to_string());
+        assert_eq!(manifest.last_complete_version(), Some(10));
+        // Completing a file merges it into the complete entry before it, the pending one after it
+        // stays on its own
+        manifest.add_pending(21, 30, "table/1/3_0.parquet".to_string());
+        assert!(manifest.complete("table/1/2_0.parquet"));
+        assert_eq!(manifest.files, vec![
+            ManifestEntry {
+                start_version: 1,
+                end_version: 20,
+                file_names: vec![
+                    "table/1/1_0.parquet".to_string(),
+                    "table/1/2_0.parquet".to_string(),
+                ],
+                status: ManifestFileStatus::Complete,
+            },
+            ManifestEntry {
+                start_version: 21,
+                end_version: 30,
+                file_names: vec!["table/1/3_0.parquet".to_string()],
+                status: ManifestFileStatus::Pending,
+            },
+        ]);
+        assert_eq!(manifest.last_complete_version(), Some(20));
+
+        // The pending file survives a round trip and is the only one taken out
+        let json = serde_json::to_string(&manifest).unwrap();
+        let mut manifest: ParquetManifest = serde_json::from_str(&json).unwrap();
+        let pending = manifest.take_pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].start_version, 21);
+        assert_eq!(pending[0].file_names, vec!["table/1/3_0.parquet"]);
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].status, ManifestFileStatus::Complete);
+        assert_eq!(manifest.files[0].file_names, vec![
+            "table/1/1_0.parquet",
+            "table/1/2_0.parquet"
+        ]);
+        assert_eq!(manifest.last_complete_version(), Some(20));
+    }
+
+    #[test]
+    fn test_manifest_complete_out_of_order() {
+        let mut manifest = ParquetManifest::default();
+        manifest.add_pending(1, 10, "table/1/1_0.parquet".to_string());
+        manifest.add_pending(11, 20, "table/1/2_0.parquet".to_string());
+        manifest.add_pending(21, 30, "table/1/3_0.parquet".to_string());
+        // A complete file isn't merged across a pending one
+        assert!(manifest.complete("table/1/1_0.parquet"));
+        assert!(manifest.complete("table/1/3_0.parquet"));
+        assert_eq!(manifest.files.len(), 3);
+        assert_eq!(manifest.last_complete_version(), Some(30));
+
+        // Completing the one in between merges all three
+        assert!(manifest.complete("table/1/2_0.parquet"));
+        assert_eq!(manifest.files, vec![ManifestEntry {
+            start_version: 1,
+            end_version: 30,
+            file_names: vec![
+                "table/1/1_0.parquet".to_string(),
+                "table/1/2_0.parquet".to_string(),
+                "table/1/3_0.parquet".to_string(),
+            ],
+            status: ManifestFileStatus::Complete,
+        }]);
+    }
+
+    #[test]
+    fn test_manifest_path() {
+        assert_eq!(
+            manifest_path(Path::new("root"), "token_datas_v2"),
+            "root/token_datas_v2/_manifest.json"
+        );
+    }
+}
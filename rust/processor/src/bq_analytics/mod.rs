@@ -1,5 +1,6 @@
 pub mod gcs_handler;
 pub mod generic_parquet_processor;
+pub mod manifest;
 
 use crate::{
     bq_analytics::generic_parquet_processor::{
@@ -95,6 +96,7 @@ pub fn create_parquet_handler_loop<ParquetType>(
     parquet_handler_response_channel_size: usize,
     max_buffer_size: usize,
     upload_interval: Duration,
+    included_fields: &AHashMap<String, Vec<String>>,
 ) -> AsyncSender<ParquetDataGeneric<ParquetType>>
 where
     ParquetType: GetTimeStamp
@@ -126,6 +128,7 @@ where
         upload_interval,
         max_buffer_size,
         processor_name.clone(),
+        included_fields.get(ParquetType::TABLE_NAME).cloned(),
     )
     .expect("Failed to create parquet manager");
 
@@ -135,6 +138,10 @@ where
             .await
             .expect("Failed to create GCS client config");
         let gcs_client = Arc::new(GCSClient::new(gcs_config));
+        parquet_handler
+            .recover(&gcs_client)
+            .await
+            .expect("Failed to read parquet manifest");
 
         loop {
             match parquet_receiver.recv().await {
@@ -2,18 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    gap_detectors::DEFAULT_GAP_DETECTION_BATCH_SIZE, processors::ProcessorConfig,
-    transaction_filter::TransactionFilter, worker::Worker,
+    db::common::models::token_models::token_utils::IdHashScheme,
+    gap_detectors::DEFAULT_GAP_DETECTION_BATCH_SIZE,
+    grpc_stream::MAX_RESPONSE_SIZE,
+    processors::ProcessorConfig,
+    transaction_filter::TransactionFilter,
+    utils::{
+        circuit_breaker::{CircuitBreakerConfig, FailedTransactionsConfig},
+        derive_from_activities::DerivedTable,
+        ownership_audit::OwnershipAuditConfig,
+        progress_file::DEFAULT_PROGRESS_FILE_INTERVAL_SECS,
+        prune_token_activities::TokenActivitiesPruningConfig,
+        statsd::{create_statsd_export_loop, StatsdConfig},
+    },
+    worker::Worker,
 };
 use ahash::AHashMap;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use server_framework::RunnableConfig;
-use std::{collections::HashSet, time::Duration};
+use std::{collections::HashSet, path::PathBuf, time::Duration};
 use url::Url;
 
 pub const QUERY_DEFAULT_RETRIES: u32 = 5;
 pub const QUERY_DEFAULT_RETRY_DELAY_MS: u64 = 500;
+pub const DEFAULT_DB_CONNECTION_RETRIES: u32 = 3;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -29,10 +42,21 @@ pub struct IndexerGrpcProcessorConfig {
     pub starting_version: Option<u64>,
     // Version to end indexing at
     pub ending_version: Option<u64>,
+    // Versions below this are never processed, whatever the starting version from config or db
+    pub min_version: Option<u64>,
     // Number of tasks waiting to pull transaction batches from the channel and process them
     pub number_concurrent_processing_tasks: Option<usize>,
     // Size of the pool for writes/reads to the DB. Limits maximum number of queries in flight
     pub db_pool_size: Option<u32>,
+    // Seconds to wait for a free connection before counting the pool as exhausted. Defaults to 30
+    pub db_pool_connection_timeout_in_secs: Option<u64>,
+    // Postgres statement_timeout for each pooled connection. Inserts that time out are retried with smaller chunks
+    pub db_statement_timeout_in_secs: Option<u64>,
+    // Chain the processor is meant to index. The processor refuses to start if the stream serves another chain
+    pub expected_chain_id: Option<u64>,
+    // Keep the tables in a schema named after expected_chain_id, e.g. mainnet, instead of the default one
+    #[serde(default)]
+    pub postgres_schema_per_chain: bool,
     // Maximum number of batches "missing" before we assume we have an issue with gaps and abort
     #[serde(default = "IndexerGrpcProcessorConfig::default_gap_detection_batch_size")]
     pub gap_detection_batch_size: u64,
@@ -42,6 +66,15 @@ pub struct IndexerGrpcProcessorConfig {
     // Number of protobuff transactions to send per chunk to the processor tasks
     #[serde(default = "IndexerGrpcProcessorConfig::default_pb_channel_txn_chunk_size")]
     pub pb_channel_txn_chunk_size: usize,
+    // Keep batches zstd compressed while they wait in the channel, trading CPU for memory
+    #[serde(default)]
+    pub compress_buffered_transactions: bool,
+    // Number of out of order transactions to hold back so batches are emitted in version order. 0 disables it
+    #[serde(default)]
+    pub transaction_reorder_buffer_size: usize,
+    // Merge stream responses until there are pb_channel_txn_chunk_size transactions or this many ms have passed
+    #[serde(default)]
+    pub pb_channel_flush_interval_ms: Option<u64>,
     // Number of rows to insert, per chunk, for each DB table. Default per table is ~32,768 (2**16/2)
     #[serde(default = "AHashMap::new")]
     pub per_table_chunk_sizes: AHashMap<String, usize>,
@@ -55,6 +88,51 @@ pub struct IndexerGrpcProcessorConfig {
     // String vector for deprecated tables to skip db writes
     #[serde(default)]
     pub deprecated_tables: HashSet<String>,
+    // Rebuild this current-state table from token_activities and exit instead of processing the stream
+    pub derive_from_token_activities: Option<DerivedTable>,
+    // Rewrite the v1 token tables' id hashes from this scheme to the current one and exit instead of processing the stream
+    pub rehash_token_ids_from: Option<IdHashScheme>,
+    // Rewrite processor_status every this many seconds while no batches come in
+    pub processor_status_heartbeat_secs: Option<u64>,
+    // Read REST API shaped json transactions, one per line and optionally gzipped, from this file
+    // instead of the GRPC stream. Needs expected_chain_id since the file doesn't carry one
+    pub json_transactions_file: Option<PathBuf>,
+    // Periodically write the latest processed version, lag and tps as json to this file
+    pub progress_file: Option<PathBuf>,
+    #[serde(default = "IndexerGrpcProcessorConfig::default_progress_file_interval_secs")]
+    pub progress_file_interval_secs: u64,
+    // Times to run a batch again after losing the db connection, on a new connection from the pool
+    #[serde(default = "IndexerGrpcProcessorConfig::default_db_connection_retries")]
+    pub db_connection_retries: u32,
+    // Periodically delete token_activities rows outside of the retention settings. Off by default
+    pub prune_token_activities: Option<TokenActivitiesPruningConfig>,
+    // More processors to run every batch through next to processor_config, e.g. to write the same
+    // data to postgres and parquet at once
+    #[serde(default)]
+    pub additional_processors: Vec<AdditionalProcessorConfig>,
+    // Retry a failing batch in place and halt or skip its failing transactions once it has failed
+    // too many times in a row. Without it the first failure stops the processor
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    // Record the transaction a failing batch stopped the processor on in failed_transactions
+    pub failed_transactions: Option<FailedTransactionsConfig>,
+    // Data service endpoints to fail over to, in order, once indexer_grpc_data_service_address keeps
+    // failing to reconnect
+    #[serde(default)]
+    pub indexer_grpc_fallback_addresses: Vec<Url>,
+    // Number of write statements running at once, across the processing tasks. Each chunk of an
+    // insert, each DB transaction and each COPY takes one. Defaults to no limit
+    pub max_concurrent_db_writes: Option<usize>,
+    // How v1 token and collection ids are hashed and their names stored. Defaults to sha256
+    pub token_id_hash_scheme: Option<IdHashScheme>,
+    // Inserts that hit db_statement_timeout_in_secs aren't split into chunks smaller than this. Defaults to 1
+    pub db_statement_timeout_min_chunk_size: Option<usize>,
+    // Convert timestamp columns to timestamptz at startup and keep the DB session in UTC
+    #[serde(default)]
+    pub store_timestamps_as_timestamptz: bool,
+    // Compare a sample of current_token_ownerships_v2 rows with what their token_activities_v2 add up to. Off by default
+    pub audit_current_token_ownerships: Option<OwnershipAuditConfig>,
+    // Also push the metrics served at /metrics to a StatsD or DogStatsD agent over UDP. Off by default
+    pub statsd: Option<StatsdConfig>,
 }
 
 impl IndexerGrpcProcessorConfig {
@@ -80,11 +158,27 @@ impl IndexerGrpcProcessorConfig {
     pub const fn default_grpc_response_item_timeout_in_secs() -> u64 {
         60
     }
+
+    pub const fn default_progress_file_interval_secs() -> u64 {
+        DEFAULT_PROGRESS_FILE_INTERVAL_SECS
+    }
+
+    pub const fn default_db_connection_retries() -> u32 {
+        DEFAULT_DB_CONNECTION_RETRIES
+    }
 }
 
 #[async_trait::async_trait]
 impl RunnableConfig for IndexerGrpcProcessorConfig {
     async fn run(&self) -> Result<()> {
+        if let Some(statsd) = self.statsd.clone() {
+            statsd.validate()?;
+            tokio::spawn(async move {
+                if let Err(e) = create_statsd_export_loop(statsd).await {
+                    tracing::error!(error = ?e, "[Parser] Failed to export metrics to StatsD");
+                }
+            });
+        }
         let mut worker = Worker::new(
             self.processor_config.clone(),
             self.postgres_connection_string.clone(),
@@ -93,16 +187,41 @@ impl RunnableConfig for IndexerGrpcProcessorConfig {
             self.auth_token.clone(),
             self.starting_version,
             self.ending_version,
+            self.min_version,
             self.number_concurrent_processing_tasks,
             self.db_pool_size,
+            self.db_statement_timeout_in_secs,
+            self.db_pool_connection_timeout_in_secs,
+            self.expected_chain_id,
+            self.postgres_schema_per_chain,
             self.gap_detection_batch_size,
             self.parquet_gap_detection_batch_size,
             self.pb_channel_txn_chunk_size,
+            self.compress_buffered_transactions,
+            self.transaction_reorder_buffer_size,
+            self.pb_channel_flush_interval_ms,
             self.per_table_chunk_sizes.clone(),
             self.enable_verbose_logging,
             self.transaction_filter.clone(),
             self.grpc_response_item_timeout_in_secs,
             self.deprecated_tables.clone(),
+            self.derive_from_token_activities,
+            self.rehash_token_ids_from,
+            self.processor_status_heartbeat_secs,
+            self.json_transactions_file.clone(),
+            self.progress_file.clone(),
+            self.progress_file_interval_secs,
+            self.db_connection_retries,
+            self.prune_token_activities.clone(),
+            self.additional_processors.clone(),
+            self.circuit_breaker.clone(),
+            self.failed_transactions.clone(),
+            self.indexer_grpc_fallback_addresses.clone(),
+            self.max_concurrent_db_writes,
+            self.token_id_hash_scheme,
+            self.db_statement_timeout_min_chunk_size,
+            self.store_timestamps_as_timestamptz,
+            self.audit_current_token_ownerships.clone(),
         )
         .await
         .context("Failed to build worker")?;
@@ -122,6 +241,26 @@ impl RunnableConfig for IndexerGrpcProcessorConfig {
     }
 }
 
+/// What happens to a batch when an additional processor fails on it
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnProcessorFailure {
+    /// Stop the processor like a failure of the main processor would, so the batch is processed
+    /// again after a restart
+    #[default]
+    Fail,
+    /// Log and count the error, and carry on with the next batch
+    Log,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdditionalProcessorConfig {
+    pub processor_config: ProcessorConfig,
+    #[serde(default)]
+    pub on_failure: OnProcessorFailure,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 #[serde(default)]
@@ -135,6 +274,10 @@ pub struct IndexerGrpcHttp2Config {
 
     /// Seconds before timeout for grpc connection.
     indexer_grpc_connection_timeout_secs: u64,
+
+    /// Largest stream response in bytes the client decodes. Defaults to 256MB.
+    /// Tonic ref: https://docs.rs/tonic/latest/tonic/client/struct.Grpc.html#method.max_decoding_message_size
+    indexer_grpc_max_decoding_message_size: usize,
 }
 
 impl IndexerGrpcHttp2Config {
@@ -149,6 +292,10 @@ impl IndexerGrpcHttp2Config {
     pub fn grpc_connection_timeout_secs(&self) -> Duration {
         Duration::from_secs(self.indexer_grpc_connection_timeout_secs)
     }
+
+    pub fn grpc_max_decoding_message_size(&self) -> usize {
+        self.indexer_grpc_max_decoding_message_size
+    }
 }
 
 impl Default for IndexerGrpcHttp2Config {
@@ -157,6 +304,7 @@ impl Default for IndexerGrpcHttp2Config {
             indexer_grpc_http2_ping_interval_in_secs: 30,
             indexer_grpc_http2_ping_timeout_in_secs: 10,
             indexer_grpc_connection_timeout_secs: 5,
+            indexer_grpc_max_decoding_message_size: MAX_RESPONSE_SIZE,
         }
     }
 }
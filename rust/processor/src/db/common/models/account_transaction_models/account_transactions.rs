@@ -58,16 +58,12 @@ impl AccountTransaction {
             panic!("Transaction info doesn't exist for version {}", txn_version)
         });
         let wscs = &transaction_info.changes;
-        let (events, signatures) = match txn_data {
+        let (events, signers) = match txn_data {
             TxnData::User(inner) => (
                 &inner.events,
-                UserTransaction::get_signatures(
-                    inner.request.as_ref().unwrap_or_else(|| {
-                        panic!("User request doesn't exist for version {}", txn_version)
-                    }),
-                    txn_version,
-                    transaction.block_height as i64,
-                ),
+                UserTransaction::get_signer_addresses(inner.request.as_ref().unwrap_or_else(
+                    || panic!("User request doesn't exist for version {}", txn_version),
+                )),
             ),
             TxnData::Genesis(inner) => (&inner.events, vec![]),
             TxnData::BlockMetadata(inner) => (&inner.events, vec![]),
@@ -77,10 +73,10 @@ impl AccountTransaction {
             },
         };
         let mut account_transactions = AHashMap::new();
-        for sig in &signatures {
-            account_transactions.insert((sig.signer.clone(), txn_version), Self {
+        for signer in signers {
+            account_transactions.insert((signer.clone(), txn_version), Self {
                 transaction_version: txn_version,
-                account_address: sig.signer.clone(),
+                account_address: signer,
             });
         }
         for event in events {
@@ -152,3 +148,52 @@ impl AccountTransaction {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{
+        signature::Signature as SignatureEnum, MultiAgentSignature, Signature as SignaturePb,
+        TransactionInfo, UserTransaction as UserTransactionPb, UserTransactionRequest,
+    };
+
+    fn multi_agent_transaction(sender: &str, secondary_signers: &[&str]) -> Transaction {
+        Transaction {
+            version: 100,
+            info: Some(TransactionInfo::default()),
+            txn_data: Some(TxnData::User(UserTransactionPb {
+                request: Some(UserTransactionRequest {
+                    sender: sender.to_string(),
+                    signature: Some(SignaturePb {
+                        signature: Some(SignatureEnum::MultiAgent(MultiAgentSignature {
+                            secondary_signer_addresses: secondary_signers
+                                .iter()
+                                .map(|address| address.to_string())
+                                .collect(),
+                            ..MultiAgentSignature::default()
+                        })),
+                        ..SignaturePb::default()
+                    }),
+                    ..UserTransactionRequest::default()
+                }),
+                ..UserTransactionPb::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_multi_agent_signers() {
+        let transaction = multi_agent_transaction("0x1", &["0x2", "0x03", "0x0002", "0x01"]);
+        let mut addresses: Vec<String> = AccountTransaction::from_transaction(&transaction)
+            .into_values()
+            .map(|account_transaction| account_transaction.account_address)
+            .collect();
+        addresses.sort();
+        assert_eq!(addresses, vec![
+            standardize_address("0x1"),
+            standardize_address("0x2"),
+            standardize_address("0x3"),
+        ]);
+    }
+}
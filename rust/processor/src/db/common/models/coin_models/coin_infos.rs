@@ -8,6 +8,7 @@
 use super::coin_utils::{CoinInfoType, CoinResource};
 use crate::schema::coin_infos;
 use aptos_protos::transaction::v1::WriteResource;
+use bigdecimal::BigDecimal;
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
 
@@ -25,10 +26,13 @@ pub struct CoinInfo {
     pub transaction_created_timestamp: chrono::NaiveDateTime,
     pub supply_aggregator_table_handle: Option<String>,
     pub supply_aggregator_table_key: Option<String>,
+    pub supply: Option<BigDecimal>,
+    pub last_transaction_version: i64,
 }
 
 impl CoinInfo {
-    /// We can find coin info from resources. If the coin info appears multiple times we will only keep the first transaction because it can't be modified.
+    /// We can find coin info from resources. Name, symbol and decimals can't be modified so we keep the
+    /// first transaction they appeared in, while supply and last_transaction_version track the latest write.
     pub fn from_write_resource(
         write_resource: &WriteResource,
         txn_version: i64,
@@ -57,9 +61,17 @@ impl CoinInfo {
                     transaction_created_timestamp: txn_timestamp,
                     supply_aggregator_table_handle,
                     supply_aggregator_table_key,
+                    supply: inner.get_integer_supply(),
+                    last_transaction_version: txn_version,
                 }))
             },
             _ => Ok(None),
         }
     }
+
+    /// Merges a later write of the same coin info into this one, keeping the creation details.
+    pub fn merge_later(&mut self, later: Self) {
+        self.supply = later.supply;
+        self.last_transaction_version = later.last_transaction_version;
+    }
 }
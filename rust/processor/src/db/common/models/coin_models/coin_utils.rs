@@ -18,7 +18,7 @@ use tracing::error;
 
 pub const COIN_ADDR: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
 const COIN_TYPE_HASH_LENGTH: usize = 5000;
-const COIN_TYPE_MAX: usize = 1000;
+pub const COIN_TYPE_MAX: usize = 1000;
 
 /**
  * This file defines deserialized coin types as defined in our 0x1 contracts.
@@ -40,6 +40,15 @@ impl CoinInfoResource {
         truncate_str(&self.symbol, 10)
     }
 
+    /// Supply is only tracked inline when it's an integer. Aggregator supply lives in a table item.
+    pub fn get_integer_supply(&self) -> Option<BigDecimal> {
+        self.supply
+            .vec
+            .first()
+            .and_then(|inner| inner.integer.vec.first())
+            .map(|integer| integer.value.clone())
+    }
+
     /// Getting the table item location of the supply aggregator
     pub fn get_aggregator_metadata(&self) -> Option<AggregatorResource> {
         if let Some(inner) = self.supply.vec.first() {
@@ -151,6 +160,26 @@ pub struct DepositCoinEvent {
     pub amount: BigDecimal,
 }
 
+/// Module event version of `WithdrawEvent`, emitted by transfers going through `0x1::aptos_account`
+/// once module events are enabled. It carries the coin type and account itself instead of relying
+/// on the CoinStore event handle.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WithdrawCoinEventV2 {
+    pub coin_type: String,
+    pub account: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub amount: BigDecimal,
+}
+
+/// Module event version of `DepositEvent`, see `WithdrawCoinEventV2`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DepositCoinEventV2 {
+    pub coin_type: String,
+    pub account: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub amount: BigDecimal,
+}
+
 pub struct CoinInfoType {
     coin_type: String,
     creator_address: String,
@@ -313,6 +342,8 @@ impl CoinResource {
 pub enum CoinEvent {
     WithdrawCoinEvent(WithdrawCoinEvent),
     DepositCoinEvent(DepositCoinEvent),
+    WithdrawCoinEventV2(WithdrawCoinEventV2),
+    DepositCoinEventV2(DepositCoinEventV2),
 }
 
 impl CoinEvent {
@@ -324,6 +355,12 @@ impl CoinEvent {
             "0x1::coin::DepositEvent" => {
                 serde_json::from_str(data).map(|inner| Some(CoinEvent::DepositCoinEvent(inner)))
             },
+            "0x1::coin::CoinWithdraw" => {
+                serde_json::from_str(data).map(|inner| Some(CoinEvent::WithdrawCoinEventV2(inner)))
+            },
+            "0x1::coin::CoinDeposit" => {
+                serde_json::from_str(data).map(|inner| Some(CoinEvent::DepositCoinEventV2(inner)))
+            },
             _ => Ok(None),
         }
         .context(format!(
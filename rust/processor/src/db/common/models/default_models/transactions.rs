@@ -37,6 +37,8 @@ pub struct Transaction {
     pub type_: String,
     pub payload: Option<serde_json::Value>,
     pub state_change_hash: String,
+    /// Root of the events accumulator from the transaction info. Together with `num_events` this
+    /// lets consumers check against a fullnode that they have every event for the version.
     pub event_root_hash: String,
     pub state_checkpoint_hash: Option<String>,
     pub gas_used: BigDecimal,
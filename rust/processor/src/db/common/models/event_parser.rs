@@ -0,0 +1,127 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{
+    counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
+    util::{get_entry_function_from_user_request, parse_timestamp},
+};
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+
+/// Transaction level data shared by every event in a user transaction.
+#[derive(Clone, Debug)]
+pub struct EventContext<'a> {
+    pub txn_version: i64,
+    pub txn_timestamp: chrono::NaiveDateTime,
+    pub epoch: i64,
+    pub event_index: i64,
+    pub entry_function_id_str: &'a Option<String>,
+}
+
+/// Builds a single model row out of an event. Implementations should be cheap to call on
+/// events they don't care about since every parser sees every event. Errors are for events the
+/// parser recognizes but can't handle, and fail the whole batch.
+pub trait EventParser: Send + Sync {
+    type Row: Send;
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>>;
+}
+
+/// Type erased view of a parser so that parsers with different row types can be driven together.
+pub trait EventHandler {
+    fn handle_event(&mut self, ctx: &EventContext, event: &Event) -> anyhow::Result<()>;
+}
+
+/// Pairs a parser with the rows it has produced so far.
+pub struct EventParserCollector<P: EventParser> {
+    parser: P,
+    rows: Vec<P::Row>,
+}
+
+impl<P: EventParser> EventParserCollector<P> {
+    pub fn new(parser: P) -> Self {
+        Self {
+            parser,
+            rows: vec![],
+        }
+    }
+
+    pub fn into_rows(self) -> Vec<P::Row> {
+        self.rows
+    }
+}
+
+impl<P: EventParser> EventHandler for EventParserCollector<P> {
+    fn handle_event(&mut self, ctx: &EventContext, event: &Event) -> anyhow::Result<()> {
+        if let Some(row) = self.parser.parse_event(ctx, event)? {
+            self.rows.push(row);
+        }
+        Ok(())
+    }
+}
+
+/// Walks the events of every user transaction exactly once and hands each event to all of
+/// the registered handlers, in registration order. Stops at the first error.
+pub fn parse_events(
+    transactions: &[Transaction],
+    handlers: &mut [&mut dyn EventHandler],
+) -> anyhow::Result<()> {
+    for transaction in transactions {
+        parse_transaction_events(transaction, handlers)?;
+    }
+    Ok(())
+}
+
+/// Same as `parse_events` but skips failed transactions, whose events had no effect
+pub fn parse_successful_events(
+    transactions: &[Transaction],
+    handlers: &mut [&mut dyn EventHandler],
+) -> anyhow::Result<()> {
+    for transaction in transactions {
+        if transaction.info.as_ref().is_some_and(|info| !info.success) {
+            continue;
+        }
+        parse_transaction_events(transaction, handlers)?;
+    }
+    Ok(())
+}
+
+/// Same as `parse_events` but for a single transaction.
+pub fn parse_transaction_events(
+    transaction: &Transaction,
+    handlers: &mut [&mut dyn EventHandler],
+) -> anyhow::Result<()> {
+    let txn_data = match transaction.txn_data.as_ref() {
+        Some(data) => data,
+        None => {
+            PROCESSOR_UNKNOWN_TYPE_COUNT
+                .with_label_values(&["EventParser"])
+                .inc();
+            tracing::warn!(
+                transaction_version = transaction.version,
+                "Transaction data doesn't exist",
+            );
+            return Ok(());
+        },
+    };
+    if let TxnData::User(user_txn) = txn_data {
+        let txn_version = transaction.version as i64;
+        let txn_timestamp = parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version);
+        let entry_function_id_str = user_txn
+            .request
+            .as_ref()
+            .and_then(get_entry_function_from_user_request);
+        for (index, event) in user_txn.events.iter().enumerate() {
+            let ctx = EventContext {
+                txn_version,
+                txn_timestamp,
+                epoch: transaction.epoch as i64,
+                event_index: index as i64,
+                entry_function_id_str: &entry_function_id_str,
+            };
+            for handler in handlers.iter_mut() {
+                handler.handle_event(&ctx, event)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::{
+    db::common::models::event_parser::{EventContext, EventParser},
+    schema::custom_events,
+    utils::util::standardize_address,
+};
+use aptos_protos::transaction::v1::Event as EventPB;
+use field_count::FieldCount;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// Turns a registered event into the json stored in `custom_events.data`. Returning None skips the event.
+pub type CustomEventParserFn = fn(&EventContext, &EventPB) -> Option<serde_json::Value>;
+
+/// Parsers registered by consumers of this crate, keyed by event type prefix (e.g. `0xcafe::market::`)
+static CUSTOM_EVENT_PARSERS: Lazy<RwLock<Vec<(String, CustomEventParserFn)>>> =
+    Lazy::new(|| RwLock::new(vec![]));
+
+/// Registers a parser for every event whose type starts with `type_str_prefix`. This should be
+/// called at startup, before the processor starts running. If several prefixes match an event
+/// the one registered first wins.
+pub fn register_custom_event_parser(type_str_prefix: &str, parser_fn: CustomEventParserFn) {
+    CUSTOM_EVENT_PARSERS
+        .write()
+        .unwrap()
+        .push((type_str_prefix.to_string(), parser_fn));
+}
+
+pub fn has_custom_event_parsers() -> bool {
+    !CUSTOM_EVENT_PARSERS.read().unwrap().is_empty()
+}
+
+fn get_custom_event_parser(type_str: &str) -> Option<CustomEventParserFn> {
+    CUSTOM_EVENT_PARSERS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(prefix, _)| type_str.starts_with(prefix.as_str()))
+        .map(|(_, parser_fn)| *parser_fn)
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = custom_events)]
+pub struct CustomEvent {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub account_address: String,
+    pub type_: String,
+    pub data: serde_json::Value,
+}
+
+/// Dispatches events to the parsers in the custom event registry
+pub struct CustomEventParser;
+
+impl EventParser for CustomEventParser {
+    type Row = CustomEvent;
+
+    fn parse_event(
+        &self,
+        ctx: &EventContext,
+        event: &EventPB,
+    ) -> anyhow::Result<Option<Self::Row>> {
+        let Some(data) = get_custom_event_parser(event.type_str.as_str())
+            .and_then(|parser_fn| parser_fn(ctx, event))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(CustomEvent {
+            transaction_version: ctx.txn_version,
+            event_index: ctx.event_index,
+            account_address: standardize_address(
+                event.key.as_ref().unwrap().account_address.as_str(),
+            ),
+            type_: event.type_str.clone(),
+            data,
+        }))
+    }
+}
@@ -13,6 +13,11 @@ use serde::{Deserialize, Serialize};
 
 // p99 currently is 303 so using 300 as a safe max length
 const EVENT_TYPE_MAX_LENGTH: usize = 300;
+/// Slots reserved per version in the global event index. The global index is
+/// `transaction_version * MAX_EVENTS_PER_TXN + event_index`, so it can't collide as long as no
+/// transaction emits more events than this, and stays within i64 up to version ~9.2e13. Gas limits
+/// keep real transactions far below it.
+pub const MAX_EVENTS_PER_TXN: i64 = 100_000;
 
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(transaction_version, event_index))]
@@ -27,6 +32,7 @@ pub struct Event {
     pub data: serde_json::Value,
     pub event_index: i64,
     pub indexed_type: String,
+    pub global_event_index: Option<i64>,
 }
 
 impl Event {
@@ -49,9 +55,26 @@ impl Event {
             data: serde_json::from_str(event.data.as_str()).unwrap(),
             event_index,
             indexed_type: truncate_str(t, EVENT_TYPE_MAX_LENGTH),
+            global_event_index: None,
         }
     }
 
+    /// Fills in `global_event_index`, an index that increases across transactions. Events past
+    /// MAX_EVENTS_PER_TXN in a transaction would collide with the next version so they're left
+    /// without one.
+    pub fn set_global_event_index(&mut self) {
+        self.global_event_index = if self.event_index < MAX_EVENTS_PER_TXN {
+            Some(self.transaction_version * MAX_EVENTS_PER_TXN + self.event_index)
+        } else {
+            tracing::warn!(
+                transaction_version = self.transaction_version,
+                event_index = self.event_index,
+                "Too many events in transaction to assign a global event index"
+            );
+            None
+        };
+    }
+
     pub fn from_events(
         events: &[EventPB],
         transaction_version: i64,
@@ -0,0 +1,45 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::{
+    schema::failed_transactions,
+    utils::database::{execute_with_better_error, ArcDbPool},
+};
+use diesel::{pg::upsert::excluded, ExpressionMethods};
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = failed_transactions)]
+/// A transaction a processor failed on, with the error it failed with. `raw_transaction` is the
+/// protobuf encoded transaction, if configured, so it can be inspected and reprocessed later.
+pub struct FailedTransaction {
+    pub processor: String,
+    pub transaction_version: i64,
+    pub error: String,
+    pub raw_transaction: Option<Vec<u8>>,
+}
+
+impl FailedTransaction {
+    /// Keeps the latest error if the transaction already failed before
+    pub async fn upsert(&self, pool: ArcDbPool) -> diesel::QueryResult<usize> {
+        execute_with_better_error(
+            pool,
+            diesel::insert_into(failed_transactions::table)
+                .values(self)
+                .on_conflict((
+                    failed_transactions::processor,
+                    failed_transactions::transaction_version,
+                ))
+                .do_update()
+                .set((
+                    failed_transactions::error.eq(excluded(failed_transactions::error)),
+                    failed_transactions::inserted_at.eq(excluded(failed_transactions::inserted_at)),
+                    failed_transactions::raw_transaction
+                        .eq(excluded(failed_transactions::raw_transaction)),
+                )),
+            None,
+        )
+        .await
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use super::v2_fungible_asset_balances::FungibleAssetBalance;
+use crate::schema::coin_balance_snapshots;
+use ahash::AHashMap;
+use bigdecimal::BigDecimal;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// An owner's primary store balance of a coin or fungible asset at the end of an epoch. Only the
+/// epochs the balance changed in have a row, so the balance at the end of epoch E is the one with
+/// the highest epoch at or below E.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(owner_address, asset_type, epoch))]
+#[diesel(table_name = coin_balance_snapshots)]
+pub struct CoinBalanceSnapshot {
+    pub owner_address: String,
+    pub asset_type: String,
+    pub epoch: i64,
+    pub amount: BigDecimal,
+    pub token_standard: String,
+    pub last_transaction_version: i64,
+    pub last_transaction_timestamp: chrono::NaiveDateTime,
+}
+
+impl CoinBalanceSnapshot {
+    /// The last of `balances` per owner, asset and epoch, sorted by primary key. `epochs` maps a
+    /// transaction version to its epoch, which block metadata transactions move forward.
+    pub fn from_balances(
+        balances: &[FungibleAssetBalance],
+        epochs: &AHashMap<i64, i64>,
+    ) -> Vec<Self> {
+        let mut snapshots: AHashMap<(String, String, i64), Self> = AHashMap::new();
+        for balance in balances.iter().filter(|balance| balance.is_primary) {
+            let epoch = *epochs
+                .get(&balance.transaction_version)
+                .expect("Balance from a transaction outside the batch");
+            let key = (
+                balance.owner_address.clone(),
+                balance.asset_type.clone(),
+                epoch,
+            );
+            match snapshots.get(&key) {
+                Some(existing)
+                    if existing.last_transaction_version > balance.transaction_version => {},
+                _ => {
+                    snapshots.insert(
+                        key,
+                        Self {
+                            owner_address: balance.owner_address.clone(),
+                            asset_type: balance.asset_type.clone(),
+                            epoch,
+                            amount: balance.amount.clone(),
+                            token_standard: balance.token_standard.clone(),
+                            last_transaction_version: balance.transaction_version,
+                            last_transaction_timestamp: balance.transaction_timestamp,
+                        },
+                    );
+                },
+            }
+        }
+        let mut snapshots = snapshots.into_values().collect::<Vec<_>>();
+        // Sorted to avoid deadlocks between concurrent batches upserting the same rows
+        snapshots.sort_by(|a, b| {
+            (&a.owner_address, &a.asset_type, a.epoch).cmp(&(
+                &b.owner_address,
+                &b.asset_type,
+                b.epoch,
+            ))
+        });
+        snapshots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balance(version: i64, owner: &str, amount: i64, is_primary: bool) -> FungibleAssetBalance {
+        FungibleAssetBalance {
+            transaction_version: version,
+            write_set_change_index: 0,
+            storage_id: format!("{}_store", owner),
+            owner_address: owner.to_string(),
+            asset_type: "0x1::aptos_coin::AptosCoin".to_string(),
+            is_primary,
+            is_frozen: false,
+            amount: BigDecimal::from(amount),
+            transaction_timestamp: chrono::NaiveDateTime::default(),
+            token_standard: "v1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_from_balances() {
+        let epochs = AHashMap::from([(1, 10), (2, 10), (3, 11)]);
+        let snapshots = CoinBalanceSnapshot::from_balances(
+            &[
+                balance(2, "0xa", 20, true),
+                balance(1, "0xa", 10, true),
+                balance(3, "0xa", 30, true),
+                balance(3, "0xb", 5, false),
+            ],
+            &epochs,
+        );
+
+        assert_eq!(snapshots.len(), 2);
+        // The end of epoch 10 is the balance as of version 2, whatever the parse order
+        assert_eq!(snapshots[0].epoch, 10);
+        assert_eq!(snapshots[0].amount, BigDecimal::from(20));
+        assert_eq!(snapshots[0].last_transaction_version, 2);
+        assert_eq!(snapshots[1].epoch, 11);
+        assert_eq!(snapshots[1].amount, BigDecimal::from(30));
+    }
+}
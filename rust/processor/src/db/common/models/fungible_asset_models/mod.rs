@@ -1,6 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod coin_balance_snapshots;
 pub mod v2_fungible_asset_activities;
 pub mod v2_fungible_asset_balances;
 pub mod v2_fungible_asset_utils;
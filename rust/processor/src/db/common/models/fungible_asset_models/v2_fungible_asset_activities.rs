@@ -10,7 +10,7 @@ use crate::{
     db::common::models::{
         coin_models::{
             coin_activities::CoinActivity,
-            coin_utils::{CoinEvent, CoinInfoType, EventGuidResource},
+            coin_utils::{CoinEvent, CoinInfoType, EventGuidResource, COIN_TYPE_MAX},
         },
         object_models::v2_object_utils::ObjectAggregatedDataMapping,
         token_v2_models::v2_token_utils::TokenStandard,
@@ -29,6 +29,8 @@ pub const GAS_FEE_EVENT: &str = "0x1::aptos_coin::GasFeeEvent";
 // We will never have a negative number on chain so this will avoid collision in postgres
 pub const BURN_GAS_EVENT_CREATION_NUM: i64 = -1;
 pub const BURN_GAS_EVENT_INDEX: i64 = -1;
+// The fee payer's side of the gas fee in sponsored transactions
+pub const FEE_PAYER_GAS_EVENT_INDEX: i64 = -2;
 
 pub type OwnerAddress = String;
 pub type CoinType = String;
@@ -56,6 +58,9 @@ pub struct FungibleAssetActivity {
     pub token_standard: String,
     pub transaction_timestamp: chrono::NaiveDateTime,
     pub storage_refund_amount: BigDecimal,
+    /// `amount` from the owner's perspective: negative for withdrawals and gas fees, positive for
+    /// deposits, so summing it per owner and asset gives the balance change. None for frozen events.
+    pub amount_signed: Option<BigDecimal>,
 }
 
 impl FungibleAssetActivity {
@@ -103,6 +108,13 @@ impl FungibleAssetActivity {
                 },
             };
 
+            let amount_signed = amount.as_ref().map(|amount| match fa_event {
+                FungibleAssetEvent::WithdrawEvent(_) | FungibleAssetEvent::WithdrawEventV2(_) => {
+                    -amount
+                },
+                _ => amount.clone(),
+            });
+
             // The event account address will also help us find fungible store which tells us where to find
             // the metadata
             let maybe_object_metadata = object_aggregated_data_mapping.get(&storage_id);
@@ -132,6 +144,7 @@ impl FungibleAssetActivity {
                 token_standard: TokenStandard::V2.to_string(),
                 transaction_timestamp: txn_timestamp,
                 storage_refund_amount: BigDecimal::zero(),
+                amount_signed,
             }));
         }
         Ok(None)
@@ -149,19 +162,38 @@ impl FungibleAssetActivity {
         if let Some(inner) =
             CoinEvent::from_event(event.type_str.as_str(), &event.data, txn_version)?
         {
-            let (owner_address, amount, coin_type_option) = match inner {
+            let (owner_address, amount, amount_signed, coin_type_option) = match inner {
                 CoinEvent::WithdrawCoinEvent(inner) => (
                     standardize_address(&event.key.as_ref().unwrap().account_address),
                     inner.amount.clone(),
+                    -&inner.amount,
                     None,
                 ),
                 CoinEvent::DepositCoinEvent(inner) => (
                     standardize_address(&event.key.as_ref().unwrap().account_address),
                     inner.amount.clone(),
+                    inner.amount.clone(),
                     None,
                 ),
+                // Module events carry the account and coin type, no CoinStore lookup needed
+                CoinEvent::WithdrawCoinEventV2(inner) => (
+                    standardize_address(&inner.account),
+                    inner.amount.clone(),
+                    -&inner.amount,
+                    Some(inner.coin_type.clone()),
+                ),
+                CoinEvent::DepositCoinEventV2(inner) => (
+                    standardize_address(&inner.account),
+                    inner.amount.clone(),
+                    inner.amount.clone(),
+                    Some(inner.coin_type.clone()),
+                ),
             };
             let coin_type = if let Some(coin_type) = coin_type_option {
+                // Same limit as the CoinStore mapping below
+                if coin_type.len() > COIN_TYPE_MAX {
+                    return Ok(None);
+                }
                 coin_type
             } else {
                 let event_key = event.key.as_ref().context("event must have a key")?;
@@ -203,6 +235,7 @@ impl FungibleAssetActivity {
                 token_standard: TokenStandard::V1.to_string(),
                 transaction_timestamp,
                 storage_refund_amount: BigDecimal::zero(),
+                amount_signed: Some(amount_signed),
             }))
         } else {
             Ok(None)
@@ -233,6 +266,12 @@ impl FungibleAssetActivity {
             v1_activity.coin_type.as_str(),
             v1_activity.owner_address.as_str(),
         );
+        // Gas comes out of the fee payer's balance, so a sponsored transaction leaves the sender's alone
+        let amount_signed = if v1_activity.gas_fee_payer_address.is_some() {
+            BigDecimal::zero()
+        } else {
+            -&v1_activity.amount
+        };
         Self {
             transaction_version,
             event_index: v1_activity.event_index.unwrap(),
@@ -250,6 +289,172 @@ impl FungibleAssetActivity {
             token_standard: TokenStandard::V1.to_string(),
             transaction_timestamp,
             storage_refund_amount: v1_activity.storage_refund_amount,
+            amount_signed: Some(amount_signed),
+        }
+    }
+
+    /// For sponsored transactions, the gas fee leaving the fee payer's balance. The sender's gas
+    /// row keeps showing the fee with a zero `amount_signed`, so summing `amount_signed` per owner
+    /// is right for both, while summing `amount` over gas rows would count the fee twice.
+    pub fn get_fee_payer_gas_event(gas_event: &Self) -> Option<Self> {
+        let fee_payer_address = gas_event.gas_fee_payer_address.as_ref()?;
+        let asset_type = gas_event.asset_type.as_ref()?;
+        Some(Self {
+            event_index: FEE_PAYER_GAS_EVENT_INDEX,
+            owner_address: Some(fee_payer_address.clone()),
+            storage_id: CoinInfoType::get_storage_id(asset_type, fee_payer_address),
+            amount_signed: gas_event.amount.as_ref().map(|amount| -amount),
+            ..gas_event.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::EventKey;
+
+    fn coin_event(type_str: &str, creation_number: u64) -> Event {
+        Event {
+            key: Some(EventKey {
+                creation_number,
+                account_address: "0x5".to_string(),
+            }),
+            type_str: type_str.to_string(),
+            data: r#"{"amount": "100"}"#.to_string(),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn test_withdraw_deposit_signed_amounts() {
+        let mut event_to_coin_type = EventToCoinType::new();
+        for creation_num in [0, 1] {
+            event_to_coin_type.insert(
+                EventGuidResource {
+                    addr: standardize_address("0x5"),
+                    creation_num,
+                },
+                "0x1::aptos_coin::AptosCoin".to_string(),
+            );
         }
+        let get_activity = |event: &Event, event_index: i64| {
+            FungibleAssetActivity::get_v1_from_event(
+                event,
+                1,
+                1,
+                chrono::NaiveDateTime::default(),
+                &None,
+                &event_to_coin_type,
+                event_index,
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        let withdraw = get_activity(&coin_event("0x1::coin::WithdrawEvent", 0), 0);
+        let deposit = get_activity(&coin_event("0x1::coin::DepositEvent", 1), 1);
+        assert_eq!(withdraw.amount, Some(BigDecimal::from(100)));
+        assert_eq!(withdraw.amount_signed, Some(BigDecimal::from(-100)));
+        assert_eq!(deposit.amount, Some(BigDecimal::from(100)));
+        assert_eq!(deposit.amount_signed, Some(BigDecimal::from(100)));
+        assert_eq!(
+            withdraw.amount_signed.unwrap() + deposit.amount_signed.unwrap(),
+            BigDecimal::zero()
+        );
+    }
+
+    #[test]
+    fn test_fee_payer_gas_event() {
+        let sender = standardize_address("0x5");
+        let fee_payer = standardize_address("0x6");
+        let gas_event = FungibleAssetActivity {
+            transaction_version: 1,
+            event_index: BURN_GAS_EVENT_INDEX,
+            owner_address: Some(sender.clone()),
+            storage_id: CoinInfoType::get_storage_id("0x1::aptos_coin::AptosCoin", &sender),
+            asset_type: Some("0x1::aptos_coin::AptosCoin".to_string()),
+            is_frozen: None,
+            amount: Some(BigDecimal::from(100)),
+            type_: GAS_FEE_EVENT.to_string(),
+            is_gas_fee: true,
+            gas_fee_payer_address: Some(fee_payer.clone()),
+            is_transaction_success: true,
+            entry_function_id_str: None,
+            block_height: 1,
+            token_standard: TokenStandard::V1.to_string(),
+            transaction_timestamp: chrono::NaiveDateTime::default(),
+            storage_refund_amount: BigDecimal::zero(),
+            amount_signed: Some(BigDecimal::zero()),
+        };
+
+        let fee_payer_gas_event =
+            FungibleAssetActivity::get_fee_payer_gas_event(&gas_event).unwrap();
+        assert_eq!(fee_payer_gas_event.event_index, FEE_PAYER_GAS_EVENT_INDEX);
+        assert_eq!(fee_payer_gas_event.owner_address, Some(fee_payer.clone()));
+        assert_eq!(
+            fee_payer_gas_event.storage_id,
+            CoinInfoType::get_storage_id("0x1::aptos_coin::AptosCoin", &fee_payer)
+        );
+        assert_eq!(
+            fee_payer_gas_event.amount_signed,
+            Some(BigDecimal::from(-100))
+        );
+
+        // Not sponsored, the sender's row already has the debit
+        let unsponsored_gas_event = FungibleAssetActivity {
+            gas_fee_payer_address: None,
+            ..gas_event
+        };
+        assert!(FungibleAssetActivity::get_fee_payer_gas_event(&unsponsored_gas_event).is_none());
+    }
+
+    /// `0x1::aptos_account::transfer_coins` with module events enabled: the events come with a
+    /// zero guid, so the owner and coin type have to come from the event data
+    #[test]
+    fn test_aptos_account_transfer_coins() {
+        let module_event = |type_str: &str, account: &str| Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: "0x0".to_string(),
+            }),
+            type_str: type_str.to_string(),
+            data: format!(
+                r#"{{"account": "{}", "amount": "100", "coin_type": "0x1::aptos_coin::AptosCoin"}}"#,
+                account
+            ),
+            ..Event::default()
+        };
+        let get_activity = |event: &Event, event_index: i64| {
+            FungibleAssetActivity::get_v1_from_event(
+                event,
+                1,
+                1,
+                chrono::NaiveDateTime::default(),
+                &Some("0x1::aptos_account::transfer_coins".to_string()),
+                &EventToCoinType::new(),
+                event_index,
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        let withdraw = get_activity(&module_event("0x1::coin::CoinWithdraw", "0x5"), 0);
+        let deposit = get_activity(&module_event("0x1::coin::CoinDeposit", "0x6"), 1);
+        assert_eq!(withdraw.owner_address, Some(standardize_address("0x5")));
+        assert_eq!(deposit.owner_address, Some(standardize_address("0x6")));
+        for activity in [&withdraw, &deposit] {
+            assert_eq!(
+                activity.asset_type.as_deref(),
+                Some("0x1::aptos_coin::AptosCoin")
+            );
+            assert_eq!(activity.token_standard, TokenStandard::V1.to_string());
+        }
+        assert_eq!(
+            withdraw.storage_id,
+            CoinInfoType::get_storage_id("0x1::aptos_coin::AptosCoin", &standardize_address("0x5"))
+        );
+        assert_eq!(withdraw.amount_signed, Some(BigDecimal::from(-100)));
+        assert_eq!(deposit.amount_signed, Some(BigDecimal::from(100)));
     }
 }
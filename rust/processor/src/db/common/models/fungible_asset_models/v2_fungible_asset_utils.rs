@@ -427,5 +427,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fungible_asset_deposit_withdraw_events() {
+        let deposit = FungibleAssetEvent::from_event(
+            "0x1::fungible_asset::Deposit",
+            r#"{"store": "0xa", "amount": "100"}"#,
+            1,
+        )
+        .unwrap();
+        if let Some(FungibleAssetEvent::DepositEventV2(inner)) = deposit {
+            assert_eq!(inner.store, "0xa");
+            assert_eq!(inner.amount, BigDecimal::from(100));
+        } else {
+            panic!("Wrong type")
+        }
+
+        let withdraw = FungibleAssetEvent::from_event(
+            "0x1::fungible_asset::WithdrawEvent",
+            r#"{"amount": "7"}"#,
+            1,
+        )
+        .unwrap();
+        if let Some(FungibleAssetEvent::WithdrawEvent(inner)) = withdraw {
+            assert_eq!(inner.amount, BigDecimal::from(7));
+        } else {
+            panic!("Wrong type")
+        }
+
+        assert!(
+            FungibleAssetEvent::from_event("0x1::coin::DepositEvent", "{}", 1)
+                .unwrap()
+                .is_none()
+        );
+    }
+
     // TODO: Add similar tests for ConcurrentFungibleAssetSupply.
 }
@@ -5,12 +5,15 @@ pub mod account_transaction_models;
 pub mod ans_models;
 pub mod coin_models;
 pub mod default_models;
+pub mod event_parser;
 pub mod events_models;
+pub mod failed_transactions;
 pub mod fungible_asset_models;
 pub mod ledger_info;
 pub mod object_models;
 pub mod processor_status;
 pub mod property_map;
+pub mod schema_check;
 pub mod stake_models;
 pub mod token_models;
 pub mod token_v2_models;
@@ -8,7 +8,7 @@
 use super::v2_object_utils::{CurrentObjectPK, ObjectAggregatedDataMapping};
 use crate::{
     db::common::models::default_models::move_resources::MoveResource,
-    schema::{current_objects, objects},
+    schema::{current_objects, current_objects_changes, objects},
     utils::{database::DbPoolConnection, util::standardize_address},
 };
 use ahash::AHashMap;
@@ -19,6 +19,9 @@ use diesel_async::RunQueryDsl;
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
 
+/// One row per write or delete of an `0x1::object::ObjectGroup`, i.e. every creation, transfer
+/// and deletion of an object. Transfers are read from the new `ObjectCore` owner rather than
+/// `0x1::object::TransferEvent`, which also covers transfers that don't emit the event.
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(transaction_version, write_set_change_index))]
 #[diesel(table_name = objects)]
@@ -34,6 +37,8 @@ pub struct Object {
     pub untransferrable: bool,
 }
 
+/// Latest state of each object. Upserts only replace a row with one at the same or a later
+/// last_transaction_version, so reprocessing older versions can't roll an owner back.
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(object_address))]
 #[diesel(table_name = current_objects)]
@@ -63,6 +68,31 @@ pub struct CurrentObjectQuery {
     pub untransferrable: bool,
 }
 
+/// A change to a row of current_objects, for consumers that build their own current state.
+/// `old_value` is None the first time an object is seen.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version, write_set_change_index))]
+#[diesel(table_name = current_objects_changes)]
+pub struct CurrentObjectChange {
+    pub transaction_version: i64,
+    pub write_set_change_index: i64,
+    pub object_address: String,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Identifiable, Queryable, Serialize)]
+#[diesel(primary_key(transaction_version, write_set_change_index))]
+#[diesel(table_name = current_objects_changes)]
+pub struct CurrentObjectChangeQuery {
+    pub transaction_version: i64,
+    pub write_set_change_index: i64,
+    pub object_address: String,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: serde_json::Value,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
 impl Object {
     pub fn from_write_resource(
         write_resource: &WriteResource,
@@ -227,3 +257,46 @@ impl CurrentObjectQuery {
             .await
     }
 }
+
+impl CurrentObjectChange {
+    pub fn new(
+        write_set_change_index: i64,
+        old_value: Option<&CurrentObject>,
+        new_value: &CurrentObject,
+    ) -> Self {
+        Self {
+            transaction_version: new_value.last_transaction_version,
+            write_set_change_index,
+            object_address: new_value.object_address.clone(),
+            old_value: old_value.map(|object| serde_json::to_value(object).unwrap()),
+            new_value: serde_json::to_value(new_value).unwrap(),
+        }
+    }
+}
+
+impl CurrentObjectChangeQuery {
+    /// Latest state of each object from the change rows written before `before_version`. Used in
+    /// place of current_objects when that table isn't being written.
+    pub async fn get_latest_by_addresses(
+        object_addresses: &[String],
+        before_version: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<AHashMap<CurrentObjectPK, CurrentObject>> {
+        let changes: Vec<Self> = current_objects_changes::table
+            .filter(current_objects_changes::object_address.eq_any(object_addresses))
+            .filter(current_objects_changes::transaction_version.lt(before_version))
+            .distinct_on(current_objects_changes::object_address)
+            .order((
+                current_objects_changes::object_address,
+                current_objects_changes::transaction_version.desc(),
+                current_objects_changes::write_set_change_index.desc(),
+            ))
+            .load(conn)
+            .await?;
+        Ok(changes
+            .into_iter()
+            .filter_map(|change| serde_json::from_value::<CurrentObject>(change.new_value).ok())
+            .map(|object| (object.object_address.clone(), object))
+            .collect())
+    }
+}
@@ -3,8 +3,11 @@
 
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::{schema::processor_status, utils::database::DbPoolConnection};
-use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use crate::{
+    schema::processor_status,
+    utils::database::{execute_with_better_error_conn, DbPoolConnection, MyDbConnection},
+};
+use diesel::{pg::upsert::excluded, ExpressionMethods, OptionalExtension, QueryDsl};
 use diesel_async::RunQueryDsl;
 
 #[derive(AsChangeset, Debug, Insertable)]
@@ -14,6 +17,31 @@ pub struct ProcessorStatus {
     pub processor: String,
     pub last_success_version: i64,
     pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub chain_id: Option<i64>,
+}
+
+impl ProcessorStatus {
+    /// Writes the status on `conn`, e.g. inside the transaction that wrote the batch. Never moves
+    /// last_success_version backwards.
+    pub async fn upsert(&self, conn: &mut MyDbConnection) -> diesel::QueryResult<usize> {
+        execute_with_better_error_conn(
+            conn,
+            diesel::insert_into(processor_status::table)
+                .values(self)
+                .on_conflict(processor_status::processor)
+                .do_update()
+                .set((
+                    processor_status::last_success_version
+                        .eq(excluded(processor_status::last_success_version)),
+                    processor_status::last_updated.eq(excluded(processor_status::last_updated)),
+                    processor_status::last_transaction_timestamp
+                        .eq(excluded(processor_status::last_transaction_timestamp)),
+                    processor_status::chain_id.eq(excluded(processor_status::chain_id)),
+                )),
+            Some(" WHERE processor_status.last_success_version <= EXCLUDED.last_success_version "),
+        )
+        .await
+    }
 }
 
 #[derive(AsChangeset, Debug, Queryable)]
@@ -24,6 +52,7 @@ pub struct ProcessorStatusQuery {
     pub last_success_version: i64,
     pub last_updated: chrono::NaiveDateTime,
     pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub chain_id: Option<i64>,
 }
 
 impl ProcessorStatusQuery {
@@ -37,4 +66,36 @@ impl ProcessorStatusQuery {
             .await
             .optional()
     }
+
+    /// Checks a batch against the committed watermark (last_success_version) and locks the
+    /// processor's row until the transaction ends, so batches committing their watermark go one at
+    /// a time. Fails if any of the batch was already committed, since a single task resuming from
+    /// the watermark never sees such a batch unless something else is writing the same tables.
+    /// Batches can start past the watermark + 1 because fully filtered batches are never written.
+    pub async fn check_watermark_for_update(
+        processor_name: &str,
+        start_version: i64,
+        end_version: i64,
+        conn: &mut MyDbConnection,
+    ) -> diesel::QueryResult<()> {
+        let watermark = processor_status::table
+            .filter(processor_status::processor.eq(processor_name))
+            .select(processor_status::last_success_version)
+            .for_update()
+            .first::<i64>(conn)
+            .await
+            .optional()?;
+        match watermark {
+            Some(watermark) if start_version <= watermark => {
+                Err(diesel::result::Error::QueryBuilderError(
+                    format!(
+                        "Batch [{}, {}] is at or below the committed version {}",
+                        start_version, end_version, watermark
+                    )
+                    .into(),
+                ))
+            },
+            _ => Ok(()),
+        }
+    }
 }
@@ -0,0 +1,135 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Startup check that a table in the DB still matches the model that gets inserted into it
+
+use crate::utils::database::DbPoolConnection;
+use diesel::{sql_query, sql_types::Text};
+use diesel_async::RunQueryDsl;
+
+/// A column as the model expects it. `data_type` is the name postgres reports in
+/// information_schema.columns, e.g. `bigint` or `character varying`.
+#[derive(Clone, Debug)]
+pub struct ExpectedColumn {
+    pub name: &'static str,
+    pub data_type: &'static str,
+    pub is_nullable: bool,
+}
+
+impl ExpectedColumn {
+    pub const fn new(name: &'static str, data_type: &'static str, is_nullable: bool) -> Self {
+        Self {
+            name,
+            data_type,
+            is_nullable,
+        }
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct ColumnInfo {
+    #[diesel(sql_type = Text)]
+    pub column_name: String,
+    #[diesel(sql_type = Text)]
+    pub data_type: String,
+    #[diesel(sql_type = Text)]
+    pub is_nullable: String,
+}
+
+/// Compares the columns of `table_name` against `expected` and errors with every mismatch found.
+/// Columns that only exist in the DB (e.g. inserted_at) are ignored since the DB fills them.
+pub async fn check_table_columns(
+    conn: &mut DbPoolConnection<'_>,
+    table_name: &str,
+    expected: &[ExpectedColumn],
+) -> anyhow::Result<()> {
+    let actual: Vec<ColumnInfo> = sql_query(
+        "SELECT column_name::TEXT, data_type::TEXT, is_nullable::TEXT FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = $1",
+    )
+    .bind::<Text, _>(table_name)
+    .get_results(conn)
+    .await?;
+    let mismatches = find_column_mismatches(&actual, expected);
+    anyhow::ensure!(
+        mismatches.is_empty(),
+        "[Parser] Table {} doesn't match its model, check for a missing migration: {}",
+        table_name,
+        mismatches.join("; ")
+    );
+    Ok(())
+}
+
+fn find_column_mismatches(actual: &[ColumnInfo], expected: &[ExpectedColumn]) -> Vec<String> {
+    expected
+        .iter()
+        .filter_map(|column| {
+            let Some(info) = actual.iter().find(|info| info.column_name == column.name) else {
+                return Some(format!("column {} is missing", column.name));
+            };
+            // store_timestamps_as_timestamptz converts the columns, the models still write UTC
+            let is_timestamptz = column.data_type == "timestamp without time zone"
+                && info.data_type == "timestamp with time zone";
+            if info.data_type != column.data_type && !is_timestamptz {
+                return Some(format!(
+                    "column {} is {} but the model expects {}",
+                    column.name, info.data_type, column.data_type
+                ));
+            }
+            let is_nullable = info.is_nullable == "YES";
+            if is_nullable != column.is_nullable {
+                return Some(format!(
+                    "column {} is {} but the model expects {}",
+                    column.name,
+                    if is_nullable { "nullable" } else { "NOT NULL" },
+                    if column.is_nullable {
+                        "nullable"
+                    } else {
+                        "NOT NULL"
+                    }
+                ));
+            }
+            None
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, data_type: &str, is_nullable: &str) -> ColumnInfo {
+        ColumnInfo {
+            column_name: name.to_string(),
+            data_type: data_type.to_string(),
+            is_nullable: is_nullable.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_column_mismatches() {
+        let actual = vec![
+            column("transaction_version", "bigint", "NO"),
+            column("name", "text", "YES"),
+            column("to_address", "character varying", "NO"),
+            column("inserted_at", "timestamp without time zone", "NO"),
+            column("transaction_timestamp", "timestamp with time zone", "NO"),
+        ];
+        let expected = [
+            ExpectedColumn::new("transaction_version", "bigint", false),
+            ExpectedColumn::new("name", "character varying", true),
+            ExpectedColumn::new("to_address", "character varying", true),
+            ExpectedColumn::new("event_index", "bigint", true),
+            // Converted by store_timestamps_as_timestamptz
+            ExpectedColumn::new(
+                "transaction_timestamp",
+                "timestamp without time zone",
+                false,
+            ),
+        ];
+        assert_eq!(find_column_mismatches(&actual, &expected), vec![
+            "column name is text but the model expects character varying",
+            "column to_address is NOT NULL but the model expects nullable",
+            "column event_index is missing",
+        ]);
+    }
+}
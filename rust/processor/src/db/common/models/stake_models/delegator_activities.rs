@@ -5,13 +5,14 @@
 
 use super::stake_utils::StakeEvent;
 use crate::{
+    db::common::models::event_parser::{EventContext, EventParser},
     schema::delegated_staking_activities,
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{standardize_address, u64_to_bigdecimal},
+        util::{parse_timestamp, standardize_address, u64_to_bigdecimal},
     },
 };
-use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
 use bigdecimal::BigDecimal;
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
@@ -26,12 +27,65 @@ pub struct DelegatedStakingActivity {
     pub pool_address: String,
     pub event_type: String,
     pub amount: BigDecimal,
+    pub epoch: Option<i64>,
+}
+
+/// Builds `DelegatedStakingActivity` rows out of `0x1::delegation_pool` stake events and
+/// `0x1::stake::DistributeRewardsEvent`
+pub struct DelegatedStakingActivityParser;
+
+impl EventParser for DelegatedStakingActivityParser {
+    type Row = DelegatedStakingActivity;
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>> {
+        let staking_event =
+            match StakeEvent::from_event(event.type_str.as_str(), &event.data, ctx.txn_version)? {
+                Some(staking_event) => staking_event,
+                None => return Ok(None),
+            };
+        let (delegator_address, pool_address, amount) = match staking_event {
+            StakeEvent::AddStakeEvent(inner) => (
+                standardize_address(&inner.delegator_address),
+                inner.pool_address,
+                inner.amount_added,
+            ),
+            StakeEvent::UnlockStakeEvent(inner) => (
+                standardize_address(&inner.delegator_address),
+                inner.pool_address,
+                inner.amount_unlocked,
+            ),
+            StakeEvent::WithdrawStakeEvent(inner) => (
+                standardize_address(&inner.delegator_address),
+                inner.pool_address,
+                inner.amount_withdrawn,
+            ),
+            StakeEvent::ReactivateStakeEvent(inner) => (
+                standardize_address(&inner.delegator_address),
+                inner.pool_address,
+                inner.amount_reactivated,
+            ),
+            StakeEvent::DistributeRewardsEvent(inner) => {
+                ("".to_string(), inner.pool_address, inner.rewards_amount)
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(DelegatedStakingActivity {
+            transaction_version: ctx.txn_version,
+            event_index: ctx.event_index,
+            delegator_address,
+            pool_address: standardize_address(&pool_address),
+            event_type: event.type_str.clone(),
+            amount: u64_to_bigdecimal(amount),
+            epoch: Some(ctx.epoch),
+        }))
+    }
 }
 
 impl DelegatedStakingActivity {
-    /// Pretty straightforward parsing from known delegated staking events
+    /// Pretty straightforward parsing from known delegated staking events. Rewards are
+    /// distributed in block metadata transactions, so unlike `parse_events` this also walks
+    /// non user transactions.
     pub fn from_transaction(transaction: &Transaction) -> anyhow::Result<Vec<Self>> {
-        let mut delegator_activities = vec![];
         let txn_data = match transaction.txn_data.as_ref() {
             Some(data) => data,
             None => {
@@ -42,7 +96,7 @@ impl DelegatedStakingActivity {
                     transaction_version = transaction.version,
                     "Transaction data doesn't exist",
                 );
-                return Ok(delegator_activities);
+                return Ok(vec![]);
             },
         };
 
@@ -51,59 +105,80 @@ impl DelegatedStakingActivity {
             TxnData::User(txn) => &txn.events,
             TxnData::BlockMetadata(txn) => &txn.events,
             TxnData::Validator(txn) => &txn.events,
-            _ => return Ok(delegator_activities),
+            _ => return Ok(vec![]),
         };
-        for (index, event) in events.iter().enumerate() {
-            let event_index = index as i64;
-            if let Some(staking_event) =
-                StakeEvent::from_event(event.type_str.as_str(), &event.data, txn_version)?
-            {
-                let activity = match staking_event {
-                    StakeEvent::AddStakeEvent(inner) => DelegatedStakingActivity {
-                        transaction_version: txn_version,
-                        event_index,
-                        delegator_address: standardize_address(&inner.delegator_address),
-                        pool_address: standardize_address(&inner.pool_address),
-                        event_type: event.type_str.clone(),
-                        amount: u64_to_bigdecimal(inner.amount_added),
-                    },
-                    StakeEvent::UnlockStakeEvent(inner) => DelegatedStakingActivity {
-                        transaction_version: txn_version,
-                        event_index,
-                        delegator_address: standardize_address(&inner.delegator_address),
-                        pool_address: standardize_address(&inner.pool_address),
-                        event_type: event.type_str.clone(),
-                        amount: u64_to_bigdecimal(inner.amount_unlocked),
-                    },
-                    StakeEvent::WithdrawStakeEvent(inner) => DelegatedStakingActivity {
-                        transaction_version: txn_version,
-                        event_index,
-                        delegator_address: standardize_address(&inner.delegator_address),
-                        pool_address: standardize_address(&inner.pool_address),
-                        event_type: event.type_str.clone(),
-                        amount: u64_to_bigdecimal(inner.amount_withdrawn),
-                    },
-                    StakeEvent::ReactivateStakeEvent(inner) => DelegatedStakingActivity {
-                        transaction_version: txn_version,
-                        event_index,
-                        delegator_address: standardize_address(&inner.delegator_address),
-                        pool_address: standardize_address(&inner.pool_address),
-                        event_type: event.type_str.clone(),
-                        amount: u64_to_bigdecimal(inner.amount_reactivated),
-                    },
-                    StakeEvent::DistributeRewardsEvent(inner) => DelegatedStakingActivity {
-                        transaction_version: txn_version,
-                        event_index,
-                        delegator_address: "".to_string(),
-                        pool_address: standardize_address(&inner.pool_address),
-                        event_type: event.type_str.clone(),
-                        amount: u64_to_bigdecimal(inner.rewards_amount),
-                    },
-                    _ => continue,
+        let txn_timestamp = parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version);
+        let entry_function_id_str = None;
+        events
+            .iter()
+            .enumerate()
+            .filter_map(|(index, event)| {
+                let ctx = EventContext {
+                    txn_version,
+                    txn_timestamp,
+                    epoch: transaction.epoch as i64,
+                    event_index: index as i64,
+                    entry_function_id_str: &entry_function_id_str,
                 };
-                delegator_activities.push(activity);
-            }
-        }
-        Ok(delegator_activities)
+                DelegatedStakingActivityParser
+                    .parse_event(&ctx, event)
+                    .transpose()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{BlockMetadataTransaction, UserTransaction},
+        util::timestamp::Timestamp,
+    };
+
+    #[test]
+    fn test_delegated_staking_activities_with_epoch() {
+        let add_stake = Event {
+            type_str: "0x1::delegation_pool::AddStakeEvent".to_string(),
+            data: r#"{"amount_added": "100", "delegator_address": "0xd", "pool_address": "0xa"}"#
+                .to_string(),
+            ..Event::default()
+        };
+        let rewards = Event {
+            type_str: "0x1::stake::DistributeRewardsEvent".to_string(),
+            data: r#"{"pool_address": "0xa", "rewards_amount": "5"}"#.to_string(),
+            ..Event::default()
+        };
+        let txn = |txn_data| Transaction {
+            version: 7,
+            epoch: 42,
+            timestamp: Some(Timestamp::default()),
+            txn_data: Some(txn_data),
+            ..Transaction::default()
+        };
+
+        let activities =
+            DelegatedStakingActivity::from_transaction(&txn(TxnData::User(UserTransaction {
+                events: vec![Event::default(), add_stake],
+                ..UserTransaction::default()
+            })))
+            .unwrap();
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].event_index, 1);
+        assert_eq!(activities[0].delegator_address, standardize_address("0xd"));
+        assert_eq!(activities[0].pool_address, standardize_address("0xa"));
+        assert_eq!(activities[0].amount, BigDecimal::from(100));
+        assert_eq!(activities[0].epoch, Some(42));
+
+        let activities = DelegatedStakingActivity::from_transaction(&txn(TxnData::BlockMetadata(
+            BlockMetadataTransaction {
+                events: vec![rewards],
+                ..BlockMetadataTransaction::default()
+            },
+        )))
+        .unwrap();
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].delegator_address, "");
+        assert_eq!(activities[0].amount, BigDecimal::from(5));
     }
 }
@@ -2,10 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub mod collection_datas;
+pub mod nft_marketplace_activities;
 pub mod nft_points;
 pub mod token_activities;
 pub mod token_claims;
+pub mod token_data_id_hashes;
 pub mod token_datas;
 pub mod token_ownerships;
+pub mod token_property_mutations;
 pub mod token_utils;
 pub mod tokens;
@@ -0,0 +1,222 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    db::common::models::event_parser::{EventContext, EventParser},
+    schema::nft_marketplace_activities,
+    utils::util::standardize_address,
+};
+use ahash::AHashSet;
+use aptos_protos::transaction::v1::Event;
+use bigdecimal::BigDecimal;
+use field_count::FieldCount;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{str::FromStr, sync::RwLock};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MarketplaceActivityType {
+    List,
+    Delist,
+    Buy,
+}
+
+impl MarketplaceActivityType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Delist => "delist",
+            Self::Buy => "buy",
+        }
+    }
+}
+
+/// Where the listing fields live in a marketplace event's data. Paths are dot separated field
+/// names, e.g. `token_metadata.token.inner`. Fields without a path are left empty.
+#[derive(Clone, Debug)]
+pub struct MarketplaceEventSchema {
+    pub activity_type: MarketplaceActivityType,
+    pub token_data_id_path: Option<&'static str>,
+    pub price_path: Option<&'static str>,
+    pub seller_path: Option<&'static str>,
+    pub buyer_path: Option<&'static str>,
+}
+
+/// Schemas keyed by `module::Struct`, so the same marketplace deployed at several addresses only
+/// needs to be registered once
+static MARKETPLACE_EVENT_SCHEMAS: Lazy<RwLock<Vec<(String, MarketplaceEventSchema)>>> =
+    Lazy::new(|| RwLock::new(vec![]));
+
+/// Registers the schema of a marketplace event by module and struct name (e.g.
+/// `coin_listing::PurchaseEvent`). Events are only picked up from the marketplace addresses in the
+/// processor config. This should be called at startup, before the processor starts running.
+pub fn register_marketplace_event_schema(event_name: &str, schema: MarketplaceEventSchema) {
+    MARKETPLACE_EVENT_SCHEMAS
+        .write()
+        .unwrap()
+        .push((event_name.to_string(), schema));
+}
+
+fn get_marketplace_event_schema(event_name: &str) -> Option<MarketplaceEventSchema> {
+    MARKETPLACE_EVENT_SCHEMAS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(name, _)| name == event_name)
+        .map(|(_, schema)| schema.clone())
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = nft_marketplace_activities)]
+pub struct NftMarketplaceActivity {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub marketplace_address: String,
+    pub event_type: String,
+    pub activity_type: String,
+    pub token_data_id: Option<String>,
+    pub price: Option<BigDecimal>,
+    pub seller: Option<String>,
+    pub buyer: Option<String>,
+    pub entry_function_id_str: Option<String>,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+/// Builds `NftMarketplaceActivity` rows out of registered marketplace events
+pub struct NftMarketplaceActivityParser {
+    marketplace_addresses: AHashSet<String>,
+}
+
+impl NftMarketplaceActivityParser {
+    pub fn new(marketplace_addresses: &[String]) -> Self {
+        Self {
+            marketplace_addresses: marketplace_addresses
+                .iter()
+                .map(|address| standardize_address(address))
+                .collect(),
+        }
+    }
+}
+
+impl EventParser for NftMarketplaceActivityParser {
+    type Row = NftMarketplaceActivity;
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>> {
+        Ok(self.parse_activity(ctx, event))
+    }
+}
+
+impl NftMarketplaceActivityParser {
+    /// Events that don't match a registered schema are skipped rather than failing the batch, as
+    /// marketplaces can change their events at any time
+    fn parse_activity(&self, ctx: &EventContext, event: &Event) -> Option<NftMarketplaceActivity> {
+        // Generic marketplace events (e.g. coin_listing::PurchaseEvent<0x1::aptos_coin::AptosCoin>)
+        // are matched on their outer type
+        let outer_type = event.type_str.split('<').next()?;
+        let (address, event_name) = outer_type.split_once("::")?;
+        let marketplace_address = standardize_address(address);
+        if !self.marketplace_addresses.contains(&marketplace_address) {
+            return None;
+        }
+        let schema = get_marketplace_event_schema(event_name)?;
+        let data: Value = serde_json::from_str(event.data.as_str()).ok()?;
+        let get_address = |path: Option<&str>| {
+            get_value_at_path(&data, path?)
+                .and_then(Value::as_str)
+                .map(standardize_address)
+        };
+        Some(NftMarketplaceActivity {
+            transaction_version: ctx.txn_version,
+            event_index: ctx.event_index,
+            marketplace_address,
+            event_type: event.type_str.clone(),
+            activity_type: schema.activity_type.as_str().to_string(),
+            token_data_id: get_address(schema.token_data_id_path),
+            price: schema
+                .price_path
+                .and_then(|path| get_value_at_path(&data, path))
+                .and_then(|value| match value {
+                    Value::String(s) => BigDecimal::from_str(s).ok(),
+                    Value::Number(n) => BigDecimal::from_str(&n.to_string()).ok(),
+                    _ => None,
+                }),
+            seller: get_address(schema.seller_path),
+            buyer: get_address(schema.buyer_path),
+            entry_function_id_str: ctx.entry_function_id_str.clone(),
+            transaction_timestamp: ctx.txn_timestamp,
+        })
+    }
+}
+
+fn get_value_at_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, field| value.get(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::EventKey;
+
+    fn event(type_str: &str, data: &str) -> Event {
+        Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: "0x1".to_string(),
+            }),
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_marketplace_event() {
+        register_marketplace_event_schema("test_listing::PurchaseEvent", MarketplaceEventSchema {
+            activity_type: MarketplaceActivityType::Buy,
+            token_data_id_path: Some("token_metadata.token.inner"),
+            price_path: Some("price"),
+            seller_path: Some("seller"),
+            buyer_path: Some("purchaser"),
+        });
+        let parser = NftMarketplaceActivityParser::new(&["0xcafe".to_string()]);
+        let entry_function_id_str = None;
+        let ctx = EventContext {
+            txn_version: 10,
+            txn_timestamp: chrono::NaiveDateTime::default(),
+            epoch: 0,
+            event_index: 2,
+            entry_function_id_str: &entry_function_id_str,
+        };
+        let data = r#"{"price": "1500", "seller": "0x5", "purchaser": "0x6", "token_metadata": {"token": {"inner": "0x7"}}}"#;
+
+        let row = parser
+            .parse_event(
+                &ctx,
+                &event(
+                    "0xcafe::test_listing::PurchaseEvent<0x1::aptos_coin::AptosCoin>",
+                    data,
+                ),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(row.activity_type, "buy");
+        assert_eq!(row.marketplace_address, standardize_address("0xcafe"));
+        assert_eq!(row.price, Some(BigDecimal::from(1500)));
+        assert_eq!(row.seller, Some(standardize_address("0x5")));
+        assert_eq!(row.buyer, Some(standardize_address("0x6")));
+        assert_eq!(row.token_data_id, Some(standardize_address("0x7")));
+
+        // Same event from an address that isn't configured
+        assert!(parser
+            .parse_event(&ctx, &event("0xbeef::test_listing::PurchaseEvent", data))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -5,19 +5,31 @@
 #![allow(clippy::extra_unused_lifetimes)]
 #![allow(clippy::unused_unit)]
 
-use super::token_utils::{TokenDataIdType, TokenEvent};
+use super::token_utils::{TokenDataIdType, TokenEvent, TokenEventKind};
 use crate::{
+    db::common::models::{
+        event_parser::{parse_transaction_events, EventContext, EventParser, EventParserCollector},
+        schema_check::{check_table_columns, ExpectedColumn},
+        token_v2_models::v2_token_utils::TokenStandard,
+    },
     schema::token_activities,
     utils::{
-        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{parse_timestamp, standardize_address},
+        avro::{confluent_framed, record_schema, AvroField, AvroType, AvroWriter},
+        database::DbPoolConnection,
+        util::{event_key_string, NumericOverflowPolicy, StandardizedAddress},
     },
 };
-use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+use ahash::{AHashMap, AHashSet};
+use aptos_protos::transaction::v1::{Event, Transaction};
 use bigdecimal::{BigDecimal, Zero};
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
 
+/// Keyed by the legacy event handle. Module events all have creation and sequence number 0, so two
+/// from the same account in one transaction would collide here, and inserts keep only the first.
+/// Written only by token_v2_processor with `token_activities` set and by
+/// token_activities_reprocessor; `token_activities_v2`, keyed by `(transaction_version,
+/// event_index)`, has every event.
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(
     transaction_version,
@@ -27,75 +39,370 @@ use serde::{Deserialize, Serialize};
 ))]
 #[diesel(table_name = token_activities)]
 pub struct TokenActivity {
+    pub transaction_version: i64,
+    pub event_account_address: StandardizedAddress,
+    pub event_creation_number: i64,
+    pub event_sequence_number: i64,
+    pub token_data_id_hash: String,
+    pub property_version: BigDecimal,
+    pub creator_address: Option<StandardizedAddress>,
+    pub collection_name: Option<String>,
+    pub name: Option<String>,
+    pub transfer_type: String,
+    pub from_address: Option<StandardizedAddress>,
+    pub to_address: Option<StandardizedAddress>,
+    pub token_amount: Option<BigDecimal>,
+    pub coin_type: Option<String>,
+    pub coin_amount: Option<BigDecimal>,
+    pub collection_data_id_hash: String,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub event_index: Option<i64>,
+    pub token_standard: String,
+    pub entry_function_id_str: Option<String>,
+    pub raw_event_data: Option<String>,
+    pub activity_type: Option<String>,
+    /// When an offer stops being claimable, None for other activities and offers that don't expire
+    pub expiration_timestamp: Option<chrono::NaiveDateTime>,
+    /// `TransferDirection` relative to `TokenActivityParser::watched_addresses`, None without them
+    pub direction: Option<String>,
+    /// event_account_address and event_creation_number as one key, formatted like the REST API
+    pub event_key_string: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Identifiable, Queryable, Serialize)]
+#[diesel(primary_key(
+    transaction_version,
+    event_account_address,
+    event_creation_number,
+    event_sequence_number
+))]
+#[diesel(table_name = token_activities)]
+pub struct TokenActivityQuery {
     pub transaction_version: i64,
     pub event_account_address: String,
     pub event_creation_number: i64,
     pub event_sequence_number: i64,
+    pub collection_data_id_hash: String,
     pub token_data_id_hash: String,
     pub property_version: BigDecimal,
-    pub creator_address: String,
-    pub collection_name: String,
-    pub name: String,
+    pub creator_address: Option<String>,
+    pub collection_name: Option<String>,
+    pub name: Option<String>,
     pub transfer_type: String,
     pub from_address: Option<String>,
     pub to_address: Option<String>,
-    pub token_amount: BigDecimal,
+    pub token_amount: Option<BigDecimal>,
     pub coin_type: Option<String>,
     pub coin_amount: Option<BigDecimal>,
-    pub collection_data_id_hash: String,
+    pub inserted_at: chrono::NaiveDateTime,
     pub transaction_timestamp: chrono::NaiveDateTime,
     pub event_index: Option<i64>,
+    pub token_standard: String,
+    pub entry_function_id_str: Option<String>,
+    pub raw_event_data: Option<String>,
+    pub activity_type: Option<String>,
+    pub expiration_timestamp: Option<chrono::NaiveDateTime>,
+    pub direction: Option<String>,
+    pub event_key_string: Option<String>,
 }
 
 /// A simplified TokenActivity (excluded common fields) to reduce code duplication
 struct TokenActivityHelper<'a> {
     pub token_data_id: &'a TokenDataIdType,
     pub property_version: BigDecimal,
-    pub from_address: Option<String>,
-    pub to_address: Option<String>,
-    pub token_amount: BigDecimal,
+    pub from_address: Option<StandardizedAddress>,
+    pub to_address: Option<StandardizedAddress>,
+    pub token_amount: Option<BigDecimal>,
     pub coin_type: Option<String>,
     pub coin_amount: Option<BigDecimal>,
 }
 
-impl TokenActivity {
-    pub fn from_transaction(transaction: &Transaction) -> Vec<Self> {
-        let mut token_activities = vec![];
-        let txn_data = match transaction.txn_data.as_ref() {
-            Some(data) => data,
-            None => {
-                PROCESSOR_UNKNOWN_TYPE_COUNT
-                    .with_label_values(&["TokenActivity"])
-                    .inc();
-                tracing::warn!(
-                    transaction_version = transaction.version,
-                    "Transaction data doesn't exist",
-                );
-                return token_activities;
-            },
+/// Short labels for the v1 token event types, for `TokenActivityParser::activity_type_labels`
+pub const DEFAULT_ACTIVITY_TYPE_LABELS: &[(&str, &str)] = &[
+    ("0x3::token::MintTokenEvent", "mint"),
+    ("0x3::token::BurnTokenEvent", "burn"),
+    ("0x3::token::MutateTokenPropertyMapEvent", "mutate"),
+    ("0x3::token::WithdrawEvent", "withdraw"),
+    ("0x3::token::DepositEvent", "deposit"),
+    ("0x3::token_transfers::TokenOfferEvent", "offer"),
+    ("0x3::token_transfers::TokenCancelOfferEvent", "cancel_offer"),
+    ("0x3::token_transfers::TokenClaimEvent", "claim"),
+];
+
+/// Which way an activity moves tokens relative to a set of watched addresses, going by the row's
+/// from_address and to_address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TransferDirection {
+    Incoming,
+    Outgoing,
+    Internal,
+}
+
+impl TransferDirection {
+    /// `Internal` when both sides are watched, `Incoming` or `Outgoing` when only the receiving or
+    /// only the sending side is, e.g. a deposit into or a withdrawal from a watched account. None
+    /// when neither is.
+    pub fn new(
+        from_address: Option<&StandardizedAddress>,
+        to_address: Option<&StandardizedAddress>,
+        watched_addresses: &AHashSet<StandardizedAddress>,
+    ) -> Option<Self> {
+        let is_watched = |address: Option<&StandardizedAddress>| {
+            address.is_some_and(|a| watched_addresses.contains(a))
         };
-        if let TxnData::User(user_txn) = txn_data {
-            for (index, event) in user_txn.events.iter().enumerate() {
-                let txn_version = transaction.version as i64;
-                if let Some(token_event) = TokenEvent::from_event(
-                    event.type_str.as_str(),
-                    event.data.as_str(),
-                    txn_version,
-                )
-                .unwrap()
-                {
-                    token_activities.push(Self::from_parsed_event(
-                        event.type_str.as_str(),
-                        event,
-                        &token_event,
-                        txn_version,
-                        parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version),
-                        index as i64,
-                    ))
-                }
+        match (is_watched(from_address), is_watched(to_address)) {
+            (true, true) => Some(TransferDirection::Internal),
+            (false, true) => Some(TransferDirection::Incoming),
+            (true, false) => Some(TransferDirection::Outgoing),
+            (false, false) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferDirection::Incoming => "incoming",
+            TransferDirection::Outgoing => "outgoing",
+            TransferDirection::Internal => "internal",
+        }
+    }
+}
+
+/// Builds `TokenActivity` rows out of v1 token events. Processors that write token_activities
+/// take it as config, where unset fields keep their defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenActivityParser {
+    /// Copy creator_address, collection_name and name into every row. Turn this off to only keep
+    /// token_data_id_hash when those are joined from token data instead.
+    pub denormalize_token_data_fields: bool,
+    /// Keep the event's data json as is in raw_event_data, so fields can be re-derived later
+    /// without refetching the transaction. Off by default since it roughly doubles row size.
+    pub store_raw_event_data: bool,
+    /// Label to store in activity_type for each full event type, e.g. `deposit` for
+    /// `0x3::token::DepositEvent`. transfer_type keeps the full type, types without a label leave
+    /// activity_type empty. Empty by default, see `DEFAULT_ACTIVITY_TYPE_LABELS`.
+    pub activity_type_labels: AHashMap<String, String>,
+    /// What to do with rows whose property_version, token_amount or coin_amount is negative or
+    /// above u64::MAX, which a malformed event can't otherwise be kept from poisoning aggregates
+    /// with. `Error` fails the batch with an error. Defaults to skipping the row.
+    pub on_numeric_overflow: NumericOverflowPolicy,
+    /// Only build rows for these kinds of events, e.g. just deposits and withdrawals. Events of
+    /// other kinds are dropped on their type, before their data is parsed. None, the default,
+    /// keeps every kind.
+    pub event_kinds: Option<AHashSet<TokenEventKind>>,
+    /// Addresses to fill in direction relative to, e.g. the wallets being tracked. Deserializing
+    /// standardizes them, so they match however they were written. None, the default, leaves
+    /// direction empty.
+    pub watched_addresses: Option<AHashSet<StandardizedAddress>>,
+}
+
+impl Default for TokenActivityParser {
+    fn default() -> Self {
+        Self {
+            denormalize_token_data_fields: true,
+            store_raw_event_data: false,
+            activity_type_labels: AHashMap::new(),
+            on_numeric_overflow: NumericOverflowPolicy::default(),
+            event_kinds: None,
+            watched_addresses: None,
+        }
+    }
+}
+
+impl TokenActivityParser {
+    fn direction(&self, activity: &TokenActivity) -> Option<String> {
+        let watched_addresses = self.watched_addresses.as_ref()?;
+        TransferDirection::new(
+            activity.from_address.as_ref(),
+            activity.to_address.as_ref(),
+            watched_addresses,
+        )
+        .map(|direction| direction.as_str().to_string())
+    }
+}
+
+impl EventParser for TokenActivityParser {
+    type Row = TokenActivity;
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>> {
+        if let Some(event_kinds) = &self.event_kinds {
+            if !event_kinds
+                .iter()
+                .any(|kind| kind.event_type() == event.type_str)
+            {
+                return Ok(None);
             }
         }
-        token_activities
+        let Some(token_event) = TokenEvent::from_event(
+            event.type_str.as_str(),
+            event.data.as_str(),
+            ctx.txn_version,
+        )?
+        else {
+            return Ok(None);
+        };
+        let activity = TokenActivity::from_parsed_event(
+            event.type_str.as_str(),
+            event,
+            &token_event,
+            ctx.txn_version,
+            ctx.txn_timestamp,
+            ctx.event_index,
+            ctx.entry_function_id_str,
+            self.denormalize_token_data_fields,
+            self.on_numeric_overflow,
+        )?;
+        Ok(activity.map(|activity| TokenActivity {
+            raw_event_data: self.store_raw_event_data.then(|| event.data.clone()),
+            activity_type: self.activity_type_labels.get(&activity.transfer_type).cloned(),
+            direction: self.direction(&activity),
+            ..activity
+        }))
+    }
+}
+
+impl TokenActivity {
+    /// Columns of token_activities as this struct writes them, in field order
+    pub const COLUMNS: &'static [ExpectedColumn] = &[
+        ExpectedColumn::new("transaction_version", "bigint", false),
+        ExpectedColumn::new("event_account_address", "character varying", false),
+        ExpectedColumn::new("event_creation_number", "bigint", false),
+        ExpectedColumn::new("event_sequence_number", "bigint", false),
+        ExpectedColumn::new("token_data_id_hash", "character varying", false),
+        ExpectedColumn::new("property_version", "numeric", false),
+        ExpectedColumn::new("creator_address", "character varying", true),
+        ExpectedColumn::new("collection_name", "character varying", true),
+        ExpectedColumn::new("name", "character varying", true),
+        ExpectedColumn::new("transfer_type", "character varying", false),
+        ExpectedColumn::new("from_address", "character varying", true),
+        ExpectedColumn::new("to_address", "character varying", true),
+        ExpectedColumn::new("token_amount", "numeric", true),
+        ExpectedColumn::new("coin_type", "text", true),
+        ExpectedColumn::new("coin_amount", "numeric", true),
+        ExpectedColumn::new("collection_data_id_hash", "character varying", false),
+        ExpectedColumn::new(
+            "transaction_timestamp",
+            "timestamp without time zone",
+            false,
+        ),
+        ExpectedColumn::new("event_index", "bigint", true),
+        ExpectedColumn::new("token_standard", "character varying", false),
+        ExpectedColumn::new("entry_function_id_str", "character varying", true),
+        ExpectedColumn::new("raw_event_data", "text", true),
+        ExpectedColumn::new("activity_type", "character varying", true),
+        ExpectedColumn::new("expiration_timestamp", "timestamp without time zone", true),
+        ExpectedColumn::new("direction", "character varying", true),
+        ExpectedColumn::new("event_key_string", "character varying", true),
+    ];
+
+    /// Fails if the token_activities table has drifted from this struct
+    pub async fn check_schema(conn: &mut DbPoolConnection<'_>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            Self::COLUMNS.len() == Self::field_count(),
+            "[Parser] TokenActivity has {} fields but TokenActivity::COLUMNS lists {}",
+            Self::field_count(),
+            Self::COLUMNS.len()
+        );
+        check_table_columns(conn, "token_activities", Self::COLUMNS).await
+    }
+
+    /// Avro fields of a token activity, in field order. Amounts and property versions are whole
+    /// numbers, hence a scale of 0.
+    pub const AVRO_FIELDS: &'static [AvroField] = &[
+        AvroField::new("transaction_version", AvroType::Long, false),
+        AvroField::new("event_account_address", AvroType::String, false),
+        AvroField::new("event_creation_number", AvroType::Long, false),
+        AvroField::new("event_sequence_number", AvroType::Long, false),
+        AvroField::new("token_data_id_hash", AvroType::String, false),
+        AvroField::new("property_version", AvroType::Decimal { scale: 0 }, false),
+        AvroField::new("creator_address", AvroType::String, true),
+        AvroField::new("collection_name", AvroType::String, true),
+        AvroField::new("name", AvroType::String, true),
+        AvroField::new("transfer_type", AvroType::String, false),
+        AvroField::new("from_address", AvroType::String, true),
+        AvroField::new("to_address", AvroType::String, true),
+        AvroField::new("token_amount", AvroType::Decimal { scale: 0 }, true),
+        AvroField::new("coin_type", AvroType::String, true),
+        AvroField::new("coin_amount", AvroType::Decimal { scale: 0 }, true),
+        AvroField::new("collection_data_id_hash", AvroType::String, false),
+        AvroField::new("transaction_timestamp", AvroType::TimestampMicros, false),
+        AvroField::new("event_index", AvroType::Long, true),
+        AvroField::new("token_standard", AvroType::String, false),
+        AvroField::new("entry_function_id_str", AvroType::String, true),
+        AvroField::new("raw_event_data", AvroType::String, true),
+        AvroField::new("activity_type", AvroType::String, true),
+        AvroField::new("expiration_timestamp", AvroType::TimestampMicros, true),
+        AvroField::new("direction", AvroType::String, true),
+        AvroField::new("event_key_string", AvroType::String, true),
+    ];
+
+    /// The Avro schema to register with the schema registry, e.g. under `<topic>-value`
+    pub fn avro_schema() -> String {
+        record_schema("TokenActivity", "aptos.indexer", Self::AVRO_FIELDS).to_string()
+    }
+
+    /// Avro binary encoding of this activity, following `AVRO_FIELDS`
+    pub fn to_avro(&self) -> Vec<u8> {
+        let mut writer = AvroWriter::default();
+        writer.long(self.transaction_version);
+        writer.string(self.event_account_address.as_str());
+        writer.long(self.event_creation_number);
+        writer.long(self.event_sequence_number);
+        writer.string(&self.token_data_id_hash);
+        writer.decimal(&self.property_version, 0);
+        writer.optional(
+            self.creator_address
+                .as_ref()
+                .map(StandardizedAddress::as_str),
+            AvroWriter::string,
+        );
+        writer.optional(self.collection_name.as_deref(), AvroWriter::string);
+        writer.optional(self.name.as_deref(), AvroWriter::string);
+        writer.string(&self.transfer_type);
+        writer.optional(
+            self.from_address.as_ref().map(StandardizedAddress::as_str),
+            AvroWriter::string,
+        );
+        writer.optional(
+            self.to_address.as_ref().map(StandardizedAddress::as_str),
+            AvroWriter::string,
+        );
+        writer.optional(self.token_amount.as_ref(), |w, v| w.decimal(v, 0));
+        writer.optional(self.coin_type.as_deref(), AvroWriter::string);
+        writer.optional(self.coin_amount.as_ref(), |w, v| w.decimal(v, 0));
+        writer.string(&self.collection_data_id_hash);
+        writer.timestamp_micros(&self.transaction_timestamp);
+        writer.optional(self.event_index, AvroWriter::long);
+        writer.string(&self.token_standard);
+        writer.optional(self.entry_function_id_str.as_deref(), AvroWriter::string);
+        writer.optional(self.raw_event_data.as_deref(), AvroWriter::string);
+        writer.optional(self.activity_type.as_deref(), AvroWriter::string);
+        writer.optional(
+            self.expiration_timestamp.as_ref(),
+            AvroWriter::timestamp_micros,
+        );
+        writer.optional(self.direction.as_deref(), AvroWriter::string);
+        writer.optional(Some(self.event_key_string.as_str()), AvroWriter::string);
+        writer.into_bytes()
+    }
+
+    /// `to_avro` in the Confluent wire format, for a schema registered under `schema_id`
+    pub fn to_confluent_avro(&self, schema_id: u32) -> Vec<u8> {
+        confluent_framed(schema_id, &self.to_avro())
+    }
+
+    pub fn from_transaction(transaction: &Transaction) -> anyhow::Result<Vec<Self>> {
+        Self::from_transaction_with_parser(transaction, TokenActivityParser::default())
+    }
+
+    /// Same as `from_transaction` with the parser's settings, e.g. to only keep some `event_kinds`
+    pub fn from_transaction_with_parser(
+        transaction: &Transaction,
+        parser: TokenActivityParser,
+    ) -> anyhow::Result<Vec<Self>> {
+        let mut collector = EventParserCollector::new(parser);
+        parse_transaction_events(transaction, &mut [&mut collector])?;
+        Ok(collector.into_rows())
     }
 
     pub fn from_parsed_event(
@@ -105,10 +412,17 @@ impl TokenActivity {
         txn_version: i64,
         txn_timestamp: chrono::NaiveDateTime,
         event_index: i64,
-    ) -> Self {
+        entry_function_id_str: &Option<String>,
+        denormalize_token_data_fields: bool,
+        on_numeric_overflow: NumericOverflowPolicy,
+    ) -> anyhow::Result<Option<Self>> {
         let event_account_address =
-            standardize_address(event.key.as_ref().unwrap().account_address.as_str());
+            StandardizedAddress::new(event.key.as_ref().unwrap().account_address.as_str());
         let event_creation_number = event.key.as_ref().unwrap().creation_number as i64;
+        let event_key_string = event_key_string(
+            &event_account_address,
+            event.key.as_ref().unwrap().creation_number,
+        );
         let event_sequence_number = event.sequence_number as i64;
         let token_activity_helper = match token_event {
             TokenEvent::MintTokenEvent(inner) => TokenActivityHelper {
@@ -116,7 +430,7 @@ impl TokenActivity {
                 property_version: BigDecimal::zero(),
                 from_address: Some(event_account_address.clone()),
                 to_address: None,
-                token_amount: inner.amount.clone(),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -125,7 +439,7 @@ impl TokenActivity {
                 property_version: inner.id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
                 to_address: None,
-                token_amount: inner.amount.clone(),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -134,7 +448,8 @@ impl TokenActivity {
                 property_version: inner.new_id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
                 to_address: None,
-                token_amount: BigDecimal::zero(),
+                // Property mutations don't move any tokens
+                token_amount: None,
                 coin_type: None,
                 coin_amount: None,
             },
@@ -143,7 +458,7 @@ impl TokenActivity {
                 property_version: inner.id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
                 to_address: None,
-                token_amount: inner.amount.clone(),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -151,8 +466,8 @@ impl TokenActivity {
                 token_data_id: &inner.id.token_data_id,
                 property_version: inner.id.property_version.clone(),
                 from_address: None,
-                to_address: Some(standardize_address(&event_account_address)),
-                token_amount: inner.amount.clone(),
+                to_address: Some(event_account_address.clone()),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -160,8 +475,8 @@ impl TokenActivity {
                 token_data_id: &inner.token_id.token_data_id,
                 property_version: inner.token_id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
-                to_address: Some(inner.get_to_address()),
-                token_amount: inner.amount.clone(),
+                to_address: Some(inner.get_standardized_to_address()),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -169,8 +484,8 @@ impl TokenActivity {
                 token_data_id: &inner.token_id.token_data_id,
                 property_version: inner.token_id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
-                to_address: Some(inner.get_to_address()),
-                token_amount: inner.amount.clone(),
+                to_address: Some(inner.get_standardized_to_address()),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
@@ -178,32 +493,400 @@ impl TokenActivity {
                 token_data_id: &inner.token_id.token_data_id,
                 property_version: inner.token_id.property_version.clone(),
                 from_address: Some(event_account_address.clone()),
-                to_address: Some(inner.get_to_address()),
-                token_amount: inner.amount.clone(),
+                to_address: Some(inner.get_standardized_to_address()),
+                token_amount: Some(inner.amount.clone()),
                 coin_type: None,
                 coin_amount: None,
             },
+            // Creation events don't move tokens. The token and collection data themselves come from
+            // the TokenData and CollectionData table items of the write set
+            TokenEvent::CreateTokenDataEvent(_) | TokenEvent::CreateCollectionEvent(_) => {
+                return Ok(None)
+            },
+        };
+        let expiration_timestamp = match token_event {
+            TokenEvent::OfferTokenEvent(inner) => inner.get_expiration_timestamp(txn_version),
+            _ => None,
+        };
+        let check = |value: BigDecimal, column: &str| {
+            on_numeric_overflow.check_u64(value, "TokenActivity", column, txn_version)
+        };
+        let Some(property_version) =
+            check(token_activity_helper.property_version, "property_version")?
+        else {
+            return Ok(None);
+        };
+        let token_amount = match token_activity_helper.token_amount {
+            Some(token_amount) => match check(token_amount, "token_amount")? {
+                Some(token_amount) => Some(token_amount),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        let coin_amount = match token_activity_helper.coin_amount {
+            Some(coin_amount) => match check(coin_amount, "coin_amount")? {
+                Some(coin_amount) => Some(coin_amount),
+                None => return Ok(None),
+            },
+            None => None,
         };
         let token_data_id = token_activity_helper.token_data_id;
-        Self {
+        Ok(Some(Self {
             event_account_address,
             event_creation_number,
             event_sequence_number,
             token_data_id_hash: token_data_id.to_hash(),
-            property_version: token_activity_helper.property_version,
+            property_version,
             collection_data_id_hash: token_data_id.get_collection_data_id_hash(),
-            creator_address: token_data_id.get_creator_address(),
-            collection_name: token_data_id.get_collection_trunc(),
-            name: token_data_id.get_name_trunc(),
+            creator_address: denormalize_token_data_fields
+                .then(|| token_data_id.get_standardized_creator_address()),
+            collection_name: denormalize_token_data_fields
+                .then(|| token_data_id.get_collection_trunc()),
+            name: denormalize_token_data_fields.then(|| token_data_id.get_name_trunc()),
             transaction_version: txn_version,
             transfer_type: event_type.to_string(),
             from_address: token_activity_helper.from_address,
             to_address: token_activity_helper.to_address,
-            token_amount: token_activity_helper.token_amount,
+            token_amount,
             coin_type: token_activity_helper.coin_type,
-            coin_amount: token_activity_helper.coin_amount,
+            coin_amount,
             transaction_timestamp: txn_timestamp,
             event_index: Some(event_index),
+            token_standard: TokenStandard::V1.to_string(),
+            entry_function_id_str: entry_function_id_str.clone(),
+            raw_event_data: None,
+            activity_type: None,
+            expiration_timestamp,
+            direction: None,
+            event_key_string,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::util::parse_timestamp_secs;
+    use aptos_protos::transaction::v1::EventKey;
+    use strum::IntoEnumIterator;
+
+    const TOKEN_ID: &str = r#"{"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}}"#;
+    static NO_ENTRY_FUNCTION: Option<String> = None;
+
+    fn ctx(txn_version: i64) -> EventContext<'static> {
+        EventContext {
+            txn_version,
+            txn_timestamp: chrono::NaiveDateTime::default(),
+            epoch: 0,
+            event_index: 0,
+            entry_function_id_str: &NO_ENTRY_FUNCTION,
+        }
+    }
+
+    fn event(account_address: &str, type_str: &str, data: &str) -> Event {
+        Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: account_address.to_string(),
+            }),
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    /// Deposit of `amount` of the `TOKEN_ID` token into `account_address`
+    fn deposit(account_address: &str, amount: &str) -> Event {
+        event(
+            account_address,
+            "0x3::token::DepositEvent",
+            &format!(r#"{{"amount": "{}", "id": {}}}"#, amount, TOKEN_ID),
+        )
+    }
+
+    #[test]
+    fn test_parser_from_config() {
+        let parser: TokenActivityParser = serde_json::from_str(
+            r#"{"store_raw_event_data": true, "event_kinds": ["deposit_token_event"], "watched_addresses": ["0x1"]}"#,
+        )
+        .unwrap();
+        assert!(parser.store_raw_event_data);
+        // Unset fields keep their defaults
+        assert!(parser.denormalize_token_data_fields);
+        assert_eq!(parser.on_numeric_overflow, NumericOverflowPolicy::Skip);
+        assert!(parser
+            .event_kinds
+            .unwrap()
+            .contains(&TokenEventKind::DepositTokenEvent));
+        assert!(parser
+            .watched_addresses
+            .unwrap()
+            .contains(&StandardizedAddress::new("0x1")));
+        assert!(
+            serde_json::from_str::<TokenActivityParser>(r#"{"raw_event_data": true}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_every_token_event_has_activity_type_label() {
+        // Don't move tokens, see from_parsed_event
+        let non_activity_kinds = [
+            TokenEventKind::CreateTokenDataEvent,
+            TokenEventKind::CreateCollectionEvent,
+        ];
+        for kind in TokenEventKind::iter() {
+            let label = DEFAULT_ACTIVITY_TYPE_LABELS
+                .iter()
+                .find(|(event_type, _)| *event_type == kind.event_type());
+            assert_eq!(
+                label.is_some(),
+                !non_activity_kinds.contains(&kind),
+                "{:?} needs an entry in DEFAULT_ACTIVITY_TYPE_LABELS",
+                kind
+            );
+        }
+        for (event_type, _) in DEFAULT_ACTIVITY_TYPE_LABELS {
+            assert!(
+                TokenEventKind::iter().any(|kind| kind.event_type() == *event_type),
+                "{} isn't a TokenEvent",
+                event_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_activity_type_labels() {
+        let parser = TokenActivityParser {
+            activity_type_labels: DEFAULT_ACTIVITY_TYPE_LABELS
+                .iter()
+                .map(|(event_type, label)| (event_type.to_string(), label.to_string()))
+                .collect(),
+            ..TokenActivityParser::default()
+        };
+        let event = deposit("0x5", "1");
+
+        let activity = parser.parse_event(&ctx(1), &event).unwrap().unwrap();
+        assert_eq!(activity.transfer_type, "0x3::token::DepositEvent");
+        assert_eq!(activity.activity_type.as_deref(), Some("deposit"));
+
+        let activity = TokenActivityParser::default()
+            .parse_event(&ctx(1), &event)
+            .unwrap()
+            .unwrap();
+        assert_eq!(activity.activity_type, None);
+    }
+
+    #[test]
+    fn test_event_kinds() {
+        let parser = TokenActivityParser {
+            event_kinds: Some(AHashSet::from([
+                TokenEventKind::WithdrawTokenEvent,
+                TokenEventKind::DepositTokenEvent,
+            ])),
+            ..TokenActivityParser::default()
+        };
+        let deposit = deposit("0x5", "1");
+        let mint = Event {
+            type_str: "0x3::token::MintTokenEvent".to_string(),
+            ..deposit.clone()
+        };
+
+        assert!(parser.parse_event(&ctx(1), &deposit).unwrap().is_some());
+        // Would fail to parse since the data isn't a mint's, but is dropped on its type first
+        assert!(parser.parse_event(&ctx(1), &mint).unwrap().is_none());
+
+        let event_kinds: AHashSet<TokenEventKind> =
+            serde_json::from_str(r#"["withdraw_token_event", "deposit_token_event"]"#).unwrap();
+        assert_eq!(Some(event_kinds), parser.event_kinds);
+    }
+
+    #[test]
+    fn test_direction() {
+        let withdraw = |account_address| {
+            event(
+                account_address,
+                "0x3::token::WithdrawEvent",
+                &format!(r#"{{"amount": "1", "id": {}}}"#, TOKEN_ID),
+            )
+        };
+        let offer = |from_address, to_address| {
+            event(
+                from_address,
+                "0x3::token_transfers::TokenOfferEvent",
+                &format!(
+                    r#"{{"amount": "1", "to_address": "{}", "token_id": {}}}"#,
+                    to_address, TOKEN_ID
+                ),
+            )
+        };
+        // Written short and long, both match the standardized addresses on rows
+        let parser = TokenActivityParser {
+            watched_addresses: Some(
+                serde_json::from_str(
+                    r#"["0x5", "0x0000000000000000000000000000000000000000000000000000000000000007"]"#,
+                )
+                .unwrap(),
+            ),
+            ..TokenActivityParser::default()
+        };
+        let direction = |event: &Event| {
+            parser
+                .parse_event(&ctx(1), event)
+                .unwrap()
+                .unwrap()
+                .direction
+        };
+
+        assert_eq!(direction(&deposit("0x5", "1")).as_deref(), Some("incoming"));
+        assert_eq!(direction(&withdraw("0x5")).as_deref(), Some("outgoing"));
+        assert_eq!(direction(&offer("0x5", "0x7")).as_deref(), Some("internal"));
+        assert_eq!(direction(&offer("0x5", "0x9")).as_deref(), Some("outgoing"));
+        assert_eq!(direction(&offer("0x9", "0x7")).as_deref(), Some("incoming"));
+        assert_eq!(direction(&offer("0x9", "0xa")), None);
+        // Left empty without watched addresses
+        let activity = TokenActivityParser::default()
+            .parse_event(&ctx(1), &deposit("0x5", "1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(activity.direction, None);
+    }
+
+    #[test]
+    fn test_out_of_range_token_amount() {
+        let parser = TokenActivityParser::default();
+        let ctx = ctx(1);
+        let deposit = |amount| deposit("0x5", amount);
+
+        assert!(parser.parse_event(&ctx, &deposit("-5")).unwrap().is_none());
+        assert!(parser
+            .parse_event(&ctx, &deposit("100000000000000000000000000000"))
+            .unwrap()
+            .is_none());
+
+        let parser = TokenActivityParser {
+            on_numeric_overflow: NumericOverflowPolicy::Saturate,
+            ..TokenActivityParser::default()
+        };
+        let activity = parser
+            .parse_event(&ctx, &deposit("100000000000000000000000000000"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(activity.token_amount, Some(BigDecimal::from(u64::MAX)));
+        let activity = parser.parse_event(&ctx, &deposit("-5")).unwrap().unwrap();
+        assert_eq!(activity.token_amount, Some(BigDecimal::zero()));
+    }
+
+    #[test]
+    fn test_deposit_to_address() {
+        // The event's account is standardized once, standardizing it again is a no-op
+        let expected = StandardizedAddress::new("0xa5");
+        assert_eq!(
+            expected.as_str(),
+            "0x00000000000000000000000000000000000000000000000000000000000000a5"
+        );
+        assert_eq!(StandardizedAddress::new(expected.as_str()), expected);
+        let activity = TokenActivityParser::default()
+            .parse_event(&ctx(1), &deposit("0xa5", "1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(activity.to_address, Some(expected.clone()));
+        assert_eq!(activity.from_address, None);
+        assert_eq!(activity.event_account_address, expected);
+    }
+
+    #[test]
+    fn test_token_amount_absent_for_mutations() {
+        let parser = TokenActivityParser::default();
+        let event = event(
+            "0x5",
+            "0x3::token::MutateTokenPropertyMapEvent",
+            &format!(
+                r#"{{"old_id": {}, "new_id": {}, "keys": [], "values": [], "types": []}}"#,
+                TOKEN_ID, TOKEN_ID
+            ),
+        );
+        let activity = parser.parse_event(&ctx(1), &event).unwrap().unwrap();
+        assert_eq!(activity.token_amount, None);
+
+        let event = Event {
+            type_str: "0x3::token::BurnTokenEvent".to_string(),
+            ..deposit("0x5", "0")
+        };
+        let activity = parser.parse_event(&ctx(1), &event).unwrap().unwrap();
+        assert_eq!(activity.token_amount, Some(BigDecimal::zero()));
+    }
+
+    #[test]
+    fn test_out_of_range_token_amount_error() {
+        let error = TokenActivityParser {
+            on_numeric_overflow: NumericOverflowPolicy::Error,
+            ..TokenActivityParser::default()
         }
+        .parse_event(&ctx(1), &deposit("0x5", "-5"))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "TokenActivity.token_amount is out of range at version 1: -5"
+        );
+    }
+
+    #[test]
+    fn test_offer_expiration_timestamp() {
+        let parser = TokenActivityParser::default();
+        let ctx = ctx(1);
+        let offer = |expiration: &str| {
+            event(
+                "0x5",
+                "0x3::token_transfers::TokenOfferEvent",
+                &format!(
+                    r#"{{"amount": "1", "to_address": "0x7", "token_id": {}{}}}"#,
+                    TOKEN_ID, expiration
+                ),
+            )
+        };
+
+        let activity = parser
+            .parse_event(&ctx, &offer(r#", "expiration_timestamp": "1700000000""#))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            activity.expiration_timestamp,
+            Some(parse_timestamp_secs(1_700_000_000, 1))
+        );
+
+        let activity = parser.parse_event(&ctx, &offer("")).unwrap().unwrap();
+        assert_eq!(activity.expiration_timestamp, None);
+    }
+
+    #[test]
+    fn test_avro() {
+        assert_eq!(
+            TokenActivity::AVRO_FIELDS.len(),
+            TokenActivity::COLUMNS.len()
+        );
+        for (field, column) in TokenActivity::AVRO_FIELDS
+            .iter()
+            .zip(TokenActivity::COLUMNS)
+        {
+            assert_eq!(field.name, column.name);
+            assert_eq!(field.nullable, column.is_nullable, "{}", field.name);
+        }
+        let schema: serde_json::Value =
+            serde_json::from_str(&TokenActivity::avro_schema()).unwrap();
+        assert_eq!(schema["name"], "TokenActivity");
+
+        let activity = TokenActivityParser::default()
+            .parse_event(&ctx(300), &deposit("0x5", "1"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            activity.event_key_string,
+            "0x00000000000000000000000000000000000000000000000000000000000000000000000000000005"
+        );
+        let avro = activity.to_avro();
+        // transaction_version 300 zigzags to 600, 0xd8 0x04 as a varint
+        assert_eq!(avro[..2], [0xd8, 0x04]);
+        assert_eq!(activity.to_confluent_avro(1)[..5], [0, 0, 0, 0, 1]);
+        assert_eq!(activity.to_confluent_avro(1)[5..], avro[..]);
     }
 }
@@ -5,7 +5,11 @@
 #![allow(clippy::extra_unused_lifetimes)]
 #![allow(clippy::unused_unit)]
 
-use super::{token_utils::TokenWriteSet, tokens::TableHandleToOwner};
+use super::{
+    token_activities::TokenActivityQuery,
+    token_utils::TokenWriteSet,
+    tokens::{CurrentTokenPendingClaimPK, TableHandleToOwner},
+};
 use crate::{schema::current_token_pending_claims, utils::util::standardize_address};
 use aptos_protos::transaction::v1::{DeleteTableItem, WriteTableItem};
 use bigdecimal::{BigDecimal, Zero};
@@ -32,6 +36,8 @@ pub struct CurrentTokenPendingClaim {
     pub last_transaction_timestamp: chrono::NaiveDateTime,
     pub token_data_id: String,
     pub collection_id: String,
+    /// From the offer event, None once the offer is claimed or cancelled or if it doesn't expire
+    pub expiration_timestamp: Option<chrono::NaiveDateTime>,
 }
 
 impl Ord for CurrentTokenPendingClaim {
@@ -51,6 +57,64 @@ impl PartialOrd for CurrentTokenPendingClaim {
 }
 
 impl CurrentTokenPendingClaim {
+    /// token_activities transfer types that change pending claims
+    pub const ACTIVITY_TYPES: &'static [&'static str] = &[
+        "0x3::token_transfers::TokenOfferEvent",
+        "0x3::token_transfers::TokenCancelOfferEvent",
+        "0x3::token_transfers::TokenClaimEvent",
+    ];
+
+    /// Applies an offer, claim or cancel activity on top of the current claim, if any. Offers add
+    /// to what's pending and a claim or cancel clears it, like the table in the offerer's account.
+    /// The activity doesn't carry the claims table handle so it's left empty, and the token's
+    /// names are empty if token_activities was written without them.
+    pub fn from_token_activity(
+        activity: &TokenActivityQuery,
+        previous: Option<&Self>,
+    ) -> Option<Self> {
+        let (Some(from_address), Some(to_address)) = (&activity.from_address, &activity.to_address)
+        else {
+            return None;
+        };
+        let (amount, expiration_timestamp) = match activity.transfer_type.as_str() {
+            "0x3::token_transfers::TokenOfferEvent" => (
+                previous.map(|p| p.amount.clone()).unwrap_or_default()
+                    + activity.token_amount.clone().unwrap_or_default(),
+                activity.expiration_timestamp,
+            ),
+            "0x3::token_transfers::TokenCancelOfferEvent"
+            | "0x3::token_transfers::TokenClaimEvent" => (BigDecimal::zero(), None),
+            _ => return None,
+        };
+        Some(Self {
+            token_data_id_hash: activity.token_data_id_hash.clone(),
+            property_version: activity.property_version.clone(),
+            from_address: from_address.clone(),
+            to_address: to_address.clone(),
+            collection_data_id_hash: activity.collection_data_id_hash.clone(),
+            creator_address: activity.creator_address.clone().unwrap_or_default(),
+            collection_name: activity.collection_name.clone().unwrap_or_default(),
+            name: activity.name.clone().unwrap_or_default(),
+            amount,
+            table_handle: String::new(),
+            last_transaction_version: activity.transaction_version,
+            last_transaction_timestamp: activity.transaction_timestamp,
+            // Same 0x prefixed ids as from_write_table_item, to be consistent with Token V2
+            token_data_id: format!("0x{}", activity.token_data_id_hash),
+            collection_id: format!("0x{}", activity.collection_data_id_hash),
+            expiration_timestamp,
+        })
+    }
+
+    pub fn pk(&self) -> CurrentTokenPendingClaimPK {
+        (
+            self.token_data_id_hash.clone(),
+            self.property_version.clone(),
+            self.from_address.clone(),
+            self.to_address.clone(),
+        )
+    }
+
     /// Token claim is stored in a table in the offerer's account. The key is token_offer_id (token_id + to address)
     /// and value is token (token_id + amount)
     pub fn from_write_table_item(
@@ -110,6 +174,8 @@ impl CurrentTokenPendingClaim {
                         last_transaction_timestamp: txn_timestamp,
                         token_data_id,
                         collection_id,
+                        // The table only has the token, set from the offer event by the caller
+                        expiration_timestamp: None,
                     }));
                 } else {
                     tracing::warn!(
@@ -183,6 +249,7 @@ impl CurrentTokenPendingClaim {
                 last_transaction_timestamp: txn_timestamp,
                 token_data_id,
                 collection_id,
+                expiration_timestamp: None,
             }));
         }
         Ok(None)
@@ -0,0 +1,134 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use super::token_utils::TokenEvent;
+use crate::{
+    db::common::models::event_parser::{EventContext, EventParser},
+    schema::token_data_id_hashes,
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::Event;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Reverse lookup of a v1 token_data_id_hash, with the full creator, collection and name it was
+/// hashed from. The names on the other token tables are truncated to `NAME_LENGTH`, so they don't
+/// always hash back to the row's token_data_id_hash.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(token_data_id_hash))]
+#[diesel(table_name = token_data_id_hashes)]
+pub struct TokenDataIdHash {
+    pub token_data_id_hash: String,
+    pub creator_address: String,
+    pub collection_name: String,
+    pub name: String,
+    /// First version the token was seen at
+    pub transaction_version: i64,
+}
+
+impl TokenDataIdHash {
+    /// One row per token, from the earliest of `rows`
+    pub fn dedup(rows: Vec<Self>) -> Vec<Self> {
+        let mut by_hash: AHashMap<String, Self> = AHashMap::new();
+        for row in rows {
+            match by_hash.get(&row.token_data_id_hash) {
+                Some(existing) if existing.transaction_version <= row.transaction_version => {},
+                _ => {
+                    by_hash.insert(row.token_data_id_hash.clone(), row);
+                },
+            }
+        }
+        let mut rows = by_hash.into_values().collect::<Vec<_>>();
+        // Sorted so concurrent batches insert in the same order
+        rows.sort_by(|a, b| a.token_data_id_hash.cmp(&b.token_data_id_hash));
+        rows
+    }
+}
+
+/// Builds a `TokenDataIdHash` out of every v1 token event
+#[derive(Clone, Debug, Default)]
+pub struct TokenDataIdHashParser;
+
+impl EventParser for TokenDataIdHashParser {
+    type Row = TokenDataIdHash;
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>> {
+        let token_event = TokenEvent::from_event(&event.type_str, &event.data, ctx.txn_version)?;
+        let Some(token_data_id) = token_event.as_ref().and_then(TokenEvent::token_data_id) else {
+            return Ok(None);
+        };
+        Ok(Some(TokenDataIdHash {
+            token_data_id_hash: token_data_id.to_hash(),
+            creator_address: token_data_id.get_creator_address(),
+            collection_name: token_data_id.get_collection().to_string(),
+            name: token_data_id.get_name().to_string(),
+            transaction_version: ctx.txn_version,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::common::models::token_models::token_utils::{
+        token_data_id_from_components, NAME_LENGTH,
+    };
+    use aptos_protos::transaction::v1::EventKey;
+
+    #[test]
+    fn test_parse_event() {
+        let entry_function_id_str = None;
+        let ctx = |txn_version| EventContext {
+            txn_version,
+            txn_timestamp: chrono::NaiveDateTime::default(),
+            epoch: 0,
+            event_index: 0,
+            entry_function_id_str: &entry_function_id_str,
+        };
+        let long_name = "a".repeat(NAME_LENGTH + 1);
+        let event = Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: "0x5".to_string(),
+            }),
+            type_str: "0x3::token::DepositEvent".to_string(),
+            data: format!(
+                r#"{{"amount": "1", "id": {{"property_version": "0", "token_data_id": {{"creator": "0x1", "collection": "collection", "name": "{}"}}}}}}"#,
+                long_name
+            ),
+            ..Event::default()
+        };
+
+        let row = TokenDataIdHashParser
+            .parse_event(&ctx(2), &event)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            row.token_data_id_hash,
+            token_data_id_from_components("0x1", "collection", &long_name)
+        );
+        assert_eq!(row.name, long_name);
+        assert_eq!(
+            row.creator_address,
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+
+        let rows = TokenDataIdHash::dedup(vec![
+            row,
+            TokenDataIdHashParser
+                .parse_event(&ctx(1), &event)
+                .unwrap()
+                .unwrap(),
+            TokenDataIdHashParser
+                .parse_event(&ctx(3), &event)
+                .unwrap()
+                .unwrap(),
+        ]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].transaction_version, 1);
+    }
+}
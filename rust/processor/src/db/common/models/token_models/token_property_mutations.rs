@@ -0,0 +1,159 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use super::token_utils::{TokenEvent, TokenWriteSet};
+use crate::schema::token_property_mutations;
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::{Event, WriteTableItem};
+use bigdecimal::BigDecimal;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Full property map of each v1 token written in a transaction, by (token_data_id_hash, property_version)
+pub type TokenPropertiesByTokenId = AHashMap<(String, BigDecimal), serde_json::Value>;
+
+/// One row per MutateTokenPropertyMapEvent, the history of a v1 token's property changes
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(token_data_id_hash, transaction_version, event_index))]
+#[diesel(table_name = token_property_mutations)]
+pub struct TokenPropertyMutation {
+    pub token_data_id_hash: String,
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub collection_data_id_hash: String,
+    pub old_property_version: BigDecimal,
+    pub new_property_version: BigDecimal,
+    /// New values of the keys the event changed
+    pub mutated_properties: serde_json::Value,
+    /// Full maps of the old and new token, when the transaction also wrote their table items. The
+    /// old token is only there when the owner still holds some of it after the mutation split it
+    /// off into a new property version.
+    pub old_properties: Option<serde_json::Value>,
+    pub new_properties: Option<serde_json::Value>,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+impl TokenPropertyMutation {
+    pub fn from_event(
+        event: &Event,
+        txn_version: i64,
+        txn_timestamp: chrono::NaiveDateTime,
+        event_index: i64,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(TokenEvent::MutateTokenPropertyMapEvent(inner)) =
+            TokenEvent::from_event(&event.type_str, &event.data, txn_version)?
+        else {
+            return Ok(None);
+        };
+        let token_data_id = &inner.new_id.token_data_id;
+        Ok(Some(Self {
+            token_data_id_hash: token_data_id.to_hash(),
+            transaction_version: txn_version,
+            event_index,
+            collection_data_id_hash: token_data_id.get_collection_data_id_hash(),
+            old_property_version: inner.old_id.property_version.clone(),
+            new_property_version: inner.new_id.property_version.clone(),
+            mutated_properties: inner.get_mutated_properties(),
+            old_properties: None,
+            new_properties: None,
+            transaction_timestamp: txn_timestamp,
+        }))
+    }
+
+    /// The token's property map if the table item is a v1 token in a TokenStore
+    pub fn get_token_properties_from_write_table_item(
+        table_item: &WriteTableItem,
+        txn_version: i64,
+    ) -> anyhow::Result<Option<((String, BigDecimal), serde_json::Value)>> {
+        let table_item_data = table_item.data.as_ref().unwrap();
+        match TokenWriteSet::from_table_item_type(
+            table_item_data.value_type.as_str(),
+            &table_item_data.value,
+            txn_version,
+        )? {
+            Some(TokenWriteSet::Token(token)) => Ok(Some((
+                (token.id.token_data_id.to_hash(), token.id.property_version),
+                token.token_properties,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn add_token_properties(&mut self, token_properties: &TokenPropertiesByTokenId) {
+        self.old_properties = token_properties
+            .get(&(
+                self.token_data_id_hash.clone(),
+                self.old_property_version.clone(),
+            ))
+            .cloned();
+        self.new_properties = token_properties
+            .get(&(
+                self.token_data_id_hash.clone(),
+                self.new_property_version.clone(),
+            ))
+            .cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::EventKey;
+
+    #[test]
+    fn test_token_property_mutation_from_event() {
+        let event = Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: "0x5".to_string(),
+            }),
+            type_str: "0x3::token::MutateTokenPropertyMapEvent".to_string(),
+            data: r#"{
+                "old_id": {"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}},
+                "new_id": {"property_version": "2", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}},
+                "keys": ["rank", "level"],
+                "values": ["0x03313233", "0x0500000000000000"],
+                "types": ["0x1::string::String", "u64"]
+            }"#
+            .to_string(),
+            ..Event::default()
+        };
+        let mut mutation =
+            TokenPropertyMutation::from_event(&event, 10, chrono::NaiveDateTime::default(), 3)
+                .unwrap()
+                .unwrap();
+        assert_eq!(mutation.old_property_version, BigDecimal::from(0));
+        assert_eq!(mutation.new_property_version, BigDecimal::from(2));
+        assert_eq!(
+            mutation.mutated_properties,
+            serde_json::json!({"rank": "123", "level": "5"})
+        );
+
+        let mut token_properties = TokenPropertiesByTokenId::new();
+        token_properties.insert(
+            (mutation.token_data_id_hash.clone(), BigDecimal::from(2)),
+            serde_json::json!({"rank": "123", "level": "5", "color": "red"}),
+        );
+        mutation.add_token_properties(&token_properties);
+        assert_eq!(mutation.old_properties, None);
+        assert_eq!(
+            mutation.new_properties,
+            Some(serde_json::json!({"rank": "123", "level": "5", "color": "red"}))
+        );
+
+        let event = Event {
+            type_str: "0x3::token::BurnTokenEvent".to_string(),
+            data: r#"{"amount": "1", "id": {"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}}}"#.to_string(),
+            ..event
+        };
+        assert!(
+            TokenPropertyMutation::from_event(&event, 10, chrono::NaiveDateTime::default(), 3)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -4,19 +4,79 @@
 // This is required because a diesel macro makes clippy sad
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::utils::util::{
-    deserialize_from_string, deserialize_property_map_from_bcs_hexstring,
-    deserialize_string_from_hexstring, hash_str, standardize_address, truncate_str,
+use crate::utils::{
+    counters::EVENT_TYPE_TOO_LONG_COUNT,
+    util::{
+        convert_bcs_hex, deserialize_from_string, deserialize_option_from_string,
+        deserialize_property_map_from_bcs_hexstring, deserialize_string_from_hexstring, hash_str,
+        parse_timestamp_secs, standardize_address, truncate_str, StandardizedAddress,
+        MAX_EVENT_TYPE_LENGTH,
+    },
 };
 use anyhow::{Context, Result};
 use bigdecimal::BigDecimal;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use std::fmt::{self, Formatter};
+use std::{
+    borrow::Cow,
+    fmt::{self, Formatter},
+};
+use unicode_normalization::UnicodeNormalization;
 
 pub const TOKEN_ADDR: &str = "0x0000000000000000000000000000000000000000000000000000000000000003";
 pub const NAME_LENGTH: usize = 128;
 pub const URI_LENGTH: usize = 512;
 
+static ID_HASH_SCHEME: OnceCell<IdHashScheme> = OnceCell::new();
+
+/// How token and collection ids are hashed into token_data_id_hash and collection_data_id_hash.
+/// Changing the hash means adding a scheme here, selecting it with `token_id_hash_scheme` and
+/// rewriting the existing rows with `rehash_token_ids_from`, otherwise a token's history is split
+/// across hashes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdHashScheme {
+    Sha256,
+    /// Sha256 of the collection and token names in Unicode NFC, so names that only differ in how
+    /// their characters are composed share a hash. The names are also stored in NFC.
+    Sha256Nfc,
+}
+
+impl IdHashScheme {
+    /// The scheme new rows are hashed with, `Sha256` unless `set_current` was called
+    pub fn current() -> IdHashScheme {
+        ID_HASH_SCHEME
+            .get()
+            .copied()
+            .unwrap_or(IdHashScheme::Sha256)
+    }
+
+    /// Selects the scheme for the rest of the process, only the first call has an effect
+    pub fn set_current(scheme: IdHashScheme) {
+        let _ = ID_HASH_SCHEME.set(scheme);
+    }
+
+    pub fn hash(&self, val: &str) -> String {
+        match self {
+            IdHashScheme::Sha256 | IdHashScheme::Sha256Nfc => hash_str(val),
+        }
+    }
+
+    /// A collection or token name as it's hashed and stored under this scheme
+    pub fn normalize_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            IdHashScheme::Sha256 => Cow::Borrowed(name),
+            IdHashScheme::Sha256Nfc => Cow::Owned(name.nfc().collect()),
+        }
+    }
+}
+
+/// A collection or token name normalized under the current `IdHashScheme` and truncated to
+/// `NAME_LENGTH`, as it's stored on rows
+pub fn truncate_name(name: &str) -> String {
+    truncate_str(&IdHashScheme::current().normalize_name(name), NAME_LENGTH)
+}
+
 /**
  * This file defines deserialized move types as defined in our 0x3 contracts.
  */
@@ -31,6 +91,17 @@ impl Table {
     }
 }
 
+/// token_data_id_hash of the v1 token with these creator, collection and name, e.g. to find the
+/// token a hash from a bug report belongs to. Takes the full names, not the truncated ones on rows.
+pub fn token_data_id_from_components(creator: &str, collection: &str, name: &str) -> String {
+    TokenDataIdType::new(
+        creator.to_string(),
+        collection.to_string(),
+        name.to_string(),
+    )
+    .to_hash()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TokenDataIdType {
     creator: String,
@@ -39,20 +110,45 @@ pub struct TokenDataIdType {
 }
 
 impl TokenDataIdType {
+    pub fn new(creator: String, collection: String, name: String) -> Self {
+        Self {
+            creator,
+            collection,
+            name,
+        }
+    }
+
     pub fn to_id(&self) -> String {
         format!("0x{}", self.to_hash())
     }
 
     pub fn to_hash(&self) -> String {
-        hash_str(&self.to_string())
+        self.to_hash_with(IdHashScheme::current())
+    }
+
+    pub fn to_hash_with(&self, scheme: IdHashScheme) -> String {
+        scheme.hash(&format!(
+            "{}::{}::{}",
+            standardize_address(self.creator.as_str()),
+            scheme.normalize_name(&self.collection),
+            scheme.normalize_name(&self.name)
+        ))
+    }
+
+    pub fn get_collection(&self) -> Cow<'_, str> {
+        IdHashScheme::current().normalize_name(&self.collection)
+    }
+
+    pub fn get_name(&self) -> Cow<'_, str> {
+        IdHashScheme::current().normalize_name(&self.name)
     }
 
     pub fn get_collection_trunc(&self) -> String {
-        truncate_str(&self.collection, NAME_LENGTH)
+        truncate_name(&self.collection)
     }
 
     pub fn get_name_trunc(&self) -> String {
-        truncate_str(&self.name, NAME_LENGTH)
+        truncate_name(&self.name)
     }
 
     pub fn get_collection_data_id_hash(&self) -> String {
@@ -66,6 +162,10 @@ impl TokenDataIdType {
     pub fn get_creator_address(&self) -> String {
         standardize_address(&self.creator)
     }
+
+    pub fn get_standardized_creator_address(&self) -> StandardizedAddress {
+        StandardizedAddress::new(&self.creator)
+    }
 }
 
 impl fmt::Display for TokenDataIdType {
@@ -92,7 +192,15 @@ impl CollectionDataIdType {
     }
 
     pub fn to_hash(&self) -> String {
-        hash_str(&self.to_string())
+        self.to_hash_with(IdHashScheme::current())
+    }
+
+    pub fn to_hash_with(&self, scheme: IdHashScheme) -> String {
+        scheme.hash(&format!(
+            "{}::{}",
+            standardize_address(self.creator.as_str()),
+            scheme.normalize_name(&self.name)
+        ))
     }
 
     pub fn to_id(&self) -> String {
@@ -145,6 +253,38 @@ impl TokenDataType {
     pub fn get_uri_trunc(&self) -> String {
         truncate_str(&self.uri, URI_LENGTH)
     }
+
+    /// Parses a TokenData table item with `default_properties` skipped instead of decoded, and left
+    /// null, for property maps too large to index
+    pub fn from_str_without_properties(data: &str) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        struct TokenDataWithoutProperties {
+            description: String,
+            #[serde(deserialize_with = "deserialize_from_string")]
+            largest_property_version: BigDecimal,
+            #[serde(deserialize_with = "deserialize_from_string")]
+            maximum: BigDecimal,
+            mutability_config: TokenDataMutabilityConfigType,
+            name: String,
+            royalty: RoyaltyType,
+            #[serde(deserialize_with = "deserialize_from_string")]
+            supply: BigDecimal,
+            uri: String,
+        }
+
+        let inner: TokenDataWithoutProperties = serde_json::from_str(data)?;
+        Ok(Self {
+            default_properties: serde_json::Value::Null,
+            description: inner.description,
+            largest_property_version: inner.largest_property_version,
+            maximum: inner.maximum,
+            mutability_config: inner.mutability_config,
+            name: inner.name,
+            royalty: inner.royalty,
+            supply: inner.supply,
+            uri: inner.uri,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -202,7 +342,7 @@ impl CollectionDataType {
     }
 
     pub fn get_name_trunc(&self) -> String {
-        truncate_str(&self.name, NAME_LENGTH)
+        truncate_name(&self.name)
     }
 }
 
@@ -273,6 +413,19 @@ pub struct BurnTokenEventType {
 pub struct MutateTokenPropertyMapEventType {
     pub old_id: TokenIdType,
     pub new_id: TokenIdType,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub values: Vec<String>,
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+impl MutateTokenPropertyMapEventType {
+    /// New values of the mutated keys only, the event doesn't carry the rest of the map
+    pub fn get_mutated_properties(&self) -> serde_json::Value {
+        property_map_from_event(&self.keys, &self.values, &self.types)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -281,12 +434,28 @@ pub struct OfferTokenEventType {
     pub amount: BigDecimal,
     to_address: String,
     pub token_id: TokenIdType,
+    /// Unix seconds after which the offer can't be claimed, only on offers that expire
+    #[serde(
+        default,
+        alias = "expiration_time",
+        deserialize_with = "deserialize_option_from_string"
+    )]
+    expiration_timestamp: Option<u64>,
 }
 
 impl OfferTokenEventType {
     pub fn get_to_address(&self) -> String {
         standardize_address(&self.to_address)
     }
+
+    pub fn get_standardized_to_address(&self) -> StandardizedAddress {
+        StandardizedAddress::new(&self.to_address)
+    }
+
+    pub fn get_expiration_timestamp(&self, txn_version: i64) -> Option<chrono::NaiveDateTime> {
+        self.expiration_timestamp
+            .map(|ts| parse_timestamp_secs(ts, txn_version))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -301,6 +470,10 @@ impl CancelTokenOfferEventType {
     pub fn get_to_address(&self) -> String {
         standardize_address(&self.to_address)
     }
+
+    pub fn get_standardized_to_address(&self) -> StandardizedAddress {
+        StandardizedAddress::new(&self.to_address)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -315,6 +488,94 @@ impl ClaimTokenEventType {
     pub fn get_to_address(&self) -> String {
         standardize_address(&self.to_address)
     }
+
+    pub fn get_standardized_to_address(&self) -> StandardizedAddress {
+        StandardizedAddress::new(&self.to_address)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateTokenDataEventType {
+    pub id: TokenDataIdType,
+    pub description: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub maximum: BigDecimal,
+    uri: String,
+    royalty_payee_address: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub royalty_points_denominator: BigDecimal,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub royalty_points_numerator: BigDecimal,
+    pub mutability_config: TokenDataMutabilityConfigType,
+    pub property_keys: Vec<String>,
+    pub property_values: Vec<String>,
+    pub property_types: Vec<String>,
+}
+
+impl CreateTokenDataEventType {
+    pub fn get_uri_trunc(&self) -> String {
+        truncate_str(&self.uri, URI_LENGTH)
+    }
+
+    pub fn get_royalty_payee_address(&self) -> String {
+        standardize_address(&self.royalty_payee_address)
+    }
+
+    /// Same flat json as `default_properties` in the TokenData table item, e.g. {"rank": "12"}
+    pub fn get_default_properties(&self) -> serde_json::Value {
+        property_map_from_event(
+            &self.property_keys,
+            &self.property_values,
+            &self.property_types,
+        )
+    }
+}
+
+/// Flat json of the parallel keys, bcs values and types vectors events carry property maps as,
+/// e.g. {"rank": "12"}. Values of unknown types are kept as the hex string.
+fn property_map_from_event(
+    keys: &[String],
+    values: &[String],
+    types: &[String],
+) -> serde_json::Value {
+    let properties: serde_json::Map<String, serde_json::Value> = keys
+        .iter()
+        .zip(values.iter())
+        .zip(types.iter())
+        .map(|((key, value), typ)| {
+            let value =
+                convert_bcs_hex(typ.clone(), value.clone()).unwrap_or_else(|| value.clone());
+            (key.clone(), serde_json::Value::String(value))
+        })
+        .collect();
+    serde_json::Value::Object(properties)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateCollectionEventType {
+    creator: String,
+    collection_name: String,
+    uri: String,
+    pub description: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub maximum: BigDecimal,
+}
+
+impl CreateCollectionEventType {
+    pub fn get_collection_data_id(&self) -> CollectionDataIdType {
+        CollectionDataIdType::new(
+            standardize_address(&self.creator),
+            self.collection_name.clone(),
+        )
+    }
+
+    pub fn get_collection_name_trunc(&self) -> String {
+        truncate_name(&self.collection_name)
+    }
+
+    pub fn get_uri_trunc(&self) -> String {
+        truncate_str(&self.uri, URI_LENGTH)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -380,7 +641,12 @@ impl TokenWriteSet {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, strum::EnumDiscriminants)]
+#[strum_discriminants(
+    name(TokenEventKind),
+    derive(strum::EnumIter, Hash, Deserialize, Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum TokenEvent {
     MintTokenEvent(MintTokenEventType),
     BurnTokenEvent(BurnTokenEventType),
@@ -390,10 +656,24 @@ pub enum TokenEvent {
     OfferTokenEvent(OfferTokenEventType),
     CancelTokenOfferEvent(CancelTokenOfferEventType),
     ClaimTokenEvent(ClaimTokenEventType),
+    CreateTokenDataEvent(CreateTokenDataEventType),
+    CreateCollectionEvent(CreateCollectionEventType),
 }
 
 impl TokenEvent {
     pub fn from_event(data_type: &str, data: &str, txn_version: i64) -> Result<Option<TokenEvent>> {
+        // Reject before doing any string comparisons, none of the types below come close to this
+        if data_type.len() > MAX_EVENT_TYPE_LENGTH {
+            EVENT_TYPE_TOO_LONG_COUNT
+                .with_label_values(&["TokenEvent"])
+                .inc();
+            tracing::warn!(
+                transaction_version = txn_version,
+                type_str_length = data_type.len(),
+                "Skipping token event with type string over the max length",
+            );
+            return Ok(None);
+        }
         match data_type {
             "0x3::token::MintTokenEvent" => {
                 serde_json::from_str(data).map(|inner| Some(TokenEvent::MintTokenEvent(inner)))
@@ -417,6 +697,10 @@ impl TokenEvent {
             "0x3::token_transfers::TokenClaimEvent" => {
                 serde_json::from_str(data).map(|inner| Some(TokenEvent::ClaimTokenEvent(inner)))
             },
+            "0x3::token::CreateTokenDataEvent" => serde_json::from_str(data)
+                .map(|inner| Some(TokenEvent::CreateTokenDataEvent(inner))),
+            "0x3::token::CreateCollectionEvent" => serde_json::from_str(data)
+                .map(|inner| Some(TokenEvent::CreateCollectionEvent(inner))),
             _ => Ok(None),
         }
         .context(format!(
@@ -424,6 +708,44 @@ impl TokenEvent {
             txn_version, data_type, data
         ))
     }
+
+    /// The v1 token the event is about, None for collection events
+    pub fn token_data_id(&self) -> Option<&TokenDataIdType> {
+        match self {
+            TokenEvent::MintTokenEvent(inner) => Some(&inner.id),
+            TokenEvent::BurnTokenEvent(inner) => Some(&inner.id.token_data_id),
+            TokenEvent::MutateTokenPropertyMapEvent(inner) => Some(&inner.new_id.token_data_id),
+            TokenEvent::WithdrawTokenEvent(inner) => Some(&inner.id.token_data_id),
+            TokenEvent::DepositTokenEvent(inner) => Some(&inner.id.token_data_id),
+            TokenEvent::OfferTokenEvent(inner) => Some(&inner.token_id.token_data_id),
+            TokenEvent::CancelTokenOfferEvent(inner) => Some(&inner.token_id.token_data_id),
+            TokenEvent::ClaimTokenEvent(inner) => Some(&inner.token_id.token_data_id),
+            TokenEvent::CreateTokenDataEvent(inner) => Some(&inner.id),
+            TokenEvent::CreateCollectionEvent(_) => None,
+        }
+    }
+}
+
+impl TokenEventKind {
+    /// Move type `TokenEvent::from_event` parses into this variant, which is also what
+    /// token_activities stores as transfer_type. Matching exhaustively means a new variant doesn't
+    /// build until it's given one.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            TokenEventKind::MintTokenEvent => "0x3::token::MintTokenEvent",
+            TokenEventKind::BurnTokenEvent => "0x3::token::BurnTokenEvent",
+            TokenEventKind::MutateTokenPropertyMapEvent => {
+                "0x3::token::MutateTokenPropertyMapEvent"
+            },
+            TokenEventKind::WithdrawTokenEvent => "0x3::token::WithdrawEvent",
+            TokenEventKind::DepositTokenEvent => "0x3::token::DepositEvent",
+            TokenEventKind::OfferTokenEvent => "0x3::token_transfers::TokenOfferEvent",
+            TokenEventKind::CancelTokenOfferEvent => "0x3::token_transfers::TokenCancelOfferEvent",
+            TokenEventKind::ClaimTokenEvent => "0x3::token_transfers::TokenClaimEvent",
+            TokenEventKind::CreateTokenDataEvent => "0x3::token::CreateTokenDataEvent",
+            TokenEventKind::CreateCollectionEvent => "0x3::token::CreateCollectionEvent",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -473,3 +795,93 @@ impl TokenResource {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_token_event_kind_event_type() {
+        for kind in TokenEventKind::iter() {
+            // Unknown types are Ok(None), a known type with bad data fails to parse instead
+            assert!(
+                !matches!(TokenEvent::from_event(kind.event_type(), "{}", 0), Ok(None)),
+                "{:?} isn't parsed from {}",
+                kind,
+                kind.event_type()
+            );
+        }
+    }
+
+    #[test]
+    fn test_collection_data_id_hash_includes_creator() {
+        let token_data_id = |creator: &str| TokenDataIdType {
+            creator: creator.to_string(),
+            collection: "collection".to_string(),
+            name: "token".to_string(),
+        };
+        assert_ne!(
+            token_data_id("0x1").get_collection_data_id_hash(),
+            token_data_id("0x2").get_collection_data_id_hash()
+        );
+        assert_ne!(
+            token_data_id("0x1").get_collection_id(),
+            token_data_id("0x2").get_collection_id()
+        );
+        // The creator is standardized before hashing, so short and long forms are the same collection
+        assert_eq!(
+            token_data_id("0x1").get_collection_data_id_hash(),
+            token_data_id("0x0000000000000000000000000000000000000000000000000000000000000001")
+                .get_collection_data_id_hash()
+        );
+    }
+
+    #[test]
+    fn test_nfc_id_hash_scheme() {
+        // "é" precomposed and as "e" followed by a combining acute accent
+        let token_data_id = |name: &str| {
+            TokenDataIdType::new("0x1".to_string(), name.to_string(), name.to_string())
+        };
+        let (composed, decomposed) = (token_data_id("caf\u{e9}"), token_data_id("cafe\u{301}"));
+        assert_ne!(
+            composed.to_hash_with(IdHashScheme::Sha256),
+            decomposed.to_hash_with(IdHashScheme::Sha256)
+        );
+        assert_eq!(
+            composed.to_hash_with(IdHashScheme::Sha256Nfc),
+            decomposed.to_hash_with(IdHashScheme::Sha256Nfc)
+        );
+        // Names that are already NFC keep their sha256 hash
+        assert_eq!(
+            composed.to_hash_with(IdHashScheme::Sha256),
+            composed.to_hash_with(IdHashScheme::Sha256Nfc)
+        );
+        assert_eq!(
+            IdHashScheme::Sha256Nfc.normalize_name("cafe\u{301}"),
+            "caf\u{e9}"
+        );
+    }
+
+    #[test]
+    fn test_token_data_id_from_components() {
+        let token_data_id = TokenDataIdType::new(
+            "0x1".to_string(),
+            "collection".to_string(),
+            "token".to_string(),
+        );
+        assert_eq!(
+            token_data_id_from_components("0x1", "collection", "token"),
+            token_data_id.to_hash()
+        );
+        // Same standardization of the creator as the hashes on rows
+        assert_eq!(
+            token_data_id_from_components(
+                "0x0000000000000000000000000000000000000000000000000000000000000001",
+                "collection",
+                "token"
+            ),
+            token_data_id.to_hash()
+        );
+    }
+}
@@ -148,6 +148,7 @@ impl TokenDataV2 {
                 last_transaction_timestamp: txn_timestamp,
                 decimals: None,
                 is_deleted_v2: Some(true),
+                generation: 0,
             }))
         } else {
             Ok(None)
@@ -180,6 +181,7 @@ impl TokenDataV2 {
                 last_transaction_timestamp: txn_timestamp,
                 decimals: None,
                 is_deleted_v2: Some(true),
+                generation: 0,
             }))
         } else {
             Ok(None)
@@ -195,6 +195,7 @@ impl TokenOwnershipV2 {
                     last_transaction_version: txn_version,
                     last_transaction_timestamp: txn_timestamp,
                     non_transferrable_by_owner: None, // default
+                    is_burned: true,
                 },
             )));
         }
@@ -247,6 +248,7 @@ impl TokenOwnershipV2 {
                             last_transaction_version: txn_version,
                             last_transaction_timestamp: txn_timestamp,
                             non_transferrable_by_owner: None,
+                            is_burned: false,
                         }),
                         Some(owner_address),
                         Some(tm.table_type.clone()),
@@ -326,6 +328,7 @@ impl TokenOwnershipV2 {
                             last_transaction_version: txn_version,
                             last_transaction_timestamp: txn_timestamp,
                             non_transferrable_by_owner: None,
+                            is_burned: false,
                         }),
                         Some(owner_address),
                         Some(tm.table_type.clone()),
@@ -424,6 +427,7 @@ impl TokenOwnershipV2 {
                         last_transaction_version: txn_version,
                         last_transaction_timestamp: txn_timestamp,
                         non_transferrable_by_owner: Some(non_transferrable_by_owner),
+                        is_burned: true,
                     },
                 )));
             } else {
@@ -500,6 +504,7 @@ impl TokenOwnershipV2 {
                     last_transaction_version: txn_version,
                     last_transaction_timestamp: txn_timestamp,
                     non_transferrable_by_owner: None, // default
+                    is_burned: true,
                 },
             )));
         }
@@ -12,12 +12,13 @@ use crate::{
         token_models::token_utils::{TokenDataIdType, TokenEvent},
     },
     schema::token_activities_v2,
-    utils::util::standardize_address,
+    utils::{database::write_copy_csv_row, util::standardize_address},
 };
 use aptos_protos::transaction::v1::Event;
 use bigdecimal::{BigDecimal, One, Zero};
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(transaction_version, event_index))]
@@ -31,13 +32,19 @@ pub struct TokenActivityV2 {
     pub type_: String,
     pub from_address: Option<String>,
     pub to_address: Option<String>,
-    pub token_amount: BigDecimal,
+    pub token_amount: Option<BigDecimal>,
     pub before_value: Option<String>,
     pub after_value: Option<String>,
     pub entry_function_id_str: Option<String>,
     pub token_standard: String,
     pub is_fungible_v2: Option<bool>,
     pub transaction_timestamp: chrono::NaiveDateTime,
+    /// When an offer stops being claimable, None for other activities and offers that don't expire
+    pub expiration_timestamp: Option<chrono::NaiveDateTime>,
+    /// Exact decimal string of property_version_v1 as parsed, set by `write_property_version_str`
+    pub property_version_v1_str: Option<String>,
+    /// False for the events of a failed transaction, set by `record_failed_transaction_events`
+    pub transaction_success: bool,
 }
 
 /// A simplified TokenActivity (excluded common fields) to reduce code duplication
@@ -46,20 +53,119 @@ struct TokenActivityHelperV1 {
     pub property_version: BigDecimal,
     pub from_address: Option<String>,
     pub to_address: Option<String>,
-    pub token_amount: BigDecimal,
+    pub token_amount: Option<BigDecimal>,
 }
 
 /// A simplified TokenActivity (excluded common fields) to reduce code duplication
 struct TokenActivityHelperV2 {
     pub from_address: Option<String>,
     pub to_address: Option<String>,
-    pub token_amount: BigDecimal,
+    pub token_amount: Option<BigDecimal>,
     pub before_value: Option<String>,
     pub after_value: Option<String>,
     pub event_type: String,
 }
 
 impl TokenActivityV2 {
+    /// Columns of `write_copy_csv_row`, in order. inserted_at is left to its default.
+    pub const COPY_COLUMNS: &'static [&'static str] = &[
+        "transaction_version",
+        "event_index",
+        "event_account_address",
+        "token_data_id",
+        "property_version_v1",
+        "type",
+        "from_address",
+        "to_address",
+        "token_amount",
+        "before_value",
+        "after_value",
+        "entry_function_id_str",
+        "token_standard",
+        "is_fungible_v2",
+        "transaction_timestamp",
+        "expiration_timestamp",
+        "property_version_v1_str",
+        "transaction_success",
+    ];
+
+    pub fn write_copy_csv_row(&self, buf: &mut Vec<u8>) {
+        write_copy_csv_row(buf, &[
+            Some(self.transaction_version.to_string().as_str()),
+            Some(self.event_index.to_string().as_str()),
+            Some(self.event_account_address.as_str()),
+            Some(self.token_data_id.as_str()),
+            Some(self.property_version_v1.to_string().as_str()),
+            Some(self.type_.as_str()),
+            self.from_address.as_deref(),
+            self.to_address.as_deref(),
+            self.token_amount.as_ref().map(|a| a.to_string()).as_deref(),
+            self.before_value.as_deref(),
+            self.after_value.as_deref(),
+            self.entry_function_id_str.as_deref(),
+            Some(self.token_standard.as_str()),
+            self.is_fungible_v2.map(|b| b.to_string()).as_deref(),
+            Some(self.transaction_timestamp.to_string().as_str()),
+            self.expiration_timestamp.map(|ts| ts.to_string()).as_deref(),
+            self.property_version_v1_str.as_deref(),
+            Some(self.transaction_success.to_string().as_str()),
+        ]);
+    }
+
+    /// Hex sha256 of the rows' `write_copy_csv_row` lines in primary key order, so the same rows
+    /// hash the same whatever order they were parsed in
+    pub fn content_hash(rows: &[Self]) -> String {
+        let mut sorted_rows = rows.iter().collect::<Vec<_>>();
+        sorted_rows.sort_by_key(|row| (row.transaction_version, row.event_index));
+        let mut buf = vec![];
+        for row in sorted_rows {
+            row.write_copy_csv_row(&mut buf);
+        }
+        hex::encode(Sha256::digest(&buf))
+    }
+
+    /// Token activities of the events of a failed transaction, with `transaction_success` false.
+    /// A failed transaction commits nothing but its gas fee, so they only record the attempt and
+    /// nothing else is derived from them.
+    pub async fn from_failed_transaction_events(
+        events: &[Event],
+        txn_version: i64,
+        txn_timestamp: chrono::NaiveDateTime,
+        entry_function_id_str: &Option<String>,
+    ) -> anyhow::Result<Vec<Self>> {
+        // Its write set has no objects, v2 activities fall back to the event alone
+        let token_v2_metadata = ObjectAggregatedDataMapping::new();
+        let mut activities = vec![];
+        for (index, event) in events.iter().enumerate() {
+            let activity = match Self::get_v1_from_parsed_event(
+                event,
+                txn_version,
+                txn_timestamp,
+                index as i64,
+                entry_function_id_str,
+                &token_v2_metadata,
+            )? {
+                Some(activity) => Some(activity),
+                None => {
+                    Self::get_nft_v2_from_parsed_event(
+                        event,
+                        txn_version,
+                        txn_timestamp,
+                        index as i64,
+                        entry_function_id_str,
+                        &token_v2_metadata,
+                    )
+                    .await?
+                },
+            };
+            activities.extend(activity.map(|activity| Self {
+                transaction_success: false,
+                ..activity
+            }));
+        }
+        Ok(activities)
+    }
+
     pub async fn get_nft_v2_from_parsed_event(
         event: &Event,
         txn_version: i64,
@@ -81,8 +187,18 @@ impl TokenActivityV2 {
                 V2TokenEvent::BurnEvent(inner) => inner.get_token_address(),
                 V2TokenEvent::Burn(inner) => inner.get_token_address(),
                 V2TokenEvent::TransferEvent(inner) => inner.get_object_address(),
-                _ => event_account_address.clone(),
+                V2TokenEvent::TokenMutation(inner) => inner.get_token_address(),
+                // Not about any one token. The collection's new state, supply included, is written
+                // to collections_v2 from its resources
+                V2TokenEvent::CollectionMutationEvent(_)
+                | V2TokenEvent::CollectionMutation(_)
+                | V2TokenEvent::SetMaxSupply(_) => return Ok(None),
+                V2TokenEvent::TokenMutationEvent(_) => event_account_address.clone(),
             };
+            let is_mutation = matches!(
+                token_event,
+                V2TokenEvent::TokenMutationEvent(_) | V2TokenEvent::TokenMutation(_)
+            );
 
             if let Some(metadata) = token_v2_metadata.get(&token_data_id) {
                 let object_core = &metadata.object.object_core;
@@ -90,7 +206,7 @@ impl TokenActivityV2 {
                     V2TokenEvent::MintEvent(_) => TokenActivityHelperV2 {
                         from_address: Some(object_core.get_owner_address()),
                         to_address: None,
-                        token_amount: BigDecimal::one(),
+                        token_amount: Some(BigDecimal::one()),
                         before_value: None,
                         after_value: None,
                         event_type: event_type.clone(),
@@ -98,7 +214,7 @@ impl TokenActivityV2 {
                     V2TokenEvent::Mint(_) => TokenActivityHelperV2 {
                         from_address: Some(object_core.get_owner_address()),
                         to_address: None,
-                        token_amount: BigDecimal::one(),
+                        token_amount: Some(BigDecimal::one()),
                         before_value: None,
                         after_value: None,
                         event_type: "0x4::collection::MintEvent".to_string(),
@@ -106,15 +222,24 @@ impl TokenActivityV2 {
                     V2TokenEvent::TokenMutationEvent(inner) => TokenActivityHelperV2 {
                         from_address: Some(object_core.get_owner_address()),
                         to_address: None,
-                        token_amount: BigDecimal::zero(),
+                        // Property mutations don't move any tokens
+                        token_amount: None,
                         before_value: Some(inner.old_value.clone()),
                         after_value: Some(inner.new_value.clone()),
                         event_type: event_type.clone(),
                     },
+                    V2TokenEvent::TokenMutation(inner) => TokenActivityHelperV2 {
+                        from_address: Some(object_core.get_owner_address()),
+                        to_address: None,
+                        token_amount: None,
+                        before_value: Some(inner.old_value.clone()),
+                        after_value: Some(inner.new_value.clone()),
+                        event_type: "0x4::token::MutationEvent".to_string(),
+                    },
                     V2TokenEvent::BurnEvent(_) => TokenActivityHelperV2 {
                         from_address: Some(object_core.get_owner_address()),
                         to_address: None,
-                        token_amount: BigDecimal::one(),
+                        token_amount: Some(BigDecimal::one()),
                         before_value: None,
                         after_value: None,
                         event_type: event_type.clone(),
@@ -122,7 +247,7 @@ impl TokenActivityV2 {
                     V2TokenEvent::Burn(_) => TokenActivityHelperV2 {
                         from_address: Some(object_core.get_owner_address()),
                         to_address: None,
-                        token_amount: BigDecimal::one(),
+                        token_amount: Some(BigDecimal::one()),
                         before_value: None,
                         after_value: None,
                         event_type: "0x4::collection::BurnEvent".to_string(),
@@ -130,11 +255,14 @@ impl TokenActivityV2 {
                     V2TokenEvent::TransferEvent(inner) => TokenActivityHelperV2 {
                         from_address: Some(inner.get_from_address()),
                         to_address: Some(inner.get_to_address()),
-                        token_amount: BigDecimal::one(),
+                        token_amount: Some(BigDecimal::one()),
                         before_value: None,
                         after_value: None,
                         event_type: event_type.clone(),
                     },
+                    V2TokenEvent::CollectionMutationEvent(_)
+                    | V2TokenEvent::CollectionMutation(_)
+                    | V2TokenEvent::SetMaxSupply(_) => return Ok(None),
                 };
                 return Ok(Some(Self {
                     transaction_version: txn_version,
@@ -152,6 +280,9 @@ impl TokenActivityV2 {
                     token_standard: TokenStandard::V2.to_string(),
                     is_fungible_v2: None,
                     transaction_timestamp: txn_timestamp,
+                    expiration_timestamp: None,
+                    property_version_v1_str: None,
+                    transaction_success: true,
                 }));
             } else {
                 // If the object metadata isn't found in the transaction, then the token was burnt.
@@ -173,13 +304,16 @@ impl TokenActivityV2 {
                     type_: event_type,
                     from_address: owner_address.clone(),
                     to_address: None,
-                    token_amount: BigDecimal::one(),
+                    token_amount: (!is_mutation).then(BigDecimal::one),
                     before_value: None,
                     after_value: None,
                     entry_function_id_str: entry_function_id_str.clone(),
                     token_standard: TokenStandard::V2.to_string(),
                     is_fungible_v2: None,
                     transaction_timestamp: txn_timestamp,
+                    expiration_timestamp: None,
+                    property_version_v1_str: None,
+                    transaction_success: true,
                 }));
             }
         }
@@ -192,6 +326,7 @@ impl TokenActivityV2 {
         txn_timestamp: chrono::NaiveDateTime,
         event_index: i64,
         entry_function_id_str: &Option<String>,
+        object_metadatas: &ObjectAggregatedDataMapping,
     ) -> anyhow::Result<Option<Self>> {
         let event_type = event.type_str.clone();
         if let Some(token_event) = &TokenEvent::from_event(&event_type, &event.data, txn_version)? {
@@ -203,57 +338,72 @@ impl TokenActivityV2 {
                     property_version: BigDecimal::zero(),
                     from_address: Some(event_account_address.clone()),
                     to_address: None,
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::BurnTokenEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.id.token_data_id.clone(),
                     property_version: inner.id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: None,
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::MutateTokenPropertyMapEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.new_id.token_data_id.clone(),
                     property_version: inner.new_id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: None,
-                    token_amount: BigDecimal::zero(),
+                    // Property mutations don't move any tokens
+                    token_amount: None,
                 },
                 TokenEvent::WithdrawTokenEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.id.token_data_id.clone(),
                     property_version: inner.id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: None,
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::DepositTokenEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.id.token_data_id.clone(),
                     property_version: inner.id.property_version.clone(),
                     from_address: None,
-                    to_address: Some(standardize_address(&event_account_address)),
-                    token_amount: inner.amount.clone(),
+                    // The token store can belong to an object, in which case the token goes to
+                    // the object's owner. event_account_address still has the object address.
+                    to_address: Some(
+                        object_metadatas
+                            .get(&event_account_address)
+                            .map(|metadata| metadata.object.object_core.get_owner_address())
+                            .unwrap_or_else(|| event_account_address.clone()),
+                    ),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::OfferTokenEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.token_id.token_data_id.clone(),
                     property_version: inner.token_id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: Some(inner.get_to_address()),
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::CancelTokenOfferEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.token_id.token_data_id.clone(),
                     property_version: inner.token_id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: Some(inner.get_to_address()),
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
                 TokenEvent::ClaimTokenEvent(inner) => TokenActivityHelperV1 {
                     token_data_id_struct: inner.token_id.token_data_id.clone(),
                     property_version: inner.token_id.property_version.clone(),
                     from_address: Some(event_account_address.clone()),
                     to_address: Some(inner.get_to_address()),
-                    token_amount: inner.amount.clone(),
+                    token_amount: Some(inner.amount.clone()),
                 },
+                TokenEvent::CreateTokenDataEvent(_) | TokenEvent::CreateCollectionEvent(_) => {
+                    return Ok(None)
+                },
+            };
+            let expiration_timestamp = match token_event {
+                TokenEvent::OfferTokenEvent(inner) => inner.get_expiration_timestamp(txn_version),
+                _ => None,
             };
             let token_data_id_struct = token_activity_helper.token_data_id_struct;
             return Ok(Some(Self {
@@ -272,8 +422,216 @@ impl TokenActivityV2 {
                 token_standard: TokenStandard::V1.to_string(),
                 is_fungible_v2: None,
                 transaction_timestamp: txn_timestamp,
+                expiration_timestamp,
+                property_version_v1_str: None,
+                transaction_success: true,
             }));
         }
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::common::models::object_models::v2_object_utils::{
+        ObjectAggregatedData, ObjectWithMetadata,
+    };
+    use aptos_protos::transaction::v1::EventKey;
+
+    #[test]
+    fn test_deposit_to_object_token_store() {
+        let object_address = standardize_address("0xa");
+        let event = Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: object_address.clone(),
+            }),
+            type_str: "0x3::token::DepositEvent".to_string(),
+            data: r#"{"amount": "1", "id": {"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}}}"#.to_string(),
+            ..Event::default()
+        };
+        let get_activity = |object_metadatas: &ObjectAggregatedDataMapping| {
+            TokenActivityV2::get_v1_from_parsed_event(
+                &event,
+                1,
+                chrono::NaiveDateTime::default(),
+                0,
+                &None,
+                object_metadatas,
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        // Without the object in the write set, the store's address is all we have
+        let activity = get_activity(&ObjectAggregatedDataMapping::new());
+        assert_eq!(activity.to_address, Some(object_address.clone()));
+
+        let object: ObjectWithMetadata = serde_json::from_value(serde_json::json!({
+            "object_core": {
+                "allow_ungated_transfer": true,
+                "guid_creation_num": "0",
+                "owner": "0xb",
+            },
+            "state_key_hash": "",
+        }))
+        .unwrap();
+        let mut object_metadatas = ObjectAggregatedDataMapping::new();
+        object_metadatas.insert(object_address.clone(), ObjectAggregatedData {
+            object,
+            ..ObjectAggregatedData::default()
+        });
+        let activity = get_activity(&object_metadatas);
+        assert_eq!(activity.to_address, Some(standardize_address("0xb")));
+        assert_eq!(activity.event_account_address, object_address);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let event = Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: standardize_address("0xa"),
+            }),
+            type_str: "0x3::token::DepositEvent".to_string(),
+            data: r#"{"amount": "1", "id": {"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}}}"#.to_string(),
+            ..Event::default()
+        };
+        let get_activity = |event_index| {
+            TokenActivityV2::get_v1_from_parsed_event(
+                &event,
+                1,
+                chrono::NaiveDateTime::default(),
+                event_index,
+                &None,
+                &ObjectAggregatedDataMapping::new(),
+            )
+            .unwrap()
+            .unwrap()
+        };
+        let rows = vec![get_activity(0), get_activity(1)];
+
+        let hash = TokenActivityV2::content_hash(&rows);
+        assert_eq!(hash.len(), 64);
+        // Parse order doesn't matter
+        let reversed = rows.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(TokenActivityV2::content_hash(&reversed), hash);
+        // Any change in a row does
+        let mut changed = rows.clone();
+        changed[1].token_amount = None;
+        assert_ne!(TokenActivityV2::content_hash(&changed), hash);
+        assert_ne!(TokenActivityV2::content_hash(&rows[..1]), hash);
+    }
+
+    async fn get_nft_v2_activity(type_str: &str, data: String) -> Option<TokenActivityV2> {
+        let event = Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: standardize_address("0x0"),
+            }),
+            type_str: type_str.to_string(),
+            data,
+            ..Event::default()
+        };
+        TokenActivityV2::get_nft_v2_from_parsed_event(
+            &event,
+            1,
+            chrono::NaiveDateTime::default(),
+            0,
+            &None,
+            &ObjectAggregatedDataMapping::new(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_v2_collection_and_token_module_events() {
+        let collection_address = standardize_address("0xc");
+        let token_address = standardize_address("0xd");
+
+        let activity = get_nft_v2_activity(
+            "0x4::token::Mutation",
+            format!(
+                r#"{{"token_address": "{}", "mutated_field_name": "uri", "old_value": "a", "new_value": "b"}}"#,
+                token_address
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(activity.token_data_id, token_address);
+        assert_eq!(activity.token_amount, None);
+        assert_eq!(activity.token_standard, TokenStandard::V2.to_string());
+
+        let Some(V2TokenEvent::SetMaxSupply(set_max_supply)) = V2TokenEvent::from_event(
+            "0x4::collection::SetMaxSupply",
+            &format!(
+                r#"{{"collection": {{"inner": "{}"}}, "old_max_supply": "10", "new_max_supply": "20"}}"#,
+                collection_address
+            ),
+            1,
+        )
+        .unwrap() else {
+            panic!("Expected a SetMaxSupply event");
+        };
+        assert_eq!(set_max_supply.get_collection_address(), collection_address);
+        assert_eq!(set_max_supply.new_max_supply, BigDecimal::from(20));
+
+        // Collection events are parsed but don't make token activities
+        for (type_str, data) in [
+            (
+                "0x4::collection::Mutation",
+                format!(
+                    r#"{{"mutated_field_name": "uri", "collection": {{"inner": "{}"}}, "old_value": "a", "new_value": "b"}}"#,
+                    collection_address
+                ),
+            ),
+            (
+                "0x4::collection::MutationEvent",
+                r#"{"mutated_field_name": "uri"}"#.to_string(),
+            ),
+            (
+                "0x4::collection::SetMaxSupply",
+                format!(
+                    r#"{{"collection": {{"inner": "{}"}}, "old_max_supply": "10", "new_max_supply": "20"}}"#,
+                    collection_address
+                ),
+            ),
+        ] {
+            assert!(V2TokenEvent::from_event(type_str, &data, 1)
+                .unwrap()
+                .is_some());
+            assert!(get_nft_v2_activity(type_str, data).await.is_none());
+        }
+    }
+
+    const FAILED_TXN: &str = r#"{"type":"user_transaction","version":"200","hash":"0x01","state_change_hash":"0x02","event_root_hash":"0x03","state_checkpoint_hash":null,"gas_used":"9","success":false,"vm_status":"Move abort in 0x3::token: EINSUFFICIENT_BALANCE(0x10005)","accumulator_root_hash":"0x04","timestamp":"1665000000123456","sender":"0xa","sequence_number":"4","max_gas_amount":"2000","gas_unit_price":"100","expiration_timestamp_secs":"1665000600","payload":{"type":"entry_function_payload","function":"0x3::token::direct_transfer_script","type_arguments":[],"arguments":["0x1","collection","token","0","1"]},"events":[{"guid":{"creation_number":"4","account_address":"0xb"},"sequence_number":"0","type":"0x3::token::DepositEvent","data":{"amount":"1","id":{"property_version":"0","token_data_id":{"creator":"0x1","collection":"collection","name":"token"}}}},{"guid":{"creation_number":"0","account_address":"0x0"},"sequence_number":"0","type":"0x1::transaction_fee::FeeStatement","data":{"execution_gas_units":"4","io_gas_units":"5","storage_fee_octas":"0","storage_fee_refund_octas":"0","total_charge_gas_units":"9"}}],"changes":[]}"#;
+
+    #[tokio::test]
+    async fn test_from_failed_transaction_events() {
+        let txn =
+            crate::json_stream::transaction_from_json(&serde_json::from_str(FAILED_TXN).unwrap())
+                .unwrap();
+        assert!(!txn.info.as_ref().unwrap().success);
+        let Some(aptos_protos::transaction::v1::transaction::TxnData::User(user_txn)) =
+            txn.txn_data.as_ref()
+        else {
+            panic!("Expected a user transaction");
+        };
+
+        let activities = TokenActivityV2::from_failed_transaction_events(
+            &user_txn.events,
+            txn.version as i64,
+            chrono::NaiveDateTime::default(),
+            &Some("0x3::token::direct_transfer_script".to_string()),
+        )
+        .await
+        .unwrap();
+        // The fee statement isn't a token event
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].transaction_version, 200);
+        assert_eq!(activities[0].type_, "0x3::token::DepositEvent");
+        assert!(!activities[0].transaction_success);
+    }
+}
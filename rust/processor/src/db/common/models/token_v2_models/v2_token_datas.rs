@@ -9,20 +9,57 @@ use super::v2_token_utils::{TokenStandard, TokenV2, TokenV2Burned};
 use crate::{
     db::common::models::{
         object_models::v2_object_utils::ObjectAggregatedDataMapping,
-        token_models::token_utils::TokenWriteSet,
+        token_models::token_utils::{TokenDataType, TokenWriteSet},
     },
     schema::{current_token_datas_v2, token_datas_v2},
-    utils::util::standardize_address,
+    utils::{counters::OVERSIZED_TOKEN_PROPERTIES_COUNT, util::standardize_address},
 };
+use ahash::AHashMap;
+use anyhow::Context;
 use aptos_protos::transaction::v1::{DeleteResource, WriteResource, WriteTableItem};
 use bigdecimal::BigDecimal;
 use diesel::prelude::*;
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
+use sha2::Digest;
 
 // PK of current_token_datas_v2, i.e. token_data_id
 pub type CurrentTokenDataV2PK = String;
 
+/// Key marking token_properties that were replaced because they were too large, see
+/// `cap_token_properties`
+pub const TOKEN_PROPERTIES_TRUNCATED_KEY: &str = "__truncated";
+
+/// Returns what to write instead of token_properties whose raw JSON, as read from the chain, is over
+/// `max_bytes`: a small object holding `TOKEN_PROPERTIES_TRUNCATED_KEY: true`, the raw size and the
+/// sha256 of the raw JSON, so the full map can still be matched against the chain. Checked before
+/// the property map is decoded, so oversized maps are never built.
+pub fn cap_token_properties(
+    raw: &str,
+    max_bytes: usize,
+    txn_version: i64,
+    token_standard: TokenStandard,
+) -> Option<serde_json::Value> {
+    if raw.len() <= max_bytes {
+        return None;
+    }
+    OVERSIZED_TOKEN_PROPERTIES_COUNT
+        .with_label_values(&[&token_standard.to_string()])
+        .inc();
+    tracing::warn!(
+        transaction_version = txn_version,
+        token_standard = token_standard.to_string(),
+        size_bytes = raw.len(),
+        max_bytes,
+        "Replaced token_properties over the max size with their hash",
+    );
+    Some(serde_json::json!({
+        TOKEN_PROPERTIES_TRUNCATED_KEY: true,
+        "size_bytes": raw.len(),
+        "sha256": hex::encode(sha2::Sha256::digest(raw.as_bytes())),
+    }))
+}
+
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(transaction_version, write_set_change_index))]
 #[diesel(table_name = token_datas_v2)]
@@ -67,6 +104,34 @@ pub struct CurrentTokenDataV2 {
     // Deprecated, but still here for backwards compatibility
     pub decimals: Option<i64>,
     pub is_deleted_v2: Option<bool>,
+    /// Number of times the token was created again after being burned. Rows are built with the
+    /// number of times that happened within the batch, the upsert adds it to the stored value when
+    /// the row is from a newer version than the stored one.
+    pub generation: i64,
+}
+
+impl CurrentTokenDataV2 {
+    /// Adds a written token data to the batch's current state. If the same token was burned earlier
+    /// in the batch, this write starts a new lifecycle (e.g. a named object re-created at the same
+    /// address), so the burn is dropped and the generation goes up.
+    pub fn add_to_batch(
+        mut self,
+        current_token_datas: &mut AHashMap<CurrentTokenDataV2PK, Self>,
+        deleted_token_datas: &mut AHashMap<CurrentTokenDataV2PK, Self>,
+    ) {
+        self.generation = current_token_datas
+            .get(&self.token_data_id)
+            .map(|token_data| token_data.generation)
+            .unwrap_or(0);
+        if deleted_token_datas
+            .get(&self.token_data_id)
+            .is_some_and(|burned| burned.last_transaction_version <= self.last_transaction_version)
+        {
+            deleted_token_datas.remove(&self.token_data_id);
+            self.generation += 1;
+        }
+        current_token_datas.insert(self.token_data_id.clone(), self);
+    }
 }
 
 impl TokenDataV2 {
@@ -143,6 +208,7 @@ impl TokenDataV2 {
                     last_transaction_timestamp: txn_timestamp,
                     decimals: None,
                     is_deleted_v2: Some(false),
+                    generation: 0,
                 },
             )))
         } else {
@@ -176,6 +242,7 @@ impl TokenDataV2 {
                 last_transaction_timestamp: txn_timestamp,
                 decimals: None,
                 is_deleted_v2: Some(true),
+                generation: 0,
             }))
         } else {
             Ok(None)
@@ -208,6 +275,7 @@ impl TokenDataV2 {
                 last_transaction_timestamp: txn_timestamp,
                 decimals: None,
                 is_deleted_v2: Some(true),
+                generation: 0,
             }))
         } else {
             Ok(None)
@@ -219,19 +287,45 @@ impl TokenDataV2 {
         txn_version: i64,
         write_set_change_index: i64,
         txn_timestamp: chrono::NaiveDateTime,
+        max_token_properties_bytes: Option<usize>,
     ) -> anyhow::Result<Option<(Self, CurrentTokenDataV2)>> {
         let table_item_data = table_item.data.as_ref().unwrap();
 
-        let maybe_token_data = match TokenWriteSet::from_table_item_type(
-            table_item_data.value_type.as_str(),
-            &table_item_data.value,
-            txn_version,
-        )? {
-            Some(TokenWriteSet::TokenData(inner)) => Some(inner),
-            _ => None,
+        // The properties are most of an oversized TokenData, so its whole raw JSON is checked
+        let capped_properties = max_token_properties_bytes
+            .filter(|_| table_item_data.value_type == "0x3::token::TokenData")
+            .and_then(|max_bytes| {
+                cap_token_properties(
+                    &table_item_data.value,
+                    max_bytes,
+                    txn_version,
+                    TokenStandard::V1,
+                )
+            });
+        let maybe_token_data = if capped_properties.is_some() {
+            Some(
+                TokenDataType::from_str_without_properties(&table_item_data.value).context(
+                    format!(
+                        "version {} failed! failed to parse oversized type {}",
+                        txn_version, table_item_data.value_type
+                    ),
+                )?,
+            )
+        } else {
+            match TokenWriteSet::from_table_item_type(
+                table_item_data.value_type.as_str(),
+                &table_item_data.value,
+                txn_version,
+            )? {
+                Some(TokenWriteSet::TokenData(inner)) => Some(inner),
+                _ => None,
+            }
         };
 
-        if let Some(token_data) = maybe_token_data {
+        if let Some(mut token_data) = maybe_token_data {
+            if let Some(token_properties) = capped_properties {
+                token_data.default_properties = token_properties;
+            }
             let maybe_token_data_id = match TokenWriteSet::from_table_item_type(
                 table_item_data.key_type.as_str(),
                 &table_item_data.key,
@@ -282,6 +376,7 @@ impl TokenDataV2 {
                         last_transaction_timestamp: txn_timestamp,
                         decimals: None,
                         is_deleted_v2: None,
+                        generation: 0,
                     },
                 )));
             } else {
@@ -296,3 +391,153 @@ impl TokenDataV2 {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::WriteTableData;
+
+    fn token_data(version: i64, is_deleted_v2: bool) -> CurrentTokenDataV2 {
+        CurrentTokenDataV2 {
+            token_data_id: standardize_address("0x123"),
+            collection_id: standardize_address("0x456"),
+            token_name: "token".to_string(),
+            maximum: None,
+            supply: None,
+            largest_property_version_v1: None,
+            token_uri: "".to_string(),
+            token_properties: serde_json::Value::Null,
+            description: "".to_string(),
+            token_standard: TokenStandard::V2.to_string(),
+            is_fungible_v2: Some(false),
+            last_transaction_version: version,
+            last_transaction_timestamp: chrono::NaiveDateTime::default(),
+            decimals: None,
+            is_deleted_v2: Some(is_deleted_v2),
+            generation: 0,
+        }
+    }
+
+    fn token_data_table_item() -> WriteTableItem {
+        WriteTableItem {
+            data: Some(WriteTableData {
+                key: r#"{"creator":"0x1","collection":"collection","name":"token"}"#.to_string(),
+                key_type: "0x3::token::TokenDataId".to_string(),
+                value: serde_json::json!({
+                    "default_properties": {"map": {"data": [
+                        {"key": "rank", "value": {"type": "u64", "value": "0x0c00000000000000"}},
+                    ]}},
+                    "description": "description",
+                    "largest_property_version": "0",
+                    "maximum": "0",
+                    "mutability_config": {
+                        "description": false,
+                        "maximum": false,
+                        "properties": false,
+                        "royalty": false,
+                        "uri": false,
+                    },
+                    "name": "token",
+                    "royalty": {
+                        "payee_address": "0x1",
+                        "royalty_points_denominator": "0",
+                        "royalty_points_numerator": "0",
+                    },
+                    "supply": "1",
+                    "uri": "uri",
+                })
+                .to_string(),
+                value_type: "0x3::token::TokenData".to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cap_token_properties() {
+        let raw = r#"{"rank":"12"}"#;
+        assert_eq!(
+            cap_token_properties(raw, raw.len(), 1, TokenStandard::V1),
+            None
+        );
+
+        let token_properties =
+            cap_token_properties(raw, raw.len() - 1, 1, TokenStandard::V1).unwrap();
+        assert_eq!(token_properties[TOKEN_PROPERTIES_TRUNCATED_KEY], true);
+        assert_eq!(token_properties["size_bytes"], raw.len());
+        assert_eq!(
+            token_properties["sha256"],
+            hex::encode(sha2::Sha256::digest(raw.as_bytes()))
+        );
+    }
+
+    #[test]
+    fn test_v1_token_properties_over_max() {
+        let table_item = token_data_table_item();
+        let raw = &table_item.data.as_ref().unwrap().value;
+        let timestamp = chrono::NaiveDateTime::default();
+
+        let (token_data, _) = TokenDataV2::get_v1_from_write_table_item(
+            &table_item,
+            1,
+            0,
+            timestamp,
+            Some(raw.len()),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            token_data.token_properties,
+            serde_json::json!({"rank": "12"})
+        );
+
+        let (token_data, current_token_data) = TokenDataV2::get_v1_from_write_table_item(
+            &table_item,
+            1,
+            0,
+            timestamp,
+            Some(raw.len() - 1),
+        )
+        .unwrap()
+        .unwrap();
+        // Everything but the properties is still parsed from the raw JSON
+        assert_eq!(token_data.token_name, "token");
+        assert_eq!(token_data.token_uri, "uri");
+        assert_eq!(token_data.supply, Some(BigDecimal::from(1)));
+        assert_eq!(
+            token_data.token_properties[TOKEN_PROPERTIES_TRUNCATED_KEY],
+            true
+        );
+        assert_eq!(token_data.token_properties["size_bytes"], raw.len());
+        assert_eq!(
+            current_token_data.token_properties,
+            token_data.token_properties
+        );
+    }
+
+    #[test]
+    fn test_burn_then_remint() {
+        let mut current_token_datas = AHashMap::new();
+        let mut deleted_token_datas = AHashMap::new();
+        let token_data_id = standardize_address("0x123");
+
+        token_data(1, false).add_to_batch(&mut current_token_datas, &mut deleted_token_datas);
+        deleted_token_datas.insert(token_data_id.clone(), token_data(2, true));
+        token_data(3, false).add_to_batch(&mut current_token_datas, &mut deleted_token_datas);
+        assert!(deleted_token_datas.is_empty());
+        let reminted = current_token_datas.get(&token_data_id).unwrap();
+        assert_eq!(reminted.last_transaction_version, 3);
+        assert_eq!(reminted.generation, 1);
+
+        // A mint that comes before the burn doesn't start a new lifecycle
+        let mut current_token_datas = AHashMap::new();
+        let mut deleted_token_datas = AHashMap::new();
+        deleted_token_datas.insert(token_data_id.clone(), token_data(5, true));
+        token_data(4, false).add_to_batch(&mut current_token_datas, &mut deleted_token_datas);
+        assert!(deleted_token_datas.contains_key(&token_data_id));
+        assert_eq!(
+            current_token_datas.get(&token_data_id).unwrap().generation,
+            0
+        );
+    }
+}
@@ -21,7 +21,7 @@ use crate::{
         util::{ensure_not_negative, standardize_address},
     },
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::Context;
 use aptos_protos::transaction::v1::{
     DeleteResource, DeleteTableItem, WriteResource, WriteTableItem,
@@ -35,6 +35,10 @@ use serde::{Deserialize, Serialize};
 // PK of current_token_ownerships_v2, i.e. token_data_id, property_version_v1, owner_address, storage_id
 pub type CurrentTokenOwnershipV2PK = (String, BigDecimal, String, String);
 
+/// v1 tokens burned in a transaction, by token_data_id, property_version_v1 and the owner they were
+/// burned from. The event doesn't say which store they were in, so this isn't the full PK.
+pub type TokenV1Burned = AHashSet<(String, BigDecimal, String)>;
+
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
 #[diesel(primary_key(transaction_version, write_set_change_index))]
 #[diesel(table_name = token_ownerships_v2)]
@@ -74,6 +78,8 @@ pub struct CurrentTokenOwnershipV2 {
     pub last_transaction_version: i64,
     pub last_transaction_timestamp: chrono::NaiveDateTime,
     pub non_transferrable_by_owner: Option<bool>,
+    /// Tombstone for a token that was burned, the row keeps the last owner with amount 0
+    pub is_burned: bool,
 }
 
 impl Ord for CurrentTokenOwnershipV2 {
@@ -92,6 +98,18 @@ impl PartialOrd for CurrentTokenOwnershipV2 {
     }
 }
 
+impl CurrentTokenOwnershipV2 {
+    /// A v1 token leaves its owner's store both when it's withdrawn and when it's burned, so the
+    /// deleted row is only a tombstone if the owner also burned it in the same transaction
+    pub fn mark_v1_burned(&mut self, tokens_burned: &TokenV1Burned) {
+        self.is_burned = tokens_burned.contains(&(
+            self.token_data_id.clone(),
+            self.property_version_v1.clone(),
+            self.owner_address.clone(),
+        ));
+    }
+}
+
 // Facilitate tracking when a token is burned
 #[derive(Clone, Debug)]
 pub struct NFTOwnershipV2 {
@@ -119,6 +137,7 @@ pub struct CurrentTokenOwnershipV2Query {
     pub last_transaction_timestamp: chrono::NaiveDateTime,
     pub inserted_at: chrono::NaiveDateTime,
     pub non_transferrable_by_owner: Option<bool>,
+    pub is_burned: bool,
 }
 
 impl TokenOwnershipV2 {
@@ -189,6 +208,7 @@ impl TokenOwnershipV2 {
                 last_transaction_version: token_data.transaction_version,
                 last_transaction_timestamp: token_data.transaction_timestamp,
                 non_transferrable_by_owner: Some(non_transferrable_by_owner),
+                is_burned: false,
             },
         );
 
@@ -240,6 +260,7 @@ impl TokenOwnershipV2 {
                     last_transaction_version: token_data.transaction_version,
                     last_transaction_timestamp: token_data.transaction_timestamp,
                     non_transferrable_by_owner: Some(is_soulbound),
+                    is_burned: false,
                 },
             );
         }
@@ -316,6 +337,7 @@ impl TokenOwnershipV2 {
                         last_transaction_version: txn_version,
                         last_transaction_timestamp: txn_timestamp,
                         non_transferrable_by_owner: Some(non_transferrable_by_owner),
+                        is_burned: true,
                     },
                 )));
             } else {
@@ -443,6 +465,7 @@ impl TokenOwnershipV2 {
                     last_transaction_version: txn_version,
                     last_transaction_timestamp: txn_timestamp,
                     non_transferrable_by_owner: None, // default
+                    is_burned: true,
                 },
             )));
         }
@@ -497,6 +520,7 @@ impl TokenOwnershipV2 {
                             last_transaction_version: txn_version,
                             last_transaction_timestamp: txn_timestamp,
                             non_transferrable_by_owner: None,
+                            is_burned: false,
                         }),
                         Some(owner_address),
                         Some(tm.table_type.clone()),
@@ -575,6 +599,7 @@ impl TokenOwnershipV2 {
                             last_transaction_version: txn_version,
                             last_transaction_timestamp: txn_timestamp,
                             non_transferrable_by_owner: None,
+                            is_burned: false,
                         }),
                         Some(owner_address),
                         Some(tm.table_type.clone()),
@@ -651,3 +676,52 @@ impl CurrentTokenOwnershipV2Query {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_ownership(owner_address: &str, amount: i64) -> CurrentTokenOwnershipV2 {
+        CurrentTokenOwnershipV2 {
+            token_data_id: "0xtoken".to_string(),
+            property_version_v1: BigDecimal::zero(),
+            owner_address: owner_address.to_string(),
+            storage_id: format!("{}_store", owner_address),
+            amount: BigDecimal::from(amount),
+            table_type_v1: Some("0x3::token::TokenStore".to_string()),
+            token_properties_mutated_v1: None,
+            is_soulbound_v2: None,
+            token_standard: TokenStandard::V1.to_string(),
+            is_fungible_v2: None,
+            last_transaction_version: 10,
+            last_transaction_timestamp: chrono::NaiveDateTime::default(),
+            non_transferrable_by_owner: None,
+            is_burned: false,
+        }
+    }
+
+    #[test]
+    fn test_v1_burn_tombstones_ownership() {
+        let mut tokens_burned = TokenV1Burned::new();
+        tokens_burned.insert(("0xtoken".to_string(), BigDecimal::zero(), "0xa".to_string()));
+
+        // Deleted from the store by a burn, the row keeps its owner as a tombstone
+        let owned = current_ownership("0xa", 1);
+        assert!(!owned.is_burned);
+        let mut burned = current_ownership("0xa", 0);
+        burned.mark_v1_burned(&tokens_burned);
+        assert!(burned.is_burned);
+        assert_eq!(burned.owner_address, owned.owner_address);
+
+        // Deleted from another owner's store in the same transaction, i.e. withdrawn, not burned
+        let mut withdrawn = current_ownership("0xb", 0);
+        withdrawn.mark_v1_burned(&tokens_burned);
+        assert!(!withdrawn.is_burned);
+
+        // Other property versions of the token aren't affected
+        let mut other_version = current_ownership("0xa", 0);
+        other_version.property_version_v1 = BigDecimal::one();
+        other_version.mark_v1_burned(&tokens_burned);
+        assert!(!other_version.is_burned);
+    }
+}
@@ -9,7 +9,8 @@ use crate::{
         coin_models::coin_utils::COIN_ADDR,
         default_models::move_resources::MoveResource,
         object_models::v2_object_utils::{CurrentObjectPK, ObjectCore, Untransferable},
-        token_models::token_utils::{NAME_LENGTH, URI_LENGTH},
+        token_models::token_utils::{truncate_name, URI_LENGTH},
+        token_v2_models::v2_token_datas::cap_token_properties,
     },
     utils::util::{
         deserialize_from_string, deserialize_token_object_property_map_from_bcs_hexstring,
@@ -75,7 +76,7 @@ impl Collection {
     }
 
     pub fn get_name_trunc(&self) -> String {
-        truncate_str(&self.name, NAME_LENGTH)
+        truncate_name(&self.name)
     }
 }
 
@@ -130,7 +131,7 @@ impl TokenV2 {
     }
 
     pub fn get_name_trunc(&self) -> String {
-        truncate_str(&self.name, NAME_LENGTH)
+        truncate_name(&self.name)
     }
 
     pub fn from_write_resource(
@@ -325,6 +326,55 @@ pub struct TokenMutationEvent {
     pub new_value: String,
 }
 
+/// Module event replacing `TokenMutationEvent`, which came from the token's event handle
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenMutation {
+    token_address: String,
+    pub mutated_field_name: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl TokenMutation {
+    pub fn get_token_address(&self) -> String {
+        standardize_address(&self.token_address)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectionMutationEvent {
+    pub mutated_field_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectionMutation {
+    pub mutated_field_name: String,
+    collection: ResourceReference,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl CollectionMutation {
+    pub fn get_collection_address(&self) -> String {
+        self.collection.get_reference_address()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetMaxSupply {
+    collection: ResourceReference,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub old_max_supply: BigDecimal,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    pub new_max_supply: BigDecimal,
+}
+
+impl SetMaxSupply {
+    pub fn get_collection_address(&self) -> String {
+        self.collection.get_reference_address()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BurnEvent {
     #[serde(deserialize_with = "deserialize_from_string")]
@@ -453,6 +503,29 @@ impl PropertyMapModel {
             Ok(None)
         }
     }
+
+    /// Same as `from_write_resource`, but a PropertyMap whose raw JSON is over `max_bytes` isn't
+    /// decoded and its replacement from `cap_token_properties` is returned instead
+    pub fn from_write_resource_capped(
+        write_resource: &WriteResource,
+        txn_version: i64,
+        max_bytes: Option<usize>,
+    ) -> anyhow::Result<Option<Self>> {
+        if let Some(max_bytes) = max_bytes {
+            let type_str = MoveResource::get_outer_type_from_write_resource(write_resource);
+            if type_str == format!("{}::property_map::PropertyMap", TOKEN_V2_ADDR) {
+                if let Some(inner) = cap_token_properties(
+                    &write_resource.data,
+                    max_bytes,
+                    txn_version,
+                    TokenStandard::V2,
+                ) {
+                    return Ok(Some(Self { inner }));
+                }
+            }
+        }
+        Self::from_write_resource(write_resource, txn_version)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -486,7 +559,7 @@ impl TokenIdentifiers {
     }
 
     pub fn get_name_trunc(&self) -> String {
-        truncate_str(&self.name.value, NAME_LENGTH)
+        truncate_name(&self.name.value)
     }
 }
 
@@ -578,9 +651,13 @@ pub enum V2TokenEvent {
     Mint(Mint),
     MintEvent(MintEvent),
     TokenMutationEvent(TokenMutationEvent),
+    TokenMutation(TokenMutation),
     Burn(Burn),
     BurnEvent(BurnEvent),
     TransferEvent(TransferEvent),
+    CollectionMutationEvent(CollectionMutationEvent),
+    CollectionMutation(CollectionMutation),
+    SetMaxSupply(SetMaxSupply),
 }
 
 impl V2TokenEvent {
@@ -595,6 +672,9 @@ impl V2TokenEvent {
             "0x4::token::MutationEvent" => {
                 serde_json::from_str(data).map(|inner| Some(Self::TokenMutationEvent(inner)))
             },
+            "0x4::token::Mutation" => {
+                serde_json::from_str(data).map(|inner| Some(Self::TokenMutation(inner)))
+            },
             "0x4::collection::Burn" => {
                 serde_json::from_str(data).map(|inner| Some(Self::Burn(inner)))
             },
@@ -604,6 +684,15 @@ impl V2TokenEvent {
             "0x1::object::TransferEvent" => {
                 serde_json::from_str(data).map(|inner| Some(Self::TransferEvent(inner)))
             },
+            "0x4::collection::MutationEvent" => {
+                serde_json::from_str(data).map(|inner| Some(Self::CollectionMutationEvent(inner)))
+            },
+            "0x4::collection::Mutation" => {
+                serde_json::from_str(data).map(|inner| Some(Self::CollectionMutation(inner)))
+            },
+            "0x4::collection::SetMaxSupply" => {
+                serde_json::from_str(data).map(|inner| Some(Self::SetMaxSupply(inner)))
+            },
             _ => Ok(None),
         }
         .context(format!(
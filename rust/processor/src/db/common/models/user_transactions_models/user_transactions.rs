@@ -16,11 +16,15 @@ use crate::{
     },
 };
 use aptos_protos::{
-    transaction::v1::{UserTransaction as UserTransactionPB, UserTransactionRequest},
+    transaction::v1::{
+        signature::Signature as SignatureEnum, UserTransaction as UserTransactionPB,
+        UserTransactionRequest,
+    },
     util::timestamp::Timestamp,
 };
 use bigdecimal::BigDecimal;
 use field_count::FieldCount;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Deserialize, Debug, FieldCount, Identifiable, Insertable, Serialize)]
@@ -99,6 +103,31 @@ impl UserTransaction {
             })
             .unwrap_or_default()
     }
+
+    /// Everyone who signed the transaction: the sender, the secondary signers of a multi agent or
+    /// fee payer transaction and the fee payer. Standardized and deduped, sender first.
+    pub fn get_signer_addresses(user_request: &UserTransactionRequest) -> Vec<String> {
+        let mut addresses = vec![user_request.sender.as_str()];
+        match user_request
+            .signature
+            .as_ref()
+            .and_then(|s| s.signature.as_ref())
+        {
+            Some(SignatureEnum::MultiAgent(sig)) => {
+                addresses.extend(sig.secondary_signer_addresses.iter().map(String::as_str));
+            },
+            Some(SignatureEnum::FeePayer(sig)) => {
+                addresses.extend(sig.secondary_signer_addresses.iter().map(String::as_str));
+                addresses.push(sig.fee_payer_address.as_str());
+            },
+            _ => {},
+        }
+        addresses
+            .into_iter()
+            .map(standardize_address)
+            .unique()
+            .collect()
+    }
 }
 
 // Prevent conflicts with other things named `Transaction`
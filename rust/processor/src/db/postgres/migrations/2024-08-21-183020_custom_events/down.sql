@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS ce_addr_type_index;
+DROP INDEX IF EXISTS ce_insat_index;
+DROP TABLE IF EXISTS custom_events;
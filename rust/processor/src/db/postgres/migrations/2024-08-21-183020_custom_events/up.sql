@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS custom_events (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  account_address VARCHAR(66) NOT NULL,
+  type TEXT NOT NULL,
+  data JSONB NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS ce_addr_type_index ON custom_events (account_address, type);
+CREATE INDEX IF NOT EXISTS ce_insat_index ON custom_events (inserted_at);
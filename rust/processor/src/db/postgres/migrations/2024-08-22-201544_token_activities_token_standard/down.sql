@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE token_activities DROP COLUMN IF EXISTS token_standard;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS token_standard VARCHAR(10) NOT NULL DEFAULT 'v1';
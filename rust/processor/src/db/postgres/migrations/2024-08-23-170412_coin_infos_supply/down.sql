@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE coin_infos DROP COLUMN IF EXISTS supply,
+  DROP COLUMN IF EXISTS last_transaction_version;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE coin_infos
+ADD COLUMN IF NOT EXISTS supply NUMERIC,
+  ADD COLUMN IF NOT EXISTS last_transaction_version BIGINT;
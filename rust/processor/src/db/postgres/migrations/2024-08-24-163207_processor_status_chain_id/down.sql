@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE processor_status DROP COLUMN IF EXISTS chain_id;
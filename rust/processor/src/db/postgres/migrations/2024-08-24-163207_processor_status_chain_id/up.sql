@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE processor_status
+ADD COLUMN IF NOT EXISTS chain_id BIGINT;
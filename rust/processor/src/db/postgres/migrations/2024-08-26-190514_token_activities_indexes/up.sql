@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- token_activities and its primary key (transaction_version, event_account_address, event_creation_number,
+-- event_sequence_number) already exist from 2022-09-22-185845_token_offers, and event_index from 2022-12-29-222902_curr_table_items.
+-- Lookups by (creator_address, collection_name) and by to_address are served by the leading columns of
+-- ta_addr_coll_name_pv_index and ta_to_ttyp_index, so only the timestamp index is missing.
+CREATE INDEX IF NOT EXISTS ta_txn_ts_index ON token_activities (transaction_timestamp);
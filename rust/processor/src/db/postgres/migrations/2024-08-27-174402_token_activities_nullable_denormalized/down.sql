@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE token_activities
+ALTER COLUMN creator_address
+SET NOT NULL,
+  ALTER COLUMN collection_name
+SET NOT NULL,
+  ALTER COLUMN name
+SET NOT NULL;
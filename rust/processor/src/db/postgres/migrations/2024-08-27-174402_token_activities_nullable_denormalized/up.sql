@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- These can be left out when token data is joined from a dimension table instead
+ALTER TABLE token_activities
+ALTER COLUMN creator_address DROP NOT NULL,
+  ALTER COLUMN collection_name DROP NOT NULL,
+  ALTER COLUMN name DROP NOT NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS coc_addr_tv_index;
+DROP INDEX IF EXISTS coc_insat_index;
+DROP TABLE IF EXISTS current_objects_changes;
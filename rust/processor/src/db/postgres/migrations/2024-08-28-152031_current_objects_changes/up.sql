@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Change rows for current_objects, written instead of upserting current_objects when the objects
+-- processor runs with emit_current_object_changes
+CREATE TABLE IF NOT EXISTS current_objects_changes (
+  transaction_version BIGINT NOT NULL,
+  write_set_change_index BIGINT NOT NULL,
+  object_address VARCHAR(66) NOT NULL,
+  old_value JSONB,
+  new_value JSONB NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, write_set_change_index)
+);
+CREATE INDEX IF NOT EXISTS coc_addr_tv_index ON current_objects_changes (object_address, transaction_version);
+CREATE INDEX IF NOT EXISTS coc_insat_index ON current_objects_changes (inserted_at);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS nma_token_data_id_index;
+DROP INDEX IF EXISTS nma_seller_index;
+DROP INDEX IF EXISTS nma_buyer_index;
+DROP INDEX IF EXISTS nma_insat_index;
+DROP TABLE IF EXISTS nft_marketplace_activities;
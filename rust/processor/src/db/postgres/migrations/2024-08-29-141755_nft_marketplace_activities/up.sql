@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS nft_marketplace_activities (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  marketplace_address VARCHAR(66) NOT NULL,
+  event_type TEXT NOT NULL,
+  activity_type VARCHAR(10) NOT NULL,
+  token_data_id VARCHAR(66),
+  price NUMERIC,
+  seller VARCHAR(66),
+  buyer VARCHAR(66),
+  entry_function_id_str VARCHAR(1000),
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS nma_token_data_id_index ON nft_marketplace_activities (token_data_id, transaction_version);
+CREATE INDEX IF NOT EXISTS nma_seller_index ON nft_marketplace_activities (seller);
+CREATE INDEX IF NOT EXISTS nma_buyer_index ON nft_marketplace_activities (buyer);
+CREATE INDEX IF NOT EXISTS nma_insat_index ON nft_marketplace_activities (inserted_at);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS ev_global_event_index;
+ALTER TABLE events DROP COLUMN IF EXISTS global_event_index;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- transaction_version * 100000 + event_index, only written when the events processor has
+-- compute_global_event_index set
+ALTER TABLE events
+ADD COLUMN IF NOT EXISTS global_event_index BIGINT;
+CREATE INDEX IF NOT EXISTS ev_global_event_index ON events (global_event_index);
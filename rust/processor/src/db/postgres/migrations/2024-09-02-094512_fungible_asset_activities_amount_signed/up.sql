@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- amount from the owner's perspective: negative for withdrawals and gas fees, positive for deposits
+ALTER TABLE fungible_asset_activities
+ADD COLUMN IF NOT EXISTS amount_signed NUMERIC;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS entry_function_id_str VARCHAR(1000);
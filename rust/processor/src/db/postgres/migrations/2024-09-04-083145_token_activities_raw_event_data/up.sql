@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS raw_event_data TEXT;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE current_token_datas_v2 DROP COLUMN IF EXISTS generation;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Number of times a token was created again after being burned
+ALTER TABLE current_token_datas_v2
+ADD COLUMN IF NOT EXISTS generation BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS ta_activity_type_index;
+ALTER TABLE token_activities DROP COLUMN IF EXISTS activity_type;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Short label for transfer_type, e.g. deposit for 0x3::token::DepositEvent
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS activity_type VARCHAR(50);
+CREATE INDEX IF NOT EXISTS ta_activity_type_index ON token_activities (activity_type);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE delegated_staking_activities DROP COLUMN IF EXISTS epoch;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Epoch of the transaction that emitted the event, null for rows written before this column
+ALTER TABLE delegated_staking_activities
+ADD COLUMN IF NOT EXISTS epoch BIGINT;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE token_activities DROP COLUMN IF EXISTS expiration_timestamp;
+ALTER TABLE token_activities_v2 DROP COLUMN IF EXISTS expiration_timestamp;
+ALTER TABLE current_token_pending_claims DROP COLUMN IF EXISTS expiration_timestamp;
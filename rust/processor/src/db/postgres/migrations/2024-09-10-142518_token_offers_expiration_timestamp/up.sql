@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Set from offer events that carry an expiration, null otherwise
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS expiration_timestamp TIMESTAMP;
+ALTER TABLE token_activities_v2
+ADD COLUMN IF NOT EXISTS expiration_timestamp TIMESTAMP;
+ALTER TABLE current_token_pending_claims
+ADD COLUMN IF NOT EXISTS expiration_timestamp TIMESTAMP;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS token_property_mutations (
+  token_data_id_hash VARCHAR(64) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  collection_data_id_hash VARCHAR(64) NOT NULL,
+  old_property_version NUMERIC NOT NULL,
+  new_property_version NUMERIC NOT NULL,
+  mutated_properties JSONB NOT NULL,
+  old_properties JSONB,
+  new_properties JSONB,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (token_data_id_hash, transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS tpm_version_index ON token_property_mutations (transaction_version);
+CREATE INDEX IF NOT EXISTS tpm_collection_index ON token_property_mutations (collection_data_id_hash);
+CREATE INDEX IF NOT EXISTS tpm_insat_index ON token_property_mutations (inserted_at);
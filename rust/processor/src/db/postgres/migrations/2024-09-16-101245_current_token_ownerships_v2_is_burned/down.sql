@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE IF EXISTS current_token_ownerships_v2 DROP COLUMN IF EXISTS is_burned;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE current_token_ownerships_v2
+ADD COLUMN IF NOT EXISTS is_burned BOOLEAN NOT NULL DEFAULT FALSE;
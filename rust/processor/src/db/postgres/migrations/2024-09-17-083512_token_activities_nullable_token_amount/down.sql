@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+UPDATE token_activities SET token_amount = 0 WHERE token_amount IS NULL;
+ALTER TABLE token_activities
+ALTER COLUMN token_amount SET NOT NULL;
+UPDATE token_activities_v2 SET token_amount = 0 WHERE token_amount IS NULL;
+ALTER TABLE token_activities_v2
+ALTER COLUMN token_amount SET NOT NULL;
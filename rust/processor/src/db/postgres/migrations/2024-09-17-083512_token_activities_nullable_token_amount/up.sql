@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE token_activities
+ALTER COLUMN token_amount DROP NOT NULL;
+ALTER TABLE token_activities_v2
+ALTER COLUMN token_amount DROP NOT NULL;
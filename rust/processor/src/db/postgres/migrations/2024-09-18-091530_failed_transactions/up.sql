@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS failed_transactions (
+  processor VARCHAR(50) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  error TEXT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  raw_transaction BYTEA,
+  PRIMARY KEY (processor, transaction_version)
+);
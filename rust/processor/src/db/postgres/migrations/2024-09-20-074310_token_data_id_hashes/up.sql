@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Full, untruncated names of each v1 token, to translate a token_data_id_hash back to the token
+CREATE TABLE IF NOT EXISTS token_data_id_hashes (
+  token_data_id_hash VARCHAR(64) NOT NULL,
+  creator_address VARCHAR(66) NOT NULL,
+  collection_name TEXT NOT NULL,
+  name TEXT NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (token_data_id_hash)
+);
+CREATE INDEX IF NOT EXISTS tdih_creator_collection_index ON token_data_id_hashes (creator_address, collection_name);
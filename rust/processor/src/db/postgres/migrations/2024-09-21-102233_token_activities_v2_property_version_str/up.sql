@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE token_activities_v2
+ADD COLUMN IF NOT EXISTS property_version_v1_str TEXT;
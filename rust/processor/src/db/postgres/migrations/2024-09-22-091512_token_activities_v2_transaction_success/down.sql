@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE token_activities_v2 DROP COLUMN IF EXISTS transaction_success;
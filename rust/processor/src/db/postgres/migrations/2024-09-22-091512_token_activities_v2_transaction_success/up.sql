@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE token_activities_v2
+ADD COLUMN IF NOT EXISTS transaction_success BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Gas and outcome of each user transaction, to join with token_activities_v2 by version. Needs
+-- both user_transaction_processor and default_processor
+CREATE OR REPLACE VIEW transaction_metadata AS
+SELECT ut.version,
+  t.gas_used,
+  ut.gas_unit_price,
+  ut.max_gas_amount,
+  ut.sender,
+  t.success,
+  t.vm_status,
+  ut.timestamp,
+  ut.epoch
+FROM user_transactions ut
+  JOIN transactions t ON t.version = ut.version;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS coin_balance_snapshots (
+  owner_address VARCHAR(66) NOT NULL,
+  asset_type VARCHAR(1000) NOT NULL,
+  epoch BIGINT NOT NULL,
+  amount NUMERIC NOT NULL,
+  token_standard VARCHAR(10) NOT NULL,
+  last_transaction_version BIGINT NOT NULL,
+  last_transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (owner_address, asset_type, epoch)
+);
+CREATE INDEX IF NOT EXISTS cbs_at_epoch_index ON coin_balance_snapshots (asset_type, epoch);
+CREATE INDEX IF NOT EXISTS cbs_insat_index ON coin_balance_snapshots (inserted_at);
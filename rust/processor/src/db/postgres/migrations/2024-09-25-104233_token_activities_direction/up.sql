@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- incoming, outgoing or internal relative to the parser's watched addresses, null without them
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS direction VARCHAR(10);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- 0x, the creation number as 8 little endian bytes and the account address, as the REST API prints event keys.
+-- Null on rows written before this column.
+ALTER TABLE token_activities
+ADD COLUMN IF NOT EXISTS event_key_string VARCHAR(82);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+-- The columns are also added by 2024-06-13-061711_untransferrable on the public schema, which drops them when it is
+-- reverted
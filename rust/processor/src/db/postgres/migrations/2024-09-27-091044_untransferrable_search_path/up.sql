@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- 2024-06-13-061711_untransferrable only alters the public schema's tables and isn't run on per chain schemas.
+-- Add the columns to the tables in the search_path instead, a no-op on the public schema.
+ALTER TABLE current_objects
+ADD COLUMN IF NOT EXISTS untransferrable BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE objects
+ADD COLUMN IF NOT EXISTS untransferrable BOOLEAN NOT NULL DEFAULT FALSE;
@@ -129,6 +129,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    coin_balance_snapshots (owner_address, asset_type, epoch) {
+        #[max_length = 66]
+        owner_address -> Varchar,
+        #[max_length = 1000]
+        asset_type -> Varchar,
+        epoch -> Int8,
+        amount -> Numeric,
+        #[max_length = 10]
+        token_standard -> Varchar,
+        last_transaction_version -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     coin_balances (transaction_version, owner_address, coin_type_hash) {
         transaction_version -> Int8,
@@ -163,6 +179,8 @@ diesel::table! {
         #[max_length = 66]
         supply_aggregator_table_handle -> Nullable<Varchar>,
         supply_aggregator_table_key -> Nullable<Text>,
+        supply -> Nullable<Numeric>,
+        last_transaction_version -> Nullable<Int8>,
     }
 }
 
@@ -457,6 +475,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    current_objects_changes (transaction_version, write_set_change_index) {
+        transaction_version -> Int8,
+        write_set_change_index -> Int8,
+        #[max_length = 66]
+        object_address -> Varchar,
+        old_value -> Nullable<Jsonb>,
+        new_value -> Jsonb,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     current_staking_pool_voter (staking_pool_address) {
         #[max_length = 66]
@@ -542,6 +572,7 @@ diesel::table! {
         inserted_at -> Timestamp,
         decimals -> Nullable<Int8>,
         is_deleted_v2 -> Nullable<Bool>,
+        generation -> Int8,
     }
 }
 
@@ -590,6 +621,7 @@ diesel::table! {
         last_transaction_timestamp -> Timestamp,
         inserted_at -> Timestamp,
         non_transferrable_by_owner -> Nullable<Bool>,
+        is_burned -> Bool,
     }
 }
 
@@ -620,6 +652,7 @@ diesel::table! {
         token_data_id -> Varchar,
         #[max_length = 66]
         collection_id -> Varchar,
+        expiration_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -680,6 +713,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    custom_events (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[max_length = 66]
+        account_address -> Varchar,
+        #[sql_name = "type"]
+        type_ -> Text,
+        data -> Jsonb,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     delegated_staking_activities (transaction_version, event_index) {
         transaction_version -> Int8,
@@ -691,6 +737,7 @@ diesel::table! {
         event_type -> Text,
         amount -> Numeric,
         inserted_at -> Timestamp,
+        epoch -> Nullable<Int8>,
     }
 }
 
@@ -763,6 +810,18 @@ diesel::table! {
         event_index -> Int8,
         #[max_length = 300]
         indexed_type -> Varchar,
+        global_event_index -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    failed_transactions (processor, transaction_version) {
+        #[max_length = 50]
+        processor -> Varchar,
+        transaction_version -> Int8,
+        error -> Text,
+        inserted_at -> Timestamp,
+        raw_transaction -> Nullable<Bytea>,
     }
 }
 
@@ -792,6 +851,7 @@ diesel::table! {
         transaction_timestamp -> Timestamp,
         inserted_at -> Timestamp,
         storage_refund_amount -> Numeric,
+        amount_signed -> Nullable<Numeric>,
     }
 }
 
@@ -896,6 +956,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    nft_marketplace_activities (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[max_length = 66]
+        marketplace_address -> Varchar,
+        event_type -> Text,
+        #[max_length = 10]
+        activity_type -> Varchar,
+        #[max_length = 66]
+        token_data_id -> Nullable<Varchar>,
+        price -> Nullable<Numeric>,
+        #[max_length = 66]
+        seller -> Nullable<Varchar>,
+        #[max_length = 66]
+        buyer -> Nullable<Varchar>,
+        #[max_length = 1000]
+        entry_function_id_str -> Nullable<Varchar>,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     nft_points (transaction_version) {
         transaction_version -> Int8,
@@ -934,6 +1017,7 @@ diesel::table! {
         last_success_version -> Int8,
         last_updated -> Timestamp,
         last_transaction_timestamp -> Nullable<Timestamp>,
+        chain_id -> Nullable<Int8>,
     }
 }
 
@@ -1019,23 +1103,35 @@ diesel::table! {
         token_data_id_hash -> Varchar,
         property_version -> Numeric,
         #[max_length = 66]
-        creator_address -> Varchar,
+        creator_address -> Nullable<Varchar>,
         #[max_length = 128]
-        collection_name -> Varchar,
+        collection_name -> Nullable<Varchar>,
         #[max_length = 128]
-        name -> Varchar,
+        name -> Nullable<Varchar>,
         #[max_length = 50]
         transfer_type -> Varchar,
         #[max_length = 66]
         from_address -> Nullable<Varchar>,
         #[max_length = 66]
         to_address -> Nullable<Varchar>,
-        token_amount -> Numeric,
+        token_amount -> Nullable<Numeric>,
         coin_type -> Nullable<Text>,
         coin_amount -> Nullable<Numeric>,
         inserted_at -> Timestamp,
         transaction_timestamp -> Timestamp,
         event_index -> Nullable<Int8>,
+        #[max_length = 10]
+        token_standard -> Varchar,
+        #[max_length = 1000]
+        entry_function_id_str -> Nullable<Varchar>,
+        raw_event_data -> Nullable<Text>,
+        #[max_length = 50]
+        activity_type -> Nullable<Varchar>,
+        expiration_timestamp -> Nullable<Timestamp>,
+        #[max_length = 10]
+        direction -> Nullable<Varchar>,
+        #[max_length = 82]
+        event_key_string -> Nullable<Varchar>,
     }
 }
 
@@ -1054,7 +1150,7 @@ diesel::table! {
         from_address -> Nullable<Varchar>,
         #[max_length = 66]
         to_address -> Nullable<Varchar>,
-        token_amount -> Numeric,
+        token_amount -> Nullable<Numeric>,
         before_value -> Nullable<Text>,
         after_value -> Nullable<Text>,
         #[max_length = 1000]
@@ -1064,6 +1160,22 @@ diesel::table! {
         is_fungible_v2 -> Nullable<Bool>,
         transaction_timestamp -> Timestamp,
         inserted_at -> Timestamp,
+        expiration_timestamp -> Nullable<Timestamp>,
+        property_version_v1_str -> Nullable<Text>,
+        transaction_success -> Bool,
+    }
+}
+
+diesel::table! {
+    token_data_id_hashes (token_data_id_hash) {
+        #[max_length = 64]
+        token_data_id_hash -> Varchar,
+        #[max_length = 66]
+        creator_address -> Varchar,
+        collection_name -> Text,
+        name -> Text,
+        transaction_version -> Int8,
+        inserted_at -> Timestamp,
     }
 }
 
@@ -1178,6 +1290,24 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    token_property_mutations (token_data_id_hash, transaction_version, event_index) {
+        #[max_length = 64]
+        token_data_id_hash -> Varchar,
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[max_length = 64]
+        collection_data_id_hash -> Varchar,
+        old_property_version -> Numeric,
+        new_property_version -> Numeric,
+        mutated_properties -> Jsonb,
+        old_properties -> Nullable<Jsonb>,
+        new_properties -> Nullable<Jsonb>,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tokens (token_data_id_hash, property_version, transaction_version) {
         #[max_length = 64]
@@ -1288,6 +1418,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     ans_primary_name_v2,
     block_metadata_transactions,
     coin_activities,
+    coin_balance_snapshots,
     coin_balances,
     coin_infos,
     coin_supply,
@@ -1305,6 +1436,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     current_delegator_balances,
     current_fungible_asset_balances,
     current_objects,
+    current_objects_changes,
     current_staking_pool_voter,
     current_table_items,
     current_token_datas,
@@ -1315,12 +1447,14 @@ diesel::allow_tables_to_appear_in_same_query!(
     current_token_royalty_v1,
     current_token_v2_metadata,
     current_unified_fungible_asset_balances_to_be_renamed,
+    custom_events,
     delegated_staking_activities,
     delegated_staking_pool_balances,
     delegated_staking_pools,
     delegator_balances,
     event_size_info,
     events,
+    failed_transactions,
     fungible_asset_activities,
     fungible_asset_balances,
     fungible_asset_metadata,
@@ -1328,6 +1462,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     ledger_infos,
     move_modules,
     move_resources,
+    nft_marketplace_activities,
     nft_points,
     objects,
     processor_status,
@@ -1338,10 +1473,12 @@ diesel::allow_tables_to_appear_in_same_query!(
     table_metadatas,
     token_activities,
     token_activities_v2,
+    token_data_id_hashes,
     token_datas,
     token_datas_v2,
     token_ownerships,
     token_ownerships_v2,
+    token_property_mutations,
     tokens,
     transaction_size_info,
     transactions,
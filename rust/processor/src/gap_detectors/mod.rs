@@ -5,7 +5,10 @@ use crate::{
         parquet_gap_detector::{ParquetFileGapDetectorInner, ParquetFileGapDetectorResult},
     },
     processors::{DefaultProcessingResult, Processor, ProcessorTrait},
-    utils::counters::{PARQUET_PROCESSOR_DATA_GAP_COUNT, PROCESSOR_DATA_GAP_COUNT},
+    utils::{
+        counters::{PARQUET_PROCESSOR_DATA_GAP_COUNT, PROCESSOR_DATA_GAP_COUNT},
+        progress_file::ProgressTracker,
+    },
     worker::PROCESSOR_SERVICE_TYPE,
 };
 use anyhow::Result;
@@ -61,6 +64,9 @@ pub async fn create_gap_detector_status_tracker_loop(
     gap_detector_receiver: AsyncReceiver<ProcessingResult>,
     processor: Processor,
     gap_detection_batch_size: u64,
+    chain_id: u64,
+    processor_status_heartbeat_secs: Option<u64>,
+    progress_tracker: Option<ProgressTracker>,
 ) {
     let processor_name = processor.name();
     tracing::info!(
@@ -70,8 +76,44 @@ pub async fn create_gap_detector_status_tracker_loop(
     );
 
     let mut last_update_time = std::time::Instant::now();
+    // Latest (version, timestamp) the status can be moved to, rewritten by the heartbeat
+    let mut latest_status = None;
     loop {
-        match gap_detector_receiver.recv().await {
+        let received = match processor_status_heartbeat_secs {
+            Some(heartbeat_secs) => {
+                match tokio::time::timeout(
+                    std::time::Duration::from_secs(heartbeat_secs),
+                    gap_detector_receiver.recv(),
+                )
+                .await
+                {
+                    Ok(received) => received,
+                    Err(_) => {
+                        // No batches for a while, e.g. caught up and idle. Rewrite the status so
+                        // last_updated keeps moving and any throttled update gets written
+                        if let Some((version, last_transaction_timestamp)) = &latest_status {
+                            tracing::debug!(
+                                processor_name,
+                                last_processed_version = version,
+                                "[Parser] Writing processor status heartbeat",
+                            );
+                            processor
+                                .update_last_processed_version(
+                                    *version,
+                                    last_transaction_timestamp.clone(),
+                                    chain_id,
+                                )
+                                .await
+                                .unwrap();
+                            last_update_time = std::time::Instant::now();
+                        }
+                        continue;
+                    },
+                }
+            },
+            None => gap_detector_receiver.recv().await,
+        };
+        match received {
             Ok(ProcessingResult::DefaultProcessingResult(result)) => {
                 match gap_detector
                     .process_versions(ProcessingResult::DefaultProcessingResult(result))
@@ -92,6 +134,18 @@ pub async fn create_gap_detector_status_tracker_loop(
                                     // We don't panic as everything downstream will panic if it doesn't work/receive
                                 }
                                 if let Some(res_last_success_batch) = res.last_success_batch {
+                                    latest_status = Some((
+                                        res_last_success_batch.end_version,
+                                        res_last_success_batch.last_transaction_timestamp.clone(),
+                                    ));
+                                    if let Some(progress_tracker) = &progress_tracker {
+                                        progress_tracker.update(
+                                            res_last_success_batch.end_version,
+                                            res_last_success_batch
+                                                .last_transaction_timestamp
+                                                .clone(),
+                                        );
+                                    }
                                     if last_update_time.elapsed().as_secs()
                                         >= UPDATE_PROCESSOR_STATUS_SECS
                                     {
@@ -101,6 +155,7 @@ pub async fn create_gap_detector_status_tracker_loop(
                                                 res_last_success_batch
                                                     .last_transaction_timestamp
                                                     .clone(),
+                                                chain_id,
                                             )
                                             .await
                                             .unwrap();
@@ -145,6 +200,16 @@ pub async fn create_gap_detector_status_tracker_loop(
                                     // We don't panic as everything downstream will panic if it doesn't work/receive
                                 }
 
+                                latest_status = Some((
+                                    res.next_version_to_process,
+                                    res.last_transaction_timestamp.clone(),
+                                ));
+                                if let Some(progress_tracker) = &progress_tracker {
+                                    progress_tracker.update(
+                                        res.next_version_to_process,
+                                        res.last_transaction_timestamp.clone(),
+                                    );
+                                }
                                 if last_update_time.elapsed().as_secs()
                                     >= UPDATE_PROCESSOR_STATUS_SECS
                                 {
@@ -157,6 +222,7 @@ pub async fn create_gap_detector_status_tracker_loop(
                                         .update_last_processed_version(
                                             res.next_version_to_process,
                                             res.last_transaction_timestamp,
+                                            chain_id,
                                         )
                                         .await
                                         .unwrap();
@@ -1,11 +1,20 @@
 use crate::utils::{
+    batch_accumulator::BatchAccumulator,
+    block_timestamp::BlockTimestampCache,
     counters::{
-        ProcessorStep, FETCHER_THREAD_CHANNEL_SIZE, LATEST_PROCESSED_VERSION,
-        NUM_TRANSACTIONS_FILTERED_OUT_COUNT, NUM_TRANSACTIONS_PROCESSED_COUNT,
-        PROCESSED_BYTES_COUNT, TRANSACTION_UNIX_TIMESTAMP,
+        ProcessorStep, BUFFERED_TRANSACTIONS_COMPRESSED_BYTES_COUNT,
+        BUFFERED_TRANSACTIONS_COMPRESSION_TIME_IN_SECS, BUFFERED_TRANSACTIONS_RAW_BYTES_COUNT,
+        FETCHER_THREAD_CHANNEL_SIZE, GRPC_ENDPOINT_FAILOVER_COUNT, LATEST_PROCESSED_VERSION,
+        MISSING_TIMESTAMP_TRANSACTIONS_COUNT, NUM_TRANSACTIONS_FILTERED_OUT_COUNT,
+        NUM_TRANSACTIONS_PROCESSED_COUNT, PROCESSED_BYTES_COUNT, REORDER_BUFFER_SIZE,
+        STREAM_RESET_SKIPPED_TRANSACTIONS_COUNT, TRANSACTION_UNIX_TIMESTAMP,
+        TRUNCATED_EVENTS_RESPONSE_COUNT,
     },
-    util::{timestamp_to_iso, timestamp_to_unixtime},
+    reorder_buffer::ReorderBuffer,
+    skipped_transactions::{SkipReason, SkippedTransactions},
+    util::{get_event_count_mismatch, timestamp_to_iso, timestamp_to_unixtime},
 };
+use anyhow::Context;
 use aptos_moving_average::MovingAverage;
 use aptos_protos::{
     indexer::v1::{raw_data_client::RawDataClient, GetTransactionsRequest, TransactionsResponse},
@@ -32,8 +41,10 @@ const GRPC_REQUEST_NAME_HEADER: &str = "x-aptos-request-name";
 const GRPC_CONNECTION_ID: &str = "x-aptos-connection-id";
 /// We will try to reconnect to GRPC 5 times in case upstream connection is being updated
 pub const RECONNECTION_MAX_RETRIES: u64 = 5;
-/// 256MB
+/// 256MB, default of `indexer_grpc_max_decoding_message_size`
 pub const MAX_RESPONSE_SIZE: usize = 1024 * 1024 * 256;
+/// zstd level used for buffered batches. Low levels are much faster and most of the gain is there
+const BUFFER_COMPRESSION_LEVEL: i32 = 1;
 
 #[derive(Clone)]
 pub struct TransactionsPBResponse {
@@ -45,6 +56,35 @@ pub struct TransactionsPBResponse {
     pub start_txn_timestamp: Option<Timestamp>,
     pub end_txn_timestamp: Option<Timestamp>,
     pub size_in_bytes: u64,
+    // zstd compressed `transactions`, set while the batch sits in the channel if buffer compression is on
+    pub compressed_transactions: Option<Vec<u8>>,
+}
+
+impl TransactionsPBResponse {
+    /// Replaces `transactions` with a zstd compressed copy and returns the compressed size
+    pub fn compress(&mut self) -> anyhow::Result<usize> {
+        let mut encoded = Vec::with_capacity(self.size_in_bytes as usize);
+        for txn in std::mem::take(&mut self.transactions) {
+            txn.encode_length_delimited(&mut encoded)?;
+        }
+        let compressed = zstd::encode_all(encoded.as_slice(), BUFFER_COMPRESSION_LEVEL)?;
+        let compressed_size = compressed.len();
+        self.compressed_transactions = Some(compressed);
+        Ok(compressed_size)
+    }
+
+    /// Restores `transactions` if the batch was compressed. No-op otherwise.
+    pub fn decompress(&mut self) -> anyhow::Result<()> {
+        if let Some(compressed) = self.compressed_transactions.take() {
+            let encoded = zstd::decode_all(compressed.as_slice())?;
+            let mut buf = encoded.as_slice();
+            while !buf.is_empty() {
+                self.transactions
+                    .push(Transaction::decode_length_delimited(&mut buf)?);
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn grpc_request_builder(
@@ -75,11 +115,12 @@ pub async fn get_stream(
     indexer_grpc_http2_ping_interval: Duration,
     indexer_grpc_http2_ping_timeout: Duration,
     indexer_grpc_reconnection_timeout_secs: Duration,
+    max_decoding_message_size: usize,
     starting_version: u64,
     ending_version: Option<u64>,
     auth_token: String,
     processor_name: String,
-) -> Response<Streaming<TransactionsResponse>> {
+) -> anyhow::Result<Response<Streaming<TransactionsResponse>>> {
     info!(
         processor_name = processor_name,
         service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
@@ -146,14 +187,14 @@ pub async fn get_stream(
             },
         }
     }
-    .expect("[Parser] Timeout connecting to GRPC server");
+    .context("[Parser] Timeout connecting to GRPC server")?;
 
     let mut rpc_client = match connect_res {
         Ok(client) => client
             .accept_compressed(tonic::codec::CompressionEncoding::Gzip)
             .accept_compressed(tonic::codec::CompressionEncoding::Zstd)
             .send_compressed(tonic::codec::CompressionEncoding::Zstd)
-            .max_decoding_message_size(MAX_RESPONSE_SIZE)
+            .max_decoding_message_size(max_decoding_message_size)
             .max_encoding_message_size(MAX_RESPONSE_SIZE),
         Err(e) => {
             error!(
@@ -165,7 +206,7 @@ pub async fn get_stream(
                 error = ?e,
                 "[Parser] Error connecting to GRPC client"
             );
-            panic!("[Parser] Error connecting to GRPC client");
+            return Err(anyhow::Error::new(e).context("[Parser] Error connecting to GRPC client"));
         },
     };
     let count = ending_version.map(|v| (v as i64 - starting_version as i64 + 1) as u64);
@@ -213,10 +254,10 @@ pub async fn get_stream(
             },
         }
     }
-    .expect("[Parser] Timed out making grpc request after max retries.");
+    .context("[Parser] Timed out making grpc request after max retries.")?;
 
     match stream_res {
-        Ok(stream) => stream,
+        Ok(stream) => Ok(stream),
         Err(e) => {
             error!(
                 processor_name = processor_name,
@@ -227,36 +268,47 @@ pub async fn get_stream(
                 error = ?e,
                 "[Parser] Failed to get grpc response. Is the server running?"
             );
-            panic!("[Parser] Failed to get grpc response. Is the server running?");
+            Err(anyhow::Error::new(e)
+                .context("[Parser] Failed to get grpc response. Is the server running?"))
         },
     }
 }
 
 pub async fn get_chain_id(
-    indexer_grpc_data_service_address: Url,
+    indexer_grpc_data_service_addresses: Vec<Url>,
     indexer_grpc_http2_ping_interval: Duration,
     indexer_grpc_http2_ping_timeout: Duration,
     indexer_grpc_reconnection_timeout_secs: Duration,
+    max_decoding_message_size: usize,
     auth_token: String,
     processor_name: String,
 ) -> u64 {
-    info!(
-        processor_name = processor_name,
-        service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
-        stream_address = indexer_grpc_data_service_address.to_string(),
-        "[Parser] Connecting to GRPC stream to get chain id",
-    );
-    let response = get_stream(
-        indexer_grpc_data_service_address.clone(),
-        indexer_grpc_http2_ping_interval,
-        indexer_grpc_http2_ping_timeout,
-        indexer_grpc_reconnection_timeout_secs,
-        1,
-        Some(2),
-        auth_token.clone(),
-        processor_name.to_string(),
-    )
-    .await;
+    let mut endpoints = GrpcEndpoints::new(indexer_grpc_data_service_addresses);
+    let response = loop {
+        info!(
+            processor_name = processor_name,
+            service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+            stream_address = endpoints.current().to_string(),
+            "[Parser] Connecting to GRPC stream to get chain id",
+        );
+        match get_stream(
+            endpoints.current().clone(),
+            indexer_grpc_http2_ping_interval,
+            indexer_grpc_http2_ping_timeout,
+            indexer_grpc_reconnection_timeout_secs,
+            max_decoding_message_size,
+            1,
+            Some(2),
+            auth_token.clone(),
+            processor_name.to_string(),
+        )
+        .await
+        {
+            Ok(response) => break response,
+            Err(_) => fail_over(&mut endpoints, &processor_name, 1),
+        }
+    };
+    let indexer_grpc_data_service_address = endpoints.current().clone();
     let connection_id = match response.metadata().get(GRPC_CONNECTION_ID) {
         Some(connection_id) => connection_id.to_str().unwrap().to_string(),
         None => "".to_string(),
@@ -281,6 +333,12 @@ pub async fn get_chain_id(
                 error = ?rpc_error,
                 "[Parser] Error receiving datastream response for chain id"
             );
+            if is_message_too_large(&rpc_error) {
+                panic!(
+                    "[Parser] Response for chain id exceeded indexer_grpc_max_decoding_message_size of {} bytes",
+                    max_decoding_message_size
+                );
+            }
             panic!("[Parser] Error receiving datastream response for chain id");
         },
         None => {
@@ -296,6 +354,175 @@ pub async fn get_chain_id(
     }
 }
 
+/// The data service endpoints to stream from, the primary first, then the fallbacks. The stream
+/// moves on to the next one once the current one has failed `RECONNECTION_MAX_RETRIES` times.
+pub struct GrpcEndpoints {
+    addresses: Vec<Url>,
+    current: usize,
+    // Endpoints given up on since the last response. Once that's all of them, the stream stops
+    failovers_since_success: usize,
+}
+
+impl GrpcEndpoints {
+    pub fn new(addresses: Vec<Url>) -> Self {
+        assert!(
+            !addresses.is_empty(),
+            "[Parser] No GRPC endpoint to stream from"
+        );
+        Self {
+            addresses,
+            current: 0,
+            failovers_since_success: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Url {
+        &self.addresses[self.current]
+    }
+
+    /// Moves on to the next endpoint, wrapping around to the primary. Returns false instead when
+    /// every endpoint has failed since the last successful response.
+    pub fn fail_over(&mut self) -> bool {
+        if self.failovers_since_success + 1 >= self.addresses.len() {
+            return false;
+        }
+        self.failovers_since_success += 1;
+        self.current = (self.current + 1) % self.addresses.len();
+        true
+    }
+
+    pub fn mark_success(&mut self) {
+        self.failovers_since_success = 0;
+    }
+}
+
+/// Fails over to the next endpoint, counting it in `indexer_processor_grpc_endpoint_failover_count`.
+/// Panics once every endpoint has failed, as a single endpoint did before failover.
+fn fail_over(endpoints: &mut GrpcEndpoints, processor_name: &str, next_version_to_fetch: u64) {
+    let failed_address = endpoints.current().to_string();
+    if !endpoints.fail_over() {
+        error!(
+            processor_name = processor_name,
+            service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+            stream_address = failed_address,
+            "[Parser] Reconnected more than {RECONNECTION_MAX_RETRIES} times to every GRPC endpoint. Will not retry.",
+        );
+        panic!("[Parser] Reconnected more than {RECONNECTION_MAX_RETRIES} times to every GRPC endpoint. Will not retry.")
+    }
+    GRPC_ENDPOINT_FAILOVER_COUNT
+        .with_label_values(&[processor_name])
+        .inc();
+    tracing::warn!(
+        processor_name = processor_name,
+        service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+        failed_address,
+        stream_address = endpoints.current().to_string(),
+        next_version_to_fetch,
+        "[Parser] Failing over to the next GRPC endpoint",
+    );
+}
+
+/// Whether the stream failed on a response over `max_decoding_message_size`, which tonic reports
+/// as OutOfRange. Servers can also use OutOfRange, hence the message check.
+fn is_message_too_large(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::OutOfRange
+        && status.message().contains("message length too large")
+}
+
+/// Compresses a batch before it goes into the channel, tracking how much memory it saves
+fn compress_txn_pb(txn_pb: &mut TransactionsPBResponse, processor_name: &str) {
+    let compress_time = std::time::Instant::now();
+    match txn_pb.compress() {
+        Ok(compressed_size) => {
+            BUFFERED_TRANSACTIONS_RAW_BYTES_COUNT
+                .with_label_values(&[processor_name])
+                .inc_by(txn_pb.size_in_bytes);
+            BUFFERED_TRANSACTIONS_COMPRESSED_BYTES_COUNT
+                .with_label_values(&[processor_name])
+                .inc_by(compressed_size as u64);
+            BUFFERED_TRANSACTIONS_COMPRESSION_TIME_IN_SECS
+                .with_label_values(&[processor_name, "compress"])
+                .observe(compress_time.elapsed().as_secs_f64());
+        },
+        Err(e) => {
+            error!(
+                processor_name = processor_name,
+                start_version = txn_pb.start_version,
+                end_version = txn_pb.end_version,
+                error = ?e,
+                "[Parser] Error compressing transactions batch"
+            );
+            panic!("[Parser] Error compressing transactions batch")
+        },
+    }
+}
+
+/// Sends a batch to the processing tasks, broken down into `pb_channel_txn_chunk_size` txns max
+async fn send_txn_pb(
+    txn_sender: &AsyncSender<TransactionsPBResponse>,
+    txn_pb: TransactionsPBResponse,
+    pb_channel_txn_chunk_size: usize,
+    compress_buffered_transactions: bool,
+    processor_name: &str,
+    indexer_grpc_data_service_address: &Url,
+    connection_id: &str,
+) {
+    let txn_pbs = if txn_pb.transactions.len() < pb_channel_txn_chunk_size {
+        // We only need to send one; avoid the chunk/clone
+        vec![txn_pb]
+    } else {
+        // We are breaking down a big batch into small batches; this involves an iterator
+        let average_size_in_bytes = txn_pb.size_in_bytes / txn_pb.transactions.len() as u64;
+        txn_pb
+            .transactions
+            .into_iter()
+            .chunks(pb_channel_txn_chunk_size)
+            .into_iter()
+            .map(|chunk| {
+                let txns: Vec<Transaction> = chunk.collect();
+                TransactionsPBResponse {
+                    size_in_bytes: average_size_in_bytes * txns.len() as u64,
+                    transactions: txns,
+                    chain_id: txn_pb.chain_id,
+                    start_version: txn_pb.start_version,
+                    end_version: txn_pb.end_version,
+                    // TODO: this is only for gap checker + filtered txns, but this is wrong
+                    start_txn_timestamp: txn_pb.start_txn_timestamp.clone(),
+                    end_txn_timestamp: txn_pb.end_txn_timestamp.clone(),
+                    compressed_transactions: None,
+                }
+            })
+            .collect()
+    };
+    for mut txn_pb in txn_pbs {
+        if compress_buffered_transactions {
+            compress_txn_pb(&mut txn_pb, processor_name);
+        }
+
+        match txn_sender.send(txn_pb).await {
+            Ok(()) => {},
+            Err(e) => {
+                error!(
+                    processor_name = processor_name,
+                    stream_address = indexer_grpc_data_service_address.to_string(),
+                    connection_id,
+                    error = ?e,
+                    "[Parser] Error sending GRPC response to channel."
+                );
+                panic!("[Parser] Error sending GRPC response to channel.")
+            },
+        }
+    }
+}
+
+/// Drops the transactions at or below `last_fetched_version`, which the stream can send again
+/// after the data service restarts from an earlier version. Returns how many were dropped.
+fn drop_already_fetched(transactions: &mut Vec<Transaction>, last_fetched_version: i64) -> usize {
+    let num_txns = transactions.len();
+    transactions.retain(|txn| txn.version as i64 > last_fetched_version);
+    num_txns - transactions.len()
+}
+
 /// Gets a batch of transactions from the stream. Batch size is set in the grpc server.
 /// The number of batches depends on our config
 /// There could be several special scenarios:
@@ -304,38 +531,57 @@ pub async fn get_chain_id(
 ///    all existing transactions are processed
 pub async fn create_fetcher_loop(
     txn_sender: AsyncSender<TransactionsPBResponse>,
-    indexer_grpc_data_service_address: Url,
+    // The primary endpoint first, then the ones to fail over to
+    indexer_grpc_data_service_addresses: Vec<Url>,
     indexer_grpc_http2_ping_interval: Duration,
     indexer_grpc_http2_ping_timeout: Duration,
     indexer_grpc_reconnection_timeout_secs: Duration,
     indexer_grpc_response_item_timeout_secs: Duration,
+    max_decoding_message_size: usize,
     starting_version: u64,
+    // Timestamp of the transaction before starting_version, for the ones that come without one
+    previous_timestamp: Option<Timestamp>,
     request_ending_version: Option<u64>,
     auth_token: String,
     processor_name: String,
     transaction_filter: crate::transaction_filter::TransactionFilter,
     // The number of transactions per protobuf batch
     pb_channel_txn_chunk_size: usize,
+    // Keep batches zstd compressed while they wait in the channel
+    compress_buffered_transactions: bool,
+    // Max number of out of order transactions to hold back. 0 disables reordering
+    reorder_buffer_size: usize,
+    // Merge responses until there are pb_channel_txn_chunk_size txns or this much time has passed
+    pb_channel_flush_interval: Option<Duration>,
 ) {
-    info!(
-        processor_name = processor_name,
-        service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
-        stream_address = indexer_grpc_data_service_address.to_string(),
-        start_version = starting_version,
-        end_version = request_ending_version,
-        "[Parser] Connecting to GRPC stream",
-    );
-    let mut response = get_stream(
-        indexer_grpc_data_service_address.clone(),
-        indexer_grpc_http2_ping_interval,
-        indexer_grpc_http2_ping_timeout,
-        indexer_grpc_reconnection_timeout_secs,
-        starting_version,
-        request_ending_version,
-        auth_token.clone(),
-        processor_name.to_string(),
-    )
-    .await;
+    let mut endpoints = GrpcEndpoints::new(indexer_grpc_data_service_addresses);
+    let response = loop {
+        info!(
+            processor_name = processor_name,
+            service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+            stream_address = endpoints.current().to_string(),
+            start_version = starting_version,
+            end_version = request_ending_version,
+            "[Parser] Connecting to GRPC stream",
+        );
+        match get_stream(
+            endpoints.current().clone(),
+            indexer_grpc_http2_ping_interval,
+            indexer_grpc_http2_ping_timeout,
+            indexer_grpc_reconnection_timeout_secs,
+            max_decoding_message_size,
+            starting_version,
+            request_ending_version,
+            auth_token.clone(),
+            processor_name.to_string(),
+        )
+        .await
+        {
+            Ok(response) => break response,
+            Err(_) => fail_over(&mut endpoints, &processor_name, starting_version),
+        }
+    };
+    let mut indexer_grpc_data_service_address = endpoints.current().clone();
     let mut connection_id = match response.metadata().get(GRPC_CONNECTION_ID) {
         Some(connection_id) => connection_id.to_str().unwrap().to_string(),
         None => "".to_string(),
@@ -357,19 +603,181 @@ pub async fn create_fetcher_loop(
     let mut last_fetched_version = starting_version as i64 - 1;
     let mut fetch_ma = MovingAverage::new(3000);
     let mut send_ma = MovingAverage::new(3000);
+    let mut reorder_buffer = (reorder_buffer_size > 0)
+        .then(|| ReorderBuffer::new(reorder_buffer_size, starting_version));
+    let mut max_fetched_version = starting_version as i64 - 1;
+    let mut batch_accumulator = pb_channel_flush_interval
+        .map(|interval| BatchAccumulator::new(pb_channel_txn_chunk_size, interval));
+    let mut block_timestamp_cache = BlockTimestampCache::new(previous_timestamp);
 
     loop {
-        let is_success = match tokio::time::timeout(
-            indexer_grpc_response_item_timeout_secs,
-            resp_stream.next(),
-        )
-        .await
+        // Stop pulling from the stream while paused, after sending on what we have so far.
+        if server_framework::is_paused() {
+            if let Some(txn_pb) = batch_accumulator.as_mut().and_then(BatchAccumulator::take) {
+                send_txn_pb(
+                    &txn_sender,
+                    txn_pb,
+                    pb_channel_txn_chunk_size,
+                    compress_buffered_transactions,
+                    &processor_name,
+                    &indexer_grpc_data_service_address,
+                    &connection_id,
+                )
+                .await;
+            }
+            info!(
+                processor_name = processor_name,
+                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                stream_address = indexer_grpc_data_service_address.to_string(),
+                connection_id,
+                next_version_to_fetch,
+                "[Parser] Processing paused, holding before the next fetch",
+            );
+            server_framework::wait_while_paused().await;
+            info!(
+                processor_name = processor_name,
+                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                stream_address = indexer_grpc_data_service_address.to_string(),
+                connection_id,
+                next_version_to_fetch,
+                "[Parser] Processing resumed",
+            );
+        }
+        let next_response =
+            tokio::time::timeout(indexer_grpc_response_item_timeout_secs, resp_stream.next());
+        let next_response = match batch_accumulator
+            .as_ref()
+            .and_then(BatchAccumulator::deadline)
         {
+            Some(deadline) => tokio::select! {
+                response = next_response => response,
+                // Nothing more came in time, send on the partial batch
+                _ = tokio::time::sleep_until(deadline) => {
+                    let txn_pb = batch_accumulator.as_mut().and_then(BatchAccumulator::take);
+                    if let Some(txn_pb) = txn_pb {
+                        send_txn_pb(
+                            &txn_sender,
+                            txn_pb,
+                            pb_channel_txn_chunk_size,
+                            compress_buffered_transactions,
+                            &processor_name,
+                            &indexer_grpc_data_service_address,
+                            &connection_id,
+                        )
+                        .await;
+                    }
+                    continue;
+                },
+            },
+            None => next_response.await,
+        };
+        let is_success = match next_response {
             // Received datastream response
             Ok(response) => {
                 match response {
+                    Some(Ok(r))
+                        if r.transactions
+                            .iter()
+                            .any(|txn| get_event_count_mismatch(txn).is_some()) =>
+                    {
+                        for txn in &r.transactions {
+                            if let Some((received, expected)) = get_event_count_mismatch(txn) {
+                                tracing::warn!(
+                                    processor_name = processor_name,
+                                    service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                                    stream_address = indexer_grpc_data_service_address.to_string(),
+                                    connection_id,
+                                    transaction_version = txn.version,
+                                    received,
+                                    expected,
+                                    "[Parser] Transaction has a truncated event list, refetching."
+                                );
+                            }
+                        }
+                        // Drop the whole response and reconnect from the last good version so the
+                        // checkpoint never moves past the incomplete transaction
+                        TRUNCATED_EVENTS_RESPONSE_COUNT
+                            .with_label_values(&[&processor_name])
+                            .inc();
+                        false
+                    },
                     Some(Ok(mut r)) => {
                         reconnection_retries = 0;
+                        endpoints.mark_success();
+                        let num_already_fetched =
+                            drop_already_fetched(&mut r.transactions, last_fetched_version);
+                        if num_already_fetched > 0 {
+                            tracing::warn!(
+                                processor_name = processor_name,
+                                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                                stream_address = indexer_grpc_data_service_address.to_string(),
+                                connection_id,
+                                last_fetched_version,
+                                num_already_fetched,
+                                "[Parser] Stream went back to already fetched versions, skipping them."
+                            );
+                            STREAM_RESET_SKIPPED_TRANSACTIONS_COUNT
+                                .with_label_values(&[&processor_name])
+                                .inc_by(num_already_fetched as u64);
+                            if r.transactions.is_empty() {
+                                grpc_channel_recv_latency = std::time::Instant::now();
+                                continue;
+                            }
+                        }
+                        if let Some(buffer) = reorder_buffer.as_mut() {
+                            max_fetched_version = r
+                                .transactions
+                                .iter()
+                                .map(|t| t.version as i64)
+                                .fold(max_fetched_version, i64::max);
+                            r.transactions = buffer.push(std::mem::take(&mut r.transactions));
+                            // Nothing else is coming once we've seen the ending version, so flush
+                            if request_ending_version
+                                .is_some_and(|ending| max_fetched_version >= ending as i64)
+                            {
+                                r.transactions.extend(buffer.flush());
+                            }
+                            REORDER_BUFFER_SIZE
+                                .with_label_values(&[&processor_name])
+                                .set(buffer.len() as i64);
+                            if r.transactions.is_empty() {
+                                // Everything in this response is waiting on an earlier version
+                                next_version_to_fetch = max_fetched_version as u64 + 1;
+                                grpc_channel_recv_latency = std::time::Instant::now();
+                                continue;
+                            }
+                        }
+                        // Before filtering, which may drop the block metadata transactions
+                        let (filled, unfilled) =
+                            block_timestamp_cache.fill_missing(&mut r.transactions);
+                        if filled + unfilled > 0 {
+                            tracing::warn!(
+                                processor_name = processor_name,
+                                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                                stream_address = indexer_grpc_data_service_address.to_string(),
+                                connection_id,
+                                filled,
+                                unfilled,
+                                "[Parser] Transactions without a timestamp, using the latest block's."
+                            );
+                            MISSING_TIMESTAMP_TRANSACTIONS_COUNT
+                                .with_label_values(&[&processor_name, "true"])
+                                .inc_by(filled as u64);
+                            MISSING_TIMESTAMP_TRANSACTIONS_COUNT
+                                .with_label_values(&[&processor_name, "false"])
+                                .inc_by(unfilled as u64);
+                        }
+                        if unfilled > 0 {
+                            error!(
+                                processor_name = processor_name,
+                                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                                stream_address = indexer_grpc_data_service_address.to_string(),
+                                connection_id,
+                                unfilled,
+                                "[Parser] Transactions without a timestamp before any block metadata transaction. Start from the first version of a block."
+                            );
+                            panic!("[Parser] Transactions without a timestamp before any block metadata transaction");
+                        }
                         let start_version = r.transactions.as_slice().first().unwrap().version;
                         let start_txn_timestamp =
                             r.transactions.as_slice().first().unwrap().timestamp.clone();
@@ -377,7 +785,9 @@ pub async fn create_fetcher_loop(
                         let end_txn_timestamp =
                             r.transactions.as_slice().last().unwrap().timestamp.clone();
 
-                        next_version_to_fetch = end_version + 1;
+                        // Buffered transactions may be ahead of this batch, don't fetch them twice
+                        next_version_to_fetch =
+                            (end_version + 1).max((max_fetched_version + 1) as u64);
 
                         let size_in_bytes = r.encoded_len() as u64;
                         let chain_id: u64 = r.chain_id.expect("[Parser] Chain Id doesn't exist.");
@@ -453,70 +863,33 @@ pub async fn create_fetcher_loop(
 
                         let txn_channel_send_latency = std::time::Instant::now();
 
-                        //potentially break txn_pb into many `TransactionsPBResponse` that are each `pb_channel_txn_chunk_size` txns max in size
-                        if num_txn_post_filter < pb_channel_txn_chunk_size {
-                            // We only need to send one; avoid the chunk/clone
-                            let txn_pb = TransactionsPBResponse {
-                                transactions: r.transactions,
-                                chain_id,
-                                start_version,
-                                end_version,
-                                start_txn_timestamp,
-                                end_txn_timestamp,
-                                size_in_bytes,
-                            };
-
-                            match txn_sender.send(txn_pb).await {
-                                Ok(()) => {},
-                                Err(e) => {
-                                    error!(
-                                        processor_name = processor_name,
-                                        stream_address = indexer_grpc_data_service_address.to_string(),
-                                        connection_id,
-                                        error = ?e,
-                                        "[Parser] Error sending GRPC response to channel."
-                                    );
-                                    panic!("[Parser] Error sending GRPC response to channel.")
-                                },
-                            }
-                        } else {
-                            // We are breaking down a big batch into small batches; this involves an iterator
-                            let average_size_in_bytes = size_in_bytes / num_txns as u64;
-
-                            let pb_txn_chunks: Vec<Vec<Transaction>> = r
-                                .transactions
-                                .into_iter()
-                                .chunks(pb_channel_txn_chunk_size)
-                                .into_iter()
-                                .map(|chunk| chunk.collect())
-                                .collect();
-                            for txns in pb_txn_chunks {
-                                let size_in_bytes = average_size_in_bytes * txns.len() as u64;
-                                let txn_pb = TransactionsPBResponse {
-                                    transactions: txns,
-                                    chain_id,
-                                    start_version,
-                                    end_version,
-                                    // TODO: this is only for gap checker + filtered txns, but this is wrong
-                                    start_txn_timestamp: start_txn_timestamp.clone(),
-                                    end_txn_timestamp: end_txn_timestamp.clone(),
-                                    size_in_bytes,
-                                };
-
-                                match txn_sender.send(txn_pb).await {
-                                    Ok(()) => {},
-                                    Err(e) => {
-                                        error!(
-                                            processor_name = processor_name,
-                                            stream_address = indexer_grpc_data_service_address.to_string(),
-                                            connection_id,
-                                            error = ?e,
-                                            "[Parser] Error sending GRPC response to channel."
-                                        );
-                                        panic!("[Parser] Error sending GRPC response to channel.")
-                                    },
-                                }
-                            }
+                        let txn_pb = TransactionsPBResponse {
+                            transactions: r.transactions,
+                            chain_id,
+                            start_version,
+                            end_version,
+                            start_txn_timestamp,
+                            end_txn_timestamp,
+                            size_in_bytes,
+                            compressed_transactions: None,
+                        };
+                        let txn_pb = match batch_accumulator.as_mut() {
+                            Some(accumulator) => {
+                                accumulator.push(txn_pb, tokio::time::Instant::now())
+                            },
+                            None => Some(txn_pb),
+                        };
+                        if let Some(txn_pb) = txn_pb {
+                            send_txn_pb(
+                                &txn_sender,
+                                txn_pb,
+                                pb_channel_txn_chunk_size,
+                                compress_buffered_transactions,
+                                &processor_name,
+                                &indexer_grpc_data_service_address,
+                                &connection_id,
+                            )
+                            .await;
                         }
 
                         let duration_in_secs = txn_channel_send_latency.elapsed().as_secs_f64();
@@ -548,9 +921,29 @@ pub async fn create_fetcher_loop(
                         NUM_TRANSACTIONS_FILTERED_OUT_COUNT
                             .with_label_values(&[&processor_name])
                             .inc_by(num_filtered_txns as u64);
+                        let mut skipped = SkippedTransactions::default();
+                        skipped.add(SkipReason::Filtered, num_filtered_txns as u64);
+                        skipped.report(&processor_name, start_version, end_version);
                         true
                     },
                     // Error receiving datastream response
+                    // Reconnecting fetches the same response again, stop with what to change
+                    Some(Err(rpc_error)) if is_message_too_large(&rpc_error) => {
+                        error!(
+                            processor_name = processor_name,
+                            service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                            stream_address = indexer_grpc_data_service_address.to_string(),
+                            connection_id,
+                            next_version_to_fetch,
+                            max_decoding_message_size,
+                            error = ?rpc_error,
+                            "[Parser] Datastream response exceeded indexer_grpc_max_decoding_message_size."
+                        );
+                        panic!(
+                            "[Parser] Datastream response starting at version {} exceeded indexer_grpc_max_decoding_message_size of {} bytes, raise it to get past these transactions",
+                            next_version_to_fetch, max_decoding_message_size
+                        );
+                    },
                     Some(Err(rpc_error)) => {
                         tracing::warn!(
                             processor_name = processor_name,
@@ -610,6 +1003,18 @@ pub async fn create_fetcher_loop(
                 next_version_to_fetch = next_version_to_fetch,
                 "[Parser] Reached ending version.",
             );
+            if let Some(txn_pb) = batch_accumulator.as_mut().and_then(BatchAccumulator::take) {
+                send_txn_pb(
+                    &txn_sender,
+                    txn_pb,
+                    pb_channel_txn_chunk_size,
+                    compress_buffered_transactions,
+                    &processor_name,
+                    &indexer_grpc_data_service_address,
+                    &connection_id,
+                )
+                .await;
+            }
             // Wait for the fetched transactions to finish processing before closing the channel
             loop {
                 let channel_size = txn_sender.len();
@@ -640,40 +1045,43 @@ pub async fn create_fetcher_loop(
                 continue;
             }
 
-            // Sleep for 100ms between reconnect tries
-            // TODO: Turn this into exponential backoff
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            let response = loop {
+                // Sleep for 100ms between reconnect tries
+                // TODO: Turn this into exponential backoff
+                tokio::time::sleep(Duration::from_millis(100)).await;
 
-            if reconnection_retries >= RECONNECTION_MAX_RETRIES {
-                error!(
+                if reconnection_retries >= RECONNECTION_MAX_RETRIES {
+                    // Resume from the same version on the next endpoint
+                    fail_over(&mut endpoints, &processor_name, next_version_to_fetch);
+                    indexer_grpc_data_service_address = endpoints.current().clone();
+                    reconnection_retries = 0;
+                }
+                reconnection_retries += 1;
+                info!(
                     processor_name = processor_name,
                     service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
                     stream_address = indexer_grpc_data_service_address.to_string(),
-                    "[Parser] Reconnected more than {RECONNECTION_MAX_RETRIES} times. Will not retry.",
+                    starting_version = next_version_to_fetch,
+                    ending_version = request_ending_version,
+                    reconnection_retries = reconnection_retries,
+                    "[Parser] Reconnecting to GRPC stream"
                 );
-                panic!("[Parser] Reconnected more than {RECONNECTION_MAX_RETRIES} times. Will not retry.")
-            }
-            reconnection_retries += 1;
-            info!(
-                processor_name = processor_name,
-                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
-                stream_address = indexer_grpc_data_service_address.to_string(),
-                starting_version = next_version_to_fetch,
-                ending_version = request_ending_version,
-                reconnection_retries = reconnection_retries,
-                "[Parser] Reconnecting to GRPC stream"
-            );
-            response = get_stream(
-                indexer_grpc_data_service_address.clone(),
-                indexer_grpc_http2_ping_interval,
-                indexer_grpc_http2_ping_timeout,
-                indexer_grpc_reconnection_timeout_secs,
-                next_version_to_fetch,
-                request_ending_version,
-                auth_token.clone(),
-                processor_name.to_string(),
-            )
-            .await;
+                if let Ok(response) = get_stream(
+                    indexer_grpc_data_service_address.clone(),
+                    indexer_grpc_http2_ping_interval,
+                    indexer_grpc_http2_ping_timeout,
+                    indexer_grpc_reconnection_timeout_secs,
+                    max_decoding_message_size,
+                    next_version_to_fetch,
+                    request_ending_version,
+                    auth_token.clone(),
+                    processor_name.to_string(),
+                )
+                .await
+                {
+                    break response;
+                }
+            };
             connection_id = match response.metadata().get(GRPC_CONNECTION_ID) {
                 Some(connection_id) => connection_id.to_str().unwrap().to_string(),
                 None => "".to_string(),
@@ -692,3 +1100,68 @@ pub async fn create_fetcher_loop(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txns(versions: &[u64]) -> Vec<Transaction> {
+        versions
+            .iter()
+            .map(|version| Transaction {
+                version: *version,
+                ..Transaction::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_drop_already_fetched() {
+        let mut transactions = txns(&[8, 9, 10, 11]);
+        assert_eq!(drop_already_fetched(&mut transactions, 9), 2);
+        assert_eq!(
+            transactions.iter().map(|t| t.version).collect::<Vec<_>>(),
+            vec![10, 11]
+        );
+
+        let mut transactions = txns(&[3, 4]);
+        assert_eq!(drop_already_fetched(&mut transactions, 9), 2);
+        assert!(transactions.is_empty());
+
+        // Nothing fetched yet when starting from version 0
+        let mut transactions = txns(&[0, 1]);
+        assert_eq!(drop_already_fetched(&mut transactions, -1), 0);
+        assert_eq!(transactions.len(), 2);
+    }
+
+    #[test]
+    fn test_is_message_too_large() {
+        assert!(is_message_too_large(&tonic::Status::out_of_range(
+            "Error, message length too large: found 300000000 bytes, the limit is: 268435456 bytes"
+        )));
+        assert!(!is_message_too_large(&tonic::Status::out_of_range(
+            "requested version is past the latest version"
+        )));
+        assert!(!is_message_too_large(&tonic::Status::internal(
+            "message length too large"
+        )));
+    }
+
+    #[test]
+    fn test_grpc_endpoints_fail_over() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let mut endpoints = GrpcEndpoints::new(vec![url("http://a:1"), url("http://b:1")]);
+        assert_eq!(endpoints.current(), &url("http://a:1"));
+        assert!(endpoints.fail_over());
+        assert_eq!(endpoints.current(), &url("http://b:1"));
+        // Every endpoint failed since the last response
+        assert!(!endpoints.fail_over());
+
+        endpoints.mark_success();
+        assert!(endpoints.fail_over());
+        assert_eq!(endpoints.current(), &url("http://a:1"));
+
+        let mut single = GrpcEndpoints::new(vec![url("http://a:1")]);
+        assert!(!single.fail_over());
+    }
+}
@@ -0,0 +1,738 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transaction source for files of newline-delimited JSON transactions in the node REST API shape
+//! (what `/v1/transactions` returns, one transaction per line), optionally gzip compressed. Each
+//! line is adapted into the `Transaction` proto so it goes through the same processors as the GRPC
+//! stream. This is mostly meant for replaying captured fixtures.
+//!
+//! Field mapping, REST -> proto. u64s may be strings or numbers, hashes are 0x hex strings.
+//! * `type` -> `type` and the matching `txn_data` variant
+//! * `version`, `epoch`, `block_height` -> same fields. REST only has `epoch` on block metadata
+//!   transactions and never has `block_height`, so both default to 0 unless the fixture adds them
+//! * `timestamp` (microseconds) -> `timestamp`
+//! * `hash`, `state_change_hash`, `event_root_hash`, `state_checkpoint_hash`, `gas_used`,
+//!   `success`, `vm_status`, `accumulator_root_hash`, `changes` -> `info`
+//! * `events[].guid` -> `key`, `events[].type` -> `type_str` and `type`, `events[].data` -> `data`
+//!   serialized back to a json string
+//! * `changes[]` -> `info.changes`, by `type` (`write_resource`, `delete_resource`,
+//!   `write_table_item`, `delete_table_item`, `write_module`, `delete_module`). Resource `data` is
+//!   serialized back to a json string, module abis are dropped and only the bytecode is kept
+//! * user transactions: `sender`, `sequence_number`, `max_gas_amount`, `gas_unit_price`,
+//!   `expiration_timestamp_secs` -> `request`. Entry function and multisig payloads are mapped,
+//!   other payloads and `signature` are left empty
+//! * block metadata transactions: `id`, `round`, `proposer`, `previous_block_votes_bitvec`,
+//!   `failed_proposer_indices`
+//! * genesis, validator and block epilogue transactions only keep their events
+//!
+//! Missing optional fields get the proto defaults. Missing `type` or `version` is an error.
+
+use crate::{
+    grpc_stream::TransactionsPBResponse,
+    transaction_filter::TransactionFilter,
+    utils::{
+        counters::{ProcessorStep, LATEST_PROCESSED_VERSION, NUM_TRANSACTIONS_FILTERED_OUT_COUNT},
+        skipped_transactions::{SkipReason, SkippedTransactions},
+        util::hex_to_raw_bytes,
+    },
+    worker::PROCESSOR_SERVICE_TYPE,
+};
+use anyhow::{bail, Context, Result};
+use aptos_protos::{
+    transaction::v1::{
+        move_type::Content,
+        multisig_transaction_payload::{
+            Payload as MultisigPayloadType, Type as MultisigTransactionPayloadType,
+        },
+        transaction::{TransactionType, TxnData},
+        transaction_payload::{Payload as PayloadType, Type as TransactionPayloadType},
+        write_set_change::{Change, Type as WriteSetChangeType},
+        BlockEpilogueTransaction, BlockMetadataTransaction, DeleteModule, DeleteResource,
+        DeleteTableData, DeleteTableItem, EntryFunctionId, EntryFunctionPayload, Event, EventKey,
+        GenesisTransaction, MoveModuleBytecode, MoveModuleId, MoveStructTag, MoveType, MoveTypes,
+        MultisigPayload, MultisigTransactionPayload, StateCheckpointTransaction, Transaction,
+        TransactionInfo, TransactionPayload, UserTransaction, UserTransactionRequest,
+        ValidatorTransaction, WriteModule, WriteResource, WriteSetChange, WriteTableData,
+        WriteTableItem,
+    },
+    util::timestamp::Timestamp,
+};
+use bigdecimal::Zero;
+use flate2::read::GzDecoder;
+use itertools::Itertools;
+use kanal::AsyncSender;
+use prost::Message;
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    time::Duration,
+};
+use tracing::{error, info};
+
+/// First two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads every transaction in the file
+pub fn read_json_transactions(path: &Path) -> Result<Vec<Transaction>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    read_json_transactions_from(BufReader::new(file))
+}
+
+/// Gzip is detected from the content, not the file extension
+pub fn read_json_transactions_from<R: BufRead>(mut reader: R) -> Result<Vec<Transaction>> {
+    let is_gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        parse_json_transactions(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        parse_json_transactions(reader)
+    }
+}
+
+/// Parses one transaction per line, skipping blank lines
+pub fn parse_json_transactions<R: BufRead>(reader: R) -> Result<Vec<Transaction>> {
+    let mut transactions = vec![];
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid json on line {}", line_number + 1))?;
+        transactions.push(
+            transaction_from_json(&value)
+                .with_context(|| format!("Invalid transaction on line {}", line_number + 1))?,
+        );
+    }
+    Ok(transactions)
+}
+
+/// Adapts a REST API transaction into the proto model, see the module docs for the mapping
+pub fn transaction_from_json(value: &Value) -> Result<Transaction> {
+    let txn_type = value
+        .get("type")
+        .and_then(Value::as_str)
+        .context("Missing transaction type")?;
+    if matches!(value.get("version"), None | Some(Value::Null)) {
+        bail!("Missing transaction version");
+    }
+    let events = get_array(value, "events")
+        .iter()
+        .map(event_from_json)
+        .collect::<Result<Vec<_>>>()?;
+
+    let (transaction_type, txn_data) = match txn_type {
+        "user_transaction" => (
+            TransactionType::User,
+            TxnData::User(UserTransaction {
+                request: Some(user_request_from_json(value)?),
+                events,
+            }),
+        ),
+        "block_metadata_transaction" => (
+            TransactionType::BlockMetadata,
+            TxnData::BlockMetadata(BlockMetadataTransaction {
+                id: get_str(value, "id").to_string(),
+                round: get_u64(value, "round")?,
+                events,
+                previous_block_votes_bitvec: get_array(value, "previous_block_votes_bitvec")
+                    .iter()
+                    .map(|bit| json_u64(bit).map(|bit| bit as u8))
+                    .collect::<Result<_>>()?,
+                proposer: get_str(value, "proposer").to_string(),
+                failed_proposer_indices: get_array(value, "failed_proposer_indices")
+                    .iter()
+                    .map(|index| json_u64(index).map(|index| index as u32))
+                    .collect::<Result<_>>()?,
+            }),
+        ),
+        "genesis_transaction" => (
+            TransactionType::Genesis,
+            TxnData::Genesis(GenesisTransaction {
+                events,
+                ..GenesisTransaction::default()
+            }),
+        ),
+        "state_checkpoint_transaction" => (
+            TransactionType::StateCheckpoint,
+            TxnData::StateCheckpoint(StateCheckpointTransaction::default()),
+        ),
+        "validator_transaction" => (
+            TransactionType::Validator,
+            TxnData::Validator(ValidatorTransaction {
+                events,
+                ..ValidatorTransaction::default()
+            }),
+        ),
+        "block_epilogue_transaction" => (
+            TransactionType::BlockEpilogue,
+            TxnData::BlockEpilogue(BlockEpilogueTransaction::default()),
+        ),
+        other => bail!("Unknown transaction type {}", other),
+    };
+
+    Ok(Transaction {
+        timestamp: Some(micros_to_timestamp(get_u64(value, "timestamp")?)),
+        version: get_u64(value, "version")?,
+        info: Some(TransactionInfo {
+            hash: get_bytes(value, "hash")?,
+            state_change_hash: get_bytes(value, "state_change_hash")?,
+            event_root_hash: get_bytes(value, "event_root_hash")?,
+            state_checkpoint_hash: match value.get("state_checkpoint_hash") {
+                Some(Value::String(hash)) => Some(hex_to_raw_bytes(hash)?),
+                _ => None,
+            },
+            gas_used: get_u64(value, "gas_used")?,
+            success: value
+                .get("success")
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+            vm_status: get_str(value, "vm_status").to_string(),
+            accumulator_root_hash: get_bytes(value, "accumulator_root_hash")?,
+            changes: get_array(value, "changes")
+                .iter()
+                .map(write_set_change_from_json)
+                .collect::<Result<_>>()?,
+        }),
+        epoch: get_u64(value, "epoch")?,
+        block_height: get_u64(value, "block_height")?,
+        r#type: transaction_type as i32,
+        txn_data: Some(txn_data),
+        ..Transaction::default()
+    })
+}
+
+fn user_request_from_json(value: &Value) -> Result<UserTransactionRequest> {
+    Ok(UserTransactionRequest {
+        sender: get_str(value, "sender").to_string(),
+        sequence_number: get_u64(value, "sequence_number")?,
+        max_gas_amount: get_u64(value, "max_gas_amount")?,
+        gas_unit_price: get_u64(value, "gas_unit_price")?,
+        expiration_timestamp_secs: Some(Timestamp {
+            seconds: get_u64(value, "expiration_timestamp_secs")? as i64,
+            nanos: 0,
+        }),
+        payload: value
+            .get("payload")
+            .map(payload_from_json)
+            .transpose()?
+            .flatten(),
+        ..UserTransactionRequest::default()
+    })
+}
+
+/// None for payloads we don't map (scripts, write sets)
+fn payload_from_json(value: &Value) -> Result<Option<TransactionPayload>> {
+    Ok(match get_str(value, "type") {
+        "entry_function_payload" => Some(TransactionPayload {
+            r#type: TransactionPayloadType::EntryFunctionPayload as i32,
+            payload: Some(PayloadType::EntryFunctionPayload(
+                entry_function_payload_from_json(value)?,
+            )),
+            ..TransactionPayload::default()
+        }),
+        "multisig_payload" => {
+            let transaction_payload = match value.get("transaction_payload") {
+                Some(inner) if !inner.is_null() => Some(MultisigTransactionPayload {
+                    r#type: MultisigTransactionPayloadType::EntryFunctionPayload as i32,
+                    payload: Some(MultisigPayloadType::EntryFunctionPayload(
+                        entry_function_payload_from_json(inner)?,
+                    )),
+                }),
+                _ => None,
+            };
+            Some(TransactionPayload {
+                r#type: TransactionPayloadType::MultisigPayload as i32,
+                payload: Some(PayloadType::MultisigPayload(MultisigPayload {
+                    multisig_address: get_str(value, "multisig_address").to_string(),
+                    transaction_payload,
+                })),
+                ..TransactionPayload::default()
+            })
+        },
+        _ => None,
+    })
+}
+
+fn entry_function_payload_from_json(value: &Value) -> Result<EntryFunctionPayload> {
+    let function = get_str(value, "function");
+    let mut parts = function.splitn(3, "::");
+    let (Some(address), Some(module), Some(name)) = (parts.next(), parts.next(), parts.next())
+    else {
+        bail!("Invalid entry function {}", function);
+    };
+    Ok(EntryFunctionPayload {
+        function: Some(EntryFunctionId {
+            module: Some(MoveModuleId {
+                address: address.to_string(),
+                name: module.to_string(),
+            }),
+            name: name.to_string(),
+        }),
+        type_arguments: get_array(value, "type_arguments")
+            .iter()
+            .map(|arg| parse_move_type(arg.as_str().unwrap_or_default()))
+            .collect(),
+        arguments: get_array(value, "arguments")
+            .iter()
+            .map(Value::to_string)
+            .collect(),
+        entry_function_id_str: function.to_string(),
+    })
+}
+
+fn event_from_json(value: &Value) -> Result<Event> {
+    let type_str = get_str(value, "type");
+    let key = match value.get("guid") {
+        Some(guid) if !guid.is_null() => Some(EventKey {
+            creation_number: get_u64(guid, "creation_number")?,
+            account_address: get_str(guid, "account_address").to_string(),
+        }),
+        _ => None,
+    };
+    Ok(Event {
+        key,
+        sequence_number: get_u64(value, "sequence_number")?,
+        r#type: Some(parse_move_type(type_str)),
+        type_str: type_str.to_string(),
+        data: json_string(value.get("data")),
+    })
+}
+
+fn write_set_change_from_json(value: &Value) -> Result<WriteSetChange> {
+    let address = get_str(value, "address").to_string();
+    let state_key_hash = get_bytes(value, "state_key_hash")?;
+    let (change_type, change) = match get_str(value, "type") {
+        "write_resource" => {
+            let data = value.get("data").context("Missing write_resource data")?;
+            let type_str = get_str(data, "type");
+            (
+                WriteSetChangeType::WriteResource,
+                Change::WriteResource(WriteResource {
+                    address,
+                    state_key_hash,
+                    r#type: parse_struct_tag(type_str),
+                    type_str: type_str.to_string(),
+                    data: json_string(data.get("data")),
+                }),
+            )
+        },
+        "delete_resource" => {
+            let type_str = get_str(value, "resource");
+            (
+                WriteSetChangeType::DeleteResource,
+                Change::DeleteResource(DeleteResource {
+                    address,
+                    state_key_hash,
+                    r#type: parse_struct_tag(type_str),
+                    type_str: type_str.to_string(),
+                }),
+            )
+        },
+        "write_table_item" => (
+            WriteSetChangeType::WriteTableItem,
+            Change::WriteTableItem(WriteTableItem {
+                state_key_hash,
+                handle: get_str(value, "handle").to_string(),
+                key: get_str(value, "key").to_string(),
+                data: match value.get("data") {
+                    Some(data) if !data.is_null() => Some(WriteTableData {
+                        key: json_string(data.get("key")),
+                        key_type: get_str(data, "key_type").to_string(),
+                        value: json_string(data.get("value")),
+                        value_type: get_str(data, "value_type").to_string(),
+                    }),
+                    _ => None,
+                },
+            }),
+        ),
+        "delete_table_item" => (
+            WriteSetChangeType::DeleteTableItem,
+            Change::DeleteTableItem(DeleteTableItem {
+                state_key_hash,
+                handle: get_str(value, "handle").to_string(),
+                key: get_str(value, "key").to_string(),
+                data: match value.get("data") {
+                    Some(data) if !data.is_null() => Some(DeleteTableData {
+                        key: json_string(data.get("key")),
+                        key_type: get_str(data, "key_type").to_string(),
+                    }),
+                    _ => None,
+                },
+            }),
+        ),
+        "write_module" => (
+            WriteSetChangeType::WriteModule,
+            Change::WriteModule(WriteModule {
+                address,
+                state_key_hash,
+                data: value
+                    .get("data")
+                    .map(|data| -> Result<MoveModuleBytecode> {
+                        Ok(MoveModuleBytecode {
+                            bytecode: get_bytes(data, "bytecode")?,
+                            abi: None,
+                        })
+                    })
+                    .transpose()?,
+            }),
+        ),
+        "delete_module" => {
+            let module = get_str(value, "module");
+            (
+                WriteSetChangeType::DeleteModule,
+                Change::DeleteModule(DeleteModule {
+                    address,
+                    state_key_hash,
+                    module: module.split_once("::").map(|(address, name)| MoveModuleId {
+                        address: address.to_string(),
+                        name: name.to_string(),
+                    }),
+                }),
+            )
+        },
+        other => bail!("Unknown write set change type {}", other),
+    };
+    Ok(WriteSetChange {
+        r#type: change_type as i32,
+        change: Some(change),
+    })
+}
+
+/// Parses a move type string such as `0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>`.
+/// Anything we can't read becomes `Unparsable` with the original string.
+pub fn parse_move_type(type_str: &str) -> MoveType {
+    let type_str = type_str.trim();
+    let primitive = |move_type: MoveTypes| MoveType {
+        r#type: move_type as i32,
+        content: None,
+    };
+    match type_str {
+        "bool" => primitive(MoveTypes::Bool),
+        "u8" => primitive(MoveTypes::U8),
+        "u16" => primitive(MoveTypes::U16),
+        "u32" => primitive(MoveTypes::U32),
+        "u64" => primitive(MoveTypes::U64),
+        "u128" => primitive(MoveTypes::U128),
+        "u256" => primitive(MoveTypes::U256),
+        "address" => primitive(MoveTypes::Address),
+        "signer" => primitive(MoveTypes::Signer),
+        _ => {
+            if let Some(inner) = type_str
+                .strip_prefix("vector<")
+                .and_then(|inner| inner.strip_suffix('>'))
+            {
+                MoveType {
+                    r#type: MoveTypes::Vector as i32,
+                    content: Some(Content::Vector(Box::new(parse_move_type(inner)))),
+                }
+            } else if let Some(index) = type_str
+                .strip_prefix('T')
+                .and_then(|index| index.parse::<u32>().ok())
+            {
+                MoveType {
+                    r#type: MoveTypes::GenericTypeParam as i32,
+                    content: Some(Content::GenericTypeParamIndex(index)),
+                }
+            } else if let Some(struct_tag) = parse_struct_tag(type_str) {
+                MoveType {
+                    r#type: MoveTypes::Struct as i32,
+                    content: Some(Content::Struct(struct_tag)),
+                }
+            } else {
+                MoveType {
+                    r#type: MoveTypes::Unparsable as i32,
+                    content: Some(Content::Unparsable(type_str.to_string())),
+                }
+            }
+        },
+    }
+}
+
+/// Parses `address::module::Name<T1, T2>`, None if the string isn't a struct
+fn parse_struct_tag(type_str: &str) -> Option<MoveStructTag> {
+    let (path, generics) = match type_str.split_once('<') {
+        Some((path, generics)) => (path, Some(generics.strip_suffix('>')?)),
+        None => (type_str, None),
+    };
+    let mut parts = path.trim().splitn(3, "::");
+    let (Some(address), Some(module), Some(name)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    if name.contains("::") {
+        return None;
+    }
+    Some(MoveStructTag {
+        address: address.to_string(),
+        module: module.to_string(),
+        name: name.to_string(),
+        generic_type_params: generics
+            .map(|generics| {
+                split_type_params(generics)
+                    .into_iter()
+                    .map(parse_move_type)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// Splits type params on top level commas only, so nested generics stay together
+fn split_type_params(generics: &str) -> Vec<&str> {
+    let mut params = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in generics.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(generics[start..i].trim());
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    params.push(generics[start..].trim());
+    params
+}
+
+fn get_str<'a>(value: &'a Value, field: &str) -> &'a str {
+    value.get(field).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn get_array<'a>(value: &'a Value, field: &str) -> &'a [Value] {
+    value
+        .get(field)
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// REST encodes u64s as strings. Missing or null is 0.
+fn get_u64(value: &Value, field: &str) -> Result<u64> {
+    match value.get(field) {
+        None | Some(Value::Null) => Ok(0),
+        Some(inner) => json_u64(inner).with_context(|| format!("Invalid {}", field)),
+    }
+}
+
+fn json_u64(value: &Value) -> Result<u64> {
+    match value {
+        Value::String(s) => Ok(s.parse()?),
+        Value::Number(n) => n.as_u64().context("Expected an unsigned integer"),
+        other => bail!("Expected an unsigned integer, got {}", other),
+    }
+}
+
+/// Decodes a 0x hex field. Missing or null is empty.
+fn get_bytes(value: &Value, field: &str) -> Result<Vec<u8>> {
+    match value.get(field).and_then(Value::as_str) {
+        Some(hex) => hex_to_raw_bytes(hex).with_context(|| format!("Invalid {}", field)),
+        None => Ok(vec![]),
+    }
+}
+
+/// The proto carries move values as json strings
+fn json_string(value: Option<&Value>) -> String {
+    value.unwrap_or(&Value::Null).to_string()
+}
+
+fn micros_to_timestamp(micros: u64) -> Timestamp {
+    Timestamp {
+        seconds: (micros / 1_000_000) as i64,
+        nanos: ((micros % 1_000_000) * 1_000) as i32,
+    }
+}
+
+/// Feeds the transactions of a json file into the channel the same way the GRPC fetcher does,
+/// then waits for the channel to drain and returns. Sparse fixtures are fine: each batch starts
+/// right after the previous one so the gap detector sees contiguous versions.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_json_file_fetcher_loop(
+    txn_sender: AsyncSender<TransactionsPBResponse>,
+    json_transactions_file: &Path,
+    chain_id: u64,
+    starting_version: u64,
+    request_ending_version: Option<u64>,
+    processor_name: String,
+    transaction_filter: TransactionFilter,
+    pb_channel_txn_chunk_size: usize,
+) {
+    info!(
+        processor_name = processor_name,
+        service_type = PROCESSOR_SERVICE_TYPE,
+        file = %json_transactions_file.display(),
+        start_version = starting_version,
+        end_version = request_ending_version,
+        "[Parser] Reading transactions from json file",
+    );
+    let mut transactions = read_json_transactions(json_transactions_file).unwrap_or_else(|e| {
+        error!(
+            processor_name = processor_name,
+            file = %json_transactions_file.display(),
+            error = ?e,
+            "[Parser] Error reading transactions from json file"
+        );
+        panic!("[Parser] Error reading transactions from json file")
+    });
+    transactions.retain(|txn| {
+        txn.version >= starting_version && txn.version <= request_ending_version.unwrap_or(u64::MAX)
+    });
+    transactions.sort_by_key(|txn| txn.version);
+
+    let step = ProcessorStep::ReceivedTxnsFromGrpc.get_step();
+    let label = ProcessorStep::ReceivedTxnsFromGrpc.get_label();
+    let mut next_start_version = starting_version;
+    for chunk in &transactions.into_iter().chunks(pb_channel_txn_chunk_size) {
+        let chunk: Vec<Transaction> = chunk.collect();
+        let end_version = chunk.last().map(|txn| txn.version).unwrap_or_default();
+        let start_txn_timestamp = chunk.first().and_then(|txn| txn.timestamp.clone());
+        let end_txn_timestamp = chunk.last().and_then(|txn| txn.timestamp.clone());
+        let num_txns = chunk.len();
+        let transactions: Vec<Transaction> = chunk
+            .into_iter()
+            .filter(|txn| transaction_filter.include(txn))
+            .collect();
+        let num_filtered_txns = num_txns - transactions.len();
+        let size_in_bytes = transactions
+            .iter()
+            .map(|txn| txn.encoded_len() as u64)
+            .sum();
+
+        let txn_pb = TransactionsPBResponse {
+            transactions,
+            chain_id,
+            start_version: next_start_version,
+            end_version,
+            start_txn_timestamp,
+            end_txn_timestamp,
+            size_in_bytes,
+            compressed_transactions: None,
+        };
+        if let Err(e) = txn_sender.send(txn_pb).await {
+            error!(
+                processor_name = processor_name,
+                error = ?e,
+                "[Parser] Error sending json transactions to channel."
+            );
+            panic!("[Parser] Error sending json transactions to channel.")
+        }
+        LATEST_PROCESSED_VERSION
+            .with_label_values(&[&processor_name, step, label, "-"])
+            .set(end_version as i64);
+        NUM_TRANSACTIONS_FILTERED_OUT_COUNT
+            .with_label_values(&[&processor_name])
+            .inc_by(num_filtered_txns as u64);
+        let mut skipped = SkippedTransactions::default();
+        skipped.add(SkipReason::Filtered, num_filtered_txns as u64);
+        skipped.report(&processor_name, next_start_version, end_version);
+        next_start_version = end_version + 1;
+    }
+
+    info!(
+        processor_name = processor_name,
+        service_type = PROCESSOR_SERVICE_TYPE,
+        end_version = next_start_version.saturating_sub(1),
+        "[Parser] Finished reading json file.",
+    );
+    // Wait for the sent transactions to finish processing before closing the channel
+    while !txn_sender.len().is_zero() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const USER_TXN: &str = r#"{"type":"user_transaction","version":"100","hash":"0x01","state_change_hash":"0x02","event_root_hash":"0x03","state_checkpoint_hash":null,"gas_used":"7","success":true,"vm_status":"Executed successfully","accumulator_root_hash":"0x04","timestamp":"1665000000123456","sender":"0xa","sequence_number":"3","max_gas_amount":"2000","gas_unit_price":"100","expiration_timestamp_secs":"1665000600","payload":{"type":"entry_function_payload","function":"0x1::coin::transfer","type_arguments":["0x1::aptos_coin::AptosCoin"],"arguments":["0xb","10"]},"events":[{"guid":{"creation_number":"2","account_address":"0xb"},"sequence_number":"0","type":"0x1::coin::DepositEvent","data":{"amount":"10"}}],"changes":[{"type":"write_resource","address":"0xb","state_key_hash":"0x05","data":{"type":"0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>","data":{"coin":{"value":"10"}}}}]}"#;
+
+    #[test]
+    fn test_user_transaction_from_json() {
+        let value: Value = serde_json::from_str(USER_TXN).unwrap();
+        let txn = transaction_from_json(&value).unwrap();
+
+        assert_eq!(txn.version, 100);
+        assert_eq!(txn.r#type, TransactionType::User as i32);
+        assert_eq!(txn.timestamp.as_ref().unwrap().seconds, 1665000000);
+        assert_eq!(txn.timestamp.as_ref().unwrap().nanos, 123456000);
+        let info = txn.info.as_ref().unwrap();
+        assert_eq!(info.hash, vec![1]);
+        assert_eq!(info.gas_used, 7);
+        assert_eq!(info.state_checkpoint_hash, None);
+
+        let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() else {
+            panic!("Expected a user transaction");
+        };
+        let request = user_txn.request.as_ref().unwrap();
+        assert_eq!(request.sender, "0xa");
+        assert_eq!(
+            crate::utils::util::get_entry_function_from_user_request(request).as_deref(),
+            Some("0x1::coin::transfer")
+        );
+        assert_eq!(user_txn.events[0].type_str, "0x1::coin::DepositEvent");
+        assert_eq!(user_txn.events[0].data, r#"{"amount":"10"}"#);
+        assert_eq!(user_txn.events[0].key.as_ref().unwrap().creation_number, 2);
+
+        let Some(Change::WriteResource(write_resource)) = info.changes[0].change.as_ref() else {
+            panic!("Expected a write resource");
+        };
+        let struct_tag = write_resource.r#type.as_ref().unwrap();
+        assert_eq!(struct_tag.name, "CoinStore");
+        assert_eq!(struct_tag.generic_type_params.len(), 1);
+        assert_eq!(write_resource.data, r#"{"coin":{"value":"10"}}"#);
+    }
+
+    #[test]
+    fn test_missing_optional_fields() {
+        let value: Value =
+            serde_json::from_str(r#"{"type":"state_checkpoint_transaction","version":5}"#).unwrap();
+        let txn = transaction_from_json(&value).unwrap();
+        assert_eq!(txn.version, 5);
+        assert!(txn.info.as_ref().unwrap().changes.is_empty());
+
+        let value: Value = serde_json::from_str(r#"{"type":"user_transaction"}"#).unwrap();
+        assert!(transaction_from_json(&value).is_err());
+    }
+
+    #[test]
+    fn test_parse_move_type() {
+        let move_type = parse_move_type("vector<0x1::object::Object<0x4::token::Token>>");
+        let Some(Content::Vector(inner)) = move_type.content else {
+            panic!("Expected a vector");
+        };
+        let Some(Content::Struct(struct_tag)) = inner.content else {
+            panic!("Expected a struct");
+        };
+        assert_eq!(struct_tag.name, "Object");
+        assert_eq!(struct_tag.generic_type_params.len(), 1);
+
+        let struct_tag = parse_struct_tag("0x1::pair::Pair<u64, vector<u8>>").unwrap();
+        assert_eq!(struct_tag.generic_type_params.len(), 2);
+        assert_eq!(
+            parse_move_type("T0").r#type,
+            MoveTypes::GenericTypeParam as i32
+        );
+        assert_eq!(
+            parse_move_type("&signer").r#type,
+            MoveTypes::Unparsable as i32
+        );
+    }
+
+    #[test]
+    fn test_read_gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        writeln!(encoder, "{}\n\n{}", USER_TXN, USER_TXN).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let transactions = read_json_transactions_from(compressed.as_slice()).unwrap();
+        assert_eq!(transactions.len(), 2);
+        let transactions = read_json_transactions_from(USER_TXN.as_bytes()).unwrap();
+        assert_eq!(transactions.len(), 1);
+    }
+}
@@ -23,6 +23,7 @@ mod config;
 pub mod db;
 pub mod gap_detectors;
 pub mod grpc_stream;
+pub mod json_stream;
 pub mod processors;
 #[path = "db/postgres/schema.rs"]
 pub mod schema;
@@ -13,7 +13,7 @@ use anyhow::bail;
 use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
 use diesel::{pg::Pg, query_builder::QueryFragment};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 pub struct AccountTransactionsProcessor {
@@ -93,7 +93,7 @@ impl ProcessorTrait for AccountTransactionsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
@@ -103,7 +103,7 @@ impl ProcessorTrait for AccountTransactionsProcessor {
 
         let mut account_transactions = AHashMap::new();
 
-        for txn in &transactions {
+        for txn in transactions.iter() {
             account_transactions.extend(AccountTransaction::from_transaction(txn));
         }
         let mut account_transactions = account_transactions
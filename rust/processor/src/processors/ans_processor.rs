@@ -31,7 +31,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -367,7 +367,7 @@ impl ProcessorTrait for AnsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
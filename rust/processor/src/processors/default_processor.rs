@@ -25,7 +25,7 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tokio::join;
 use tracing::error;
 
@@ -317,7 +317,7 @@ impl ProcessorTrait for DefaultProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -330,7 +330,7 @@ impl ProcessorTrait for DefaultProcessor {
             block_metadata_transactions,
             write_set_changes,
             (move_modules, move_resources, table_items, current_table_items, table_metadata),
-        ) = tokio::task::spawn_blocking(move || process_transactions(transactions, flags))
+        ) = tokio::task::spawn_blocking(move || process_transactions(&transactions, flags))
             .await
             .expect("Failed to spawn_blocking for TransactionModel::from_transactions");
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
@@ -385,7 +385,7 @@ impl ProcessorTrait for DefaultProcessor {
 }
 
 fn process_transactions(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
     flags: TableFlags,
 ) -> (
     Vec<crate::db::common::models::default_models::transactions::Transaction>,
@@ -400,7 +400,7 @@ fn process_transactions(
     ),
 ) {
     let (mut txns, block_metadata_txns, mut write_set_changes, wsc_details) =
-        TransactionModel::from_transactions(&transactions);
+        TransactionModel::from_transactions(transactions);
     let mut block_metadata_transactions = vec![];
     for block_metadata_txn in block_metadata_txns {
         block_metadata_transactions.push(block_metadata_txn.clone());
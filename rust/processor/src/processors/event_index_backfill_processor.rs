@@ -0,0 +1,331 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fills in `event_index` on legacy token_activities and coin_activities rows, which were written
+//! before the column existed. Run it over the versions of those rows with `starting_version` and
+//! `ending_version`; it re-reads the transactions from the configured source and updates the rows
+//! in place. Nothing is inserted, rows that already have an `event_index` are left alone, and the
+//! processor keeps its own checkpoint, so it can run next to the live processors and be rerun.
+//!
+//! Legacy rows are keyed by the event's handle (account address, creation number and sequence
+//! number), which is matched against the events of the transaction to find the index.
+
+use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use crate::{
+    db::common::models::{
+        event_parser::{parse_events, EventContext, EventParser, EventParserCollector},
+        fungible_asset_models::v2_fungible_asset_activities::{
+            BURN_GAS_EVENT_CREATION_NUM, BURN_GAS_EVENT_INDEX,
+        },
+    },
+    gap_detectors::ProcessingResult,
+    utils::{
+        database::{execute_in_transaction, ArcDbPool},
+        util::standardize_address,
+    },
+};
+use ahash::AHashMap;
+use anyhow::bail;
+use aptos_protos::transaction::v1::{Event, Transaction};
+use async_trait::async_trait;
+use diesel::{
+    sql_query,
+    sql_types::{Array, BigInt, Text},
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, RunQueryDsl};
+use std::{fmt::Debug, sync::Arc};
+use tracing::{error, info};
+
+const BACKFILLED_TABLES: [&str; 2] = ["token_activities", "coin_activities"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct EventKey {
+    transaction_version: i64,
+    event_account_address: String,
+    event_creation_number: i64,
+    event_sequence_number: i64,
+}
+
+struct EventKeyParser;
+
+impl EventParser for EventKeyParser {
+    type Row = (EventKey, i64);
+
+    fn parse_event(&self, ctx: &EventContext, event: &Event) -> anyhow::Result<Option<Self::Row>> {
+        let Some(key) = event.key.as_ref() else {
+            return Ok(None);
+        };
+        Ok(Some((
+            EventKey {
+                transaction_version: ctx.txn_version,
+                event_account_address: standardize_address(&key.account_address),
+                event_creation_number: key.creation_number as i64,
+                event_sequence_number: event.sequence_number as i64,
+            },
+            ctx.event_index,
+        )))
+    }
+}
+
+/// Index of each event by its handle. Module events all share the same empty handle, and a legacy
+/// row could never have told them apart, so handles seen more than once in a transaction are left
+/// out rather than guessed.
+fn get_event_indices(transactions: &[Transaction]) -> anyhow::Result<Vec<(EventKey, i64)>> {
+    let mut collector = EventParserCollector::new(EventKeyParser);
+    parse_events(transactions, &mut [&mut collector])?;
+
+    let mut indices: AHashMap<EventKey, Option<i64>> = AHashMap::new();
+    for (key, event_index) in collector.into_rows() {
+        indices
+            .entry(key)
+            .and_modify(|index| *index = None)
+            .or_insert(Some(event_index));
+    }
+    let mut indices = indices
+        .into_iter()
+        .filter_map(|(key, event_index)| event_index.map(|event_index| (key, event_index)))
+        .collect::<Vec<_>>();
+    // Sort by PK
+    indices.sort_by(|(a, _), (b, _)| {
+        (
+            a.transaction_version,
+            &a.event_account_address,
+            a.event_creation_number,
+            a.event_sequence_number,
+        )
+            .cmp(&(
+                b.transaction_version,
+                &b.event_account_address,
+                b.event_creation_number,
+                b.event_sequence_number,
+            ))
+    });
+    Ok(indices)
+}
+
+pub struct EventIndexBackfillProcessor {
+    connection_pool: ArcDbPool,
+}
+
+impl EventIndexBackfillProcessor {
+    pub fn new(connection_pool: ArcDbPool) -> Self {
+        Self { connection_pool }
+    }
+}
+
+impl Debug for EventIndexBackfillProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "EventIndexBackfillProcessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+/// Updates both tables in one transaction. Returns the number of rows updated per table.
+async fn update_event_indices(
+    pool: ArcDbPool,
+    start_version: i64,
+    end_version: i64,
+    indices: Vec<(EventKey, i64)>,
+) -> Result<Vec<usize>, diesel::result::Error> {
+    let mut transaction_versions = Vec::with_capacity(indices.len());
+    let mut event_account_addresses = Vec::with_capacity(indices.len());
+    let mut event_creation_numbers = Vec::with_capacity(indices.len());
+    let mut event_sequence_numbers = Vec::with_capacity(indices.len());
+    let mut event_indices = Vec::with_capacity(indices.len());
+    for (key, event_index) in indices {
+        transaction_versions.push(key.transaction_version);
+        event_account_addresses.push(key.event_account_address);
+        event_creation_numbers.push(key.event_creation_number);
+        event_sequence_numbers.push(key.event_sequence_number);
+        event_indices.push(event_index);
+    }
+
+    execute_in_transaction(pool, move |conn| {
+        async move {
+            let mut updated_rows = vec![];
+            for table in BACKFILLED_TABLES {
+                let query = format!(
+                    "UPDATE {table} AS t SET event_index = e.event_index
+                    FROM unnest($1::bigint[], $2::text[], $3::bigint[], $4::bigint[], $5::bigint[])
+                        AS e(transaction_version, event_account_address, event_creation_number,
+                            event_sequence_number, event_index)
+                    WHERE t.transaction_version BETWEEN $6 AND $7
+                    AND t.transaction_version = e.transaction_version
+                    AND t.event_account_address = e.event_account_address
+                    AND t.event_creation_number = e.event_creation_number
+                    AND t.event_sequence_number = e.event_sequence_number
+                    AND t.event_index IS NULL",
+                );
+                updated_rows.push(
+                    sql_query(query)
+                        .bind::<Array<BigInt>, _>(&transaction_versions)
+                        .bind::<Array<Text>, _>(&event_account_addresses)
+                        .bind::<Array<BigInt>, _>(&event_creation_numbers)
+                        .bind::<Array<BigInt>, _>(&event_sequence_numbers)
+                        .bind::<Array<BigInt>, _>(&event_indices)
+                        .bind::<BigInt, _>(start_version)
+                        .bind::<BigInt, _>(end_version)
+                        .execute(conn)
+                        .await?,
+                );
+            }
+            // Gas fee rows don't come from an event and always get the same index
+            let gas_fee_rows = sql_query(
+                "UPDATE coin_activities SET event_index = $1
+                WHERE transaction_version BETWEEN $2 AND $3
+                AND event_creation_number = $4 AND is_gas_fee AND event_index IS NULL",
+            )
+            .bind::<BigInt, _>(BURN_GAS_EVENT_INDEX)
+            .bind::<BigInt, _>(start_version)
+            .bind::<BigInt, _>(end_version)
+            .bind::<BigInt, _>(BURN_GAS_EVENT_CREATION_NUM)
+            .execute(conn)
+            .await?;
+            updated_rows[1] += gas_fee_rows;
+            Ok(updated_rows)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[async_trait]
+impl ProcessorTrait for EventIndexBackfillProcessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::EventIndexBackfillProcessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp.clone();
+
+        let indices = get_event_indices(&transactions)?;
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = update_event_indices(
+            self.get_pool(),
+            start_version as i64,
+            end_version as i64,
+            indices,
+        )
+        .await;
+
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(updated_rows) => {
+                for (table, updated_rows) in BACKFILLED_TABLES.iter().zip(updated_rows) {
+                    if updated_rows > 0 {
+                        info!(
+                            processor_name = self.name(),
+                            table = table,
+                            start_version = start_version,
+                            end_version = end_version,
+                            updated_rows,
+                            "[Parser] Backfilled event_index"
+                        );
+                    }
+                }
+                Ok(ProcessingResult::DefaultProcessingResult(
+                    DefaultProcessingResult {
+                        start_version,
+                        end_version,
+                        processing_duration_in_secs,
+                        db_insertion_duration_in_secs,
+                        last_transaction_timestamp,
+                    },
+                ))
+            },
+            Err(err) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    "[Parser] Error backfilling event_index: {:?}",
+                    err
+                );
+                bail!(format!(
+                    "Error backfilling event_index. Processor {}. Start {}. End {}. Error {:?}",
+                    self.name(),
+                    start_version,
+                    end_version,
+                    err
+                ))
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{transaction::TxnData, EventKey as ProtoEventKey, UserTransaction},
+        util::timestamp::Timestamp,
+    };
+
+    fn event(account_address: &str, creation_number: u64, sequence_number: u64) -> Event {
+        Event {
+            key: Some(ProtoEventKey {
+                creation_number,
+                account_address: account_address.to_string(),
+            }),
+            sequence_number,
+            ..Event::default()
+        }
+    }
+
+    #[test]
+    fn test_get_event_indices() {
+        let transaction = Transaction {
+            version: 7,
+            timestamp: Some(Timestamp::default()),
+            txn_data: Some(TxnData::User(UserTransaction {
+                events: vec![
+                    event("0x1", 2, 10),
+                    // Module events share an empty handle
+                    event("0x0", 0, 0),
+                    event("0xa", 3, 0),
+                    event("0x0", 0, 0),
+                ],
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        };
+
+        let indices = get_event_indices(&[transaction]).unwrap();
+        assert_eq!(indices.len(), 2);
+        assert_eq!(
+            indices[0].0.event_account_address,
+            standardize_address("0x1")
+        );
+        assert_eq!(
+            (
+                indices[0].0.event_creation_number,
+                indices[0].0.event_sequence_number
+            ),
+            (2, 10)
+        );
+        assert_eq!(indices[0].1, 0);
+        assert_eq!(
+            indices[1].0.event_account_address,
+            standardize_address("0xa")
+        );
+        assert_eq!(indices[1].0.transaction_version, 7);
+        assert_eq!(indices[1].1, 2);
+    }
+}
@@ -3,7 +3,13 @@
 
 use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
-    db::common::models::events_models::events::EventModel,
+    db::common::models::{
+        event_parser::{parse_transaction_events, EventParserCollector},
+        events_models::{
+            custom_events::{has_custom_event_parsers, CustomEvent, CustomEventParser},
+            events::EventModel,
+        },
+    },
     gap_detectors::ProcessingResult,
     schema,
     utils::{
@@ -20,18 +26,34 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventsProcessorConfig {
+    /// Also write `global_event_index`, which increases across transactions. `event_index` stays
+    /// the index within the transaction either way.
+    #[serde(default)]
+    pub compute_global_event_index: bool,
+}
+
 pub struct EventsProcessor {
     connection_pool: ArcDbPool,
+    config: EventsProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
 }
 
 impl EventsProcessor {
-    pub fn new(connection_pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        config: EventsProcessorConfig,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+    ) -> Self {
         Self {
             connection_pool,
+            config,
             per_table_chunk_sizes,
         }
     }
@@ -54,6 +76,7 @@ async fn insert_to_db(
     start_version: u64,
     end_version: u64,
     events: &[EventModel],
+    custom_events: &[CustomEvent],
     per_table_chunk_sizes: &AHashMap<String, usize>,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
@@ -62,13 +85,22 @@ async fn insert_to_db(
         end_version = end_version,
         "Inserting to db",
     );
-    execute_in_chunks(
-        conn,
+    let ev = execute_in_chunks(
+        conn.clone(),
         insert_events_query,
         events,
         get_config_table_chunk_size::<EventModel>("events", per_table_chunk_sizes),
-    )
-    .await?;
+    );
+    let ce = execute_in_chunks(
+        conn,
+        insert_custom_events_query,
+        custom_events,
+        get_config_table_chunk_size::<CustomEvent>("custom_events", per_table_chunk_sizes),
+    );
+    let (ev_res, ce_res) = tokio::join!(ev, ce);
+    for res in [ev_res, ce_res] {
+        res?;
+    }
     Ok(())
 }
 
@@ -87,6 +119,27 @@ fn insert_events_query(
             .set((
                 inserted_at.eq(excluded(inserted_at)),
                 indexed_type.eq(excluded(indexed_type)),
+                global_event_index.eq(excluded(global_event_index)),
+            )),
+        None,
+    )
+}
+
+fn insert_custom_events_query(
+    items_to_insert: Vec<CustomEvent>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::custom_events::dsl::*;
+    (
+        diesel::insert_into(schema::custom_events::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_update()
+            .set((
+                data.eq(excluded(data)),
+                inserted_at.eq(excluded(inserted_at)),
             )),
         None,
     )
@@ -100,7 +153,7 @@ impl ProcessorTrait for EventsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -109,7 +162,10 @@ impl ProcessorTrait for EventsProcessor {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp.clone();
 
         let mut events = vec![];
-        for txn in &transactions {
+        // Only events of types registered in the custom event registry end up here
+        let mut custom_events = EventParserCollector::new(CustomEventParser);
+        let should_parse_custom_events = has_custom_event_parsers();
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let block_height = txn.block_height as i64;
             let txn_data = match txn.txn_data.as_ref() {
@@ -134,9 +190,19 @@ impl ProcessorTrait for EventsProcessor {
                 _ => &default,
             };
 
-            let txn_events = EventModel::from_events(raw_events, txn_version, block_height);
+            let mut txn_events = EventModel::from_events(raw_events, txn_version, block_height);
+            if self.config.compute_global_event_index {
+                txn_events
+                    .iter_mut()
+                    .for_each(EventModel::set_global_event_index);
+            }
             events.extend(txn_events);
+
+            if should_parse_custom_events {
+                parse_transaction_events(txn, &mut [&mut custom_events])?;
+            }
         }
+        let custom_events = custom_events.into_rows();
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
@@ -147,6 +213,7 @@ impl ProcessorTrait for EventsProcessor {
             start_version,
             end_version,
             &events,
+            &custom_events,
             &self.per_table_chunk_sizes,
         )
         .await;
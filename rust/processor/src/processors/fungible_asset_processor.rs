@@ -4,8 +4,9 @@
 use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
     db::common::models::{
-        coin_models::coin_supply::CoinSupply,
+        coin_models::{coin_infos::CoinInfo, coin_supply::CoinSupply},
         fungible_asset_models::{
+            coin_balance_snapshots::CoinBalanceSnapshot,
             v2_fungible_asset_activities::{EventToCoinType, FungibleAssetActivity},
             v2_fungible_asset_balances::{
                 CurrentFungibleAssetBalance, CurrentFungibleAssetMapping,
@@ -40,11 +41,21 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FungibleAssetProcessorConfig {
+    /// Write the last balance per owner, asset and epoch to coin_balance_snapshots
+    #[serde(default)]
+    pub write_coin_balance_snapshots: bool,
+}
+
 pub struct FungibleAssetProcessor {
     connection_pool: ArcDbPool,
+    config: FungibleAssetProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
     deprecated_tables: TableFlags,
 }
@@ -52,11 +63,13 @@ pub struct FungibleAssetProcessor {
 impl FungibleAssetProcessor {
     pub fn new(
         connection_pool: ArcDbPool,
+        config: FungibleAssetProcessorConfig,
         per_table_chunk_sizes: AHashMap<String, usize>,
         deprecated_tables: TableFlags,
     ) -> Self {
         Self {
             connection_pool,
+            config,
             per_table_chunk_sizes,
             deprecated_tables,
         }
@@ -88,6 +101,8 @@ async fn insert_to_db(
         &[CurrentUnifiedFungibleAssetBalance],
     ),
     coin_supply: &[CoinSupply],
+    coin_infos: &[CoinInfo],
+    coin_balance_snapshots: &[CoinBalanceSnapshot],
     per_table_chunk_sizes: &AHashMap<String, usize>,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
@@ -152,15 +167,30 @@ async fn insert_to_db(
         ),
     );
     let cs = execute_in_chunks(
-        conn,
+        conn.clone(),
         insert_coin_supply_query,
         coin_supply,
         get_config_table_chunk_size::<CoinSupply>("coin_supply", per_table_chunk_sizes),
     );
-    let (faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res) =
-        tokio::join!(faa, fam, fab, cfab, cufab_v1, cufab_v2, cs);
+    let ci = execute_in_chunks(
+        conn.clone(),
+        insert_coin_infos_query,
+        coin_infos,
+        get_config_table_chunk_size::<CoinInfo>("coin_infos", per_table_chunk_sizes),
+    );
+    let cbs = execute_in_chunks(
+        conn,
+        insert_coin_balance_snapshots_query,
+        coin_balance_snapshots,
+        get_config_table_chunk_size::<CoinBalanceSnapshot>(
+            "coin_balance_snapshots",
+            per_table_chunk_sizes,
+        ),
+    );
+    let (faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res, ci_res, cbs_res) =
+        tokio::join!(faa, fam, fab, cfab, cufab_v1, cufab_v2, cs, ci, cbs);
     for res in [
-        faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res,
+        faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res, ci_res, cbs_res,
     ] {
         res?;
     }
@@ -343,6 +373,52 @@ fn insert_coin_supply_query(
     )
 }
 
+fn insert_coin_infos_query(
+    items_to_insert: Vec<CoinInfo>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::coin_infos::dsl::*;
+
+    (
+        diesel::insert_into(schema::coin_infos::table)
+            .values(items_to_insert)
+            .on_conflict(coin_type_hash)
+            .do_update()
+            .set((
+                supply.eq(excluded(supply)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE coin_infos.last_transaction_version IS NULL OR coin_infos.last_transaction_version <= excluded.last_transaction_version "),
+    )
+}
+
+fn insert_coin_balance_snapshots_query(
+    items_to_insert: Vec<CoinBalanceSnapshot>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::coin_balance_snapshots::dsl::*;
+
+    (
+        diesel::insert_into(schema::coin_balance_snapshots::table)
+            .values(items_to_insert)
+            .on_conflict((owner_address, asset_type, epoch))
+            .do_update()
+            .set((
+                amount.eq(excluded(amount)),
+                token_standard.eq(excluded(token_standard)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE coin_balance_snapshots.last_transaction_version <= excluded.last_transaction_version "),
+    )
+}
+
 #[async_trait]
 impl ProcessorTrait for FungibleAssetProcessor {
     fn name(&self) -> &'static str {
@@ -351,7 +427,7 @@ impl ProcessorTrait for FungibleAssetProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -366,6 +442,7 @@ impl ProcessorTrait for FungibleAssetProcessor {
             mut current_fungible_asset_balances,
             current_unified_fungible_asset_balances,
             mut coin_supply,
+            mut coin_infos,
         ) = parse_v2_coin(&transactions).await;
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
@@ -375,6 +452,17 @@ impl ProcessorTrait for FungibleAssetProcessor {
             .into_iter()
             .partition(|x| x.is_primary.is_none());
 
+        // Taken before the deprecated tables are cleared since snapshots only need the balances
+        let coin_balance_snapshots = if self.config.write_coin_balance_snapshots {
+            let epochs = transactions
+                .iter()
+                .map(|txn| (txn.version as i64, txn.epoch as i64))
+                .collect::<AHashMap<_, _>>();
+            CoinBalanceSnapshot::from_balances(&fungible_asset_balances, &epochs)
+        } else {
+            vec![]
+        };
+
         if self
             .deprecated_tables
             .contains(TableFlags::FUNGIBLE_ASSET_BALANCES)
@@ -393,6 +481,10 @@ impl ProcessorTrait for FungibleAssetProcessor {
             coin_supply.clear();
         }
 
+        if self.deprecated_tables.contains(TableFlags::COIN_INFOS) {
+            coin_infos.clear();
+        }
+
         let tx_result = insert_to_db(
             self.get_pool(),
             self.name(),
@@ -404,6 +496,8 @@ impl ProcessorTrait for FungibleAssetProcessor {
             &current_fungible_asset_balances,
             (&coin_balance, &fa_balance),
             &coin_supply,
+            &coin_infos,
+            &coin_balance_snapshots,
             &self.per_table_chunk_sizes,
         )
         .await;
@@ -446,12 +540,14 @@ async fn parse_v2_coin(
     Vec<CurrentFungibleAssetBalance>,
     Vec<CurrentUnifiedFungibleAssetBalance>,
     Vec<CoinSupply>,
+    Vec<CoinInfo>,
 ) {
     let mut fungible_asset_activities = vec![];
     let mut fungible_asset_balances = vec![];
     let mut all_coin_supply = vec![];
     let mut current_fungible_asset_balances: CurrentFungibleAssetMapping = AHashMap::new();
     let mut fungible_asset_metadata: FungibleAssetMetadataMapping = AHashMap::new();
+    let mut coin_infos: AHashMap<String, CoinInfo> = AHashMap::new();
 
     // Get Metadata for fungible assets by object
     let mut fungible_asset_object_helper: ObjectAggregatedDataMapping = AHashMap::new();
@@ -618,6 +714,11 @@ async fn parse_v2_coin(
                 block_height,
                 fee_statement,
             );
+            if let Some(fee_payer_gas_event) =
+                FungibleAssetActivity::get_fee_payer_gas_event(&gas_event)
+            {
+                fungible_asset_activities.push(fee_payer_gas_event);
+            }
             fungible_asset_activities.push(gas_event);
         }
 
@@ -703,6 +804,17 @@ async fn parse_v2_coin(
                     {
                         fungible_asset_metadata.insert(fa_metadata.asset_type.clone(), fa_metadata);
                     }
+                    if let Some(coin_info) =
+                        CoinInfo::from_write_resource(write_resource, txn_version, txn_timestamp)
+                            .unwrap()
+                    {
+                        match coin_infos.get_mut(&coin_info.coin_type_hash) {
+                            Some(existing) => existing.merge_later(coin_info),
+                            None => {
+                                coin_infos.insert(coin_info.coin_type_hash.clone(), coin_info);
+                            },
+                        }
+                    }
                     if let Some((balance, curr_balance)) =
                         FungibleAssetBalance::get_v2_from_write_resource(
                             write_resource,
@@ -752,8 +864,11 @@ async fn parse_v2_coin(
         .into_values()
         .collect::<Vec<CurrentFungibleAssetBalance>>();
 
+    let mut coin_infos = coin_infos.into_values().collect::<Vec<CoinInfo>>();
+
     // Sort by PK
     fungible_asset_metadata.sort_by(|a, b| a.asset_type.cmp(&b.asset_type));
+    coin_infos.sort_by(|a, b| a.coin_type_hash.cmp(&b.coin_type_hash));
     current_fungible_asset_balances.sort_by(|a, b| a.storage_id.cmp(&b.storage_id));
 
     // Process the unified balance
@@ -768,5 +883,6 @@ async fn parse_v2_coin(
         current_fungible_asset_balances,
         current_unified_fungible_asset_balances,
         all_coin_supply,
+        coin_infos,
     )
 }
@@ -7,6 +7,7 @@
 pub mod account_transactions_processor;
 pub mod ans_processor;
 pub mod default_processor;
+pub mod event_index_backfill_processor;
 pub mod events_processor;
 pub mod fungible_asset_processor;
 pub mod monitoring_processor;
@@ -14,6 +15,7 @@ pub mod nft_metadata_processor;
 pub mod objects_processor;
 pub mod parquet_processors;
 pub mod stake_processor;
+pub mod token_activities_reprocessor;
 pub mod token_v2_processor;
 pub mod transaction_metadata_processor;
 pub mod user_transaction_processor;
@@ -22,12 +24,14 @@ use self::{
     account_transactions_processor::AccountTransactionsProcessor,
     ans_processor::{AnsProcessor, AnsProcessorConfig},
     default_processor::DefaultProcessor,
-    events_processor::EventsProcessor,
-    fungible_asset_processor::FungibleAssetProcessor,
+    event_index_backfill_processor::EventIndexBackfillProcessor,
+    events_processor::{EventsProcessor, EventsProcessorConfig},
+    fungible_asset_processor::{FungibleAssetProcessor, FungibleAssetProcessorConfig},
     monitoring_processor::MonitoringProcessor,
     nft_metadata_processor::{NftMetadataProcessor, NftMetadataProcessorConfig},
     objects_processor::{ObjectsProcessor, ObjectsProcessorConfig},
     stake_processor::{StakeProcessor, StakeProcessorConfig},
+    token_activities_reprocessor::{TokenActivitiesReprocessor, TokenActivitiesReprocessorConfig},
     token_v2_processor::{TokenV2Processor, TokenV2ProcessorConfig},
     transaction_metadata_processor::TransactionMetadataProcessor,
     user_transaction_processor::UserTransactionProcessor,
@@ -50,7 +54,7 @@ use crate::{
     schema::processor_status,
     utils::{
         counters::{GOT_CONNECTION_COUNT, UNABLE_TO_GET_CONNECTION_COUNT},
-        database::{execute_with_better_error, ArcDbPool, DbPoolConnection},
+        database::{execute_with_better_error, get_pool_connection, ArcDbPool, DbPoolConnection},
         util::parse_timestamp,
     },
 };
@@ -59,7 +63,7 @@ use async_trait::async_trait;
 use diesel::{pg::upsert::excluded, ExpressionMethods};
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct DefaultProcessingResult {
@@ -76,10 +80,15 @@ pub struct DefaultProcessingResult {
 pub trait ProcessorTrait: Send + Sync + Debug {
     fn name(&self) -> &'static str;
 
+    /// Key of the processor's row in processor_status
+    fn processor_status_name(&self) -> String {
+        self.name().to_string()
+    }
+
     /// Process all transactions including writing to the database
     async fn process_transactions(
         &self,
-        transactions: Vec<ProtoTransaction>,
+        transactions: Arc<Vec<ProtoTransaction>>,
         start_version: u64,
         end_version: u64,
         db_chain_id: Option<u64>,
@@ -98,11 +107,12 @@ pub trait ProcessorTrait: Send + Sync + Debug {
     }
 
     /// Gets the connection.
-    /// If it was unable to do so (default timeout: 30s), it will keep retrying until it can.
+    /// If it was unable to do so (db_pool_connection_timeout_in_secs, default 30s), it will keep
+    /// retrying until it can. Each timeout counts towards indexer_processor_db_pool_exhausted_count.
     async fn get_conn(&self) -> DbPoolConnection {
         let pool = self.connection_pool();
         loop {
-            match pool.get().await {
+            match get_pool_connection(pool).await {
                 Ok(conn) => {
                     GOT_CONNECTION_COUNT.inc();
                     return conn;
@@ -127,12 +137,14 @@ pub trait ProcessorTrait: Send + Sync + Debug {
         &self,
         version: u64,
         last_transaction_timestamp: Option<aptos_protos::util::timestamp::Timestamp>,
+        chain_id: u64,
     ) -> anyhow::Result<()> {
         let timestamp = last_transaction_timestamp.map(|t| parse_timestamp(&t, version as i64));
         let status = ProcessorStatus {
-            processor: self.name().to_string(),
+            processor: self.processor_status_name(),
             last_success_version: version as i64,
             last_transaction_timestamp: timestamp,
+            chain_id: Some(chain_id as i64),
         };
         execute_with_better_error(
             self.get_pool(),
@@ -146,6 +158,7 @@ pub trait ProcessorTrait: Send + Sync + Debug {
                     processor_status::last_updated.eq(excluded(processor_status::last_updated)),
                     processor_status::last_transaction_timestamp
                         .eq(excluded(processor_status::last_transaction_timestamp)),
+                    processor_status::chain_id.eq(excluded(processor_status::chain_id)),
                 )),
             Some(" WHERE processor_status.last_success_version <= EXCLUDED.last_success_version "),
         )
@@ -189,12 +202,14 @@ pub enum ProcessorConfig {
     AccountTransactionsProcessor,
     AnsProcessor(AnsProcessorConfig),
     DefaultProcessor,
-    EventsProcessor,
-    FungibleAssetProcessor,
+    EventIndexBackfillProcessor,
+    EventsProcessor(EventsProcessorConfig),
+    FungibleAssetProcessor(FungibleAssetProcessorConfig),
     MonitoringProcessor,
     NftMetadataProcessor(NftMetadataProcessorConfig),
     ObjectsProcessor(ObjectsProcessorConfig),
     StakeProcessor(StakeProcessorConfig),
+    TokenActivitiesReprocessor(TokenActivitiesReprocessorConfig),
     TokenV2Processor(TokenV2ProcessorConfig),
     TransactionMetadataProcessor,
     UserTransactionProcessor,
@@ -213,6 +228,14 @@ impl ProcessorConfig {
         self.into()
     }
 
+    /// Key of the processor's row in processor_status, see `ProcessorTrait::processor_status_name`
+    pub fn processor_status_name(&self) -> String {
+        match self {
+            ProcessorConfig::TokenV2Processor(config) => config.shadowed_name(self.name()),
+            _ => self.name().to_string(),
+        }
+    }
+
     pub fn is_parquet_processor(&self) -> bool {
         matches!(
             self,
@@ -247,12 +270,14 @@ pub enum Processor {
     AccountTransactionsProcessor,
     AnsProcessor,
     DefaultProcessor,
+    EventIndexBackfillProcessor,
     EventsProcessor,
     FungibleAssetProcessor,
     MonitoringProcessor,
     NftMetadataProcessor,
     ObjectsProcessor,
     StakeProcessor,
+    TokenActivitiesReprocessor,
     TokenV2Processor,
     TransactionMetadataProcessor,
     UserTransactionProcessor,
@@ -277,4 +302,26 @@ mod test {
     fn test_processor_names_complete() {
         assert_eq!(ProcessorName::VARIANTS, ProcessorDiscriminants::VARIANTS);
     }
+
+    #[test]
+    fn test_processor_status_name() {
+        let config: ProcessorConfig = serde_json::from_str(
+            r#"{"type": "token_v2_processor", "shadow_table_suffix": "_shadow"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.processor_status_name(), "token_v2_processor_shadow");
+
+        let config: ProcessorConfig =
+            serde_json::from_str(r#"{"type": "token_v2_processor"}"#).unwrap();
+        assert_eq!(config.processor_status_name(), "token_v2_processor");
+    }
+
+    #[test]
+    fn test_invalid_shadow_table_suffix() {
+        for suffix in ["", "_Shadow", "_shadow; DROP TABLE x", "\"_shadow\""] {
+            let config =
+                serde_json::json!({"type": "token_v2_processor", "shadow_table_suffix": suffix});
+            assert!(serde_json::from_value::<ProcessorConfig>(config).is_err());
+        }
+    }
 }
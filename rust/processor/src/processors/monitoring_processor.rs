@@ -5,7 +5,7 @@ use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{gap_detectors::ProcessingResult, utils::database::ArcDbPool};
 use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 pub struct MonitoringProcessor {
     connection_pool: ArcDbPool,
@@ -36,7 +36,7 @@ impl ProcessorTrait for MonitoringProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -29,6 +29,7 @@ use google_cloud_pubsub::client::{Client, ClientConfig};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 use tracing::{error, info};
@@ -93,7 +94,7 @@ impl ProcessorTrait for NftMetadataProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         db_chain_id: Option<u64>,
@@ -274,6 +275,7 @@ async fn parse_v2_token(
                             txn_version,
                             wsc_index,
                             txn_timestamp,
+                            None,
                         )
                         .unwrap()
                     {
@@ -5,7 +5,7 @@ use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
     db::common::models::object_models::{
         v2_object_utils::{ObjectAggregatedData, ObjectAggregatedDataMapping, ObjectWithMetadata},
-        v2_objects::{CurrentObject, Object},
+        v2_objects::{CurrentObject, CurrentObjectChange, CurrentObjectChangeQuery, Object},
     },
     gap_detectors::ProcessingResult,
     schema,
@@ -16,7 +16,7 @@ use crate::{
     worker::TableFlags,
     IndexerGrpcProcessorConfig,
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::bail;
 use aptos_protos::transaction::v1::{write_set_change::Change, Transaction};
 use async_trait::async_trait;
@@ -26,7 +26,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -36,6 +36,10 @@ pub struct ObjectsProcessorConfig {
     pub query_retries: u32,
     #[serde(default = "IndexerGrpcProcessorConfig::default_query_retry_delay_ms")]
     pub query_retry_delay_ms: u64,
+    /// Write change rows (old value, new value, version) to current_objects_changes instead of
+    /// upserting current_objects
+    #[serde(default)]
+    pub emit_current_object_changes: bool,
 }
 pub struct ObjectsProcessor {
     connection_pool: ArcDbPool,
@@ -76,7 +80,11 @@ async fn insert_to_db(
     name: &'static str,
     start_version: u64,
     end_version: u64,
-    (objects, current_objects): (&[Object], &[CurrentObject]),
+    (objects, current_objects, current_object_changes): (
+        &[Object],
+        &[CurrentObject],
+        &[CurrentObjectChange],
+    ),
     per_table_chunk_sizes: &AHashMap<String, usize>,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
@@ -93,13 +101,22 @@ async fn insert_to_db(
         get_config_table_chunk_size::<Object>("objects", per_table_chunk_sizes),
     );
     let co = execute_in_chunks(
-        conn,
+        conn.clone(),
         insert_current_objects_query,
         current_objects,
         get_config_table_chunk_size::<CurrentObject>("current_objects", per_table_chunk_sizes),
     );
-    let (io_res, co_res) = tokio::join!(io, co);
-    for res in [io_res, co_res] {
+    let coc = execute_in_chunks(
+        conn,
+        insert_current_object_changes_query,
+        current_object_changes,
+        get_config_table_chunk_size::<CurrentObjectChange>(
+            "current_objects_changes",
+            per_table_chunk_sizes,
+        ),
+    );
+    let (io_res, co_res, coc_res) = tokio::join!(io, co, coc);
+    for res in [io_res, co_res, coc_res] {
         res?;
     }
 
@@ -151,6 +168,49 @@ fn insert_current_objects_query(
     )
 }
 
+fn insert_current_object_changes_query(
+    items_to_insert: Vec<CurrentObjectChange>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::current_objects_changes::dsl::*;
+    (
+        diesel::insert_into(schema::current_objects_changes::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, write_set_change_index))
+            .do_update()
+            .set((
+                old_value.eq(excluded(old_value)),
+                new_value.eq(excluded(new_value)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        None,
+    )
+}
+
+/// Addresses of all objects written or deleted in the batch
+fn get_object_addresses(transactions: &[Transaction]) -> Vec<String> {
+    let mut addresses = AHashSet::new();
+    for txn in transactions {
+        let Some(info) = txn.info.as_ref() else {
+            continue;
+        };
+        for wsc in info.changes.iter() {
+            match wsc.change.as_ref() {
+                Some(Change::WriteResource(wr)) if wr.type_str == "0x1::object::ObjectCore" => {
+                    addresses.insert(standardize_address(&wr.address));
+                },
+                Some(Change::DeleteResource(dr)) if dr.type_str == "0x1::object::ObjectGroup" => {
+                    addresses.insert(standardize_address(&dr.address));
+                },
+                _ => {},
+            }
+        }
+    }
+    addresses.into_iter().collect()
+}
+
 #[async_trait]
 impl ProcessorTrait for ObjectsProcessor {
     fn name(&self) -> &'static str {
@@ -159,7 +219,7 @@ impl ProcessorTrait for ObjectsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -170,6 +230,21 @@ impl ProcessorTrait for ObjectsProcessor {
         let mut conn = self.get_conn().await;
         let query_retries = self.config.query_retries;
         let query_retry_delay_ms = self.config.query_retry_delay_ms;
+        let emit_changes = self.config.emit_current_object_changes;
+
+        // When emitting changes current_objects isn't written, so the latest state of each object
+        // comes from the change rows instead. This is kept up to date through the batch.
+        let mut latest_objects = if emit_changes {
+            CurrentObjectChangeQuery::get_latest_by_addresses(
+                &get_object_addresses(&transactions),
+                start_version as i64,
+                &mut conn,
+            )
+            .await?
+        } else {
+            AHashMap::new()
+        };
+        let mut all_current_object_changes = vec![];
 
         // Moving object handling here because we need a single object
         // map through transactions for lookups
@@ -177,7 +252,7 @@ impl ProcessorTrait for ObjectsProcessor {
         let mut all_current_objects = AHashMap::new();
         let mut object_metadata_helper: ObjectAggregatedDataMapping = AHashMap::new();
 
-        for txn in &transactions {
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let changes = &txn
                 .info
@@ -233,6 +308,15 @@ impl ProcessorTrait for ObjectsProcessor {
                         )
                         .unwrap()
                         {
+                            if emit_changes {
+                                all_current_object_changes.push(CurrentObjectChange::new(
+                                    index,
+                                    latest_objects.get(&object.object_address),
+                                    current_object,
+                                ));
+                                latest_objects
+                                    .insert(object.object_address.clone(), current_object.clone());
+                            }
                             all_objects.push(object.clone());
                             all_current_objects
                                 .insert(object.object_address.clone(), current_object.clone());
@@ -245,7 +329,11 @@ impl ProcessorTrait for ObjectsProcessor {
                             inner,
                             txn_version,
                             index,
-                            &all_current_objects,
+                            if emit_changes {
+                                &latest_objects
+                            } else {
+                                &all_current_objects
+                            },
                             &mut conn,
                             query_retries,
                             query_retry_delay_ms,
@@ -253,6 +341,15 @@ impl ProcessorTrait for ObjectsProcessor {
                         .await
                         .unwrap()
                         {
+                            if emit_changes {
+                                all_current_object_changes.push(CurrentObjectChange::new(
+                                    index,
+                                    latest_objects.get(&object.object_address),
+                                    &current_object,
+                                ));
+                                latest_objects
+                                    .insert(object.object_address.clone(), current_object.clone());
+                            }
                             all_objects.push(object.clone());
                             all_current_objects
                                 .insert(object.object_address.clone(), current_object.clone());
@@ -269,6 +366,9 @@ impl ProcessorTrait for ObjectsProcessor {
             .collect::<Vec<CurrentObject>>();
         all_current_objects.sort_by(|a, b| a.object_address.cmp(&b.object_address));
 
+        if emit_changes {
+            all_current_objects.clear();
+        }
         if self.deprecated_tables.contains(TableFlags::OBJECTS) {
             all_objects.clear();
         }
@@ -281,7 +381,11 @@ impl ProcessorTrait for ObjectsProcessor {
             self.name(),
             start_version,
             end_version,
-            (&all_objects, &all_current_objects),
+            (
+                &all_objects,
+                &all_current_objects,
+                &all_current_object_changes,
+            ),
             &self.per_table_chunk_sizes,
         )
         .await;
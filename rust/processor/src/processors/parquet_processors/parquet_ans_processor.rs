@@ -4,7 +4,8 @@
 use super::ParquetProcessorTrait;
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::ans_models::{
@@ -23,7 +24,7 @@ use aptos_protos::transaction::v1::{
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -38,6 +39,9 @@ pub struct ParquetAnsProcessorConfig {
     pub ans_v1_name_records_table_handle: String,
     pub ans_v2_contract_address: String,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 
 impl ParquetProcessorTrait for ParquetAnsProcessorConfig {
@@ -60,6 +64,9 @@ impl ParquetAnsProcessor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(&config.included_fields, &[AnsPrimaryNameV2::TABLE_NAME])
+            .expect("Invalid included_fields");
+
         let ans_primary_name_v2_sender = create_parquet_handler_loop::<AnsPrimaryNameV2>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetAnsProcessor.into(),
@@ -68,6 +75,7 @@ impl ParquetAnsProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         Self {
@@ -96,7 +104,7 @@ impl ProcessorTrait for ParquetAnsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
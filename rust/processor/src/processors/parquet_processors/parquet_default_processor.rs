@@ -3,7 +3,8 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::default_models::{
@@ -25,6 +26,7 @@ use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Debug, Formatter, Result},
+    sync::Arc,
     time::Duration,
 };
 
@@ -37,6 +39,9 @@ pub struct ParquetDefaultProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 impl ParquetProcessorTrait for ParquetDefaultProcessorConfig {
     fn parquet_upload_interval_in_secs(&self) -> Duration {
@@ -63,6 +68,18 @@ impl ParquetDefaultProcessor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(
+            &config.included_fields,
+            &[
+                ParquetTransaction::TABLE_NAME,
+                MoveResource::TABLE_NAME,
+                WriteSetChangeModel::TABLE_NAME,
+                TableItem::TABLE_NAME,
+                MoveModule::TABLE_NAME,
+            ],
+        )
+        .expect("Invalid included_fields");
+
         let transaction_sender = create_parquet_handler_loop::<ParquetTransaction>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetDefaultProcessor.into(),
@@ -71,6 +88,7 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         let move_resource_sender = create_parquet_handler_loop::<MoveResource>(
@@ -81,6 +99,7 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         let wsc_sender = create_parquet_handler_loop::<WriteSetChangeModel>(
@@ -91,6 +110,7 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         let table_item_sender = create_parquet_handler_loop::<TableItem>(
@@ -101,6 +121,7 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
         let move_module_sender = create_parquet_handler_loop::<MoveModule>(
             new_gap_detector_sender.clone(),
@@ -110,6 +131,7 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         Self {
@@ -145,7 +167,7 @@ impl ProcessorTrait for ParquetDefaultProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -155,7 +177,7 @@ impl ProcessorTrait for ParquetDefaultProcessor {
         let (
             (move_resources, write_set_changes, transactions, table_items, move_modules),
             transaction_version_to_struct_count,
-        ) = tokio::task::spawn_blocking(move || process_transactions(transactions))
+        ) = tokio::task::spawn_blocking(move || process_transactions(&transactions))
             .await
             .expect("Failed to spawn_blocking for TransactionModel::from_transactions");
 
@@ -214,7 +236,7 @@ impl ProcessorTrait for ParquetDefaultProcessor {
 }
 
 pub fn process_transactions(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
 ) -> (
     (
         Vec<MoveResource>,
@@ -227,10 +249,7 @@ pub fn process_transactions(
 ) {
     let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
     let (txns, _block_metadata_txns, write_set_changes, wsc_details) =
-        TransactionModel::from_transactions(
-            &transactions,
-            &mut transaction_version_to_struct_count,
-        );
+        TransactionModel::from_transactions(transactions, &mut transaction_version_to_struct_count);
 
     let mut move_modules = vec![];
     let mut move_resources = vec![];
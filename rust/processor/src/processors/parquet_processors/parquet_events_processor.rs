@@ -3,7 +3,8 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::events_models::parquet_events::{Event, ParquetEventModel},
@@ -17,7 +18,7 @@ use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::warn;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -28,6 +29,9 @@ pub struct ParquetEventsProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 
 impl ParquetProcessorTrait for ParquetEventsProcessorConfig {
@@ -49,6 +53,9 @@ impl ParquetEventsProcessor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(&config.included_fields, &[Event::TABLE_NAME])
+            .expect("Invalid included_fields");
+
         let event_sender = create_parquet_handler_loop::<Event>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetDefaultProcessor.into(),
@@ -57,6 +64,7 @@ impl ParquetEventsProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         Self {
@@ -84,7 +92,7 @@ impl ProcessorTrait for ParquetEventsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -93,7 +101,7 @@ impl ProcessorTrait for ParquetEventsProcessor {
         let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
 
         let mut events = vec![];
-        for txn in &transactions {
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let block_height = txn.block_height as i64;
             let block_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
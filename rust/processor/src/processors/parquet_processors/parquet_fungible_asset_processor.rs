@@ -4,7 +4,8 @@
 use super::ParquetProcessorTrait;
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::{
@@ -27,7 +28,7 @@ use async_trait::async_trait;
 use chrono::NaiveDateTime;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -38,6 +39,9 @@ pub struct ParquetFungibleAssetProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 
 impl ParquetProcessorTrait for ParquetFungibleAssetProcessorConfig {
@@ -60,6 +64,12 @@ impl ParquetFungibleAssetProcessor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(
+            &config.included_fields,
+            &[CoinSupply::TABLE_NAME, FungibleAssetBalance::TABLE_NAME],
+        )
+        .expect("Invalid included_fields");
+
         let coin_supply_sender = create_parquet_handler_loop::<CoinSupply>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetFungibleAssetProcessor.into(),
@@ -68,6 +78,7 @@ impl ParquetFungibleAssetProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         let fungible_asset_balances_sender = create_parquet_handler_loop::<FungibleAssetBalance>(
@@ -78,6 +89,7 @@ impl ParquetFungibleAssetProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         Self {
@@ -107,7 +119,7 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -3,7 +3,8 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::{
@@ -37,7 +38,7 @@ use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Chan
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -48,6 +49,9 @@ pub struct ParquetTokenV2ProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 impl ParquetProcessorTrait for ParquetTokenV2ProcessorConfig {
     fn parquet_upload_interval_in_secs(&self) -> Duration {
@@ -69,6 +73,12 @@ impl ParquetTokenV2Processor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(
+            &config.included_fields,
+            &[TokenDataV2::TABLE_NAME, TokenOwnershipV2::TABLE_NAME],
+        )
+        .expect("Invalid included_fields");
+
         let v2_token_datas_sender = create_parquet_handler_loop::<TokenDataV2>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetTokenV2Processor.into(),
@@ -77,6 +87,7 @@ impl ParquetTokenV2Processor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         let v2_token_ownerships_sender = create_parquet_handler_loop::<TokenOwnershipV2>(
@@ -87,6 +98,7 @@ impl ParquetTokenV2Processor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
 
         Self {
@@ -116,7 +128,7 @@ impl ProcessorTrait for ParquetTokenV2Processor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -3,7 +3,8 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        generic_parquet_processor::{validate_included_tables, NamedTable, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::common::models::transaction_metadata_model::parquet_write_set_size_info::WriteSetSize,
@@ -17,7 +18,7 @@ use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::warn;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -29,6 +30,9 @@ pub struct ParquetTransactionMetadataProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    // Columns to write per table. Tables that aren't listed get all of their columns
+    #[serde(default = "AHashMap::new")]
+    pub included_fields: AHashMap<String, Vec<String>>,
 }
 
 impl ParquetProcessorTrait for ParquetTransactionMetadataProcessorConfig {
@@ -50,6 +54,9 @@ impl ParquetTransactionMetadataProcessor {
     ) -> Self {
         config.set_google_credentials(config.google_application_credentials.clone());
 
+        validate_included_tables(&config.included_fields, &[WriteSetSize::TABLE_NAME])
+            .expect("Invalid included_fields");
+
         let write_set_size_info_sender = create_parquet_handler_loop::<WriteSetSize>(
             new_gap_detector_sender.clone(),
             ProcessorName::ParquetTransactionMetadataProcessor.into(),
@@ -58,6 +65,7 @@ impl ParquetTransactionMetadataProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            &config.included_fields,
         );
         Self {
             connection_pool,
@@ -84,7 +92,7 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -94,7 +102,7 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
 
         let mut write_set_sizes = vec![];
 
-        for txn in &transactions {
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let block_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
             let size_info = match txn.size_info.as_ref() {
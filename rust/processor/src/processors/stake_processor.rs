@@ -34,7 +34,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -390,7 +390,7 @@ impl ProcessorTrait for StakeProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -416,7 +416,7 @@ impl ProcessorTrait for StakeProcessor {
         let mut all_current_delegated_voter = AHashMap::new();
         let mut all_vote_delegation_handle_to_pool_address = AHashMap::new();
 
-        for txn in &transactions {
+        for txn in transactions.iter() {
             // Add votes data
             let current_stake_pool_voter = CurrentStakingPoolVoter::from_transaction(txn).unwrap();
             all_current_stake_pool_voters.extend(current_stake_pool_voter);
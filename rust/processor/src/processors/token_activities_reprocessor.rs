@@ -0,0 +1,305 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rewrites the legacy token_activities rows of some event types, e.g. after fixing how offers
+//! are parsed. Run it over the affected versions with `starting_version` and `ending_version`; it
+//! re-reads the transactions from the configured source, deletes the rows of the configured
+//! `transfer_types` in each batch's version range and inserts them again as they parse now. Rows
+//! of other types aren't touched. Each batch is one transaction and the processor keeps its own
+//! checkpoint, so it can run next to the live processors and be resumed or rerun.
+
+use super::{
+    token_v2_processor::insert_token_activities_query, DefaultProcessingResult, ProcessorName,
+    ProcessorTrait,
+};
+use crate::{
+    db::common::models::{
+        event_parser::{parse_successful_events, EventParserCollector},
+        token_models::{
+            token_activities::{TokenActivity, TokenActivityParser},
+            token_utils::TokenEventKind,
+        },
+    },
+    gap_detectors::ProcessingResult,
+    utils::database::{
+        execute_in_chunks_with_conn, execute_in_transaction, get_config_table_chunk_size, ArcDbPool,
+    },
+};
+use ahash::{AHashMap, AHashSet};
+use anyhow::bail;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use diesel::{
+    sql_query,
+    sql_types::{Array, BigInt, Text},
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
+use strum::IntoEnumIterator;
+use tracing::{error, info};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenActivitiesReprocessorConfig {
+    /// Full event types of the rows to rewrite, as stored in transfer_type, e.g.
+    /// `0x3::token_transfers::TokenOfferEvent`
+    pub transfer_types: Vec<String>,
+    /// Settings the rows are parsed with, which should match token_v2_processor's
+    /// `token_activities`. Its `event_kinds` is replaced by the kinds of `transfer_types`.
+    #[serde(default)]
+    pub token_activities: TokenActivityParser,
+}
+
+impl TokenActivitiesReprocessorConfig {
+    /// The kinds of token events the configured transfer types are parsed from
+    fn event_kinds(&self) -> AHashSet<TokenEventKind> {
+        assert!(
+            !self.transfer_types.is_empty(),
+            "transfer_types can't be empty"
+        );
+        self.transfer_types
+            .iter()
+            .map(|transfer_type| {
+                TokenEventKind::iter()
+                    .find(|kind| kind.event_type() == transfer_type)
+                    .unwrap_or_else(|| {
+                        panic!("transfer_type {} isn't a token event", transfer_type)
+                    })
+            })
+            .collect()
+    }
+
+    /// `token_activities` restricted to the configured transfer types
+    fn parser(&self) -> TokenActivityParser {
+        TokenActivityParser {
+            event_kinds: Some(self.event_kinds()),
+            ..self.token_activities.clone()
+        }
+    }
+}
+
+pub struct TokenActivitiesReprocessor {
+    connection_pool: ArcDbPool,
+    config: TokenActivitiesReprocessorConfig,
+    parser: TokenActivityParser,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+}
+
+impl TokenActivitiesReprocessor {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        config: TokenActivitiesReprocessorConfig,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+    ) -> Self {
+        let parser = config.parser();
+        Self {
+            connection_pool,
+            config,
+            parser,
+            per_table_chunk_sizes,
+        }
+    }
+}
+
+impl Debug for TokenActivitiesReprocessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "TokenActivitiesReprocessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+fn parse_token_activities(
+    transactions: &[Transaction],
+    parser: &TokenActivityParser,
+) -> anyhow::Result<Vec<TokenActivity>> {
+    let mut collector = EventParserCollector::new(parser.clone());
+    parse_successful_events(transactions, &mut [&mut collector])?;
+    Ok(collector.into_rows())
+}
+
+/// Deletes the rows of `transfer_types` between the versions and inserts `token_activities` in
+/// their place, in one transaction. Returns the number of rows deleted.
+async fn replace_token_activities(
+    pool: ArcDbPool,
+    start_version: i64,
+    end_version: i64,
+    transfer_types: Vec<String>,
+    token_activities: Vec<TokenActivity>,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<usize, diesel::result::Error> {
+    let chunk_size =
+        get_config_table_chunk_size::<TokenActivity>("token_activities", per_table_chunk_sizes);
+    execute_in_transaction(pool, move |conn| {
+        async move {
+            let deleted_rows = sql_query(
+                "DELETE FROM token_activities
+                WHERE transaction_version BETWEEN $1 AND $2 AND transfer_type = ANY($3)",
+            )
+            .bind::<BigInt, _>(start_version)
+            .bind::<BigInt, _>(end_version)
+            .bind::<Array<Text>, _>(&transfer_types)
+            .execute(conn)
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_activities_query,
+                &token_activities,
+                chunk_size,
+            )
+            .await?;
+            Ok(deleted_rows)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+#[async_trait]
+impl ProcessorTrait for TokenActivitiesReprocessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::TokenActivitiesReprocessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp.clone();
+
+        let token_activities = parse_token_activities(&transactions, &self.parser)?;
+        let inserted_rows = token_activities.len();
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = replace_token_activities(
+            self.get_pool(),
+            start_version as i64,
+            end_version as i64,
+            self.config.transfer_types.clone(),
+            token_activities,
+            &self.per_table_chunk_sizes,
+        )
+        .await;
+
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(deleted_rows) => {
+                if deleted_rows > 0 || inserted_rows > 0 {
+                    info!(
+                        processor_name = self.name(),
+                        start_version = start_version,
+                        end_version = end_version,
+                        deleted_rows,
+                        inserted_rows,
+                        "[Parser] Reprocessed token_activities"
+                    );
+                }
+                Ok(ProcessingResult::DefaultProcessingResult(
+                    DefaultProcessingResult {
+                        start_version,
+                        end_version,
+                        processing_duration_in_secs,
+                        db_insertion_duration_in_secs,
+                        last_transaction_timestamp,
+                    },
+                ))
+            },
+            Err(err) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    "[Parser] Error reprocessing token_activities: {:?}",
+                    err
+                );
+                bail!(format!(
+                    "Error reprocessing token_activities. Processor {}. Start {}. End {}. Error {:?}",
+                    self.name(),
+                    start_version,
+                    end_version,
+                    err
+                ))
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{transaction::TxnData, Event, EventKey, UserTransaction},
+        util::timestamp::Timestamp,
+    };
+
+    #[test]
+    fn test_parse_token_activities() {
+        let config = TokenActivitiesReprocessorConfig {
+            transfer_types: vec!["0x3::token_transfers::TokenOfferEvent".to_string()],
+            token_activities: TokenActivityParser::default(),
+        };
+        let token_id = r#"{"property_version": "0", "token_data_id": {"creator": "0x1", "collection": "collection", "name": "token"}}"#;
+        let event = |type_str: &str, data: String| Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: "0x5".to_string(),
+            }),
+            type_str: type_str.to_string(),
+            data,
+            ..Event::default()
+        };
+        let transaction = Transaction {
+            version: 7,
+            timestamp: Some(Timestamp::default()),
+            txn_data: Some(TxnData::User(UserTransaction {
+                events: vec![
+                    event(
+                        "0x3::token::DepositEvent",
+                        format!(r#"{{"amount": "1", "id": {}}}"#, token_id),
+                    ),
+                    event(
+                        "0x3::token_transfers::TokenOfferEvent",
+                        format!(
+                            r#"{{"amount": "1", "to_address": "0x7", "token_id": {}}}"#,
+                            token_id
+                        ),
+                    ),
+                ],
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        };
+
+        // The deposit isn't one of the configured types and is left alone
+        let token_activities = parse_token_activities(&[transaction], &config.parser()).unwrap();
+        assert_eq!(token_activities.len(), 1);
+        assert_eq!(
+            token_activities[0].transfer_type,
+            "0x3::token_transfers::TokenOfferEvent"
+        );
+        assert_eq!(token_activities[0].event_index, Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a token event")]
+    fn test_unknown_transfer_type() {
+        TokenActivitiesReprocessorConfig {
+            transfer_types: vec!["0x3::token::OfferEvent".to_string()],
+            token_activities: TokenActivityParser::default(),
+        }
+        .event_kinds();
+    }
+}
@@ -4,12 +4,19 @@
 use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
     db::common::models::{
+        event_parser::{parse_successful_events, EventParserCollector},
         fungible_asset_models::v2_fungible_asset_utils::FungibleAssetMetadata,
         object_models::v2_object_utils::{
             ObjectAggregatedData, ObjectAggregatedDataMapping, ObjectWithMetadata, Untransferable,
         },
+        processor_status::{ProcessorStatus, ProcessorStatusQuery},
         token_models::{
+            nft_marketplace_activities::{NftMarketplaceActivity, NftMarketplaceActivityParser},
+            token_activities::{TokenActivity, TokenActivityParser},
             token_claims::CurrentTokenPendingClaim,
+            token_data_id_hashes::{TokenDataIdHash, TokenDataIdHashParser},
+            token_property_mutations::{TokenPropertiesByTokenId, TokenPropertyMutation},
+            token_utils::TokenEventKind,
             tokens::{CurrentTokenPendingClaimPK, TableHandleToOwner, TableMetadataForToken},
         },
         token_v2_models::{
@@ -20,7 +27,7 @@ use crate::{
             v2_token_metadata::{CurrentTokenV2Metadata, CurrentTokenV2MetadataPK},
             v2_token_ownerships::{
                 CurrentTokenOwnershipV2, CurrentTokenOwnershipV2PK, NFTOwnershipV2,
-                TokenOwnershipV2,
+                TokenOwnershipV2, TokenV1Burned,
             },
             v2_token_utils::{
                 AptosCollection, Burn, BurnEvent, ConcurrentSupply, FixedSupply, MintEvent,
@@ -32,25 +39,42 @@ use crate::{
     gap_detectors::ProcessingResult,
     schema,
     utils::{
+        activity_observer::notify_activity_observers,
+        collection_volume::{
+            aggregate_by_collection, CollectionVolumeTracker, TopCollectionsConfig,
+        },
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool, DbPoolConnection},
-        util::{get_entry_function_from_user_request, parse_timestamp, standardize_address},
+        database::{
+            copy_in_version_range, execute_in_chunks_with_conn, execute_in_transaction,
+            get_config_table_chunk_size, ArcDbPool, ConflictStrategy, DbPoolConnection,
+            MyDbConnection, WriteStrategy,
+        },
+        slow_transactions::{SlowTransactionLogConfig, SlowTransactionLogger},
+        util::{
+            get_entry_function_from_user_request, parse_timestamp, set_numeric_scale,
+            standardize_address, NumericOverflowPolicy,
+        },
     },
     worker::TableFlags,
     IndexerGrpcProcessorConfig,
 };
 use ahash::{AHashMap, AHashSet};
-use anyhow::bail;
 use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Change, Transaction};
 use async_trait::async_trait;
+use bigdecimal::BigDecimal;
 use diesel::{
     pg::{upsert::excluded, Pg},
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
-use tracing::error;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+use tracing::{error, info, info_span, Instrument};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -59,27 +83,204 @@ pub struct TokenV2ProcessorConfig {
     pub query_retries: u32,
     #[serde(default = "IndexerGrpcProcessorConfig::default_query_retry_delay_ms")]
     pub query_retry_delay_ms: u64,
+    /// Number of transactions that current_collections_v2 and current_token_datas_v2 writes are
+    /// split across, by collection. With more than 1 these commit separately from the rest of the
+    /// batch, each on its own connection, so busy collections don't hold up the others. This gives
+    /// up the batch committing atomically: if one transaction fails, the ones that committed stay,
+    /// until the batch is processed again, e.g. after a restart.
+    #[serde(default = "TokenV2ProcessorConfig::default_collection_write_shards")]
+    pub collection_write_shards: usize,
+    /// Addresses of marketplace contracts to index listing activity from. The marketplaces' event
+    /// schemas have to be registered with `register_marketplace_event_schema`.
+    #[serde(default)]
+    pub marketplace_addresses: Vec<String>,
+    /// Keep a live ranking of collections by number of activities, served at
+    /// `/stats/top_collections` on the health check port
+    #[serde(default)]
+    pub top_collections: Option<TopCollectionsConfig>,
+    /// Move processor_status to the end of each batch in the same transaction that writes it, so
+    /// the checkpoint can't diverge from the data on a crash. Batches at or below the checkpoint
+    /// fail. Needs number_concurrent_processing_tasks: 1 and collection_write_shards: 1.
+    #[serde(default)]
+    pub commit_watermark_with_batch: bool,
+    /// Fractional digits to round numeric columns to before insertion, keyed by `table.column`,
+    /// so values match the deployment's numeric(p, s). Columns that can be set are in
+    /// `NUMERIC_SCALE_COLUMNS`, the rest are inserted as parsed.
+    #[serde(default)]
+    pub numeric_scales: AHashMap<String, i64>,
+    /// `copy` writes token_activities_v2 with COPY after the rest of the batch commits, for fast
+    /// backfills. Keep the default `insert` when tailing the chain.
+    #[serde(default)]
+    pub token_activities_write_strategy: WriteStrategy,
+    /// What to do with token activities whose token_amount or property_version_v1 doesn't fit in
+    /// a u64: `saturate`, `skip` (default) or `error`, which fails the batch
+    #[serde(default)]
+    pub on_numeric_overflow: NumericOverflowPolicy,
+    /// Write token_activities_v2 to the table named with this suffix, e.g. `_shadow`, and nothing
+    /// else, to compare a new parser version with the one writing the live tables. processor_status
+    /// gets the same suffix so both can run side by side. Only `[a-z0-9_]`, since it ends up in
+    /// SQL as part of the table name.
+    #[serde(default, deserialize_with = "deserialize_table_suffix")]
+    pub shadow_table_suffix: Option<String>,
+    /// Log a hash of each batch's token_activities_v2 rows, see `TokenActivityV2::content_hash`,
+    /// to check that two runs over the same versions wrote the same rows
+    #[serde(default)]
+    pub log_token_activities_hash: bool,
+    /// Also write token_data_id_hashes, the creator, collection and name behind each v1
+    /// token_data_id_hash, to look up tokens by the hash
+    #[serde(default)]
+    pub write_token_data_id_hashes: bool,
+    /// Count transactions that take longer than `threshold_ms` to parse, and log a sample of them
+    /// with their version and number of events
+    #[serde(default)]
+    pub slow_transaction_log: Option<SlowTransactionLogConfig>,
+    /// Also write property_version_v1_str on token_activities_v2, the exact property version as
+    /// a string, for consumers that can't read arbitrary precision decimals
+    #[serde(default)]
+    pub write_property_version_str: bool,
+    /// What inserts do with rows already in the table, keyed by table. Tables default to
+    /// `update`; those that can be set are in `ON_CONFLICT_TABLES`.
+    #[serde(default)]
+    pub on_conflict: AHashMap<String, ConflictStrategy>,
+    /// Write token_activities_v2 rows for the events of failed transactions too, with
+    /// transaction_success false. Without it failed transactions are skipped.
+    #[serde(default)]
+    pub record_failed_transaction_events: bool,
+    /// Largest token_properties, as the raw JSON read from the chain, decoded into token_datas_v2
+    /// and current_token_datas_v2. Larger ones, e.g. from airdrops with huge property maps, are
+    /// replaced by their size and sha256 without being decoded, see `cap_token_properties`. No
+    /// limit by default.
+    #[serde(default)]
+    pub max_token_properties_bytes: Option<usize>,
+    /// Also write the legacy token_activities table from v1 token events, parsed with these
+    /// settings. Not written by default.
+    #[serde(default)]
+    pub token_activities: Option<TokenActivityParser>,
+}
+
+fn deserialize_table_suffix<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let suffix = Option::<String>::deserialize(deserializer)?;
+    if let Some(suffix) = &suffix {
+        if suffix.is_empty()
+            || !suffix
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(D::Error::custom(format!(
+                "shadow_table_suffix must be non-empty and only contain [a-z0-9_], got {:?}",
+                suffix
+            )));
+        }
+    }
+    Ok(suffix)
+}
+
+/// Columns `numeric_scales` can set the scale of
+pub const NUMERIC_SCALE_COLUMNS: &[&str] = &[
+    "token_activities_v2.token_amount",
+    "token_ownerships_v2.amount",
+    "current_token_ownerships_v2.amount",
+];
+
+/// Tables `on_conflict` can set the conflict strategy of
+pub const ON_CONFLICT_TABLES: &[&str] = &["token_activities_v2"];
+
+impl TokenV2ProcessorConfig {
+    pub const fn default_collection_write_shards() -> usize {
+        1
+    }
+
+    /// `name` with `shadow_table_suffix` appended, if there is one
+    pub fn shadowed_name(&self, name: &str) -> String {
+        format!(
+            "{}{}",
+            name,
+            self.shadow_table_suffix.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Conflict strategy for inserts into `table`, `update` unless set in `on_conflict`
+    pub fn on_conflict(&self, table: &str) -> ConflictStrategy {
+        self.on_conflict.get(table).copied().unwrap_or_default()
+    }
 }
 
 pub struct TokenV2Processor {
     connection_pool: ArcDbPool,
+    postgres_connection_string: String,
     config: TokenV2ProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
     deprecated_tables: TableFlags,
+    // Shared by every instance the worker builds, since they all serve one endpoint
+    collection_volume: Option<Arc<Mutex<CollectionVolumeTracker>>>,
+    slow_transactions: Option<SlowTransactionLogger>,
 }
 
 impl TokenV2Processor {
     pub fn new(
         connection_pool: ArcDbPool,
+        postgres_connection_string: String,
         config: TokenV2ProcessorConfig,
         per_table_chunk_sizes: AHashMap<String, usize>,
         deprecated_tables: TableFlags,
+        collection_volume: Option<Arc<Mutex<CollectionVolumeTracker>>>,
     ) -> Self {
+        assert!(
+            !config.commit_watermark_with_batch || config.collection_write_shards <= 1,
+            "commit_watermark_with_batch can't be used with collection_write_shards"
+        );
+        assert!(
+            !config.commit_watermark_with_batch
+                || config.token_activities_write_strategy == WriteStrategy::Insert,
+            "commit_watermark_with_batch can't be used with token_activities_write_strategy: copy"
+        );
+        assert!(
+            !config.commit_watermark_with_batch || config.shadow_table_suffix.is_none(),
+            "commit_watermark_with_batch can't be used with shadow_table_suffix"
+        );
+        for column in config.numeric_scales.keys() {
+            assert!(
+                NUMERIC_SCALE_COLUMNS.contains(&column.as_str()),
+                "numeric_scales can't set {}, supported columns are {:?}",
+                column,
+                NUMERIC_SCALE_COLUMNS
+            );
+        }
+        for table in config.on_conflict.keys() {
+            assert!(
+                ON_CONFLICT_TABLES.contains(&table.as_str()),
+                "on_conflict can't be set for {}, supported tables are {:?}",
+                table,
+                ON_CONFLICT_TABLES
+            );
+        }
+        // COPY replaces the batch's rows whatever the strategy
+        assert!(
+            config.on_conflict("token_activities_v2") == ConflictStrategy::Update
+                || (config.token_activities_write_strategy == WriteStrategy::Insert
+                    && config.shadow_table_suffix.is_none()),
+            "on_conflict for token_activities_v2 can't be used with COPY writes"
+        );
+        let slow_transactions = config
+            .slow_transaction_log
+            .as_ref()
+            .map(|slow_transaction_log| {
+                SlowTransactionLogger::new(
+                    ProcessorName::TokenV2Processor.into(),
+                    slow_transaction_log,
+                )
+            });
         Self {
             connection_pool,
+            postgres_connection_string,
             config,
             per_table_chunk_sizes,
             deprecated_tables,
+            collection_volume,
+            slow_transactions,
         }
     }
 }
@@ -101,23 +302,30 @@ async fn insert_to_db(
     name: &'static str,
     start_version: u64,
     end_version: u64,
+    watermark: Option<&ProcessorStatus>,
     collections_v2: &[CollectionV2],
     token_datas_v2: &[TokenDataV2],
     token_ownerships_v2: &[TokenOwnershipV2],
-    current_collections_v2: &[CurrentCollectionV2],
-    (current_token_datas_v2, current_deleted_token_datas_v2): (
-        &[CurrentTokenDataV2],
-        &[CurrentTokenDataV2],
+    mut current_collections_v2: Vec<CurrentCollectionV2>,
+    (mut current_token_datas_v2, mut current_deleted_token_datas_v2): (
+        Vec<CurrentTokenDataV2>,
+        Vec<CurrentTokenDataV2>,
     ),
     (current_token_ownerships_v2, current_deleted_token_ownerships_v2): (
         &[CurrentTokenOwnershipV2],
         &[CurrentTokenOwnershipV2],
     ),
     token_activities_v2: &[TokenActivityV2],
+    nft_marketplace_activities: &[NftMarketplaceActivity],
     current_token_v2_metadata: &[CurrentTokenV2Metadata],
     current_token_royalties_v1: &[CurrentTokenRoyaltyV1],
     current_token_claims: &[CurrentTokenPendingClaim],
+    token_property_mutations: &[TokenPropertyMutation],
+    token_data_id_hashes: &[TokenDataIdHash],
+    token_activities: &[TokenActivity],
     per_table_chunk_sizes: &AHashMap<String, usize>,
+    collection_write_shards: usize,
+    token_activities_v2_on_conflict: ConflictStrategy,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
         name = name,
@@ -126,145 +334,286 @@ async fn insert_to_db(
         "Inserting to db",
     );
 
-    let coll_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_collections_v2_query,
-        collections_v2,
-        get_config_table_chunk_size::<CollectionV2>("collections_v2", per_table_chunk_sizes),
-    );
-    let td_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_token_datas_v2_query,
-        token_datas_v2,
-        get_config_table_chunk_size::<TokenDataV2>("token_datas_v2", per_table_chunk_sizes),
-    );
-    let to_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_token_ownerships_v2_query,
-        token_ownerships_v2,
-        get_config_table_chunk_size::<TokenOwnershipV2>(
-            "token_ownerships_v2",
-            per_table_chunk_sizes,
-        ),
-    );
-    let cc_v2 = execute_in_chunks(
-        conn.clone(),
+    let is_sharded = collection_write_shards > 1;
+    // Sharded, the tables keyed by collection are written in their own transactions below
+    let (collection_shards, token_data_shards, deleted_token_data_shards) = if is_sharded {
+        (
+            shard_by_collection(
+                std::mem::take(&mut current_collections_v2),
+                collection_write_shards,
+                |c| c.collection_id.as_str(),
+            ),
+            shard_by_collection(
+                std::mem::take(&mut current_token_datas_v2),
+                collection_write_shards,
+                |t| t.collection_id.as_str(),
+            ),
+            shard_by_collection(
+                std::mem::take(&mut current_deleted_token_datas_v2),
+                collection_write_shards,
+                |t| t.collection_id.as_str(),
+            ),
+        )
+    } else {
+        Default::default()
+    };
+    let current_collections_v2 = current_collections_v2.as_slice();
+    let current_token_datas_v2 = current_token_datas_v2.as_slice();
+    let current_deleted_token_datas_v2 = current_deleted_token_datas_v2.as_slice();
+    // Everything in the batch commits together so a failure can't leave e.g. activities written
+    // without the current ownerships they imply
+    let batch_tx = execute_in_transaction(conn.clone(), |conn| {
+        async move {
+            if let Some(watermark) = watermark {
+                ProcessorStatusQuery::check_watermark_for_update(
+                    &watermark.processor,
+                    start_version as i64,
+                    end_version as i64,
+                    conn,
+                )
+                .await?;
+            }
+            execute_in_chunks_with_conn(
+                conn,
+                insert_collections_v2_query,
+                collections_v2,
+                get_config_table_chunk_size::<CollectionV2>(
+                    "collections_v2",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_datas_v2_query,
+                token_datas_v2,
+                get_config_table_chunk_size::<TokenDataV2>("token_datas_v2", per_table_chunk_sizes),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_ownerships_v2_query,
+                token_ownerships_v2,
+                get_config_table_chunk_size::<TokenOwnershipV2>(
+                    "token_ownerships_v2",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            if !is_sharded {
+                insert_current_collection_tables(
+                    conn,
+                    current_collections_v2,
+                    current_token_datas_v2,
+                    current_deleted_token_datas_v2,
+                    per_table_chunk_sizes,
+                )
+                .await?;
+            }
+            execute_in_chunks_with_conn(
+                conn,
+                insert_current_token_ownerships_v2_query,
+                current_token_ownerships_v2,
+                get_config_table_chunk_size::<CurrentTokenOwnershipV2>(
+                    "current_token_ownerships_v2",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_current_deleted_token_ownerships_v2_query,
+                current_deleted_token_ownerships_v2,
+                get_config_table_chunk_size::<CurrentTokenOwnershipV2>(
+                    "current_token_ownerships_v2",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            let token_activities_v2_chunk_size = get_config_table_chunk_size::<TokenActivityV2>(
+                "token_activities_v2",
+                per_table_chunk_sizes,
+            );
+            match token_activities_v2_on_conflict {
+                ConflictStrategy::Update => {
+                    execute_in_chunks_with_conn(
+                        conn,
+                        insert_token_activities_v2_query,
+                        token_activities_v2,
+                        token_activities_v2_chunk_size,
+                    )
+                    .await?
+                },
+                ConflictStrategy::Nothing => {
+                    execute_in_chunks_with_conn(
+                        conn,
+                        insert_token_activities_v2_do_nothing_query,
+                        token_activities_v2,
+                        token_activities_v2_chunk_size,
+                    )
+                    .await?
+                },
+            }
+            execute_in_chunks_with_conn(
+                conn,
+                insert_nft_marketplace_activities_query,
+                nft_marketplace_activities,
+                get_config_table_chunk_size::<NftMarketplaceActivity>(
+                    "nft_marketplace_activities",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_current_token_v2_metadatas_query,
+                current_token_v2_metadata,
+                get_config_table_chunk_size::<CurrentTokenV2Metadata>(
+                    "current_token_v2_metadata",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_current_token_royalties_v1_query,
+                current_token_royalties_v1,
+                get_config_table_chunk_size::<CurrentTokenRoyaltyV1>(
+                    "current_token_royalty_v1",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_current_token_claims_query,
+                current_token_claims,
+                get_config_table_chunk_size::<CurrentTokenPendingClaim>(
+                    "current_token_pending_claims",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_property_mutations_query,
+                token_property_mutations,
+                get_config_table_chunk_size::<TokenPropertyMutation>(
+                    "token_property_mutations",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_data_id_hashes_query,
+                token_data_id_hashes,
+                get_config_table_chunk_size::<TokenDataIdHash>(
+                    "token_data_id_hashes",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            execute_in_chunks_with_conn(
+                conn,
+                insert_token_activities_query,
+                token_activities,
+                get_config_table_chunk_size::<TokenActivity>(
+                    "token_activities",
+                    per_table_chunk_sizes,
+                ),
+            )
+            .await?;
+            if let Some(watermark) = watermark {
+                watermark.upsert(conn).await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    });
+    if !is_sharded {
+        return batch_tx.await;
+    }
+
+    let shard_txs = collection_shards
+        .iter()
+        .zip(token_data_shards.iter())
+        .zip(deleted_token_data_shards.iter())
+        .map(|((collections, token_datas), deleted_token_datas)| {
+            execute_in_transaction(conn.clone(), move |conn| {
+                async move {
+                    insert_current_collection_tables(
+                        conn,
+                        collections,
+                        token_datas,
+                        deleted_token_datas,
+                        per_table_chunk_sizes,
+                    )
+                    .await
+                }
+                .scope_boxed()
+            })
+        });
+    futures::future::try_join(batch_tx, futures::future::try_join_all(shard_txs)).await?;
+    Ok(())
+}
+
+/// Writes the tables keyed by collection. Run as part of the batch transaction, or once per shard
+/// when collection_write_shards is set.
+async fn insert_current_collection_tables(
+    conn: &mut MyDbConnection,
+    current_collections_v2: &[CurrentCollectionV2],
+    current_token_datas_v2: &[CurrentTokenDataV2],
+    current_deleted_token_datas_v2: &[CurrentTokenDataV2],
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    execute_in_chunks_with_conn(
+        conn,
         insert_current_collections_v2_query,
         current_collections_v2,
         get_config_table_chunk_size::<CurrentCollectionV2>(
             "current_collections_v2",
             per_table_chunk_sizes,
         ),
-    );
-    let ctd_v2 = execute_in_chunks(
-        conn.clone(),
+    )
+    .await?;
+    execute_in_chunks_with_conn(
+        conn,
         insert_current_token_datas_v2_query,
         current_token_datas_v2,
         get_config_table_chunk_size::<CurrentTokenDataV2>(
             "current_token_datas_v2",
             per_table_chunk_sizes,
         ),
-    );
-    let cdtd_v2 = execute_in_chunks(
-        conn.clone(),
+    )
+    .await?;
+    execute_in_chunks_with_conn(
+        conn,
         insert_current_deleted_token_datas_v2_query,
         current_deleted_token_datas_v2,
         get_config_table_chunk_size::<CurrentTokenDataV2>(
             "current_token_datas_v2",
             per_table_chunk_sizes,
         ),
-    );
-    let cto_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_current_token_ownerships_v2_query,
-        current_token_ownerships_v2,
-        get_config_table_chunk_size::<CurrentTokenOwnershipV2>(
-            "current_token_ownerships_v2",
-            per_table_chunk_sizes,
-        ),
-    );
-    let cdto_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_current_deleted_token_ownerships_v2_query,
-        current_deleted_token_ownerships_v2,
-        get_config_table_chunk_size::<CurrentTokenOwnershipV2>(
-            "current_token_ownerships_v2",
-            per_table_chunk_sizes,
-        ),
-    );
-    let ta_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_token_activities_v2_query,
-        token_activities_v2,
-        get_config_table_chunk_size::<TokenActivityV2>(
-            "token_activities_v2",
-            per_table_chunk_sizes,
-        ),
-    );
-    let ct_v2 = execute_in_chunks(
-        conn.clone(),
-        insert_current_token_v2_metadatas_query,
-        current_token_v2_metadata,
-        get_config_table_chunk_size::<CurrentTokenV2Metadata>(
-            "current_token_v2_metadata",
-            per_table_chunk_sizes,
-        ),
-    );
-    let ctr_v1 = execute_in_chunks(
-        conn.clone(),
-        insert_current_token_royalties_v1_query,
-        current_token_royalties_v1,
-        get_config_table_chunk_size::<CurrentTokenRoyaltyV1>(
-            "current_token_royalty_v1",
-            per_table_chunk_sizes,
-        ),
-    );
-    let ctc_v1 = execute_in_chunks(
-        conn,
-        insert_current_token_claims_query,
-        current_token_claims,
-        get_config_table_chunk_size::<CurrentTokenPendingClaim>(
-            "current_token_pending_claims",
-            per_table_chunk_sizes,
-        ),
-    );
-
-    let (
-        coll_v2_res,
-        td_v2_res,
-        to_v2_res,
-        cc_v2_res,
-        ctd_v2_res,
-        cdtd_v2_res,
-        cto_v2_res,
-        cdto_v2_res,
-        ta_v2_res,
-        ct_v2_res,
-        ctr_v1_res,
-        ctc_v1_res,
-    ) = tokio::join!(
-        coll_v2, td_v2, to_v2, cc_v2, ctd_v2, cdtd_v2, cto_v2, cdto_v2, ta_v2, ct_v2, ctr_v1,
-        ctc_v1
-    );
+    )
+    .await?;
+    Ok(())
+}
 
-    for res in [
-        coll_v2_res,
-        td_v2_res,
-        to_v2_res,
-        cc_v2_res,
-        ctd_v2_res,
-        cdtd_v2_res,
-        cto_v2_res,
-        cdto_v2_res,
-        ta_v2_res,
-        ct_v2_res,
-        ctr_v1_res,
-        ctc_v1_res,
-    ] {
-        res?;
+/// Splits rows into `num_shards` groups by a hash of their collection id, keeping their order.
+/// The hash is sha256, so a collection always lands in the same shard whatever the Rust release.
+fn shard_by_collection<T>(
+    items: Vec<T>,
+    num_shards: usize,
+    collection_id: fn(&T) -> &str,
+) -> Vec<Vec<T>> {
+    let mut shards: Vec<Vec<T>> = std::iter::repeat_with(Vec::new).take(num_shards).collect();
+    for item in items {
+        let digest = Sha256::digest(collection_id(&item).as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        shards[(hash % num_shards as u64) as usize].push(item);
     }
-
-    Ok(())
+    shards
 }
 
 fn insert_collections_v2_query(
@@ -394,8 +743,16 @@ fn insert_current_token_datas_v2_query(
                 last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
                 inserted_at.eq(excluded(inserted_at)),
                 decimals.eq(excluded(decimals)),
-                // Intentionally not including is_deleted because it should always be true in this part
-                // and doesn't need to override
+                // Clears the burn if the token was created again
+                is_deleted_v2.eq(excluded(is_deleted_v2)),
+                // Only a newer version adds to it, re-applying a batch keeps the stored count
+                generation.eq(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+                    "CASE WHEN excluded.last_transaction_version \
+                     > current_token_datas_v2.last_transaction_version \
+                     THEN current_token_datas_v2.generation + excluded.generation \
+                     + CASE WHEN current_token_datas_v2.is_deleted_v2 THEN 1 ELSE 0 END \
+                     ELSE current_token_datas_v2.generation END",
+                )),
             )),
         Some(" WHERE current_token_datas_v2.last_transaction_version <= excluded.last_transaction_version "),
     )
@@ -448,6 +805,7 @@ fn insert_current_token_ownerships_v2_query(
                 last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
                 inserted_at.eq(excluded(inserted_at)),
                 non_transferrable_by_owner.eq(excluded(non_transferrable_by_owner)),
+                is_burned.eq(excluded(is_burned)),
             )),
         Some(" WHERE current_token_ownerships_v2.last_transaction_version <= excluded.last_transaction_version "),
     )
@@ -472,6 +830,7 @@ fn insert_current_deleted_token_ownerships_v2_query(
                 last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
                 is_fungible_v2.eq(excluded(is_fungible_v2)),
                 inserted_at.eq(excluded(inserted_at)),
+                is_burned.eq(excluded(is_burned)),
             )),
         Some(" WHERE current_token_ownerships_v2.last_transaction_version <= excluded.last_transaction_version "),
     )
@@ -493,11 +852,48 @@ fn insert_token_activities_v2_query(
             .set((
                 is_fungible_v2.eq(excluded(is_fungible_v2)),
                 inserted_at.eq(excluded(inserted_at)),
+                property_version_v1_str.eq(excluded(property_version_v1_str)),
+                transaction_success.eq(excluded(transaction_success)),
             )),
         None,
     )
 }
 
+fn insert_token_activities_v2_do_nothing_query(
+    items_to_insert: Vec<TokenActivityV2>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::token_activities_v2::dsl::*;
+
+    (
+        diesel::insert_into(schema::token_activities_v2::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+fn insert_nft_marketplace_activities_query(
+    items_to_insert: Vec<NftMarketplaceActivity>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::nft_marketplace_activities::dsl::*;
+
+    (
+        diesel::insert_into(schema::nft_marketplace_activities::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_update()
+            .set((inserted_at.eq(excluded(inserted_at)),)),
+        None,
+    )
+}
+
 fn insert_current_token_v2_metadatas_query(
     items_to_insert: Vec<CurrentTokenV2Metadata>,
 ) -> (
@@ -571,23 +967,158 @@ fn insert_current_token_claims_query(
                 inserted_at.eq(excluded(inserted_at)),
                 token_data_id.eq(excluded(token_data_id)),
                 collection_id.eq(excluded(collection_id)),
+                expiration_timestamp.eq(excluded(expiration_timestamp)),
             )),
         Some(" WHERE current_token_pending_claims.last_transaction_version <= excluded.last_transaction_version "),
     )
 }
 
+fn insert_token_property_mutations_query(
+    items_to_insert: Vec<TokenPropertyMutation>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::token_property_mutations::dsl::*;
+
+    (
+        diesel::insert_into(schema::token_property_mutations::table)
+            .values(items_to_insert)
+            .on_conflict((token_data_id_hash, transaction_version, event_index))
+            .do_update()
+            .set((
+                mutated_properties.eq(excluded(mutated_properties)),
+                old_properties.eq(excluded(old_properties)),
+                new_properties.eq(excluded(new_properties)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        None,
+    )
+}
+
+fn insert_token_data_id_hashes_query(
+    items_to_insert: Vec<TokenDataIdHash>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::token_data_id_hashes::dsl::*;
+
+    // A hash always comes from the same token, keep the first version it was seen at
+    (
+        diesel::insert_into(schema::token_data_id_hashes::table)
+            .values(items_to_insert)
+            .on_conflict(token_data_id_hash)
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_token_activities_query(
+    items_to_insert: Vec<TokenActivity>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::token_activities::dsl::*;
+
+    (
+        diesel::insert_into(schema::token_activities::table)
+            .values(items_to_insert)
+            .on_conflict((
+                transaction_version,
+                event_account_address,
+                event_creation_number,
+                event_sequence_number,
+            ))
+            .do_nothing(),
+        None,
+    )
+}
+
+/// Applies `on_numeric_overflow` to the u64 columns of token_activities_v2
+fn check_numeric_overflow(
+    on_numeric_overflow: NumericOverflowPolicy,
+    token_activities_v2: &mut Vec<TokenActivityV2>,
+) -> anyhow::Result<()> {
+    let mut checked = Vec::with_capacity(token_activities_v2.len());
+    for activity in std::mem::take(token_activities_v2) {
+        let check = |value: BigDecimal, column: &str| {
+            on_numeric_overflow.check_u64(
+                value,
+                "TokenActivityV2",
+                column,
+                activity.transaction_version,
+            )
+        };
+        let token_amount = match activity.token_amount.clone() {
+            Some(token_amount) => match check(token_amount, "token_amount")? {
+                Some(token_amount) => Some(token_amount),
+                None => continue,
+            },
+            None => None,
+        };
+        let Some(property_version_v1) =
+            check(activity.property_version_v1.clone(), "property_version_v1")?
+        else {
+            continue;
+        };
+        checked.push(TokenActivityV2 {
+            token_amount,
+            property_version_v1,
+            ..activity
+        });
+    }
+    *token_activities_v2 = checked;
+    Ok(())
+}
+
+/// Rounds the columns in `numeric_scales` to their configured scale, see `NUMERIC_SCALE_COLUMNS`
+fn apply_numeric_scales(
+    numeric_scales: &AHashMap<String, i64>,
+    token_activities_v2: &mut [TokenActivityV2],
+    token_ownerships_v2: &mut [TokenOwnershipV2],
+    current_token_ownerships_v2: &mut [CurrentTokenOwnershipV2],
+    current_deleted_token_ownerships_v2: &mut [CurrentTokenOwnershipV2],
+) {
+    if let Some(&scale) = numeric_scales.get("token_activities_v2.token_amount") {
+        for activity in token_activities_v2.iter_mut() {
+            if let Some(token_amount) = &activity.token_amount {
+                activity.token_amount = Some(set_numeric_scale(token_amount, scale));
+            }
+        }
+    }
+    if let Some(&scale) = numeric_scales.get("token_ownerships_v2.amount") {
+        for ownership in token_ownerships_v2.iter_mut() {
+            ownership.amount = set_numeric_scale(&ownership.amount, scale);
+        }
+    }
+    if let Some(&scale) = numeric_scales.get("current_token_ownerships_v2.amount") {
+        for ownership in current_token_ownerships_v2
+            .iter_mut()
+            .chain(current_deleted_token_ownerships_v2.iter_mut())
+        {
+            ownership.amount = set_numeric_scale(&ownership.amount, scale);
+        }
+    }
+}
+
 #[async_trait]
 impl ProcessorTrait for TokenV2Processor {
     fn name(&self) -> &'static str {
         ProcessorName::TokenV2Processor.into()
     }
 
+    fn processor_status_name(&self) -> String {
+        self.config.shadowed_name(self.name())
+    }
+
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
-        _: Option<u64>,
+        db_chain_id: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let processing_start = std::time::Instant::now();
         let last_transaction_timestamp = transactions.last().unwrap().timestamp.clone();
@@ -601,6 +1132,12 @@ impl ProcessorTrait for TokenV2Processor {
 
         let query_retries = self.config.query_retries;
         let query_retry_delay_ms = self.config.query_retry_delay_ms;
+        let parse_span = info_span!(
+            "parse",
+            start_version,
+            end_version,
+            rows = tracing::field::Empty,
+        );
         // Token V2 processing which includes token v1
         let (
             mut collections_v2,
@@ -609,20 +1146,53 @@ impl ProcessorTrait for TokenV2Processor {
             current_collections_v2,
             current_token_datas_v2,
             current_deleted_token_datas_v2,
-            current_token_ownerships_v2,
-            current_deleted_token_ownerships_v2,
-            token_activities_v2,
+            mut current_token_ownerships_v2,
+            mut current_deleted_token_ownerships_v2,
+            mut token_activities_v2,
             mut current_token_v2_metadata,
             current_token_royalties_v1,
             current_token_claims,
+            token_property_mutations,
         ) = parse_v2_token(
             &transactions,
             &table_handle_to_owner,
             &mut conn,
             query_retries,
             query_retry_delay_ms,
+            self.slow_transactions.as_ref(),
+            self.config.record_failed_transaction_events,
+            self.config.max_token_properties_bytes,
         )
-        .await;
+        .instrument(parse_span.clone())
+        .await?;
+
+        let nft_marketplace_activities = if self.config.marketplace_addresses.is_empty() {
+            vec![]
+        } else {
+            let mut collector = EventParserCollector::new(NftMarketplaceActivityParser::new(
+                &self.config.marketplace_addresses,
+            ));
+            parse_span
+                .in_scope(|| parse_successful_events(&transactions, &mut [&mut collector]))?;
+            collector.into_rows()
+        };
+        let token_data_id_hashes = if self.config.write_token_data_id_hashes {
+            let mut collector = EventParserCollector::new(TokenDataIdHashParser);
+            parse_span
+                .in_scope(|| parse_successful_events(&transactions, &mut [&mut collector]))?;
+            TokenDataIdHash::dedup(collector.into_rows())
+        } else {
+            vec![]
+        };
+        let token_activities = match &self.config.token_activities {
+            Some(parser) => {
+                let mut collector = EventParserCollector::new(parser.clone());
+                parse_span
+                    .in_scope(|| parse_successful_events(&transactions, &mut [&mut collector]))?;
+                collector.into_rows()
+            },
+            None => vec![],
+        };
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
@@ -645,40 +1215,156 @@ impl ProcessorTrait for TokenV2Processor {
         {
             current_token_v2_metadata.clear();
         }
+        // Before on_numeric_overflow can saturate property_version_v1
+        if self.config.write_property_version_str {
+            for activity in token_activities_v2.iter_mut() {
+                activity.property_version_v1_str = Some(activity.property_version_v1.to_string());
+            }
+        }
+        check_numeric_overflow(self.config.on_numeric_overflow, &mut token_activities_v2)?;
+        apply_numeric_scales(
+            &self.config.numeric_scales,
+            &mut token_activities_v2,
+            &mut token_ownerships_v2,
+            &mut current_token_ownerships_v2,
+            &mut current_deleted_token_ownerships_v2,
+        );
+        if self.config.log_token_activities_hash {
+            info!(
+                processor_name = self.name(),
+                start_version,
+                end_version,
+                rows = token_activities_v2.len(),
+                content_hash = TokenActivityV2::content_hash(&token_activities_v2),
+                "[Parser] Token activities content hash"
+            );
+        }
+        let num_rows = collections_v2.len()
+            + token_datas_v2.len()
+            + token_ownerships_v2.len()
+            + current_collections_v2.len()
+            + current_token_datas_v2.len()
+            + current_deleted_token_datas_v2.len()
+            + current_token_ownerships_v2.len()
+            + current_deleted_token_ownerships_v2.len()
+            + token_activities_v2.len()
+            + nft_marketplace_activities.len()
+            + current_token_v2_metadata.len()
+            + current_token_royalties_v1.len()
+            + current_token_claims.len()
+            + token_property_mutations.len()
+            + token_data_id_hashes.len()
+            + token_activities.len();
+        parse_span.record("rows", num_rows);
+        drop(parse_span);
+        let write_span = info_span!("write", start_version, end_version, rows = num_rows);
 
-        let tx_result = insert_to_db(
-            self.get_pool(),
-            self.name(),
-            start_version,
-            end_version,
-            &collections_v2,
-            &token_datas_v2,
-            &token_ownerships_v2,
-            &current_collections_v2,
-            (&current_token_datas_v2, &current_deleted_token_datas_v2),
-            (
-                &current_token_ownerships_v2,
-                &current_deleted_token_ownerships_v2,
-            ),
-            &token_activities_v2,
-            &current_token_v2_metadata,
-            &current_token_royalties_v1,
-            &current_token_claims,
-            &self.per_table_chunk_sizes,
-        )
-        .await;
+        // Aggregated before the write, which takes the current token datas
+        let collection_volumes = self
+            .collection_volume
+            .as_ref()
+            .map(|_| aggregate_by_collection(&token_activities_v2, &current_token_datas_v2));
+        let watermark = self
+            .config
+            .commit_watermark_with_batch
+            .then(|| ProcessorStatus {
+                processor: self.name().to_string(),
+                last_success_version: end_version as i64,
+                last_transaction_timestamp: last_transaction_timestamp
+                    .as_ref()
+                    .map(|t| parse_timestamp(t, end_version as i64)),
+                chain_id: db_chain_id.map(|chain_id| chain_id as i64),
+            });
+        // With COPY the activities are written once the rest of the batch has committed
+        let token_activities_v2_to_insert: &[TokenActivityV2] =
+            match self.config.token_activities_write_strategy {
+                WriteStrategy::Insert => &token_activities_v2,
+                WriteStrategy::Copy => &[],
+            };
+        let tx_result = if self.config.shadow_table_suffix.is_some() {
+            // The other tables belong to the live processor, only the activities are shadowed
+            Ok(())
+        } else {
+            insert_to_db(
+                self.get_pool(),
+                self.name(),
+                start_version,
+                end_version,
+                watermark.as_ref(),
+                &collections_v2,
+                &token_datas_v2,
+                &token_ownerships_v2,
+                current_collections_v2,
+                (current_token_datas_v2, current_deleted_token_datas_v2),
+                (
+                    &current_token_ownerships_v2,
+                    &current_deleted_token_ownerships_v2,
+                ),
+                token_activities_v2_to_insert,
+                &nft_marketplace_activities,
+                &current_token_v2_metadata,
+                &current_token_royalties_v1,
+                &current_token_claims,
+                &token_property_mutations,
+                &token_data_id_hashes,
+                &token_activities,
+                &self.per_table_chunk_sizes,
+                self.config.collection_write_shards,
+                self.config.on_conflict("token_activities_v2"),
+            )
+            .instrument(write_span.clone())
+            .await
+            .map_err(anyhow::Error::from)
+        };
+        let write_with_copy = self.config.shadow_table_suffix.is_some()
+            || self.config.token_activities_write_strategy == WriteStrategy::Copy;
+        let tx_result = match tx_result {
+            Ok(()) if write_with_copy => {
+                let mut csv = vec![];
+                for activity in &token_activities_v2 {
+                    activity.write_copy_csv_row(&mut csv);
+                }
+                copy_in_version_range(
+                    &self.postgres_connection_string,
+                    &self.config.shadowed_name("token_activities_v2"),
+                    TokenActivityV2::COPY_COLUMNS,
+                    start_version as i64,
+                    end_version as i64,
+                    csv,
+                )
+                .instrument(write_span.clone())
+                .await
+                .map(|_| ())
+            },
+            tx_result => tx_result,
+        };
+        drop(write_span);
 
         let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
         match tx_result {
-            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
-                DefaultProcessingResult {
-                    start_version,
-                    end_version,
-                    processing_duration_in_secs,
-                    db_insertion_duration_in_secs,
-                    last_transaction_timestamp,
-                },
-            )),
+            Ok(_) => {
+                if let (Some(collection_volume), Some(volumes)) =
+                    (&self.collection_volume, collection_volumes)
+                {
+                    collection_volume
+                        .lock()
+                        .unwrap()
+                        .add_batch(end_version, volumes);
+                }
+                // Observers act on what the live processor writes
+                if self.config.shadow_table_suffix.is_none() {
+                    notify_activity_observers(&token_activities_v2);
+                }
+                Ok(ProcessingResult::DefaultProcessingResult(
+                    DefaultProcessingResult {
+                        start_version,
+                        end_version,
+                        processing_duration_in_secs,
+                        db_insertion_duration_in_secs,
+                        last_transaction_timestamp,
+                    },
+                ))
+            },
             Err(e) => {
                 error!(
                     start_version = start_version,
@@ -687,7 +1373,7 @@ impl ProcessorTrait for TokenV2Processor {
                     error = ?e,
                     "[Parser] Error inserting transactions to db",
                 );
-                bail!(e)
+                Err(e)
             },
         }
     }
@@ -703,7 +1389,10 @@ async fn parse_v2_token(
     conn: &mut DbPoolConnection<'_>,
     query_retries: u32,
     query_retry_delay_ms: u64,
-) -> (
+    slow_transactions: Option<&SlowTransactionLogger>,
+    record_failed_transaction_events: bool,
+    max_token_properties_bytes: Option<usize>,
+) -> anyhow::Result<(
     Vec<CollectionV2>,
     Vec<TokenDataV2>,
     Vec<TokenOwnershipV2>,
@@ -716,13 +1405,17 @@ async fn parse_v2_token(
     Vec<CurrentTokenV2Metadata>,
     Vec<CurrentTokenRoyaltyV1>,
     Vec<CurrentTokenPendingClaim>,
-) {
+    Vec<TokenPropertyMutation>,
+)> {
     // Token V2 and V1 combined
     let mut collections_v2 = vec![];
     let mut token_datas_v2 = vec![];
     let mut token_ownerships_v2 = vec![];
     let mut token_activities_v2 = vec![];
+    let mut token_property_mutations = vec![];
 
+    // Current tables are keyed by primary key and transactions are parsed in version order, so a
+    // key updated many times in the batch is upserted once with its latest value
     let mut current_collections_v2: AHashMap<CurrentCollectionV2PK, CurrentCollectionV2> =
         AHashMap::new();
     let mut current_token_datas_v2: AHashMap<CurrentTokenDataV2PK, CurrentTokenDataV2> =
@@ -753,6 +1446,7 @@ async fn parse_v2_token(
 
     // Code above is inefficient (multiple passthroughs) so I'm approaching TokenV2 with a cleaner code structure
     for txn in transactions {
+        let txn_start = std::time::Instant::now();
         let txn_version = txn.version;
         let txn_data = match txn.txn_data.as_ref() {
             Some(data) => data,
@@ -778,12 +1472,43 @@ async fn parse_v2_token(
                 .expect("Sends is not present in user txn");
             let entry_function_id_str = get_entry_function_from_user_request(user_request);
 
+            // A failed transaction only commits its gas fee, so its events have no effects
+            if !transaction_info.success {
+                if record_failed_transaction_events {
+                    token_activities_v2.extend(
+                        TokenActivityV2::from_failed_transaction_events(
+                            &user_txn.events,
+                            txn_version,
+                            txn_timestamp,
+                            &entry_function_id_str,
+                        )
+                        .await?,
+                    );
+                }
+                if let Some(slow_transactions) = slow_transactions {
+                    slow_transactions.observe(txn, txn_start.elapsed());
+                }
+                continue;
+            }
+
             // Get burn events for token v2 by object
             let mut tokens_burned: TokenV2Burned = AHashMap::new();
 
+            // v1 tokens burned in this transaction, to tombstone the ownerships they leave
+            let mut v1_tokens_burned = TokenV1Burned::new();
+
             // Get mint events for token v2 by object
             let mut tokens_minted: TokenV2Minted = AHashSet::new();
 
+            // Expiration of the offers made in this transaction, by token_data_id, property
+            // version, from and to address, for the pending claims they write
+            let mut offer_expirations: AHashMap<_, chrono::NaiveDateTime> = AHashMap::new();
+
+            // Property mutations in this transaction, and the property maps of the v1 tokens it
+            // wrote to fill in their old and new maps
+            let mut txn_token_property_mutations = vec![];
+            let mut txn_token_properties = TokenPropertiesByTokenId::new();
+
             // Need to do a first pass to get all the objects
             for wsc in transaction_info.changes.iter() {
                 if let Change::WriteResource(wr) = wsc.change.as_ref().unwrap() {
@@ -821,8 +1546,12 @@ async fn parse_v2_token(
                         {
                             aggregated_data.aptos_collection = Some(aptos_collection);
                         }
-                        if let Some(property_map) =
-                            PropertyMapModel::from_write_resource(wr, txn_version).unwrap()
+                        if let Some(property_map) = PropertyMapModel::from_write_resource_capped(
+                            wr,
+                            txn_version,
+                            max_token_properties_bytes,
+                        )
+                        .unwrap()
                         {
                             aggregated_data.property_map = Some(property_map);
                         }
@@ -897,11 +1626,46 @@ async fn parse_v2_token(
                     txn_timestamp,
                     index as i64,
                     &entry_function_id_str,
+                    &token_v2_metadata_helper,
                 )
                 .unwrap()
                 {
+                    if let (Some(expiration_timestamp), Some(from_address), Some(to_address)) = (
+                        event.expiration_timestamp,
+                        &event.from_address,
+                        &event.to_address,
+                    ) {
+                        offer_expirations.insert(
+                            (
+                                event.token_data_id.clone(),
+                                event.property_version_v1.clone(),
+                                from_address.clone(),
+                                to_address.clone(),
+                            ),
+                            expiration_timestamp,
+                        );
+                    }
+                    if event.type_ == TokenEventKind::BurnTokenEvent.event_type() {
+                        if let Some(from_address) = &event.from_address {
+                            v1_tokens_burned.insert((
+                                event.token_data_id.clone(),
+                                event.property_version_v1.clone(),
+                                from_address.clone(),
+                            ));
+                        }
+                    }
                     token_activities_v2.push(event);
                 }
+                if let Some(mutation) = TokenPropertyMutation::from_event(
+                    event,
+                    txn_version,
+                    txn_timestamp,
+                    index as i64,
+                )
+                .unwrap()
+                {
+                    txn_token_property_mutations.push(mutation);
+                }
                 // handling all the token v2 events
                 if let Some(event) = TokenActivityV2::get_nft_v2_from_parsed_event(
                     event,
@@ -948,13 +1712,14 @@ async fn parse_v2_token(
                                 txn_version,
                                 wsc_index,
                                 txn_timestamp,
+                                max_token_properties_bytes,
                             )
                             .unwrap()
                         {
                             token_datas_v2.push(token_data);
-                            current_token_datas_v2.insert(
-                                current_token_data.token_data_id.clone(),
-                                current_token_data,
+                            current_token_data.add_to_batch(
+                                &mut current_token_datas_v2,
+                                &mut current_deleted_token_datas_v2,
                             );
                         }
                         if let Some(current_token_royalty) =
@@ -1001,7 +1766,18 @@ async fn parse_v2_token(
                                 );
                             }
                         }
-                        if let Some(current_token_token_claim) =
+                        if !txn_token_property_mutations.is_empty() {
+                            if let Some((token_id, token_properties)) =
+                                TokenPropertyMutation::get_token_properties_from_write_table_item(
+                                    table_item,
+                                    txn_version,
+                                )
+                                .unwrap()
+                            {
+                                txn_token_properties.insert(token_id, token_properties);
+                            }
+                        }
+                        if let Some(mut current_token_token_claim) =
                             CurrentTokenPendingClaim::from_write_table_item(
                                 table_item,
                                 txn_version,
@@ -1010,6 +1786,14 @@ async fn parse_v2_token(
                             )
                             .unwrap()
                         {
+                            current_token_token_claim.expiration_timestamp = offer_expirations
+                                .get(&(
+                                    current_token_token_claim.token_data_id.clone(),
+                                    current_token_token_claim.property_version.clone(),
+                                    current_token_token_claim.from_address.clone(),
+                                    current_token_token_claim.to_address.clone(),
+                                ))
+                                .copied();
                             all_current_token_claims.insert(
                                 (
                                     current_token_token_claim.token_data_id_hash.clone(),
@@ -1033,7 +1817,8 @@ async fn parse_v2_token(
                             .unwrap()
                         {
                             token_ownerships_v2.push(token_ownership);
-                            if let Some(cto) = current_token_ownership {
+                            if let Some(mut cto) = current_token_ownership {
+                                cto.mark_v1_burned(&v1_tokens_burned);
                                 prior_nft_ownership.insert(
                                     cto.token_data_id.clone(),
                                     NFTOwnershipV2 {
@@ -1127,9 +1912,9 @@ async fn parse_v2_token(
                             token_ownerships_v2.append(&mut ownerships);
                             current_token_ownerships_v2.extend(current_ownerships);
                             token_datas_v2.push(token_data);
-                            current_token_datas_v2.insert(
-                                current_token_data.token_data_id.clone(),
-                                current_token_data,
+                            current_token_data.add_to_batch(
+                                &mut current_token_datas_v2,
+                                &mut current_deleted_token_datas_v2,
                             );
                         }
 
@@ -1258,6 +2043,14 @@ async fn parse_v2_token(
                     _ => {},
                 }
             }
+
+            for mut mutation in txn_token_property_mutations {
+                mutation.add_token_properties(&txn_token_properties);
+                token_property_mutations.push(mutation);
+            }
+        }
+        if let Some(slow_transactions) = slow_transactions {
+            slow_transactions.observe(txn, txn_start.elapsed());
         }
     }
 
@@ -1296,7 +2089,7 @@ async fn parse_v2_token(
     current_token_royalties_v1.sort();
     all_current_token_claims.sort();
 
-    (
+    Ok((
         collections_v2,
         token_datas_v2,
         token_ownerships_v2,
@@ -1309,5 +2102,6 @@ async fn parse_v2_token(
         current_token_v2_metadata,
         current_token_royalties_v1,
         all_current_token_claims,
-    )
+        token_property_mutations,
+    ))
 }
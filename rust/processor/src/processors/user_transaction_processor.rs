@@ -23,7 +23,7 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 pub struct UserTransactionProcessor {
@@ -145,7 +145,7 @@ impl ProcessorTrait for UserTransactionProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -155,7 +155,7 @@ impl ProcessorTrait for UserTransactionProcessor {
 
         let mut signatures = vec![];
         let mut user_transactions = vec![];
-        for txn in &transactions {
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let block_height = txn.block_height as i64;
             let txn_data = match txn.txn_data.as_ref() {
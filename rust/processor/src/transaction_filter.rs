@@ -1,9 +1,11 @@
+use crate::utils::util::standardize_address;
+use anyhow::bail;
 use aptos_protos::transaction::v1::{
     transaction::{TransactionType, TxnData},
     transaction_payload::Payload,
     Transaction,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
 
 /// Allows filtering transactions based on various criteria
 /// The criteria are combined with `AND`
@@ -14,8 +16,10 @@ use serde::{Deserialize, Serialize};
 #[serde(default)]
 pub struct TransactionFilter {
     // Only allow transactions from these contract addresses
+    #[serde(deserialize_with = "deserialize_addresses")]
     focus_contract_addresses: Option<ahash::HashSet<String>>,
     // Skip transactions from these sender addresses
+    #[serde(deserialize_with = "deserialize_addresses")]
     skip_sender_addresses: Option<ahash::HashSet<String>>,
     // Skip all transactions that aren't user transactions
     focus_user_transactions: bool,
@@ -26,13 +30,17 @@ impl TransactionFilter {
         focus_contract_addresses: Option<ahash::HashSet<String>>,
         skip_sender_addresses: Option<ahash::HashSet<String>>,
         focus_user_transactions: bool,
-    ) -> Self {
-        // TODO: normalize addresses
-        Self {
-            focus_contract_addresses,
-            skip_sender_addresses,
+    ) -> anyhow::Result<Self> {
+        let parse_addresses = |addresses: Option<ahash::HashSet<String>>| {
+            addresses
+                .map(|addresses| addresses.iter().map(|a| parse_address(a)).collect())
+                .transpose()
+        };
+        Ok(Self {
+            focus_contract_addresses: parse_addresses(focus_contract_addresses)?,
+            skip_sender_addresses: parse_addresses(skip_sender_addresses)?,
             focus_user_transactions,
-        }
+        })
     }
 
     /// Returns true if the transaction should be included
@@ -53,7 +61,7 @@ impl TransactionFilter {
             if let Some(utr) = user_transaction.request.as_ref() {
                 // Skip if sender is in the skip list
                 if let Some(skip_sender_addresses) = &self.skip_sender_addresses {
-                    if skip_sender_addresses.contains(&utr.sender) {
+                    if skip_sender_addresses.contains(&standardize_address(&utr.sender)) {
                         return false;
                     }
                 }
@@ -64,7 +72,9 @@ impl TransactionFilter {
                         if let Some(Payload::EntryFunctionPayload(efp)) = payload.payload.as_ref() {
                             if let Some(function) = efp.function.as_ref() {
                                 if let Some(module) = function.module.as_ref() {
-                                    if !focus_contract_addresses.contains(&module.address) {
+                                    if !focus_contract_addresses
+                                        .contains(&standardize_address(&module.address))
+                                    {
                                         return false;
                                     }
                                 }
@@ -78,3 +88,56 @@ impl TransactionFilter {
         true
     }
 }
+
+/// Standardizes a configured address, so `0x1`, `1` and the full 64 character form all match the
+/// same account
+fn parse_address(address: &str) -> anyhow::Result<String> {
+    let address = address.trim();
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!(
+            "Invalid address {:?} in transaction_filter, expected up to 64 hex characters with an optional 0x prefix",
+            address
+        );
+    }
+    Ok(standardize_address(&hex.to_lowercase()))
+}
+
+fn deserialize_addresses<'de, D>(
+    deserializer: D,
+) -> Result<Option<ahash::HashSet<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|addresses| {
+            addresses
+                .iter()
+                .map(|address| parse_address(address).map_err(D::Error::custom))
+                .collect()
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addresses_are_standardized() {
+        let filter: TransactionFilter = serde_json::from_str(
+            r#"{"skip_sender_addresses": ["0x1", "A", "0x00000000000000000000000000000000000000000000000000000000000000b"]}"#,
+        )
+        .unwrap();
+        let skip_sender_addresses = filter.skip_sender_addresses.unwrap();
+        assert!(skip_sender_addresses.contains(&standardize_address("0x1")));
+        assert!(skip_sender_addresses.contains(&standardize_address("0xa")));
+        assert!(skip_sender_addresses.contains(&standardize_address("0xb")));
+
+        let err = serde_json::from_str::<TransactionFilter>(
+            r#"{"focus_contract_addresses": ["0xnot_hex"]}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Invalid address \"0xnot_hex\""));
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::db::common::models::token_v2_models::v2_token_activities::TokenActivityV2;
+use std::sync::RwLock;
+
+/// Gets every token activity token_v2_processor produces, once the batch it's in has been
+/// committed, so activities from a failed batch are never seen. A batch processed again after a
+/// restart is seen again, so side effects should be idempotent. Calls happen on the processing
+/// tasks, in version order within a batch but not across batches when there are several tasks.
+/// Anything slow, like a webhook, should be handed off (e.g. to a channel) instead of done inline.
+pub trait ActivityObserver: Send + Sync {
+    fn on_activity(&self, activity: &TokenActivityV2);
+}
+
+static ACTIVITY_OBSERVERS: RwLock<Vec<Box<dyn ActivityObserver>>> = RwLock::new(Vec::new());
+
+/// Adds an observer for token activities. This should be called at startup, before the processor
+/// starts running. Without any, nothing is called.
+pub fn register_activity_observer(observer: impl ActivityObserver + 'static) {
+    ACTIVITY_OBSERVERS.write().unwrap().push(Box::new(observer));
+}
+
+/// Passes the committed activities of a batch to every registered observer
+pub fn notify_activity_observers(activities: &[TokenActivityV2]) {
+    let observers = ACTIVITY_OBSERVERS.read().unwrap();
+    if observers.is_empty() {
+        return;
+    }
+    for activity in activities {
+        for observer in observers.iter() {
+            observer.on_activity(activity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingObserver(Arc<Mutex<Vec<(i64, i64)>>>);
+
+    impl ActivityObserver for RecordingObserver {
+        fn on_activity(&self, activity: &TokenActivityV2) {
+            // Other tests may notify the same registry, only keep this test's activities
+            if activity.token_data_id == "activity_observer_test" {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((activity.transaction_version, activity.event_index));
+            }
+        }
+    }
+
+    fn activity(transaction_version: i64, event_index: i64) -> TokenActivityV2 {
+        TokenActivityV2 {
+            transaction_version,
+            event_index,
+            event_account_address: "0x1".to_string(),
+            token_data_id: "activity_observer_test".to_string(),
+            property_version_v1: BigDecimal::from(0),
+            type_: "0x4::collection::Mint".to_string(),
+            from_address: None,
+            to_address: None,
+            token_amount: Some(BigDecimal::from(1)),
+            before_value: None,
+            after_value: None,
+            entry_function_id_str: None,
+            token_standard: "v2".to_string(),
+            is_fungible_v2: None,
+            transaction_timestamp: chrono::NaiveDateTime::default(),
+            expiration_timestamp: None,
+            property_version_v1_str: None,
+            transaction_success: true,
+        }
+    }
+
+    #[test]
+    fn test_notify_activity_observers() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        register_activity_observer(RecordingObserver(seen.clone()));
+        notify_activity_observers(&[activity(1, 0), activity(1, 1), activity(2, 0)]);
+        assert_eq!(*seen.lock().unwrap(), vec![(1, 0), (1, 1), (2, 0)]);
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Just enough Avro to write flat records of longs, strings, decimals and timestamps, nullable or
+//! not, for consumers reading through Confluent Schema Registry. A model lists its fields as
+//! `AvroField`s, in the order `AvroWriter` writes them; `record_schema` turns that list into the
+//! schema to register, and `confluent_framed` prefixes an encoded record with the id the registry
+//! gave that schema.
+
+use bigdecimal::BigDecimal;
+use serde_json::{json, Value};
+
+/// Precision of every decimal field. Numeric columns are kept within u64 (20 digits), see
+/// `util::NumericOverflowPolicy`, which leaves room for a scale.
+pub const DECIMAL_PRECISION: u32 = 38;
+/// First byte of a Confluent framed message, followed by the 4 byte schema id
+const CONFLUENT_MAGIC_BYTE: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvroType {
+    Long,
+    String,
+    /// `bytes` with the decimal logical type, holding the unscaled value
+    Decimal {
+        scale: u32,
+    },
+    /// `long` with the timestamp-micros logical type
+    TimestampMicros,
+}
+
+impl AvroType {
+    fn schema(&self) -> Value {
+        match self {
+            AvroType::Long => json!("long"),
+            AvroType::String => json!("string"),
+            AvroType::Decimal { scale } => json!({
+                "type": "bytes",
+                "logicalType": "decimal",
+                "precision": DECIMAL_PRECISION,
+                "scale": scale,
+            }),
+            AvroType::TimestampMicros => json!({
+                "type": "long",
+                "logicalType": "timestamp-micros",
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AvroField {
+    pub name: &'static str,
+    pub avro_type: AvroType,
+    /// Written as a union of null and the type, defaulting to null
+    pub nullable: bool,
+}
+
+impl AvroField {
+    pub const fn new(name: &'static str, avro_type: AvroType, nullable: bool) -> Self {
+        Self {
+            name,
+            avro_type,
+            nullable,
+        }
+    }
+
+    fn schema(&self) -> Value {
+        if self.nullable {
+            json!({"name": self.name, "type": ["null", self.avro_type.schema()], "default": null})
+        } else {
+            json!({"name": self.name, "type": self.avro_type.schema()})
+        }
+    }
+}
+
+/// Avro schema of a record with these fields, as registered with the schema registry
+pub fn record_schema(name: &str, namespace: &str, fields: &[AvroField]) -> Value {
+    json!({
+        "type": "record",
+        "name": name,
+        "namespace": namespace,
+        "fields": fields.iter().map(AvroField::schema).collect::<Vec<_>>(),
+    })
+}
+
+/// Prefixes an encoded record with the Confluent wire format header
+pub fn confluent_framed(schema_id: u32, record: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(record.len() + 5);
+    buf.push(CONFLUENT_MAGIC_BYTE);
+    buf.extend_from_slice(&schema_id.to_be_bytes());
+    buf.extend_from_slice(record);
+    buf
+}
+
+/// Avro binary encoding of a record's fields, written in schema order
+#[derive(Default)]
+pub struct AvroWriter {
+    buf: Vec<u8>,
+}
+
+impl AvroWriter {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Zigzag encoded variable length integer
+    pub fn long(&mut self, value: i64) {
+        let mut n = ((value << 1) ^ (value >> 63)) as u64;
+        while n >= 0x80 {
+            self.buf.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.long(value.len() as i64);
+        self.buf.extend_from_slice(value);
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    /// Big endian two's complement of the value at `scale`. Extra fractional digits are dropped.
+    pub fn decimal(&mut self, value: &BigDecimal, scale: u32) {
+        let (unscaled, _) = value.with_scale(scale as i64).as_bigint_and_exponent();
+        self.bytes(&unscaled.to_signed_bytes_be());
+    }
+
+    pub fn timestamp_micros(&mut self, value: &chrono::NaiveDateTime) {
+        self.long(value.and_utc().timestamp_micros());
+    }
+
+    /// Writes a nullable field: the union branch, then the value if there is one
+    pub fn optional<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        match value {
+            None => self.long(0),
+            Some(value) => {
+                self.long(1);
+                write(self, value);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn encode(write: impl FnOnce(&mut AvroWriter)) -> Vec<u8> {
+        let mut writer = AvroWriter::default();
+        write(&mut writer);
+        writer.into_bytes()
+    }
+
+    #[test]
+    fn test_long() {
+        assert_eq!(encode(|w| w.long(0)), vec![0x00]);
+        assert_eq!(encode(|w| w.long(-1)), vec![0x01]);
+        assert_eq!(encode(|w| w.long(1)), vec![0x02]);
+        assert_eq!(encode(|w| w.long(-64)), vec![0x7f]);
+        assert_eq!(encode(|w| w.long(64)), vec![0x80, 0x01]);
+        assert_eq!(encode(|w| w.long(i64::MIN)).len(), 10);
+    }
+
+    #[test]
+    fn test_string_and_optional() {
+        assert_eq!(encode(|w| w.string("foo")), vec![0x06, b'f', b'o', b'o']);
+        assert_eq!(encode(|w| w.optional(None::<&str>, AvroWriter::string)), vec![
+            0x00
+        ]);
+        assert_eq!(
+            encode(|w| w.optional(Some("a"), AvroWriter::string)),
+            vec![0x02, 0x02, b'a']
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        // 1.5 at scale 2 is 150 unscaled, 0x0096 since 0x96 alone would be negative
+        assert_eq!(
+            encode(|w| w.decimal(&BigDecimal::from_str("1.5").unwrap(), 2)),
+            vec![0x04, 0x00, 0x96]
+        );
+        assert_eq!(encode(|w| w.decimal(&BigDecimal::from(-1), 0)), vec![
+            0x02, 0xff
+        ]);
+        assert_eq!(
+            encode(|w| w.decimal(&BigDecimal::from(u64::MAX), 0)).len(),
+            1 + 9
+        );
+    }
+
+    #[test]
+    fn test_timestamp_micros() {
+        let timestamp = chrono::DateTime::from_timestamp(1, 500_000)
+            .unwrap()
+            .naive_utc();
+        assert_eq!(
+            encode(|w| w.timestamp_micros(&timestamp)),
+            encode(|w| w.long(1_000_500))
+        );
+    }
+
+    #[test]
+    fn test_record_schema_and_framing() {
+        let schema = record_schema("Row", "aptos", &[
+            AvroField::new("version", AvroType::Long, false),
+            AvroField::new("amount", AvroType::Decimal { scale: 0 }, true),
+        ]);
+        assert_eq!(
+            schema,
+            json!({
+                "type": "record",
+                "name": "Row",
+                "namespace": "aptos",
+                "fields": [
+                    {"name": "version", "type": "long"},
+                    {
+                        "name": "amount",
+                        "type": ["null", {"type": "bytes", "logicalType": "decimal", "precision": 38, "scale": 0}],
+                        "default": null,
+                    },
+                ],
+            })
+        );
+        assert_eq!(confluent_framed(7, &[0x02]), vec![0, 0, 0, 0, 7, 0x02]);
+    }
+}
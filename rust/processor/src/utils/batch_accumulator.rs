@@ -0,0 +1,123 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::grpc_stream::TransactionsPBResponse;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Merges consecutive stream responses into one batch for the processing tasks. The batch is let
+/// go once it holds `max_txns` transactions or `max_wait` after its first response arrived,
+/// whichever comes first, so backfills still get full batches while a quiet chain isn't held up
+/// waiting for one to fill.
+pub struct BatchAccumulator {
+    max_txns: usize,
+    max_wait: Duration,
+    pending: Option<(TransactionsPBResponse, Instant)>,
+}
+
+impl BatchAccumulator {
+    pub fn new(max_txns: usize, max_wait: Duration) -> Self {
+        Self {
+            max_txns,
+            max_wait,
+            pending: None,
+        }
+    }
+
+    /// Adds a response, which must follow the pending one, and returns the batch if it's now due.
+    pub fn push(
+        &mut self,
+        txn_pb: TransactionsPBResponse,
+        now: Instant,
+    ) -> Option<TransactionsPBResponse> {
+        match self.pending.as_mut() {
+            Some((pending, _)) => {
+                pending.transactions.extend(txn_pb.transactions);
+                pending.end_version = txn_pb.end_version;
+                pending.end_txn_timestamp = txn_pb.end_txn_timestamp;
+                pending.size_in_bytes += txn_pb.size_in_bytes;
+            },
+            None => self.pending = Some((txn_pb, now)),
+        }
+        let is_due = self.pending.as_ref().is_some_and(|(pending, first_at)| {
+            pending.transactions.len() >= self.max_txns
+                || now.duration_since(*first_at) >= self.max_wait
+        });
+        if is_due {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    /// When the pending batch has to go out even if it isn't full
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending
+            .as_ref()
+            .map(|(_, first_at)| *first_at + self.max_wait)
+    }
+
+    /// Takes the pending batch regardless of its size
+    pub fn take(&mut self) -> Option<TransactionsPBResponse> {
+        self.pending.take().map(|(pending, _)| pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::Transaction;
+
+    fn txn_pb(start_version: u64, end_version: u64) -> TransactionsPBResponse {
+        TransactionsPBResponse {
+            transactions: (start_version..=end_version)
+                .map(|version| Transaction {
+                    version,
+                    ..Transaction::default()
+                })
+                .collect(),
+            chain_id: 1,
+            start_version,
+            end_version,
+            start_txn_timestamp: None,
+            end_txn_timestamp: None,
+            size_in_bytes: 10 * (end_version - start_version + 1),
+            compressed_transactions: None,
+        }
+    }
+
+    #[test]
+    fn test_flushes_when_full() {
+        let mut accumulator = BatchAccumulator::new(10, Duration::from_millis(500));
+        let now = Instant::now();
+        assert!(accumulator.push(txn_pb(0, 3), now).is_none());
+        assert!(accumulator.push(txn_pb(4, 7), now).is_none());
+        let batch = accumulator.push(txn_pb(8, 11), now).unwrap();
+        assert_eq!(batch.start_version, 0);
+        assert_eq!(batch.end_version, 11);
+        assert_eq!(batch.transactions.len(), 12);
+        assert_eq!(batch.size_in_bytes, 120);
+        assert!(accumulator.deadline().is_none());
+    }
+
+    #[test]
+    fn test_flushes_after_max_wait() {
+        let max_wait = Duration::from_millis(500);
+        let mut accumulator = BatchAccumulator::new(100, max_wait);
+        let now = Instant::now();
+        assert!(accumulator.push(txn_pb(0, 1), now).is_none());
+        assert_eq!(accumulator.deadline(), Some(now + max_wait));
+        // Later responses don't push the deadline back
+        assert!(accumulator
+            .push(txn_pb(2, 3), now + Duration::from_millis(200))
+            .is_none());
+        assert_eq!(accumulator.deadline(), Some(now + max_wait));
+        let batch = accumulator.push(txn_pb(4, 4), now + max_wait).unwrap();
+        assert_eq!((batch.start_version, batch.end_version), (0, 4));
+
+        assert!(accumulator.push(txn_pb(5, 6), now + max_wait).is_none());
+        let batch = accumulator.take().unwrap();
+        assert_eq!((batch.start_version, batch.end_version), (5, 6));
+        assert!(accumulator.take().is_none());
+    }
+}
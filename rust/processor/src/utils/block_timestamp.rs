@@ -0,0 +1,116 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_protos::{
+    transaction::v1::{transaction::TxnData, Transaction},
+    util::timestamp::Timestamp,
+};
+
+/// Timestamp of the latest block metadata transaction seen in the stream. Every transaction in a
+/// block shares the block's timestamp, so it stands in for transactions that come without one.
+#[derive(Debug, Default)]
+pub struct BlockTimestampCache {
+    latest: Option<Timestamp>,
+}
+
+impl BlockTimestampCache {
+    /// Starts from the timestamp of the transaction right before the stream, e.g. the processor's
+    /// checkpoint, which shares a block with any transaction at the start of the stream that comes
+    /// before a block metadata transaction.
+    pub fn new(latest: Option<Timestamp>) -> Self {
+        Self { latest }
+    }
+
+    /// Sets the timestamp of the transactions that are missing one, which must be in version order,
+    /// and returns how many were filled in and how many were left without one because no block had
+    /// been seen yet.
+    pub fn fill_missing(&mut self, transactions: &mut [Transaction]) -> (usize, usize) {
+        let (mut filled, mut unfilled) = (0, 0);
+        for txn in transactions {
+            match &txn.timestamp {
+                Some(timestamp) => {
+                    if matches!(txn.txn_data, Some(TxnData::BlockMetadata(_))) {
+                        self.latest = Some(timestamp.clone());
+                    }
+                },
+                None => match &self.latest {
+                    Some(timestamp) => {
+                        txn.timestamp = Some(timestamp.clone());
+                        filled += 1;
+                    },
+                    None => unfilled += 1,
+                },
+            }
+        }
+        (filled, unfilled)
+    }
+
+    pub fn latest(&self) -> Option<&Timestamp> {
+        self.latest.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{BlockMetadataTransaction, UserTransaction};
+
+    fn txn(version: u64, is_block: bool, seconds: Option<i64>) -> Transaction {
+        Transaction {
+            version,
+            timestamp: seconds.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            txn_data: Some(if is_block {
+                TxnData::BlockMetadata(BlockMetadataTransaction::default())
+            } else {
+                TxnData::User(UserTransaction::default())
+            }),
+            ..Transaction::default()
+        }
+    }
+
+    fn seconds(transactions: &[Transaction]) -> Vec<Option<i64>> {
+        transactions
+            .iter()
+            .map(|txn| txn.timestamp.as_ref().map(|t| t.seconds))
+            .collect()
+    }
+
+    #[test]
+    fn test_fill_missing() {
+        let mut cache = BlockTimestampCache::default();
+        let mut transactions = vec![
+            txn(1, false, None),
+            txn(2, true, Some(10)),
+            txn(3, false, None),
+            // Only block metadata moves the cached timestamp
+            txn(4, false, Some(15)),
+            txn(5, false, None),
+            txn(6, true, Some(20)),
+            txn(7, false, None),
+        ];
+        assert_eq!(cache.fill_missing(&mut transactions), (3, 1));
+        assert_eq!(seconds(&transactions), vec![
+            None,
+            Some(10),
+            Some(10),
+            Some(15),
+            Some(10),
+            Some(20),
+            Some(20)
+        ]);
+
+        // The cache carries over to the next batch
+        let mut transactions = vec![txn(8, false, None)];
+        assert_eq!(cache.fill_missing(&mut transactions), (1, 0));
+        assert_eq!(seconds(&transactions), vec![Some(20)]);
+        assert_eq!(cache.latest().map(|t| t.seconds), Some(20));
+
+        let mut cache = BlockTimestampCache::new(Some(Timestamp {
+            seconds: 5,
+            nanos: 0,
+        }));
+        let mut transactions = vec![txn(1, false, None)];
+        assert_eq!(cache.fill_missing(&mut transactions), (1, 0));
+        assert_eq!(seconds(&transactions), vec![Some(5)]);
+    }
+}
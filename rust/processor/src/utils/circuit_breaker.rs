@@ -0,0 +1,123 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in limit on how often the same batch is retried. Without it the first failure stops the
+//! processor, which fails the same batch again after every restart. With it, a failing batch is
+//! retried in place, and once it has failed `max_consecutive_failures` times in a row the breaker
+//! trips: the processor either stops with an error naming the versions, or runs the batch one
+//! transaction at a time and skips the transactions that still fail, recording them in
+//! failed_transactions.
+//!
+//! Recording the transaction a batch stopped the processor on is opt-in through
+//! `FailedTransactionsConfig`, which also decides whether the raw transactions are kept.
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MAX_CONSECUTIVE_FAILURES: u32 = 3;
+pub const DEFAULT_CIRCUIT_BREAKER_RETRY_DELAY_MS: u64 = 1_000;
+
+/// What happens to a batch once it has failed `max_consecutive_failures` times
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnCircuitBreakerTrip {
+    /// Stop the processor with an error naming the batch
+    #[default]
+    Halt,
+    /// Process the batch one transaction at a time and skip the ones that fail
+    Skip,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    // Failures in a row on the same batch, including the first one, before the breaker trips
+    #[serde(default = "CircuitBreakerConfig::default_max_consecutive_failures")]
+    pub max_consecutive_failures: u32,
+    #[serde(default)]
+    pub on_trip: OnCircuitBreakerTrip,
+    // Wait between two attempts at the same batch
+    #[serde(default = "CircuitBreakerConfig::default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+}
+
+impl CircuitBreakerConfig {
+    pub const fn default_max_consecutive_failures() -> u32 {
+        DEFAULT_MAX_CONSECUTIVE_FAILURES
+    }
+
+    pub const fn default_retry_delay_ms() -> u64 {
+        DEFAULT_CIRCUIT_BREAKER_RETRY_DELAY_MS
+    }
+
+    pub fn validate(&self, is_parquet_processor: bool) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.max_consecutive_failures > 0,
+            "The circuit breaker needs a positive max_consecutive_failures"
+        );
+        // Parquet processors report their progress through the gap detector per upload, not per
+        // batch, so a partly skipped batch can't be accounted for
+        anyhow::ensure!(
+            !(is_parquet_processor && self.on_trip == OnCircuitBreakerTrip::Skip),
+            "Parquet processors can't skip transactions when the circuit breaker trips"
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailedTransactionsConfig {
+    // Also store the protobuf encoded transaction, to inspect it or process it again later
+    #[serde(default)]
+    pub store_raw_transactions: bool,
+}
+
+impl FailedTransactionsConfig {
+    pub fn validate(&self, is_parquet_processor: bool) -> anyhow::Result<()> {
+        // Finding the failing transaction means processing transactions on their own, which
+        // parquet processors would buffer and report to the gap detector as partial batches
+        anyhow::ensure!(
+            !is_parquet_processor,
+            "Parquet processors can't record failed transactions"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let config: CircuitBreakerConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, CircuitBreakerConfig {
+            max_consecutive_failures: DEFAULT_MAX_CONSECUTIVE_FAILURES,
+            on_trip: OnCircuitBreakerTrip::Halt,
+            retry_delay_ms: DEFAULT_CIRCUIT_BREAKER_RETRY_DELAY_MS,
+        });
+        assert!(config.validate(true).is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config: CircuitBreakerConfig =
+            serde_json::from_str(r#"{"max_consecutive_failures": 5, "on_trip": "skip"}"#).unwrap();
+        assert!(config.validate(false).is_ok());
+        assert!(config.validate(true).is_err());
+        assert!(CircuitBreakerConfig {
+            max_consecutive_failures: 0,
+            ..config
+        }
+        .validate(false)
+        .is_err());
+    }
+
+    #[test]
+    fn test_failed_transactions_config() {
+        let config: FailedTransactionsConfig = serde_json::from_str("{}").unwrap();
+        assert!(!config.store_raw_transactions);
+        assert!(config.validate(false).is_ok());
+        assert!(config.validate(true).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rolling count of token activities per collection, kept in memory so operators can see the
+//! busiest collections without querying the DB.
+
+use crate::db::common::models::token_v2_models::{
+    v2_token_activities::TokenActivityV2, v2_token_datas::CurrentTokenDataV2,
+};
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopCollectionsConfig {
+    /// Only batches ending within this many versions of the latest processed one are counted
+    pub window_versions: u64,
+    /// Number of collections to report
+    pub top_n: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CollectionVolume {
+    pub collection_id: String,
+    pub num_activities: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TopCollections {
+    pub latest_version: Option<u64>,
+    pub window_versions: u64,
+    pub collections: Vec<CollectionVolume>,
+}
+
+/// Counts activities per collection. Tokens are mapped to their collection through the token data
+/// written in the same batch, activities on tokens without one are not counted.
+pub fn aggregate_by_collection(
+    activities: &[TokenActivityV2],
+    token_datas: &[CurrentTokenDataV2],
+) -> AHashMap<String, u64> {
+    let token_collections: AHashMap<&str, &str> = token_datas
+        .iter()
+        .map(|token_data| {
+            (
+                token_data.token_data_id.as_str(),
+                token_data.collection_id.as_str(),
+            )
+        })
+        .collect();
+    let mut volumes = AHashMap::new();
+    for activity in activities {
+        if let Some(collection_id) = token_collections.get(activity.token_data_id.as_str()) {
+            *volumes.entry(collection_id.to_string()).or_insert(0) += 1;
+        }
+    }
+    volumes
+}
+
+pub struct CollectionVolumeTracker {
+    config: TopCollectionsConfig,
+    /// Activity counts per collection for each processed batch, by the batch's end version
+    batches: BTreeMap<u64, AHashMap<String, u64>>,
+}
+
+impl CollectionVolumeTracker {
+    pub fn new(config: TopCollectionsConfig) -> Self {
+        Self {
+            config,
+            batches: BTreeMap::new(),
+        }
+    }
+
+    /// Batches can come in out of order, the window follows the highest end version seen
+    pub fn add_batch(&mut self, end_version: u64, volumes: AHashMap<String, u64>) {
+        self.batches.insert(end_version, volumes);
+        let latest_version = self.latest_version().unwrap_or_default();
+        let window_versions = self.config.window_versions;
+        self.batches
+            .retain(|version, _| version + window_versions > latest_version);
+    }
+
+    pub fn latest_version(&self) -> Option<u64> {
+        self.batches.keys().next_back().copied()
+    }
+
+    pub fn top_collections(&self) -> TopCollections {
+        let mut totals: AHashMap<&str, u64> = AHashMap::new();
+        for volumes in self.batches.values() {
+            for (collection_id, num_activities) in volumes {
+                *totals.entry(collection_id.as_str()).or_insert(0) += num_activities;
+            }
+        }
+        let mut collections = totals
+            .into_iter()
+            .map(|(collection_id, num_activities)| CollectionVolume {
+                collection_id: collection_id.to_string(),
+                num_activities,
+            })
+            .collect::<Vec<_>>();
+        collections.sort_by(|a, b| {
+            b.num_activities
+                .cmp(&a.num_activities)
+                .then_with(|| a.collection_id.cmp(&b.collection_id))
+        });
+        collections.truncate(self.config.top_n);
+        TopCollections {
+            latest_version: self.latest_version(),
+            window_versions: self.config.window_versions,
+            collections,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volumes(entries: &[(&str, u64)]) -> AHashMap<String, u64> {
+        entries
+            .iter()
+            .map(|(collection_id, count)| (collection_id.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_top_collections_window() {
+        let mut tracker = CollectionVolumeTracker::new(TopCollectionsConfig {
+            window_versions: 100,
+            top_n: 2,
+        });
+        tracker.add_batch(50, volumes(&[("a", 10), ("b", 1)]));
+        tracker.add_batch(100, volumes(&[("b", 3), ("c", 2)]));
+        let top = tracker.top_collections();
+        assert_eq!(top.latest_version, Some(100));
+        assert_eq!(top.collections, vec![
+            CollectionVolume {
+                collection_id: "a".to_string(),
+                num_activities: 10,
+            },
+            CollectionVolume {
+                collection_id: "b".to_string(),
+                num_activities: 4,
+            },
+        ]);
+
+        // The batch ending at 50 falls out of the window
+        tracker.add_batch(150, volumes(&[("c", 1)]));
+        let top = tracker.top_collections();
+        assert_eq!(top.collections, vec![
+            CollectionVolume {
+                collection_id: "b".to_string(),
+                num_activities: 3,
+            },
+            CollectionVolume {
+                collection_id: "c".to_string(),
+                num_activities: 3,
+            },
+        ]);
+    }
+}
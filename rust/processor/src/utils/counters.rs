@@ -3,8 +3,9 @@
 
 use once_cell::sync::Lazy;
 use prometheus::{
-    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
-    register_int_gauge_vec, GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
+    register_gauge_vec, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, GaugeVec, Histogram,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 
 pub enum ProcessorStep {
@@ -98,6 +99,42 @@ pub static UNABLE_TO_GET_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of connections in the DB pool, idle or in use
+pub static DB_POOL_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "indexer_processor_db_pool_connections",
+        "Number of connections in the DB pool, idle or in use"
+    )
+    .unwrap()
+});
+
+/// Number of idle connections in the DB pool
+pub static DB_POOL_IDLE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "indexer_processor_db_pool_idle_connections",
+        "Number of idle connections in the DB pool"
+    )
+    .unwrap()
+});
+
+/// Time spent waiting for a connection from the DB pool
+pub static DB_POOL_WAIT_TIME_IN_SECS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "indexer_processor_db_pool_wait_time_in_secs",
+        "Time spent waiting for a connection from the DB pool"
+    )
+    .unwrap()
+});
+
+/// Number of times no DB connection freed up within the pool's connection timeout
+pub static DB_POOL_EXHAUSTED_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "indexer_processor_db_pool_exhausted_count",
+        "Number of times no DB connection freed up within the pool's connection timeout"
+    )
+    .unwrap()
+});
+
 /// Number of times the connection pool got a connection
 pub static GOT_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
@@ -107,6 +144,25 @@ pub static GOT_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of times a db statement was cancelled by the statement timeout
+pub static DB_STATEMENT_TIMEOUT_COUNT: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "indexer_db_statement_timeout_count",
+        "Number of times a db statement was cancelled by the statement timeout"
+    )
+    .unwrap()
+});
+
+/// Number of times a batch was retried after losing the db connection
+pub static DB_CONNECTION_RETRY_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_db_connection_retry_count",
+        "Number of times a batch was retried after losing the db connection",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
 #[allow(dead_code)]
 /// Number of times the indexer has been unable to fetch a transaction. Ideally zero.
 pub static UNABLE_TO_FETCH_TRANSACTION: Lazy<IntCounter> = Lazy::new(|| {
@@ -147,6 +203,36 @@ pub static PROCESSED_BYTES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Uncompressed size of the batches that were compressed before going into the channel
+pub static BUFFERED_TRANSACTIONS_RAW_BYTES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_buffered_transactions_raw_bytes_count",
+        "Uncompressed size of buffered transaction batches",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Compressed size of the batches held in the channel. Ratio against the raw count is the memory saved
+pub static BUFFERED_TRANSACTIONS_COMPRESSED_BYTES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_buffered_transactions_compressed_bytes_count",
+        "Compressed size of buffered transaction batches",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Time spent compressing and decompressing buffered batches, i.e. the CPU cost of compression
+pub static BUFFERED_TRANSACTIONS_COMPRESSION_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "indexer_processor_buffered_transactions_compression_time_in_secs",
+        "Time taken to compress or decompress a buffered transaction batch",
+        &["processor_name", "step"]
+    )
+    .unwrap()
+});
+
 /// The amount of time that a task spent waiting for a protobuf bundle of transactions
 pub static PB_CHANNEL_FETCH_WAIT_TIME_SECS: Lazy<GaugeVec> = Lazy::new(|| {
     register_gauge_vec!(
@@ -177,6 +263,16 @@ pub static NUM_TRANSACTIONS_FILTERED_OUT_COUNT: Lazy<IntCounterVec> = Lazy::new(
     .unwrap()
 });
 
+/// Transactions skipped instead of processed, by reason
+pub static SKIPPED_TRANSACTIONS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_skipped_transactions_total",
+        "Number of transactions skipped instead of processed",
+        &["processor_name", "reason"]
+    )
+    .unwrap()
+});
+
 /// Size of the channel containing transactions fetched from GRPC, waiting to be processed
 pub static FETCHER_THREAD_CHANNEL_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
@@ -187,6 +283,67 @@ pub static FETCHER_THREAD_CHANNEL_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of out of order transactions held back in the fetcher's reorder buffer
+pub static REORDER_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "indexer_processor_reorder_buffer_size",
+        "Number of transactions waiting in the reorder buffer",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Number of stream responses dropped because a transaction's events didn't match its size info
+pub static TRUNCATED_EVENTS_RESPONSE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_truncated_events_response_count",
+        "Number of responses refetched because a transaction had a truncated event list",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Number of times the stream moved on to the next GRPC endpoint after failing to reconnect
+pub static GRPC_ENDPOINT_FAILOVER_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_grpc_endpoint_failover_count",
+        "Number of times the stream failed over to the next GRPC endpoint",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Number of transactions dropped because the stream sent versions we had already fetched
+pub static STREAM_RESET_SKIPPED_TRANSACTIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_stream_reset_skipped_transactions_count",
+        "Number of transactions skipped because the stream went back to already fetched versions",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Number of transactions that came without a timestamp, by whether the latest block's was used
+pub static MISSING_TIMESTAMP_TRANSACTIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_missing_timestamp_transactions_count",
+        "Number of transactions without a timestamp, by whether it was filled from the latest block",
+        &["processor_name", "filled"]
+    )
+    .unwrap()
+});
+
+/// Wall clock time between the last transaction of a batch being committed on chain and the batch
+/// being processed. Unlike version lag this includes the block production rate.
+pub static PROCESSING_LATENCY_IN_SECS: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "indexer_processor_processing_latency_seconds",
+        "Seconds between the last processed transaction's timestamp and when it was processed",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
 /// Overall processing time for a single batch of transactions (per task)
 pub static SINGLE_BATCH_PROCESSING_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
@@ -265,6 +422,27 @@ pub static PROCESSOR_UNKNOWN_TYPE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Events skipped because their type string is implausibly long
+pub static EVENT_TYPE_TOO_LONG_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_event_type_too_long_count",
+        "Number of events skipped because the type string exceeds the max length",
+        &["model_name"]
+    )
+    .unwrap()
+});
+
+/// Numeric values out of range, by model, column and what `NumericOverflowPolicy` did about it.
+/// Named after token_amount, the first column checked.
+pub static INVALID_TOKEN_AMOUNT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_invalid_token_amount_count",
+        "Number of numeric values that were negative or too large for their type",
+        &["model_name", "column", "action"]
+    )
+    .unwrap()
+});
+
 /// Parquet struct size
 pub static PARQUET_STRUCT_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!("indexer_parquet_struct_size", "Parquet struct size", &[
@@ -303,3 +481,53 @@ pub static PARQUET_BUFFER_SIZE_AFTER_UPLOAD: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+/// Number of rows deleted by background pruning
+pub static PRUNED_ROWS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_pruned_rows_count",
+        "Number of rows deleted by background pruning",
+        &["processor_name", "table_name"]
+    )
+    .unwrap()
+});
+
+/// Number of current_token_ownerships_v2 rows checked against their activities
+pub static OWNERSHIP_AUDIT_ROWS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_ownership_audit_rows_count",
+        "Number of current ownership rows checked against their activities, by outcome",
+        &["processor_name", "outcome"]
+    )
+    .unwrap()
+});
+
+/// Number of transactions written to failed_transactions, by whether they were skipped
+pub static FAILED_TRANSACTIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_failed_transactions_count",
+        "Number of transactions written to failed_transactions",
+        &["processor_name", "outcome"]
+    )
+    .unwrap()
+});
+
+/// Number of transactions that took longer than slow_transaction_log's threshold to parse
+pub static SLOW_TRANSACTIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_slow_transactions_count",
+        "Number of transactions that took longer than the threshold to parse",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Number of token_properties replaced because they were over max_token_properties_bytes
+pub static OVERSIZED_TOKEN_PROPERTIES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_processor_oversized_token_properties_count",
+        "Number of token_properties replaced by a hash because they were over the max size",
+        &["token_standard"]
+    )
+    .unwrap()
+});
@@ -4,22 +4,37 @@
 //! Database-related functions
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::utils::util::remove_null_bytes;
+use crate::utils::{
+    counters::{
+        DB_POOL_CONNECTIONS, DB_POOL_EXHAUSTED_COUNT, DB_POOL_IDLE_CONNECTIONS,
+        DB_POOL_WAIT_TIME_IN_SECS, DB_STATEMENT_TIMEOUT_COUNT,
+    },
+    util::remove_null_bytes,
+};
 use ahash::AHashMap;
+use bytes::Bytes;
 use diesel::{
+    connection::BoxableConnection,
+    migration::{Migration, MigrationMetadata, MigrationName},
     query_builder::{AstPass, Query, QueryFragment},
+    result::DatabaseErrorKind,
     ConnectionResult, QueryResult,
 };
 use diesel_async::{
     pooled_connection::{
-        bb8::{Pool, PooledConnection},
+        bb8::{Pool, PooledConnection, RunError},
         AsyncDieselConnectionManager, ManagerConfig, PoolError,
     },
-    AsyncPgConnection, RunQueryDsl,
+    scoped_futures::{ScopedBoxFuture, ScopedFutureExt},
+    AsyncConnection, AsyncPgConnection, RunQueryDsl,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use futures_util::{future::BoxFuture, FutureExt};
-use std::sync::Arc;
+use futures_util::{future::BoxFuture, FutureExt, SinkExt};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio_postgres::error::SqlState;
 
 pub type Backend = diesel::pg::Pg;
 
@@ -29,8 +44,50 @@ pub type ArcDbPool = Arc<DbPool>;
 pub type DbPoolConnection<'a> = PooledConnection<'a, MyDbConnection>;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/db/postgres/migrations");
+/// Migrations that name the `public` schema's tables, or define the `legacy_migration_v1` views
+/// every schema in the database would share. Per chain schemas record them as run without running
+/// them, so the views keep reading the public schema. Columns they add are added to the
+/// search_path's tables by later migrations.
+const PUBLIC_SCHEMA_MIGRATIONS: &[&str] = &[
+    // 2024-05-22-200847_add_v1_migration_views
+    "20240522200847",
+    // 2024-06-13-061711_untransferrable
+    "20240613061711",
+    // 2024-06-27-213336_add_v1_migration_view
+    "20240627213336",
+];
 
 pub const DEFAULT_MAX_POOL_SIZE: u32 = 150;
+// Same as bb8's default
+pub const DEFAULT_POOL_CONNECTION_TIMEOUT_IN_SECS: u64 = 30;
+
+/// Bounds the writes running at once across processing tasks, see `set_max_concurrent_db_writes`
+static DB_WRITE_PERMITS: OnceCell<Semaphore> = OnceCell::new();
+/// Smallest chunk a timed out insert is split into, see `set_statement_timeout_min_chunk_size`
+static STATEMENT_TIMEOUT_MIN_CHUNK_SIZE: OnceCell<usize> = OnceCell::new();
+
+/// How a table's rows are written. `Insert` upserts in chunks inside the batch transaction.
+/// `Copy` replaces the batch's version range with a single COPY on its own connection. That is
+/// much faster for backfills, but it commits separately from the rest of the batch and
+/// overwrites rows instead of upserting them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteStrategy {
+    #[default]
+    Insert,
+    Copy,
+}
+
+/// What an insert does with rows whose primary key is already in the table. `Update` upserts
+/// them with the new values. `Nothing` keeps the row already there, which saves rewriting every
+/// row when an append-only table is reprocessed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    #[default]
+    Update,
+    Nothing,
+}
 
 #[derive(QueryId)]
 /// Using this will append a where clause at the end of the string upsert function, e.g.
@@ -57,21 +114,23 @@ pub fn clean_data_for_db<T: serde::Serialize + for<'de> serde::Deserialize<'de>>
     }
 }
 
-fn establish_connection(database_url: &str) -> BoxFuture<ConnectionResult<AsyncPgConnection>> {
+fn make_tls_connector(cert_path: &str) -> postgres_native_tls::MakeTlsConnector {
     use native_tls::{Certificate, TlsConnector};
-    use postgres_native_tls::MakeTlsConnector;
 
+    let cert = std::fs::read(cert_path).expect("Could not read certificate");
+    let cert = Certificate::from_pem(&cert).expect("Could not parse certificate");
+    let connector = TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .add_root_certificate(cert)
+        .build()
+        .expect("Could not build TLS connector");
+    postgres_native_tls::MakeTlsConnector::new(connector)
+}
+
+fn establish_connection(database_url: &str) -> BoxFuture<ConnectionResult<AsyncPgConnection>> {
     (async move {
         let (url, cert_path) = parse_and_clean_db_url(database_url);
-        let cert = std::fs::read(cert_path.unwrap()).expect("Could not read certificate");
-
-        let cert = Certificate::from_pem(&cert).expect("Could not parse certificate");
-        let connector = TlsConnector::builder()
-            .danger_accept_invalid_certs(true)
-            .add_root_certificate(cert)
-            .build()
-            .expect("Could not build TLS connector");
-        let connector = MakeTlsConnector::new(connector);
+        let connector = make_tls_connector(&cert_path.unwrap());
 
         let (client, connection) = tokio_postgres::connect(&url, connector)
             .await
@@ -103,10 +162,118 @@ fn parse_and_clean_db_url(url: &str) -> (String, Option<String>) {
     (db_url.to_string(), cert_path)
 }
 
+/// Sets the postgres `statement_timeout` for every connection opened with this url
+fn add_statement_timeout_to_db_url(url: &str, statement_timeout_in_secs: u64) -> String {
+    add_option_to_db_url(
+        url,
+        &format!(
+            "-c%20statement_timeout%3D{}",
+            statement_timeout_in_secs * 1000
+        ),
+    )
+}
+
+/// Sets the postgres `search_path` for every connection opened with this url, so every
+/// unqualified table, including diesel's and the migrations', resolves in `schema` only
+pub fn add_search_path_to_db_url(url: &str, schema: &str) -> String {
+    add_option_to_db_url(url, &format!("-c%20search_path%3D{}", schema))
+}
+
+/// Sets the postgres session `timezone` to UTC for every connection opened with this url, so
+/// the processor's UTC `NaiveDateTime`s are stored as the right instant in `timestamptz` columns
+pub fn add_utc_timezone_to_db_url(url: &str) -> String {
+    add_option_to_db_url(url, "-c%20timezone%3DUTC")
+}
+
+/// Schema holding a chain's tables when `postgres_schema_per_chain` is set
+pub fn chain_schema_name(chain_id: u64) -> String {
+    match chain_id {
+        1 => "mainnet".to_string(),
+        2 => "testnet".to_string(),
+        _ => format!("chain_{}", chain_id),
+    }
+}
+
+/// Creates the schema the pool's search_path points at, before migrations fill it
+pub async fn create_schema(pool: &DbPool, schema: &str) -> anyhow::Result<()> {
+    diesel::sql_query(format!("CREATE SCHEMA IF NOT EXISTS {}", schema))
+        .execute(&mut pool.get().await?)
+        .await?;
+    Ok(())
+}
+
+/// Appends a percent-encoded `-c name=value` to the url's `options` connection parameter, which
+/// both libpq and tokio-postgres understand. Both clients only percent-decode the url, so spaces
+/// can't be encoded as `+` here.
+fn add_option_to_db_url(url: &str, option: &str) -> String {
+    let mut db_url = url::Url::parse(url).expect("Could not parse database url");
+
+    let mut has_options = false;
+    let mut query_pairs = db_url
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.strip_prefix("options=") {
+            Some(existing) => {
+                has_options = true;
+                format!("options={}%20{}", existing, option)
+            },
+            None => pair.to_string(),
+        })
+        .collect::<Vec<_>>();
+    if !has_options {
+        query_pairs.push(format!("options={}", option));
+    }
+    db_url.set_query(Some(&query_pairs.join("&")));
+    db_url.to_string()
+}
+
+/// Postgres cancels statements that run past `statement_timeout` with this error message
+pub fn is_statement_timeout_error(err: &diesel::result::Error) -> bool {
+    matches!(
+        err,
+        diesel::result::Error::DatabaseError(_, info)
+            if info.message().contains("canceling statement due to statement timeout")
+    )
+}
+
+/// Whether the error, or one it was caused by, means the connection to postgres was lost or
+/// couldn't be set up, rather than a statement failing. Running the batch again on a connection
+/// that works then has a chance to succeed, unlike with a query error.
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<diesel::result::Error>() {
+            matches!(
+                err,
+                diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::ClosedConnection | DatabaseErrorKind::UnableToSendCommand,
+                    _
+                ) | diesel::result::Error::BrokenTransactionManager
+            )
+        } else if let Some(err) = cause.downcast_ref::<tokio_postgres::Error>() {
+            // Class 08 is connection exceptions, 57P01 the server shutting down
+            err.is_closed()
+                || err.code().is_some_and(|code| {
+                    code.code().starts_with("08") || *code == SqlState::ADMIN_SHUTDOWN
+                })
+        } else {
+            cause.is::<std::io::Error>()
+        }
+    })
+}
+
 pub async fn new_db_pool(
     database_url: &str,
     max_pool_size: Option<u32>,
+    statement_timeout_in_secs: Option<u64>,
+    connection_timeout_in_secs: Option<u64>,
 ) -> Result<ArcDbPool, PoolError> {
+    let database_url = match statement_timeout_in_secs {
+        Some(timeout) => add_statement_timeout_to_db_url(database_url, timeout),
+        None => database_url.to_string(),
+    };
+    let database_url = database_url.as_str();
     let (_url, cert_path) = parse_and_clean_db_url(database_url);
 
     let config = if cert_path.is_some() {
@@ -118,11 +285,103 @@ pub async fn new_db_pool(
     };
     let pool = Pool::builder()
         .max_size(max_pool_size.unwrap_or(DEFAULT_MAX_POOL_SIZE))
+        .connection_timeout(Duration::from_secs(
+            connection_timeout_in_secs.unwrap_or(DEFAULT_POOL_CONNECTION_TIMEOUT_IN_SECS),
+        ))
         .build(config)
         .await?;
     Ok(Arc::new(pool))
 }
 
+/// Gets a connection from the pool, recording the wait and how busy the pool is. If none frees up
+/// within the pool's connection timeout this returns `RunError::TimedOut` and counts the pool as
+/// exhausted, instead of waiting indefinitely.
+pub async fn get_pool_connection(pool: &DbPool) -> Result<DbPoolConnection<'_>, RunError> {
+    let wait_start = std::time::Instant::now();
+    let res = pool.get().await;
+    let wait_time_in_secs = wait_start.elapsed().as_secs_f64();
+    DB_POOL_WAIT_TIME_IN_SECS.observe(wait_time_in_secs);
+    let state = pool.state();
+    DB_POOL_CONNECTIONS.set(state.connections as i64);
+    DB_POOL_IDLE_CONNECTIONS.set(state.idle_connections as i64);
+    if let Err(RunError::TimedOut) = &res {
+        DB_POOL_EXHAUSTED_COUNT.inc();
+        tracing::warn!(
+            connections = state.connections,
+            idle_connections = state.idle_connections,
+            wait_time_in_secs,
+            "[Parser] pool_exhausted: timed out waiting for a DB connection, consider raising db_pool_size"
+        );
+    }
+    res
+}
+
+/// Caps the writes that `execute_in_chunks`, `execute_in_transaction` and `copy_in_version_range`
+/// run at once, across every processing task, so parsing can run wider than the DB takes writes.
+/// The permit is per statement, not per batch: every chunk of `execute_in_chunks` takes its own.
+/// Without it writes are only bounded by the pool. Can only be set once.
+pub fn set_max_concurrent_db_writes(max_concurrent_db_writes: usize) {
+    assert!(
+        max_concurrent_db_writes > 0,
+        "max_concurrent_db_writes has to be positive"
+    );
+    DB_WRITE_PERMITS
+        .set(Semaphore::new(max_concurrent_db_writes))
+        .expect("max_concurrent_db_writes is already set");
+}
+
+/// Stops splitting inserts that hit the statement timeout once the halves would be smaller than
+/// this, so a batch fails instead of writing row by row. Defaults to 1. Can only be set once.
+pub fn set_statement_timeout_min_chunk_size(min_chunk_size: usize) {
+    assert!(
+        min_chunk_size > 0,
+        "db_statement_timeout_min_chunk_size has to be positive"
+    );
+    STATEMENT_TIMEOUT_MIN_CHUNK_SIZE
+        .set(min_chunk_size)
+        .expect("db_statement_timeout_min_chunk_size is already set");
+}
+
+/// Whether `num_items` that timed out can be split in two halves of at least the minimum size
+fn can_split_on_timeout(num_items: usize) -> bool {
+    num_items >= 2 * STATEMENT_TIMEOUT_MIN_CHUNK_SIZE.get().copied().unwrap_or(1)
+}
+
+/// Counts and logs a timed out insert of `num_items`, returning where to split it if it still can be
+fn split_on_timeout(num_items: usize) -> Option<usize> {
+    DB_STATEMENT_TIMEOUT_COUNT.inc();
+    if !can_split_on_timeout(num_items) {
+        tracing::warn!(
+            num_items = num_items,
+            min_chunk_size = STATEMENT_TIMEOUT_MIN_CHUNK_SIZE.get().copied().unwrap_or(1),
+            "Statement timed out at the minimum chunk size, not splitting further"
+        );
+        return None;
+    }
+    let mid = num_items / 2;
+    tracing::warn!(
+        num_items = num_items,
+        first_half = mid,
+        second_half = num_items - mid,
+        "Statement timed out, retrying with half the batch size"
+    );
+    Some(mid)
+}
+
+/// Waits for a write slot if `set_max_concurrent_db_writes` was called. The slot is held until the
+/// permit is dropped.
+async fn acquire_db_write_permit() -> Option<SemaphorePermit<'static>> {
+    match DB_WRITE_PERMITS.get() {
+        Some(permits) => Some(
+            permits
+                .acquire()
+                .await
+                .expect("DB write semaphore is never closed"),
+        ),
+        None => None,
+    }
+}
+
 pub async fn execute_in_chunks<U, T>(
     conn: ArcDbPool,
     build_query: fn(Vec<T>) -> (U, Option<&'static str>),
@@ -139,9 +398,8 @@ where
             let conn = conn.clone();
             let items = chunk.to_vec();
             tokio::spawn(async move {
-                let (query, additional_where_clause) = build_query(items.clone());
-                execute_or_retry_cleaned(conn, build_query, items, query, additional_where_clause)
-                    .await
+                let _permit = acquire_db_write_permit().await;
+                execute_or_split_on_timeout(conn, build_query, items).await
             })
         })
         .collect::<Vec<_>>();
@@ -156,6 +414,169 @@ where
     Ok(())
 }
 
+/// Runs `callback` inside a single DB transaction on one pooled connection. Nothing is committed
+/// unless the callback returns Ok.
+pub async fn execute_in_transaction<'a, R, F>(pool: ArcDbPool, callback: F) -> QueryResult<R>
+where
+    F: for<'r> FnOnce(&'r mut MyDbConnection) -> ScopedBoxFuture<'a, 'r, QueryResult<R>>
+        + Send
+        + 'a,
+    R: Send + 'a,
+{
+    let _permit = acquire_db_write_permit().await;
+    let mut conn = get_pool_connection(&pool).await.map_err(|e| {
+        tracing::warn!("Error getting connection from pool: {:?}", e);
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UnableToSendCommand,
+            Box::new(e.to_string()),
+        )
+    })?;
+    conn.transaction(callback).await
+}
+
+/// Opens a tokio-postgres client outside of the pool, for statements diesel can't run such as
+/// COPY. TLS is set up the same way as for the pool when the url has a `sslrootcert`.
+pub async fn connect_tokio_postgres(database_url: &str) -> anyhow::Result<tokio_postgres::Client> {
+    let (url, cert_path) = parse_and_clean_db_url(database_url);
+    let client = match cert_path {
+        Some(cert_path) => {
+            let (client, connection) =
+                tokio_postgres::connect(&url, make_tls_connector(&cert_path)).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    tracing::error!(error = ?e, "COPY connection error");
+                }
+            });
+            client
+        },
+        None => {
+            let (client, connection) = tokio_postgres::connect(&url, tokio_postgres::NoTls).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    tracing::error!(error = ?e, "COPY connection error");
+                }
+            });
+            client
+        },
+    };
+    Ok(client)
+}
+
+/// Appends one line of COPY csv. Values are always quoted so an empty string stays empty, and
+/// None is written as an unquoted empty field, which COPY reads as NULL.
+pub fn write_copy_csv_row(buf: &mut Vec<u8>, fields: &[Option<&str>]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buf.push(b',');
+        }
+        if let Some(value) = field {
+            buf.push(b'"');
+            buf.extend_from_slice(value.replace('"', "\"\"").as_bytes());
+            buf.push(b'"');
+        }
+    }
+    buf.push(b'\n');
+}
+
+/// Replaces the rows of `table` with versions in `[start_version, end_version]` by the `csv`
+/// rows, see `write_copy_csv_row`. The delete and the COPY share a transaction, so replaying a
+/// batch after a restart doesn't run into the rows it already wrote.
+pub async fn copy_in_version_range(
+    database_url: &str,
+    table: &str,
+    columns: &[&str],
+    start_version: i64,
+    end_version: i64,
+    csv: Vec<u8>,
+) -> anyhow::Result<u64> {
+    let _permit = acquire_db_write_permit().await;
+    let mut client = connect_tokio_postgres(database_url).await?;
+    let tx = client.transaction().await?;
+    tx.execute(
+        &format!(
+            "DELETE FROM {} WHERE transaction_version BETWEEN $1 AND $2",
+            table
+        ),
+        &[&start_version, &end_version],
+    )
+    .await?;
+    let sink = tx
+        .copy_in(&format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+            table,
+            columns.join(", ")
+        ))
+        .await?;
+    futures_util::pin_mut!(sink);
+    sink.send(Bytes::from(csv)).await?;
+    let rows = sink.finish().await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+/// Same as `execute_in_chunks` but runs the chunks one after another on `conn`, so that it can be
+/// used inside `execute_in_transaction`.
+pub async fn execute_in_chunks_with_conn<U, T>(
+    conn: &mut MyDbConnection,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    items_to_insert: &[T],
+    chunk_size: usize,
+) -> Result<(), diesel::result::Error>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    for chunk in items_to_insert.chunks(chunk_size) {
+        execute_or_split_on_timeout_conn(conn, build_query, chunk.to_vec()).await?;
+    }
+    Ok(())
+}
+
+/// `execute_or_split_on_timeout` inside a transaction. Each attempt runs in a savepoint, since a
+/// failed statement aborts the whole transaction, so the halves of a timed out chunk commit with
+/// the rest of the batch.
+fn execute_or_split_on_timeout_conn<'a, U, T>(
+    conn: &'a mut MyDbConnection,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    items: Vec<T>,
+) -> BoxFuture<'a, Result<(), diesel::result::Error>>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    async move {
+        let (query, additional_where_clause) = build_query(items.clone());
+        let res = conn
+            .transaction(|conn| {
+                async move {
+                    execute_with_better_error_conn(conn, query, additional_where_clause).await
+                }
+                .scope_boxed()
+            })
+            .await;
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) if is_statement_timeout_error(&e) => match split_on_timeout(items.len()) {
+                Some(mid) => {
+                    let mut second_half = items;
+                    let first_half = second_half.drain(..mid).collect();
+                    execute_or_split_on_timeout_conn(conn, build_query, first_half).await?;
+                    execute_or_split_on_timeout_conn(conn, build_query, second_half).await
+                },
+                None => Err(e),
+            },
+            Err(_) => {
+                let (cleaned_query, additional_where_clause) =
+                    build_query(clean_data_for_db(items, true));
+                execute_with_better_error_conn(conn, cleaned_query, additional_where_clause)
+                    .await
+                    .map(|_| ())
+            },
+        }
+    }
+    .boxed()
+}
+
 pub async fn execute_with_better_error<U>(
     pool: ArcDbPool,
     query: U,
@@ -176,7 +597,7 @@ where
     };
     let debug_string = diesel::debug_query::<Backend, _>(&final_query).to_string();
     tracing::debug!("Executing query: {:?}", debug_string);
-    let conn = &mut pool.get().await.map_err(|e| {
+    let conn = &mut get_pool_connection(&pool).await.map_err(|e| {
         tracing::warn!("Error getting connection from pool: {:?}", e);
         diesel::result::Error::DatabaseError(
             diesel::result::DatabaseErrorKind::UnableToSendCommand,
@@ -230,6 +651,44 @@ where
     res
 }
 
+/// Runs the query for `items`. If postgres cancels it because of the statement timeout, the
+/// items are split in half and each half is retried on its own, down to the minimum chunk size.
+/// Halves that succeed stay committed even if the other half fails for good.
+fn execute_or_split_on_timeout<U, T>(
+    conn: ArcDbPool,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    items: Vec<T>,
+) -> BoxFuture<'static, Result<(), diesel::result::Error>>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    async move {
+        let (query, additional_where_clause) = build_query(items.clone());
+        match execute_or_retry_cleaned(
+            conn.clone(),
+            build_query,
+            items.clone(),
+            query,
+            additional_where_clause,
+        )
+        .await
+        {
+            Err(e) if is_statement_timeout_error(&e) => match split_on_timeout(items.len()) {
+                Some(mid) => {
+                    let mut second_half = items;
+                    let first_half = second_half.drain(..mid).collect();
+                    execute_or_split_on_timeout(conn.clone(), build_query, first_half).await?;
+                    execute_or_split_on_timeout(conn, build_query, second_half).await
+                },
+                None => Err(e),
+            },
+            res => res,
+        }
+    }
+    .boxed()
+}
+
 async fn execute_or_retry_cleaned<U, T>(
     conn: ArcDbPool,
     build_query: fn(Vec<T>) -> (U, Option<&'static str>),
@@ -243,6 +702,8 @@ where
 {
     match execute_with_better_error(conn.clone(), query, additional_where_clause).await {
         Ok(_) => {},
+        // Cleaning the data won't make the statement any faster
+        Err(e) if is_statement_timeout_error(&e) => return Err(e),
         Err(_) => {
             let cleaned_items = clean_data_for_db(items, true);
             let (cleaned_query, additional_where_clause) = build_query(cleaned_items);
@@ -259,9 +720,50 @@ where
     Ok(())
 }
 
-pub fn run_pending_migrations<DB: diesel::backend::Backend>(conn: &mut impl MigrationHarness<DB>) {
-    conn.run_pending_migrations(MIGRATIONS)
+/// Runs the pending migrations. With `per_chain_schema`, the ones in `PUBLIC_SCHEMA_MIGRATIONS`
+/// are only recorded as run.
+pub fn run_pending_migrations<DB: diesel::backend::Backend>(
+    conn: &mut impl MigrationHarness<DB>,
+    per_chain_schema: bool,
+) {
+    if !per_chain_schema {
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("[Parser] Migrations failed!");
+        return;
+    }
+    let pending_migrations = conn
+        .pending_migrations(MIGRATIONS)
+        .expect("[Parser] Migrations failed!");
+    for migration in pending_migrations {
+        let version = migration.name().version().to_string();
+        if PUBLIC_SCHEMA_MIGRATIONS.contains(&version.as_str()) {
+            conn.run_migration(&SkippedMigration(migration.as_ref()))
+        } else {
+            conn.run_migration(migration.as_ref())
+        }
         .expect("[Parser] Migrations failed!");
+    }
+}
+
+/// Records a migration as run without running it
+struct SkippedMigration<'a, DB>(&'a dyn Migration<DB>);
+
+impl<DB: diesel::backend::Backend> Migration<DB> for SkippedMigration<'_, DB> {
+    fn run(&self, _conn: &mut dyn BoxableConnection<DB>) -> diesel::migration::Result<()> {
+        Ok(())
+    }
+
+    fn revert(&self, _conn: &mut dyn BoxableConnection<DB>) -> diesel::migration::Result<()> {
+        Ok(())
+    }
+
+    fn metadata(&self) -> &dyn MigrationMetadata {
+        self.0.metadata()
+    }
+
+    fn name(&self) -> &dyn MigrationName {
+        self.0.name()
+    }
 }
 
 /// Section below is required to modify the query.
@@ -283,3 +785,101 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_copy_csv_row() {
+        let mut buf = vec![];
+        write_copy_csv_row(&mut buf, &[Some("1"), None, Some(""), Some("a,\"b\"\nc")]);
+        write_copy_csv_row(&mut buf, &[Some("2"), Some("x"), None, None]);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\"1\",,\"\",\"a,\"\"b\"\"\nc\"\n\"2\",\"x\",,\n"
+        );
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        let database_error = |kind, message: &str| -> anyhow::Error {
+            diesel::result::Error::DatabaseError(kind, Box::new(message.to_string())).into()
+        };
+        assert!(is_connection_error(&database_error(
+            DatabaseErrorKind::ClosedConnection,
+            "server closed the connection unexpectedly"
+        )));
+        assert!(is_connection_error(
+            &database_error(DatabaseErrorKind::UnableToSendCommand, "pool timed out")
+                .context("inserting token_activities_v2")
+        ));
+        assert!(is_connection_error(
+            &anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+                .context("copying token_activities_v2")
+        ));
+        assert!(!is_connection_error(&database_error(
+            DatabaseErrorKind::UniqueViolation,
+            "duplicate key value violates unique constraint"
+        )));
+        assert!(!is_connection_error(&database_error(
+            DatabaseErrorKind::Unknown,
+            "canceling statement due to statement timeout"
+        )));
+        assert!(!is_connection_error(&anyhow::anyhow!(
+            "failed to parse event"
+        )));
+    }
+
+    #[test]
+    fn test_split_on_timeout() {
+        // Down to single rows unless a minimum chunk size is set
+        assert_eq!(split_on_timeout(1), None);
+        assert_eq!(split_on_timeout(2), Some(1));
+        assert_eq!(split_on_timeout(5), Some(2));
+    }
+
+    #[test]
+    fn test_add_statement_timeout_to_db_url() {
+        assert_eq!(
+            add_statement_timeout_to_db_url("postgresql://user:pw@localhost:5432/db", 30),
+            "postgresql://user:pw@localhost:5432/db?options=-c%20statement_timeout%3D30000"
+        );
+        assert_eq!(
+            add_statement_timeout_to_db_url(
+                "postgresql://localhost/db?sslmode=require&options=-c%20search_path%3Dfoo",
+                1
+            ),
+            "postgresql://localhost/db?sslmode=require&options=-c%20search_path%3Dfoo%20-c%20statement_timeout%3D1000"
+        );
+    }
+
+    #[test]
+    fn test_add_utc_timezone_to_db_url() {
+        assert_eq!(
+            add_utc_timezone_to_db_url(&add_search_path_to_db_url(
+                "postgresql://localhost/db",
+                "mainnet"
+            )),
+            "postgresql://localhost/db?options=-c%20search_path%3Dmainnet%20-c%20timezone%3DUTC"
+        );
+    }
+
+    #[test]
+    fn test_add_search_path_to_db_url() {
+        let url = add_search_path_to_db_url(
+            "postgresql://user:pw@localhost:5432/db",
+            &chain_schema_name(2),
+        );
+        assert_eq!(
+            url,
+            "postgresql://user:pw@localhost:5432/db?options=-c%20search_path%3Dtestnet"
+        );
+        assert_eq!(
+            add_statement_timeout_to_db_url(&url, 5),
+            "postgresql://user:pw@localhost:5432/db?options=-c%20search_path%3Dtestnet%20-c%20statement_timeout%3D5000"
+        );
+        assert_eq!(chain_schema_name(1), "mainnet");
+        assert_eq!(chain_schema_name(4), "chain_4");
+    }
+}
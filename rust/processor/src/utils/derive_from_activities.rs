@@ -0,0 +1,148 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rebuilds a current-state table out of the rows already in token_activities, independent of the
+//! stream. Meant for populating a newly added table without re-indexing everything.
+
+use crate::{
+    db::common::models::token_models::{
+        token_activities::TokenActivityQuery, token_claims::CurrentTokenPendingClaim,
+        tokens::CurrentTokenPendingClaimPK,
+    },
+    schema,
+    utils::database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+};
+use ahash::AHashMap;
+use bigdecimal::Zero;
+use diesel::{
+    pg::{upsert::excluded, Pg},
+    query_builder::QueryFragment,
+    ExpressionMethods, QueryDsl,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Number of versions of token_activities read per query
+const VERSION_RANGE_SIZE: i64 = 100_000;
+
+/// Current-state tables that can be derived from token_activities alone
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivedTable {
+    CurrentTokenPendingClaims,
+}
+
+/// Reads token_activities in version order and upserts `table` from it. Rows already written with
+/// a later version (e.g. by the live processor) are left alone.
+pub async fn derive_from_token_activities(
+    pool: ArcDbPool,
+    table: DerivedTable,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> anyhow::Result<()> {
+    match table {
+        DerivedTable::CurrentTokenPendingClaims => {
+            derive_current_token_pending_claims(pool, per_table_chunk_sizes).await
+        },
+    }
+}
+
+async fn derive_current_token_pending_claims(
+    pool: ArcDbPool,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> anyhow::Result<()> {
+    use schema::token_activities::dsl::*;
+
+    let max_version: Option<i64> = token_activities
+        .select(diesel::dsl::max(transaction_version))
+        .first(&mut pool.get().await?)
+        .await?;
+    let Some(max_version) = max_version else {
+        info!("[Parser] token_activities is empty, nothing to derive");
+        return Ok(());
+    };
+
+    // Claims that are still pending, carried across ranges so later offers add to them
+    let mut pending_claims: AHashMap<CurrentTokenPendingClaimPK, CurrentTokenPendingClaim> =
+        AHashMap::new();
+    let mut start_version = 0;
+    while start_version <= max_version {
+        let end_version = start_version + VERSION_RANGE_SIZE;
+        let activities: Vec<TokenActivityQuery> = token_activities
+            .filter(transaction_version.ge(start_version))
+            .filter(transaction_version.lt(end_version))
+            .filter(transfer_type.eq_any(CurrentTokenPendingClaim::ACTIVITY_TYPES))
+            .order((transaction_version.asc(), event_index.asc()))
+            .load(&mut pool.get().await?)
+            .await?;
+
+        let mut changed_claims = AHashMap::new();
+        for activity in &activities {
+            let (Some(from), Some(to)) = (&activity.from_address, &activity.to_address) else {
+                continue;
+            };
+            let pk = (
+                activity.token_data_id_hash.clone(),
+                activity.property_version.clone(),
+                from.clone(),
+                to.clone(),
+            );
+            if let Some(claim) =
+                CurrentTokenPendingClaim::from_token_activity(activity, pending_claims.get(&pk))
+            {
+                pending_claims.insert(pk.clone(), claim.clone());
+                changed_claims.insert(pk, claim);
+            }
+        }
+        let mut changed_claims = changed_claims.into_values().collect::<Vec<_>>();
+        changed_claims.sort();
+        execute_in_chunks(
+            pool.clone(),
+            insert_derived_current_token_pending_claims_query,
+            &changed_claims,
+            get_config_table_chunk_size::<CurrentTokenPendingClaim>(
+                "current_token_pending_claims",
+                per_table_chunk_sizes,
+            ),
+        )
+        .await?;
+        // A claimed or cancelled offer starts from zero again, no need to keep it around
+        pending_claims.retain(|_, claim| !claim.amount.is_zero());
+
+        info!(
+            start_version,
+            end_version = end_version.min(max_version + 1) - 1,
+            max_version,
+            num_activities = activities.len(),
+            num_changed = changed_claims.len(),
+            "[Parser] Derived current_token_pending_claims from token_activities"
+        );
+        start_version = end_version;
+    }
+    Ok(())
+}
+
+/// Unlike the processor's upsert this keeps the existing table_handle, which activities don't
+/// have, and only overwrites rows from strictly earlier versions
+fn insert_derived_current_token_pending_claims_query(
+    items_to_insert: Vec<CurrentTokenPendingClaim>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::current_token_pending_claims::dsl::*;
+
+    (
+        diesel::insert_into(schema::current_token_pending_claims::table)
+            .values(items_to_insert)
+            .on_conflict((token_data_id_hash, property_version, from_address, to_address))
+            .do_update()
+            .set((
+                amount.eq(excluded(amount)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE current_token_pending_claims.last_transaction_version < excluded.last_transaction_version "),
+    )
+}
@@ -1,6 +1,22 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod activity_observer;
+pub mod avro;
+pub mod batch_accumulator;
+pub mod block_timestamp;
+pub mod circuit_breaker;
+pub mod collection_volume;
 pub mod counters;
 pub mod database;
+pub mod derive_from_activities;
+pub mod ownership_audit;
+pub mod progress_file;
+pub mod prune_token_activities;
+pub mod rehash_token_ids;
+pub mod reorder_buffer;
+pub mod skipped_transactions;
+pub mod slow_transactions;
+pub mod statsd;
+pub mod timestamptz;
 pub mod util;
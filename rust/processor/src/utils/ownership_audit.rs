@@ -0,0 +1,294 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks current_token_ownerships_v2 against token_activities_v2, to catch current-state rows
+//! that were upserted out of order or missed an update.
+//!
+//! Only v1 tokens are checked: their balance is exactly the deposits into the owner's token store
+//! minus the withdrawals out of it. A random sample of rows is recomputed from all of the token's
+//! activities and compared with the stored amount. Both sides stop at the processor's checkpoint
+//! in processor_status and rows updated past it are skipped, so batches that commit out of order
+//! don't show up as divergences. Everything is read in one statement, i.e. one snapshot.
+//!
+//! Balances moved before the processor's starting_version have no activities, so only tokens whose
+//! whole supply was minted in the indexed history are sampled: there has to be a mint at or after
+//! starting_version that leaves the TokenData's supply equal to the minted amount. Tokens without
+//! a maximum don't track their supply and are never sampled.
+
+use crate::{
+    db::common::models::token_models::token_utils::TokenEventKind,
+    utils::{counters::OWNERSHIP_AUDIT_ROWS_COUNT, database::ArcDbPool},
+};
+use anyhow::Context;
+use bigdecimal::BigDecimal;
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Numeric, Text},
+    OptionalExtension, QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+pub const DEFAULT_AUDIT_SAMPLE_SIZE: i64 = 100;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OwnershipAuditConfig {
+    // Rows of current_token_ownerships_v2 checked per run
+    #[serde(default = "OwnershipAuditConfig::default_sample_size")]
+    pub sample_size: i64,
+    // Run every this many seconds next to the processor. Without it the audit runs once and the
+    // processor exits instead of processing the stream
+    pub interval_secs: Option<u64>,
+}
+
+impl OwnershipAuditConfig {
+    pub const fn default_sample_size() -> i64 {
+        DEFAULT_AUDIT_SAMPLE_SIZE
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.sample_size > 0,
+            "Auditing current_token_ownerships_v2 needs a positive sample_size"
+        );
+        anyhow::ensure!(
+            self.interval_secs != Some(0),
+            "Auditing current_token_ownerships_v2 needs a positive interval_secs"
+        );
+        Ok(())
+    }
+}
+
+/// A sampled current ownership and the amount its activities add up to
+#[derive(Clone, Debug, QueryableByName)]
+pub struct AuditedOwnership {
+    #[diesel(sql_type = Text)]
+    pub token_data_id: String,
+    #[diesel(sql_type = Numeric)]
+    pub property_version_v1: BigDecimal,
+    #[diesel(sql_type = Text)]
+    pub owner_address: String,
+    #[diesel(sql_type = BigInt)]
+    pub last_transaction_version: i64,
+    #[diesel(sql_type = Numeric)]
+    pub amount: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    pub derived_amount: BigDecimal,
+}
+
+impl AuditedOwnership {
+    pub fn is_divergent(&self) -> bool {
+        // BigDecimal compares values, so 1 and 1.00 are the same amount
+        self.amount != self.derived_amount
+    }
+}
+
+#[derive(QueryableByName)]
+struct Checkpoint {
+    #[diesel(sql_type = BigInt)]
+    last_success_version: i64,
+}
+
+/// Rows from a random token_data_id onwards, which walks the primary key instead of sorting the
+/// table, wrapping around to the lowest token_data_id when there aren't enough past the start.
+/// v1 token_data_ids are hashes, so the start is uniform over them. Only tokens minted in full
+/// from `$5` onwards are sampled, see the module docs.
+const AUDIT_QUERY: &str = "
+WITH start AS MATERIALIZED (
+    SELECT '0x' || md5(random()::text) || md5(random()::text) AS token_data_id
+),
+candidates AS NOT MATERIALIZED (
+    SELECT o.token_data_id, o.property_version_v1, o.owner_address, o.last_transaction_version,
+        o.amount
+    FROM current_token_ownerships_v2 o
+    WHERE o.token_standard = 'v1'
+        AND o.last_transaction_version <= $1
+        AND EXISTS (
+            SELECT 1
+            FROM token_activities_v2 m
+            JOIN token_datas_v2 d
+                ON d.transaction_version = m.transaction_version
+                AND d.token_data_id = m.token_data_id
+            WHERE m.token_data_id = o.token_data_id
+                AND m.type = $6
+                AND m.transaction_success
+                AND m.transaction_version >= $5
+                AND m.transaction_version <= $1
+                AND d.supply = m.token_amount
+        )
+),
+after_start AS (
+    SELECT c.*
+    FROM candidates c
+    WHERE c.token_data_id >= (SELECT token_data_id FROM start)
+    ORDER BY c.token_data_id
+    LIMIT $2
+),
+sample AS (
+    SELECT * FROM after_start
+    UNION ALL
+    (
+        SELECT c.*
+        FROM candidates c
+        WHERE c.token_data_id < (SELECT token_data_id FROM start)
+        ORDER BY c.token_data_id
+        LIMIT $2 - (SELECT COUNT(*) FROM after_start)
+    )
+)
+SELECT s.token_data_id, s.property_version_v1, s.owner_address, s.last_transaction_version,
+    s.amount,
+    COALESCE((
+        SELECT SUM(CASE WHEN a.type = $3 THEN a.token_amount ELSE -a.token_amount END)
+        FROM token_activities_v2 a
+        WHERE a.token_data_id = s.token_data_id
+            AND a.property_version_v1 = s.property_version_v1
+            AND a.event_account_address = s.owner_address
+            AND a.type IN ($3, $4)
+            AND a.transaction_success
+            AND a.transaction_version <= $1
+    ), 0) AS derived_amount
+FROM sample s";
+
+/// Recomputes a sample of current ownerships from their activities, and logs and counts the ones
+/// that don't match. Only tokens minted in full at or after `starting_version` are sampled.
+/// Returns the sample.
+pub async fn audit_current_token_ownerships(
+    pool: &ArcDbPool,
+    processor_name: &str,
+    sample_size: i64,
+    starting_version: u64,
+) -> anyhow::Result<Vec<AuditedOwnership>> {
+    let mut conn = pool.get().await?;
+    let checkpoint =
+        sql_query("SELECT last_success_version FROM processor_status WHERE processor = $1")
+            .bind::<Text, _>(processor_name)
+            .get_result::<Checkpoint>(&mut conn)
+            .await
+            .optional()?;
+    let Some(checkpoint) = checkpoint else {
+        // Nothing processed yet
+        return Ok(vec![]);
+    };
+
+    let ownerships = sql_query(AUDIT_QUERY)
+        .bind::<BigInt, _>(checkpoint.last_success_version)
+        .bind::<BigInt, _>(sample_size)
+        .bind::<Text, _>(TokenEventKind::DepositTokenEvent.event_type())
+        .bind::<Text, _>(TokenEventKind::WithdrawTokenEvent.event_type())
+        .bind::<BigInt, _>(starting_version as i64)
+        .bind::<Text, _>(TokenEventKind::MintTokenEvent.event_type())
+        .load::<AuditedOwnership>(&mut conn)
+        .await?;
+    if (ownerships.len() as i64) < sample_size {
+        warn!(
+            processor_name = processor_name,
+            sample_size,
+            checked_rows = ownerships.len(),
+            starting_version,
+            "[Parser] Fewer current_token_ownerships_v2 rows can be audited than sample_size"
+        );
+    }
+    let mut divergent_rows = 0;
+    for ownership in ownerships.iter().filter(|o| o.is_divergent()) {
+        divergent_rows += 1;
+        warn!(
+            processor_name = processor_name,
+            token_data_id = ownership.token_data_id,
+            property_version_v1 = %ownership.property_version_v1,
+            owner_address = ownership.owner_address,
+            last_transaction_version = ownership.last_transaction_version,
+            amount = %ownership.amount,
+            derived_amount = %ownership.derived_amount,
+            "[Parser] current_token_ownerships_v2 row doesn't match its activities"
+        );
+    }
+    OWNERSHIP_AUDIT_ROWS_COUNT
+        .with_label_values(&[processor_name, "match"])
+        .inc_by((ownerships.len() - divergent_rows) as u64);
+    OWNERSHIP_AUDIT_ROWS_COUNT
+        .with_label_values(&[processor_name, "divergent"])
+        .inc_by(divergent_rows as u64);
+    info!(
+        processor_name = processor_name,
+        checkpoint_version = checkpoint.last_success_version,
+        starting_version,
+        checked_rows = ownerships.len(),
+        divergent_rows,
+        "[Parser] Audited current_token_ownerships_v2"
+    );
+    Ok(ownerships)
+}
+
+/// Audits every `interval_secs` until the processor exits. Failures are logged and retried on the
+/// next tick.
+pub async fn create_ownership_audit_loop(
+    pool: ArcDbPool,
+    processor_name: String,
+    sample_size: i64,
+    starting_version: u64,
+    interval_secs: u64,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) =
+            audit_current_token_ownerships(&pool, &processor_name, sample_size, starting_version)
+                .await
+                .context("Failed to audit current_token_ownerships_v2")
+        {
+            error!(
+                processor_name = processor_name,
+                error = ?e,
+                "[Parser] Error auditing current-state rows"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_is_divergent() {
+        let ownership = AuditedOwnership {
+            token_data_id: "0x1".to_string(),
+            property_version_v1: BigDecimal::from(0),
+            owner_address: "0x2".to_string(),
+            last_transaction_version: 10,
+            amount: BigDecimal::from(1),
+            derived_amount: BigDecimal::from_str("1.00").unwrap(),
+        };
+        assert!(!ownership.is_divergent());
+        assert!(AuditedOwnership {
+            derived_amount: BigDecimal::from(2),
+            ..ownership
+        }
+        .is_divergent());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = OwnershipAuditConfig {
+            sample_size: DEFAULT_AUDIT_SAMPLE_SIZE,
+            interval_secs: None,
+        };
+        assert!(config.validate().is_ok());
+        assert!(OwnershipAuditConfig {
+            sample_size: 0,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(OwnershipAuditConfig {
+            interval_secs: Some(0),
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::util::{parse_timestamp, time_diff_since_pb_timestamp_in_secs};
+use aptos_protos::util::timestamp::Timestamp;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+pub const DEFAULT_PROGRESS_FILE_INTERVAL_SECS: u64 = 10;
+
+/// Latest (version, timestamp) committed by the processor, shared between the gap detector loop
+/// which moves it and the progress file task which reads it
+#[derive(Clone, Debug, Default)]
+pub struct ProgressTracker(Arc<Mutex<Option<(u64, Option<Timestamp>)>>>);
+
+impl ProgressTracker {
+    pub fn update(&self, version: u64, last_transaction_timestamp: Option<Timestamp>) {
+        *self.0.lock().unwrap() = Some((version, last_transaction_timestamp));
+    }
+
+    pub fn latest(&self) -> Option<(u64, Option<Timestamp>)> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// What gets written to the progress file, for tooling that can't scrape the metrics endpoint
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Progress {
+    pub processor_name: String,
+    pub last_processed_version: Option<u64>,
+    pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    // Seconds between the last transaction's timestamp and now
+    pub lag_in_secs: Option<f64>,
+    // Versions processed per second since the previous write
+    pub tps: f64,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Writes to a temp file next to `path` and renames it over `path`, so readers never see a
+/// partially written file
+pub fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_file_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_file_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_file_name);
+    std::fs::write(&tmp_path, contents)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Rewrites the progress file every `interval_secs` until the processor exits
+pub async fn create_progress_file_loop(
+    processor_name: String,
+    path: PathBuf,
+    interval_secs: u64,
+    tracker: ProgressTracker,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    let mut previous: Option<(u64, Instant)> = None;
+    loop {
+        interval.tick().await;
+        let latest = tracker.latest();
+        let now = Instant::now();
+        let last_processed_version = latest.as_ref().map(|(version, _)| *version);
+        let tps = match (previous, last_processed_version) {
+            (Some((previous_version, previous_time)), Some(version)) => {
+                let elapsed = now.duration_since(previous_time).as_secs_f64();
+                if elapsed > 0.0 {
+                    version.saturating_sub(previous_version) as f64 / elapsed
+                } else {
+                    0.0
+                }
+            },
+            _ => 0.0,
+        };
+        if let Some(version) = last_processed_version {
+            previous = Some((version, now));
+        }
+        let timestamp = latest.and_then(|(_, timestamp)| timestamp);
+        let progress = Progress {
+            processor_name: processor_name.clone(),
+            last_processed_version,
+            last_transaction_timestamp: last_processed_version.and_then(|version| {
+                timestamp
+                    .as_ref()
+                    .map(|t| parse_timestamp(t, version as i64))
+            }),
+            lag_in_secs: timestamp.as_ref().map(time_diff_since_pb_timestamp_in_secs),
+            tps,
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        let contents = serde_json::to_vec(&progress).unwrap();
+        if let Err(e) = write_atomically(&path, &contents) {
+            error!(
+                processor_name = processor_name,
+                file = %path.display(),
+                error = ?e,
+                "[Parser] Failed to write progress file"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically() {
+        let dir = std::env::temp_dir().join(format!("progress_file_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("progress.json");
+
+        write_atomically(&path, b"{\"a\":1}").unwrap();
+        write_atomically(&path, b"{\"a\":2}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"a\":2}");
+        assert!(!dir.join("progress.json.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_progress_tracker() {
+        let tracker = ProgressTracker::default();
+        assert_eq!(tracker.latest(), None);
+        tracker.clone().update(
+            42,
+            Some(Timestamp {
+                seconds: 1,
+                nanos: 0,
+            }),
+        );
+        assert_eq!(
+            tracker.latest(),
+            Some((
+                42,
+                Some(Timestamp {
+                    seconds: 1,
+                    nanos: 0
+                })
+            ))
+        );
+    }
+}
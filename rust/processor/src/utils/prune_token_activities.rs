@@ -0,0 +1,249 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in background task deleting old token_activities rows, for deployments that only need
+//! recent history. Current-state tables are never touched.
+//!
+//! How much is kept is measured from the processor's own progress in processor_status, not from
+//! the wall clock, so a backfill doesn't delete rows as soon as it writes them. Rows go in batches
+//! of `batch_size`, oldest version first, each batch in its own statement so locks stay short.
+
+use crate::utils::{counters::PRUNED_ROWS_COUNT, database::ArcDbPool};
+use anyhow::Context;
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Nullable, Text, Timestamp},
+    OptionalExtension, QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info};
+
+pub const DEFAULT_PRUNING_BATCH_SIZE: i64 = 10_000;
+pub const DEFAULT_PRUNING_INTERVAL_SECS: u64 = 300;
+
+const TABLE_NAME: &str = "token_activities";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenActivitiesPruningConfig {
+    // Keep the rows within this many versions of the last processed version
+    pub retain_versions: Option<u64>,
+    // Keep the rows within this many seconds of the last processed transaction's timestamp
+    pub retain_secs: Option<u64>,
+    // Rows deleted per statement
+    #[serde(default = "TokenActivitiesPruningConfig::default_batch_size")]
+    pub batch_size: i64,
+    #[serde(default = "TokenActivitiesPruningConfig::default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl TokenActivitiesPruningConfig {
+    pub const fn default_batch_size() -> i64 {
+        DEFAULT_PRUNING_BATCH_SIZE
+    }
+
+    pub const fn default_interval_secs() -> u64 {
+        DEFAULT_PRUNING_INTERVAL_SECS
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.retain_versions.is_some() || self.retain_secs.is_some(),
+            "Pruning token_activities needs retain_versions or retain_secs"
+        );
+        anyhow::ensure!(
+            self.batch_size > 0,
+            "Pruning token_activities needs a positive batch_size"
+        );
+        anyhow::ensure!(
+            self.interval_secs > 0,
+            "Pruning token_activities needs a positive interval_secs"
+        );
+        Ok(())
+    }
+}
+
+#[derive(QueryableByName)]
+struct ProcessorProgress {
+    #[diesel(sql_type = BigInt)]
+    last_success_version: i64,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(QueryableByName)]
+struct Version {
+    #[diesel(sql_type = BigInt)]
+    transaction_version: i64,
+}
+
+/// First version to keep. A row is kept as long as any of the retention settings keeps it.
+fn keep_from_version(
+    last_success_version: i64,
+    retain_versions: Option<u64>,
+    retained_time_start_version: Option<i64>,
+) -> i64 {
+    let retained_versions_start_version =
+        retain_versions.map(|retain| last_success_version.saturating_sub(retain as i64));
+    [retained_versions_start_version, retained_time_start_version]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(i64::MIN)
+}
+
+/// Deletes the rows outside of the retention settings. Returns how many were deleted.
+async fn prune_once(
+    pool: &ArcDbPool,
+    processor_name: &str,
+    config: &TokenActivitiesPruningConfig,
+) -> anyhow::Result<usize> {
+    let progress = sql_query(
+        "SELECT last_success_version, last_transaction_timestamp FROM processor_status WHERE processor = $1",
+    )
+    .bind::<Text, _>(processor_name)
+    .get_result::<ProcessorProgress>(&mut pool.get().await?)
+    .await
+    .optional()?;
+    let Some(progress) = progress else {
+        // Nothing processed yet
+        return Ok(0);
+    };
+
+    let retained_time_start_version = match config.retain_secs {
+        Some(retain_secs) => {
+            let Some(last_transaction_timestamp) = progress.last_transaction_timestamp else {
+                return Ok(0);
+            };
+            let horizon =
+                last_transaction_timestamp - chrono::Duration::seconds(retain_secs as i64);
+            // Walks the version index from the oldest row, so it only reads what gets deleted
+            let first_retained = sql_query(format!(
+                "SELECT transaction_version FROM {} WHERE transaction_timestamp >= $1
+                ORDER BY transaction_version LIMIT 1",
+                TABLE_NAME
+            ))
+            .bind::<Timestamp, _>(horizon)
+            .get_result::<Version>(&mut pool.get().await?)
+            .await
+            .optional()?;
+            Some(
+                first_retained
+                    .map(|version| version.transaction_version)
+                    .unwrap_or(progress.last_success_version + 1),
+            )
+        },
+        None => None,
+    };
+    let keep_from = keep_from_version(
+        progress.last_success_version,
+        config.retain_versions,
+        retained_time_start_version,
+    );
+
+    let delete_query = format!(
+        "DELETE FROM {table} WHERE ctid = ANY(ARRAY(
+            SELECT ctid FROM {table} WHERE transaction_version < $1
+            ORDER BY transaction_version LIMIT $2
+        ))",
+        table = TABLE_NAME
+    );
+    let mut pruned_rows = 0;
+    loop {
+        let deleted = sql_query(&delete_query)
+            .bind::<BigInt, _>(keep_from)
+            .bind::<BigInt, _>(config.batch_size)
+            .execute(&mut pool.get().await?)
+            .await?;
+        pruned_rows += deleted;
+        PRUNED_ROWS_COUNT
+            .with_label_values(&[processor_name, TABLE_NAME])
+            .inc_by(deleted as u64);
+        if (deleted as i64) < config.batch_size {
+            break;
+        }
+    }
+    if pruned_rows > 0 {
+        info!(
+            processor_name = processor_name,
+            table = TABLE_NAME,
+            keep_from_version = keep_from,
+            pruned_rows,
+            "[Parser] Pruned old rows"
+        );
+    }
+    Ok(pruned_rows)
+}
+
+/// Prunes token_activities every `interval_secs` until the processor exits. Failures are logged
+/// and retried on the next tick.
+pub async fn create_token_activities_pruning_loop(
+    pool: ArcDbPool,
+    processor_name: String,
+    config: TokenActivitiesPruningConfig,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+        if let Err(e) = prune_once(&pool, &processor_name, &config)
+            .await
+            .context("Failed to prune token_activities")
+        {
+            error!(
+                processor_name = processor_name,
+                error = ?e,
+                "[Parser] Error pruning rows"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_from_version() {
+        assert_eq!(keep_from_version(1_000, Some(100), None), 900);
+        assert_eq!(keep_from_version(1_000, None, Some(950)), 950);
+        // Rows are kept if either setting keeps them
+        assert_eq!(keep_from_version(1_000, Some(100), Some(950)), 900);
+        assert_eq!(keep_from_version(1_000, Some(10), Some(950)), 950);
+        // Not far enough along to delete anything
+        assert_eq!(keep_from_version(50, Some(100), None), -50);
+        assert_eq!(keep_from_version(1_000, None, None), i64::MIN);
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = TokenActivitiesPruningConfig {
+            retain_versions: None,
+            retain_secs: None,
+            batch_size: DEFAULT_PRUNING_BATCH_SIZE,
+            interval_secs: DEFAULT_PRUNING_INTERVAL_SECS,
+        };
+        assert!(config.validate().is_err());
+        assert!(TokenActivitiesPruningConfig {
+            retain_secs: Some(86_400),
+            ..config.clone()
+        }
+        .validate()
+        .is_ok());
+        assert!(TokenActivitiesPruningConfig {
+            retain_secs: Some(86_400),
+            batch_size: 0,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(TokenActivitiesPruningConfig {
+            retain_secs: Some(86_400),
+            interval_secs: 0,
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+}
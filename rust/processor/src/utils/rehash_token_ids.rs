@@ -0,0 +1,455 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rewrites token_data_id_hash and collection_data_id_hash of the rows already in the v1 token
+//! tables from one `IdHashScheme` to another, independent of the stream. Run once after changing
+//! `token_id_hash_scheme` so rows written before and after the change keep sharing ids. The
+//! stored names are left as they are, e.g. not converted to NFC for `IdHashScheme::Sha256Nfc`.
+//!
+//! The hashes are recomputed from the creator_address, collection_name and name stored on each
+//! row, and a row is only rewritten if those reproduce its hash under the old scheme. Rows whose
+//! names were truncated to `NAME_LENGTH`, or token_activities rows written without the
+//! denormalized names, can't be verified and are left alone and counted. The `0x` prefixed
+//! token_data_id / collection_id of v1 tokens in the v2 tables aren't covered.
+
+use crate::{
+    db::common::models::token_models::token_utils::{
+        CollectionDataIdType, IdHashScheme, TokenDataIdType,
+    },
+    utils::database::{execute_in_transaction, ArcDbPool},
+};
+use ahash::AHashMap;
+use diesel::{
+    sql_query,
+    sql_types::{Array, BigInt, Nullable, Text},
+    QueryableByName,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, RunQueryDsl};
+use tracing::{info, warn};
+
+/// Number of versions read and rewritten per transaction
+const VERSION_RANGE_SIZE: i64 = 100_000;
+
+#[derive(Clone, Copy, Debug)]
+enum HashedId {
+    TokenData,
+    Collection,
+}
+
+struct HashColumn {
+    column: &'static str,
+    id: HashedId,
+    // Rest of the primary key when the hash is part of it. Set on current tables, where the live
+    // processor may already have written the same row under the new hash
+    current_key: Option<&'static [&'static str]>,
+}
+
+struct HashedTable {
+    table: &'static str,
+    version_column: &'static str,
+    // Tables without one get a temporary index for the job, instead of a scan per version range
+    has_version_index: bool,
+    columns: &'static [HashColumn],
+}
+
+const TOKEN_DATA_ID_HASH: HashColumn = HashColumn {
+    column: "token_data_id_hash",
+    id: HashedId::TokenData,
+    current_key: None,
+};
+
+const COLLECTION_DATA_ID_HASH: HashColumn = HashColumn {
+    column: "collection_data_id_hash",
+    id: HashedId::Collection,
+    current_key: None,
+};
+
+const HASHED_TABLES: &[HashedTable] = &[
+    HashedTable {
+        table: "token_activities",
+        version_column: "transaction_version",
+        has_version_index: true,
+        columns: &[TOKEN_DATA_ID_HASH, COLLECTION_DATA_ID_HASH],
+    },
+    HashedTable {
+        table: "tokens",
+        version_column: "transaction_version",
+        has_version_index: false,
+        columns: &[TOKEN_DATA_ID_HASH, COLLECTION_DATA_ID_HASH],
+    },
+    HashedTable {
+        table: "token_datas",
+        version_column: "transaction_version",
+        has_version_index: false,
+        columns: &[TOKEN_DATA_ID_HASH, COLLECTION_DATA_ID_HASH],
+    },
+    HashedTable {
+        table: "token_ownerships",
+        version_column: "transaction_version",
+        has_version_index: false,
+        columns: &[TOKEN_DATA_ID_HASH, COLLECTION_DATA_ID_HASH],
+    },
+    HashedTable {
+        table: "collection_datas",
+        version_column: "transaction_version",
+        has_version_index: false,
+        columns: &[COLLECTION_DATA_ID_HASH],
+    },
+    HashedTable {
+        table: "current_token_datas",
+        version_column: "last_transaction_version",
+        has_version_index: false,
+        columns: &[
+            HashColumn {
+                current_key: Some(&[]),
+                ..TOKEN_DATA_ID_HASH
+            },
+            COLLECTION_DATA_ID_HASH,
+        ],
+    },
+    HashedTable {
+        table: "current_token_ownerships",
+        version_column: "last_transaction_version",
+        has_version_index: false,
+        columns: &[
+            HashColumn {
+                current_key: Some(&["property_version", "owner_address"]),
+                ..TOKEN_DATA_ID_HASH
+            },
+            COLLECTION_DATA_ID_HASH,
+        ],
+    },
+    HashedTable {
+        table: "current_token_pending_claims",
+        version_column: "last_transaction_version",
+        has_version_index: false,
+        columns: &[
+            HashColumn {
+                current_key: Some(&["property_version", "from_address", "to_address"]),
+                ..TOKEN_DATA_ID_HASH
+            },
+            COLLECTION_DATA_ID_HASH,
+        ],
+    },
+    HashedTable {
+        table: "current_collection_datas",
+        version_column: "last_transaction_version",
+        has_version_index: false,
+        columns: &[HashColumn {
+            current_key: Some(&[]),
+            ..COLLECTION_DATA_ID_HASH
+        }],
+    },
+];
+
+#[derive(QueryableByName)]
+struct MaxVersion {
+    #[diesel(sql_type = Nullable<BigInt>)]
+    max_version: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct IdRow {
+    #[diesel(sql_type = Text)]
+    stored_hash: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    creator_address: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    collection_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    name: Option<String>,
+}
+
+impl IdRow {
+    fn hash_with(&self, id: HashedId, scheme: IdHashScheme) -> Option<String> {
+        let creator = self.creator_address.clone()?;
+        let collection = self.collection_name.clone()?;
+        Some(match id {
+            HashedId::TokenData => {
+                TokenDataIdType::new(creator, collection, self.name.clone()?).to_hash_with(scheme)
+            },
+            HashedId::Collection => {
+                CollectionDataIdType::new(creator, collection).to_hash_with(scheme)
+            },
+        })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Rehash {
+    AlreadyMigrated,
+    Rewrite(String),
+    Unverifiable,
+}
+
+/// Compares the stored hash against the row's id hashed under both schemes, so rerunning the job
+/// after an interruption skips what's already done
+fn rehash(stored_hash: &str, from_hash: Option<String>, to_hash: Option<String>) -> Rehash {
+    match (from_hash, to_hash) {
+        (_, Some(to_hash)) if to_hash == stored_hash => Rehash::AlreadyMigrated,
+        (Some(from_hash), Some(to_hash)) if from_hash == stored_hash => Rehash::Rewrite(to_hash),
+        _ => Rehash::Unverifiable,
+    }
+}
+
+#[derive(Debug, Default)]
+struct RehashCounts {
+    rewritten_rows: usize,
+    stale_current_rows: usize,
+    unverifiable_ids: usize,
+}
+
+/// Reads every v1 token table in version order and rewrites its id hashes from `from` to
+/// `IdHashScheme::current()`
+pub async fn rehash_token_ids(pool: ArcDbPool, from: IdHashScheme) -> anyhow::Result<()> {
+    let to = IdHashScheme::current();
+    let mut total_unverifiable_ids = 0;
+    for table in HASHED_TABLES {
+        let counts = rehash_table(pool.clone(), table, from, to).await?;
+        info!(
+            table = table.table,
+            from = ?from,
+            to = ?to,
+            rewritten_rows = counts.rewritten_rows,
+            stale_current_rows = counts.stale_current_rows,
+            unverifiable_ids = counts.unverifiable_ids,
+            "[Parser] Finished rehashing token ids"
+        );
+        total_unverifiable_ids += counts.unverifiable_ids;
+    }
+    if total_unverifiable_ids > 0 {
+        warn!(
+            unverifiable_ids = total_unverifiable_ids,
+            "[Parser] Some token ids couldn't be recomputed from their rows and kept the old hash"
+        );
+    }
+    Ok(())
+}
+
+async fn rehash_table(
+    pool: ArcDbPool,
+    table: &HashedTable,
+    from: IdHashScheme,
+    to: IdHashScheme,
+) -> anyhow::Result<RehashCounts> {
+    let mut counts = RehashCounts::default();
+    let max_version = sql_query(format!(
+        "SELECT MAX({}) AS max_version FROM {}",
+        table.version_column, table.table
+    ))
+    .get_result::<MaxVersion>(&mut pool.get().await?)
+    .await?
+    .max_version;
+    let Some(max_version) = max_version else {
+        info!(
+            table = table.table,
+            "[Parser] Table is empty, nothing to rehash"
+        );
+        return Ok(counts);
+    };
+
+    let version_index = format!("{}_rehash_version_index", table.table);
+    if !table.has_version_index {
+        info!(
+            table = table.table,
+            "[Parser] Creating a temporary version index for rehashing"
+        );
+        sql_query(format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+            version_index, table.table, table.version_column
+        ))
+        .execute(&mut pool.get().await?)
+        .await?;
+    }
+
+    let mut start_version = 0;
+    while start_version <= max_version {
+        let end_version = start_version + VERSION_RANGE_SIZE;
+        for column in table.columns {
+            let select_query = format!(
+                "SELECT DISTINCT {column} AS stored_hash, creator_address, collection_name, {name} AS name
+                FROM {table} WHERE {version} >= $1 AND {version} < $2",
+                column = column.column,
+                name = match column.id {
+                    HashedId::TokenData => "name",
+                    HashedId::Collection => "NULL::text",
+                },
+                table = table.table,
+                version = table.version_column,
+            );
+            let rows: Vec<IdRow> = sql_query(select_query)
+                .bind::<BigInt, _>(start_version)
+                .bind::<BigInt, _>(end_version)
+                .load(&mut pool.get().await?)
+                .await?;
+
+            let mut new_hashes = AHashMap::new();
+            for row in &rows {
+                match rehash(
+                    &row.stored_hash,
+                    row.hash_with(column.id, from),
+                    row.hash_with(column.id, to),
+                ) {
+                    Rehash::AlreadyMigrated => {},
+                    Rehash::Rewrite(new_hash) => {
+                        new_hashes.insert(row.stored_hash.clone(), new_hash);
+                    },
+                    Rehash::Unverifiable => counts.unverifiable_ids += 1,
+                }
+            }
+            if new_hashes.is_empty() {
+                continue;
+            }
+            let (old_hashes, new_hashes): (Vec<String>, Vec<String>) =
+                new_hashes.into_iter().unzip();
+            let (stale_rows, rewritten_rows) = rewrite_hashes(
+                pool.clone(),
+                table,
+                column,
+                old_hashes,
+                new_hashes,
+                start_version,
+                end_version,
+            )
+            .await?;
+            counts.stale_current_rows += stale_rows;
+            counts.rewritten_rows += rewritten_rows;
+        }
+        info!(
+            table = table.table,
+            start_version,
+            end_version = end_version.min(max_version + 1) - 1,
+            max_version,
+            rewritten_rows = counts.rewritten_rows,
+            "[Parser] Rehashed token ids"
+        );
+        start_version = end_version;
+    }
+
+    if !table.has_version_index {
+        sql_query(format!("DROP INDEX IF EXISTS {}", version_index))
+            .execute(&mut pool.get().await?)
+            .await?;
+    }
+    Ok(counts)
+}
+
+/// Moves the range's rows from the old to the new hashes in one transaction. On current tables a
+/// row that already exists under the new hash was written by the processor after the scheme
+/// change, so it's newer and the old row is dropped instead.
+async fn rewrite_hashes(
+    pool: ArcDbPool,
+    table: &HashedTable,
+    column: &HashColumn,
+    old_hashes: Vec<String>,
+    new_hashes: Vec<String>,
+    start_version: i64,
+    end_version: i64,
+) -> anyhow::Result<(usize, usize)> {
+    let range_filter = format!(
+        "{table}.{column} = m.old_hash AND {table}.{version} >= $3 AND {table}.{version} < $4",
+        table = table.table,
+        column = column.column,
+        version = table.version_column,
+    );
+    let delete_query = column.current_key.map(|current_key| {
+        let same_key = current_key
+            .iter()
+            .map(|key| format!(" AND migrated.{key} = {}.{key}", table.table))
+            .collect::<String>();
+        format!(
+            "DELETE FROM {table} USING unnest($1::text[], $2::text[]) AS m(old_hash, new_hash)
+            WHERE {range_filter} AND EXISTS (
+                SELECT 1 FROM {table} AS migrated WHERE migrated.{column} = m.new_hash{same_key}
+            )",
+            table = table.table,
+            column = column.column,
+        )
+    });
+    let update_query = format!(
+        "UPDATE {table} SET {column} = m.new_hash
+        FROM unnest($1::text[], $2::text[]) AS m(old_hash, new_hash) WHERE {range_filter}",
+        table = table.table,
+        column = column.column,
+    );
+
+    Ok(execute_in_transaction(pool, move |conn| {
+        async move {
+            let stale_rows = match delete_query {
+                Some(delete_query) => {
+                    sql_query(delete_query)
+                        .bind::<Array<Text>, _>(&old_hashes)
+                        .bind::<Array<Text>, _>(&new_hashes)
+                        .bind::<BigInt, _>(start_version)
+                        .bind::<BigInt, _>(end_version)
+                        .execute(conn)
+                        .await?
+                },
+                None => 0,
+            };
+            let rewritten_rows = sql_query(update_query)
+                .bind::<Array<Text>, _>(&old_hashes)
+                .bind::<Array<Text>, _>(&new_hashes)
+                .bind::<BigInt, _>(start_version)
+                .bind::<BigInt, _>(end_version)
+                .execute(conn)
+                .await?;
+            Ok((stale_rows, rewritten_rows))
+        }
+        .scope_boxed()
+    })
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rehash() {
+        let old = || Some("old".to_string());
+        let new = || Some("new".to_string());
+        assert_eq!(
+            rehash("old", old(), new()),
+            Rehash::Rewrite("new".to_string())
+        );
+        assert_eq!(rehash("new", old(), new()), Rehash::AlreadyMigrated);
+        // Same scheme on both sides, nothing to do
+        assert_eq!(rehash("new", new(), new()), Rehash::AlreadyMigrated);
+        // Truncated names hash to something else under both schemes
+        assert_eq!(rehash("other", old(), new()), Rehash::Unverifiable);
+        // Names weren't written on the row
+        assert_eq!(rehash("old", None, None), Rehash::Unverifiable);
+    }
+
+    #[test]
+    fn test_id_row_hash_matches_token_utils() {
+        let row = IdRow {
+            stored_hash: String::new(),
+            creator_address: Some(
+                "0x0000000000000000000000000000000000000000000000000000000000000001".to_string(),
+            ),
+            collection_name: Some("collection".to_string()),
+            name: Some("token".to_string()),
+        };
+        assert_eq!(
+            row.hash_with(HashedId::TokenData, IdHashScheme::current()),
+            Some(
+                TokenDataIdType::new(
+                    "0x1".to_string(),
+                    "collection".to_string(),
+                    "token".to_string()
+                )
+                .to_hash()
+            )
+        );
+        assert_eq!(
+            row.hash_with(HashedId::Collection, IdHashScheme::current()),
+            Some(CollectionDataIdType::new("0x1".to_string(), "collection".to_string()).to_hash())
+        );
+        assert_eq!(
+            IdRow { name: None, ..row }.hash_with(HashedId::TokenData, IdHashScheme::current()),
+            None
+        );
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_protos::transaction::v1::Transaction;
+use std::collections::BTreeMap;
+
+/// Holds up to `capacity` out of order transactions so that they can be released in version order.
+/// Transactions are released as soon as they continue the expected sequence, so an in-order stream
+/// passes straight through. Once the buffer is over capacity the lowest versions are released even
+/// if there's still a hole before them; the gap check downstream will catch that.
+pub struct ReorderBuffer {
+    capacity: usize,
+    next_version: u64,
+    pending: BTreeMap<u64, Transaction>,
+}
+
+impl ReorderBuffer {
+    pub fn new(capacity: usize, starting_version: u64) -> Self {
+        Self {
+            capacity,
+            next_version: starting_version,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Adds transactions to the buffer and returns the ones that can be released, in version order.
+    pub fn push(&mut self, transactions: Vec<Transaction>) -> Vec<Transaction> {
+        for txn in transactions {
+            self.pending.insert(txn.version, txn);
+        }
+        let mut ready = vec![];
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() != self.next_version && self.pending.len() <= self.capacity {
+                break;
+            }
+            let txn = entry.remove();
+            self.next_version = txn.version + 1;
+            ready.push(txn);
+        }
+        ready
+    }
+
+    /// Releases everything left in the buffer, in version order.
+    pub fn flush(&mut self) -> Vec<Transaction> {
+        let ready: Vec<Transaction> = std::mem::take(&mut self.pending).into_values().collect();
+        if let Some(last) = ready.last() {
+            self.next_version = last.version + 1;
+        }
+        ready
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txns(versions: &[u64]) -> Vec<Transaction> {
+        versions
+            .iter()
+            .map(|version| Transaction {
+                version: *version,
+                ..Transaction::default()
+            })
+            .collect()
+    }
+
+    fn versions(transactions: &[Transaction]) -> Vec<u64> {
+        transactions.iter().map(|t| t.version).collect()
+    }
+
+    #[test]
+    fn test_in_order_passes_through() {
+        let mut buffer = ReorderBuffer::new(5, 10);
+        assert_eq!(versions(&buffer.push(txns(&[10, 11, 12]))), vec![
+            10, 11, 12
+        ]);
+        assert_eq!(versions(&buffer.push(txns(&[13]))), vec![13]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_reorders_within_window() {
+        let mut buffer = ReorderBuffer::new(5, 0);
+        assert_eq!(versions(&buffer.push(txns(&[0, 2, 3]))), vec![0]);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(versions(&buffer.push(txns(&[1, 4]))), vec![1, 2, 3, 4]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_releases_lowest_when_over_capacity() {
+        let mut buffer = ReorderBuffer::new(2, 0);
+        assert!(buffer.push(txns(&[2, 3])).is_empty());
+        assert_eq!(versions(&buffer.push(txns(&[4]))), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_zero_capacity_never_holds() {
+        let mut buffer = ReorderBuffer::new(0, 0);
+        assert_eq!(versions(&buffer.push(txns(&[1, 0, 2]))), vec![0, 1, 2]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut buffer = ReorderBuffer::new(5, 0);
+        assert!(buffer.push(txns(&[3, 1])).is_empty());
+        assert_eq!(versions(&buffer.flush()), vec![1, 3]);
+        assert!(buffer.is_empty());
+        assert_eq!(versions(&buffer.push(txns(&[4]))), vec![4]);
+    }
+}
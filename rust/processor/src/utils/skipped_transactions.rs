@@ -0,0 +1,139 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::counters::SKIPPED_TRANSACTIONS_TOTAL;
+use aptos_protos::transaction::v1::Transaction;
+use tracing::info;
+
+/// Why a transaction in a batch wasn't processed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SkipReason {
+    /// No txn_data, processors can't do anything with it
+    MissingTxnData,
+    /// Below the configured min_version
+    BelowMinVersion,
+    /// Dropped by the transaction_filter before reaching the processor
+    Filtered,
+}
+
+impl SkipReason {
+    pub const ALL: [SkipReason; 3] = [
+        SkipReason::MissingTxnData,
+        SkipReason::BelowMinVersion,
+        SkipReason::Filtered,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::MissingTxnData => "missing_txn_data",
+            SkipReason::BelowMinVersion => "below_min_version",
+            SkipReason::Filtered => "filtered",
+        }
+    }
+}
+
+/// Number of skipped transactions in one batch by reason, reported as a single summary
+#[derive(Debug, Default)]
+pub struct SkippedTransactions {
+    counts: [u64; SkipReason::ALL.len()],
+}
+
+impl SkippedTransactions {
+    pub fn add(&mut self, reason: SkipReason, count: u64) {
+        self.counts[reason as usize] += count;
+    }
+
+    pub fn count(&self, reason: SkipReason) -> u64 {
+        self.counts[reason as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Drops the transactions below `min_version` and counts the ones without txn_data, which
+    /// stay in the batch for processors to skip.
+    pub fn check_batch(transactions: &mut Vec<Transaction>, min_version: Option<u64>) -> Self {
+        let mut skipped = Self::default();
+        if let Some(min_version) = min_version {
+            let num_txns = transactions.len();
+            transactions.retain(|txn| txn.version >= min_version);
+            skipped.add(
+                SkipReason::BelowMinVersion,
+                (num_txns - transactions.len()) as u64,
+            );
+        }
+        skipped.add(
+            SkipReason::MissingTxnData,
+            transactions
+                .iter()
+                .filter(|txn| txn.txn_data.is_none())
+                .count() as u64,
+        );
+        skipped
+    }
+
+    /// Adds to `skipped_transactions_total` and logs the batch's summary, if anything was skipped
+    pub fn report(&self, processor_name: &str, start_version: u64, end_version: u64) {
+        if self.total() == 0 {
+            return;
+        }
+        for reason in SkipReason::ALL {
+            let count = self.count(reason);
+            if count > 0 {
+                SKIPPED_TRANSACTIONS_TOTAL
+                    .with_label_values(&[processor_name, reason.as_str()])
+                    .inc_by(count);
+            }
+        }
+        info!(
+            processor_name = processor_name,
+            start_version,
+            end_version,
+            missing_txn_data = self.count(SkipReason::MissingTxnData),
+            below_min_version = self.count(SkipReason::BelowMinVersion),
+            filtered = self.count(SkipReason::Filtered),
+            total = self.total(),
+            "[Parser] Skipped transactions in batch"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{transaction::TxnData, UserTransaction};
+
+    fn txn(version: u64, has_txn_data: bool) -> Transaction {
+        Transaction {
+            version,
+            txn_data: has_txn_data.then(|| TxnData::User(UserTransaction::default())),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_check_batch() {
+        let mut transactions = vec![
+            txn(8, false),
+            txn(9, true),
+            txn(10, false),
+            txn(11, true),
+            txn(12, false),
+        ];
+        let skipped = SkippedTransactions::check_batch(&mut transactions, Some(10));
+        assert_eq!(
+            transactions.iter().map(|t| t.version).collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(skipped.count(SkipReason::BelowMinVersion), 2);
+        assert_eq!(skipped.count(SkipReason::MissingTxnData), 2);
+        assert_eq!(skipped.count(SkipReason::Filtered), 0);
+        assert_eq!(skipped.total(), 4);
+
+        let mut transactions = vec![txn(1, true)];
+        let skipped = SkippedTransactions::check_batch(&mut transactions, None);
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(skipped.total(), 0);
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Finds the transactions behind batch latency spikes. Each transaction's parse time is checked
+//! against a threshold; the ones over it are counted in `indexer_processor_slow_transactions_count`
+//! and one in `log_every` is logged with its version and number of events.
+
+use crate::utils::{counters::SLOW_TRANSACTIONS_COUNT, util::get_transaction_event_count};
+use aptos_protos::transaction::v1::Transaction;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::warn;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlowTransactionLogConfig {
+    // Transactions that take longer than this to parse are counted as slow
+    pub threshold_ms: u64,
+    // Log one in this many slow transactions, the rest are only counted
+    #[serde(default = "SlowTransactionLogConfig::default_log_every")]
+    pub log_every: u64,
+}
+
+impl SlowTransactionLogConfig {
+    pub const fn default_log_every() -> u64 {
+        1
+    }
+}
+
+pub struct SlowTransactionLogger {
+    processor_name: &'static str,
+    threshold: Duration,
+    log_every: u64,
+    // Slow transactions seen so far, across the processing tasks
+    num_slow: AtomicU64,
+}
+
+impl SlowTransactionLogger {
+    pub fn new(processor_name: &'static str, config: &SlowTransactionLogConfig) -> Self {
+        assert!(
+            config.log_every > 0,
+            "slow_transaction_log needs a positive log_every"
+        );
+        Self {
+            processor_name,
+            threshold: Duration::from_millis(config.threshold_ms),
+            log_every: config.log_every,
+            num_slow: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts the transaction if it took longer than the threshold, and logs it if it's sampled.
+    /// Returns whether it was logged.
+    pub fn observe(&self, txn: &Transaction, elapsed: Duration) -> bool {
+        if elapsed <= self.threshold {
+            return false;
+        }
+        SLOW_TRANSACTIONS_COUNT
+            .with_label_values(&[self.processor_name])
+            .inc();
+        if self.num_slow.fetch_add(1, Ordering::Relaxed) % self.log_every != 0 {
+            return false;
+        }
+        warn!(
+            processor_name = self.processor_name,
+            transaction_version = txn.version,
+            num_events = get_transaction_event_count(txn),
+            elapsed_ms = elapsed.as_millis() as u64,
+            threshold_ms = self.threshold.as_millis() as u64,
+            "[Parser] Slow transaction",
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observe() {
+        let config: SlowTransactionLogConfig =
+            serde_json::from_str(r#"{"threshold_ms": 10, "log_every": 2}"#).unwrap();
+        let logger = SlowTransactionLogger::new("test_processor", &config);
+        let txn = Transaction::default();
+
+        assert!(!logger.observe(&txn, Duration::from_millis(10)));
+        assert!(logger.observe(&txn, Duration::from_millis(11)));
+        assert!(!logger.observe(&txn, Duration::from_millis(11)));
+        assert!(logger.observe(&txn, Duration::from_millis(11)));
+    }
+
+    #[test]
+    fn test_default_log_every() {
+        let config: SlowTransactionLogConfig =
+            serde_json::from_str(r#"{"threshold_ms": 10}"#).unwrap();
+        assert_eq!(config.log_every, 1);
+    }
+}
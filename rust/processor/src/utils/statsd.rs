@@ -0,0 +1,269 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pushes the processor's metrics to a StatsD or DogStatsD agent over UDP, for deployments that
+//! don't scrape Prometheus. The metrics are the ones in the Prometheus registry, gathered every
+//! `flush_interval_ms`; `/metrics` keeps serving them as before.
+//!
+//! Counters are sent as the increase since the previous flush, gauges as their current value and
+//! histograms as the increase of their count and sum. Summaries and untyped metrics are skipped.
+
+use ahash::AHashMap;
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::{error, info};
+
+pub const DEFAULT_STATSD_FLUSH_INTERVAL_MS: u64 = 10_000;
+
+/// Largest datagram sent, small enough to not be fragmented on common networks
+const MAX_PACKET_SIZE: usize = 1432;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdConfig {
+    // host:port of the agent, e.g. localhost:8125
+    pub address: String,
+    // Prepended to every metric name with a dot, e.g. aptos_indexer
+    pub prefix: Option<String>,
+    // Send labels as DogStatsD tags. Otherwise their values are appended to the metric name
+    #[serde(default)]
+    pub dogstatsd_tags: bool,
+    #[serde(default = "StatsdConfig::default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+}
+
+impl StatsdConfig {
+    pub const fn default_flush_interval_ms() -> u64 {
+        DEFAULT_STATSD_FLUSH_INTERVAL_MS
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.flush_interval_ms > 0,
+            "Exporting metrics to StatsD needs a positive flush_interval_ms"
+        );
+        Ok(())
+    }
+}
+
+/// Turns gathered metrics into StatsD lines, keeping the counter values of the last flush to send
+/// increases
+#[derive(Default)]
+struct StatsdFormatter {
+    prefix: Option<String>,
+    dogstatsd_tags: bool,
+    last_values: AHashMap<String, f64>,
+}
+
+impl StatsdFormatter {
+    fn new(config: &StatsdConfig) -> Self {
+        Self {
+            prefix: config.prefix.clone(),
+            dogstatsd_tags: config.dogstatsd_tags,
+            last_values: AHashMap::new(),
+        }
+    }
+
+    fn format(&mut self, families: &[MetricFamily]) -> Vec<String> {
+        let mut lines = vec![];
+        for family in families {
+            for metric in family.get_metric() {
+                let (name, tags) = self.name_and_tags(family.get_name(), metric);
+                match family.get_field_type() {
+                    MetricType::COUNTER => {
+                        let value = metric.get_counter().get_value();
+                        self.push_increase(&mut lines, name, &tags, value);
+                    },
+                    MetricType::GAUGE => {
+                        let value = metric.get_gauge().get_value();
+                        // A signed gauge value is read as a change, so negative values are sent
+                        // as a reset to 0 followed by the decrease
+                        if value < 0.0 {
+                            lines.push(format!("{}:0|g{}", name, tags));
+                        }
+                        lines.push(format!("{}:{}|g{}", name, value, tags));
+                    },
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        let count = histogram.get_sample_count() as f64;
+                        let sum = histogram.get_sample_sum();
+                        self.push_increase(&mut lines, format!("{}.count", name), &tags, count);
+                        self.push_increase(&mut lines, format!("{}.sum", name), &tags, sum);
+                    },
+                    MetricType::SUMMARY | MetricType::UNTYPED => {},
+                }
+            }
+        }
+        lines
+    }
+
+    fn name_and_tags(&self, family_name: &str, metric: &Metric) -> (String, String) {
+        let mut name = match &self.prefix {
+            Some(prefix) => format!("{}.{}", prefix, family_name),
+            None => family_name.to_string(),
+        };
+        let labels = metric.get_label();
+        if self.dogstatsd_tags {
+            let tags = labels
+                .iter()
+                .map(|label| format!("{}:{}", label.get_name(), sanitize(label.get_value())))
+                .collect::<Vec<_>>();
+            let tags = if tags.is_empty() {
+                String::new()
+            } else {
+                format!("|#{}", tags.join(","))
+            };
+            (name, tags)
+        } else {
+            for label in labels {
+                name.push('.');
+                name.push_str(&sanitize(label.get_value()));
+            }
+            (name, String::new())
+        }
+    }
+
+    /// Sends the increase since the last flush, or the whole value if the counter was reset
+    fn push_increase(&mut self, lines: &mut Vec<String>, name: String, tags: &str, value: f64) {
+        let key = format!("{}{}", name, tags);
+        let last_value = self.last_values.insert(key, value).unwrap_or(0.0);
+        let increase = if value >= last_value {
+            value - last_value
+        } else {
+            value
+        };
+        if increase > 0.0 {
+            lines.push(format!("{}:{}|c{}", name, increase, tags));
+        }
+    }
+}
+
+/// Replaces the characters StatsD uses as separators
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' | '\n' | ' ' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Joins lines into as few datagrams of at most `MAX_PACKET_SIZE` bytes as possible
+fn packets(lines: &[String]) -> Vec<String> {
+    let mut packets = vec![];
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > MAX_PACKET_SIZE {
+            packets.push(std::mem::take(&mut packet));
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(line);
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+/// Sends the metrics to the agent every `flush_interval_ms` until the processor exits. Like StatsD
+/// itself this is best effort: failed sends are logged, and counter increases in them are lost.
+pub async fn create_statsd_export_loop(config: StatsdConfig) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(&config.address).await?;
+    info!(
+        address = config.address,
+        flush_interval_ms = config.flush_interval_ms,
+        "[Parser] Exporting metrics to StatsD"
+    );
+    let mut formatter = StatsdFormatter::new(&config);
+    let mut interval = tokio::time::interval(Duration::from_millis(config.flush_interval_ms));
+    loop {
+        interval.tick().await;
+        let lines = formatter.format(&prometheus::gather());
+        for packet in packets(&lines) {
+            if let Err(e) = socket.send(packet.as_bytes()).await {
+                error!(
+                    address = config.address,
+                    error = ?e,
+                    "[Parser] Error sending metrics to StatsD"
+                );
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{IntCounterVec, IntGauge, Opts, Registry};
+
+    #[test]
+    fn test_format() {
+        let registry = Registry::new();
+        let counter = IntCounterVec::new(
+            Opts::new("processed_count", "Processed count"),
+            &["processor_name"],
+        )
+        .unwrap();
+        let gauge = IntGauge::new("lag", "Lag").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+
+        let mut formatter = StatsdFormatter {
+            prefix: Some("indexer".to_string()),
+            dogstatsd_tags: true,
+            ..StatsdFormatter::default()
+        };
+        counter.with_label_values(&["token_v2"]).inc_by(5);
+        gauge.set(-3);
+        assert_eq!(formatter.format(&registry.gather()), vec![
+            "indexer.lag:0|g",
+            "indexer.lag:-3|g",
+            "indexer.processed_count:5|c|#processor_name:token_v2",
+        ]);
+        // Only the increase is sent, and nothing once the counter stops moving
+        counter.with_label_values(&["token_v2"]).inc_by(2);
+        gauge.set(4);
+        assert_eq!(formatter.format(&registry.gather()), vec![
+            "indexer.lag:4|g",
+            "indexer.processed_count:2|c|#processor_name:token_v2",
+        ]);
+        assert_eq!(formatter.format(&registry.gather()), vec!["indexer.lag:4|g"]);
+
+        // Without tags the label values go into the name
+        let mut formatter = StatsdFormatter::default();
+        let lines = formatter.format(&registry.gather());
+        assert_eq!(lines[1], "processed_count.token_v2:7|c");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = StatsdConfig {
+            address: "localhost:8125".to_string(),
+            prefix: None,
+            dogstatsd_tags: false,
+            flush_interval_ms: DEFAULT_STATSD_FLUSH_INTERVAL_MS,
+        };
+        assert!(config.validate().is_ok());
+        assert!(StatsdConfig {
+            flush_interval_ms: 0,
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_packets() {
+        let line = "a".repeat(1000);
+        let lines = vec![line.clone(), line.clone(), "b:1|c".to_string()];
+        assert_eq!(packets(&lines), vec![line.clone(), format!("{}\nb:1|c", line)]);
+        assert!(packets(&[]).is_empty());
+    }
+}
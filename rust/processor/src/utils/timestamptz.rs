@@ -0,0 +1,228 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Converts the `timestamp without time zone` columns of the processor's tables to `timestamptz`
+//! for `store_timestamps_as_timestamptz`. Every timestamp the processor writes is UTC (see
+//! `parse_timestamp`), but a `timestamp` column doesn't say so and readers that assume local time
+//! get it wrong. The conversion has to run with the session time zone set to UTC, which the pool
+//! does when the option is on, so the stored values are read as the UTC instants they are.
+//!
+//! The models keep writing `NaiveDateTime`, which postgres casts to `timestamptz` in the session's
+//! UTC time zone, so nothing else changes. Columns added by later migrations are converted the
+//! next time the processor starts.
+//!
+//! Postgres won't change the type of a column a view reads, so the views over the converted tables
+//! are dropped and created again from their definitions around the ALTERs, all in one transaction.
+//! Their columns come back as `timestamptz` too. Grants and comments on those views aren't kept.
+
+use crate::utils::database::{execute_in_transaction, get_pool_connection, ArcDbPool};
+use diesel::{
+    sql_query,
+    sql_types::{Array, Text},
+    QueryableByName,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, RunQueryDsl};
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// Diesel's own bookkeeping, not written by the processor
+const EXCLUDED_TABLES: &[&str] = &["__diesel_schema_migrations"];
+
+#[derive(QueryableByName)]
+struct TimestampColumn {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+    #[diesel(sql_type = Text)]
+    column_name: String,
+}
+
+#[derive(QueryableByName)]
+struct DependentView {
+    /// Schema qualified and quoted
+    #[diesel(sql_type = Text)]
+    view_name: String,
+    #[diesel(sql_type = Text)]
+    definition: String,
+}
+
+/// Views reading any of `tables` in the current schema, directly or through other views, in the
+/// order they were created
+const DEPENDENT_VIEWS_QUERY: &str = "
+    WITH RECURSIVE dependent_views(oid) AS (
+        SELECT r.ev_class
+        FROM pg_depend d
+        JOIN pg_rewrite r ON r.oid = d.objid
+        JOIN pg_class t ON t.oid = d.refobjid
+        WHERE d.classid = 'pg_rewrite'::regclass
+        AND t.relnamespace = current_schema()::TEXT::regnamespace
+        AND t.relname = ANY($1)
+        AND r.ev_class <> d.refobjid
+        UNION
+        SELECT r.ev_class
+        FROM dependent_views v
+        JOIN pg_depend d ON d.refobjid = v.oid AND d.classid = 'pg_rewrite'::regclass
+        JOIN pg_rewrite r ON r.oid = d.objid
+        WHERE r.ev_class <> v.oid
+    )
+    SELECT format('%I.%I', n.nspname, c.relname) AS view_name,
+        pg_get_viewdef(c.oid) AS definition
+    FROM dependent_views v
+    JOIN pg_class c ON c.oid = v.oid
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+    WHERE c.relkind = 'v'
+    ORDER BY c.oid";
+
+/// One ALTER TABLE per table, so each table is locked once however many columns it converts
+fn alter_table_statements(columns: Vec<TimestampColumn>) -> Vec<String> {
+    let mut by_table: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for column in columns {
+        if !EXCLUDED_TABLES.contains(&column.table_name.as_str()) {
+            by_table
+                .entry(column.table_name)
+                .or_default()
+                .push(column.column_name);
+        }
+    }
+    by_table
+        .into_iter()
+        .map(|(table, columns)| {
+            format!(
+                "ALTER TABLE \"{}\" {}",
+                table,
+                columns
+                    .iter()
+                    .map(|column| format!("ALTER COLUMN \"{}\" TYPE TIMESTAMPTZ", column))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Drops the views in one statement, so their order doesn't matter, and creates them again in the
+/// order they were first created, so each one's dependencies exist by then
+fn recreate_view_statements(views: &[DependentView]) -> (String, Vec<String>) {
+    let drop_statement = format!(
+        "DROP VIEW {}",
+        views
+            .iter()
+            .map(|view| view.view_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let create_statements = views
+        .iter()
+        .map(|view| format!("CREATE VIEW {} AS {}", view.view_name, view.definition))
+        .collect();
+    (drop_statement, create_statements)
+}
+
+/// Converts every `timestamp without time zone` column of the tables in the current schema and
+/// returns how many tables were altered. Columns that are already `timestamptz` are skipped, so
+/// this is a no-op once everything is converted. Postgres 12 and later don't rewrite the table
+/// for this when the session time zone is UTC, older versions do.
+pub async fn convert_timestamps_to_timestamptz(pool: ArcDbPool) -> anyhow::Result<usize> {
+    let mut conn = get_pool_connection(&pool).await?;
+    let columns: Vec<TimestampColumn> = sql_query(
+        "SELECT c.table_name::TEXT, c.column_name::TEXT FROM information_schema.columns c \
+        JOIN information_schema.tables t ON t.table_schema = c.table_schema AND t.table_name = c.table_name \
+        WHERE c.table_schema = current_schema() AND t.table_type = 'BASE TABLE' \
+        AND c.data_type = 'timestamp without time zone'",
+    )
+    .get_results(&mut conn)
+    .await?;
+    drop(conn);
+    let tables = columns
+        .iter()
+        .filter(|column| !EXCLUDED_TABLES.contains(&column.table_name.as_str()))
+        .map(|column| column.table_name.clone())
+        .collect::<Vec<_>>();
+    let statements = alter_table_statements(columns);
+    if statements.is_empty() {
+        return Ok(0);
+    }
+
+    execute_in_transaction(pool, move |conn| {
+        async move {
+            let views: Vec<DependentView> = sql_query(DEPENDENT_VIEWS_QUERY)
+                .bind::<Array<Text>, _>(&tables)
+                .get_results(conn)
+                .await?;
+            let (drop_views, create_views) = recreate_view_statements(&views);
+            if !views.is_empty() {
+                info!(
+                    statement = drop_views,
+                    "[Parser] Dropping views to convert timestamps to timestamptz"
+                );
+                sql_query(&drop_views).execute(conn).await?;
+            }
+            for statement in &statements {
+                info!(
+                    statement = statement,
+                    "[Parser] Converting timestamps to timestamptz"
+                );
+                sql_query(statement).execute(conn).await?;
+            }
+            for statement in &create_views {
+                info!(
+                    statement = statement,
+                    "[Parser] Recreating view after converting timestamps"
+                );
+                sql_query(statement).execute(conn).await?;
+            }
+            Ok(statements.len())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(anyhow::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(table_name: &str, column_name: &str) -> TimestampColumn {
+        TimestampColumn {
+            table_name: table_name.to_string(),
+            column_name: column_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_alter_table_statements() {
+        let statements = alter_table_statements(vec![
+            column("token_activities_v2", "transaction_timestamp"),
+            column("__diesel_schema_migrations", "run_on"),
+            column("events", "inserted_at"),
+            column("token_activities_v2", "inserted_at"),
+        ]);
+        assert_eq!(statements, vec![
+            r#"ALTER TABLE "events" ALTER COLUMN "inserted_at" TYPE TIMESTAMPTZ"#,
+            r#"ALTER TABLE "token_activities_v2" ALTER COLUMN "transaction_timestamp" TYPE TIMESTAMPTZ, ALTER COLUMN "inserted_at" TYPE TIMESTAMPTZ"#,
+        ]);
+    }
+
+    #[test]
+    fn test_recreate_view_statements() {
+        let views = vec![
+            DependentView {
+                view_name: "public.transaction_metadata".to_string(),
+                definition: " SELECT ut.version FROM user_transactions ut;".to_string(),
+            },
+            DependentView {
+                view_name: "public.transactions_view".to_string(),
+                definition: " SELECT t.version FROM transactions t;".to_string(),
+            },
+        ];
+        let (drop_statement, create_statements) = recreate_view_statements(&views);
+        assert_eq!(
+            drop_statement,
+            "DROP VIEW public.transaction_metadata, public.transactions_view"
+        );
+        assert_eq!(create_statements, vec![
+            "CREATE VIEW public.transaction_metadata AS  SELECT ut.version FROM user_transactions ut;",
+            "CREATE VIEW public.transactions_view AS  SELECT t.version FROM transactions t;",
+        ]);
+    }
+}
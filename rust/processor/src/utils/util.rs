@@ -3,30 +3,37 @@
 
 use crate::{
     db::common::models::property_map::{PropertyMap, TokenObjectPropertyMap},
-    utils::counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
+    utils::counters::{INVALID_TOKEN_AMOUNT_COUNT, PROCESSOR_UNKNOWN_TYPE_COUNT},
 };
 use aptos_protos::{
     transaction::v1::{
-        multisig_transaction_payload::Payload as MultisigPayloadType,
+        multisig_transaction_payload::Payload as MultisigPayloadType, transaction::TxnData,
         transaction_payload::Payload as PayloadType, write_set::WriteSet as WriteSetType,
         EntryFunctionId, EntryFunctionPayload, MoveScriptBytecode, MoveType, ScriptPayload,
-        TransactionPayload, UserTransactionRequest, WriteSet,
+        Transaction, TransactionPayload, UserTransactionRequest, WriteSet,
     },
     util::timestamp::Timestamp,
 };
-use bigdecimal::{BigDecimal, Signed, ToPrimitive, Zero};
+use bigdecimal::{BigDecimal, RoundingMode, Signed, ToPrimitive, Zero};
 use chrono::NaiveDateTime;
+use diesel::{
+    pg::Pg,
+    serialize::{self, Output, ToSql},
+    sql_types::Text,
+};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use sha2::Digest;
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 use tiny_keccak::{Hasher, Sha3};
 
 // 9999-12-31 23:59:59, this is the max supported by Google BigQuery
 pub const MAX_TIMESTAMP_SECS: i64 = 253_402_300_799;
 // Max length of entry function id string to ensure that db doesn't explode
 pub const MAX_ENTRY_FUNCTION_LENGTH: usize = 1000;
+// Max length of an event type string we'll try to match. Anything longer can't be a type we parse
+pub const MAX_EVENT_TYPE_LENGTH: usize = 1000;
 
 pub const APTOS_COIN_TYPE_STR: &str = "0x1::aptos_coin::AptosCoin";
 
@@ -75,6 +82,59 @@ pub fn standardize_address(handle: &str) -> String {
     }
 }
 
+/// An address that went through `standardize_address`, which `new` is the only way to build, so
+/// raw and standardized addresses can't be mixed up. Serialized and written to the DB as the
+/// plain string.
+#[derive(AsExpression, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[diesel(sql_type = Text)]
+#[serde(transparent)]
+pub struct StandardizedAddress(String);
+
+impl StandardizedAddress {
+    pub fn new(address: &str) -> Self {
+        Self(standardize_address(address))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for StandardizedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for StandardizedAddress {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(|address| Self::new(&address))
+    }
+}
+
+impl ToSql<Text, Pg> for StandardizedAddress {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.0.as_str(), out)
+    }
+}
+
+/// An event handle's key the way the REST API prints it: `0x` and the hex of its BCS bytes, i.e. the
+/// creation number as 8 little endian bytes followed by the account address
+pub fn event_key_string(account_address: &StandardizedAddress, creation_number: u64) -> String {
+    format!(
+        "0x{}{}",
+        hex::encode(creation_number.to_le_bytes()),
+        account_address.as_str().trim_start_matches("0x")
+    )
+}
+
 /// Standardizes all addresses and table handles to be length 66 (0x-64 length hash) that takes in a slice.
 pub fn standardize_address_from_bytes(bytes: &[u8]) -> String {
     let encdoed_bytes = hex::encode(bytes);
@@ -120,6 +180,74 @@ pub fn ensure_not_negative(val: BigDecimal) -> BigDecimal {
     val
 }
 
+/// Rounds to `scale` fractional digits the way postgres stores into numeric(p, s), half away
+/// from zero, so the stored value doesn't depend on the driver
+pub fn set_numeric_scale(val: &BigDecimal, scale: i64) -> BigDecimal {
+    val.with_scale_round(scale, RoundingMode::HalfUp)
+}
+
+/// What to do with a row whose numeric value doesn't fit the Move type it comes from, which
+/// only a malformed event can produce
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumericOverflowPolicy {
+    /// Clamp the value to the closest one in range
+    Saturate,
+    /// Drop the row
+    #[default]
+    Skip,
+    /// Fail the batch
+    Error,
+}
+
+impl NumericOverflowPolicy {
+    /// Applies the policy if `value` is outside of 0..=u64::MAX. None means the row should be
+    /// dropped. Every out of range value is logged and counted in `INVALID_TOKEN_AMOUNT_COUNT`.
+    pub fn check_u64(
+        &self,
+        value: BigDecimal,
+        model_name: &str,
+        column: &str,
+        txn_version: i64,
+    ) -> anyhow::Result<Option<BigDecimal>> {
+        let max = BigDecimal::from(u64::MAX);
+        let clamped = if value.is_negative() {
+            BigDecimal::zero()
+        } else if value > max {
+            max
+        } else {
+            return Ok(Some(value));
+        };
+        let action = match self {
+            Self::Saturate => "saturated",
+            Self::Skip => "skipped",
+            Self::Error => "error",
+        };
+        tracing::warn!(
+            transaction_version = txn_version,
+            model_name,
+            column,
+            value = %value,
+            action,
+            "Numeric value out of range",
+        );
+        INVALID_TOKEN_AMOUNT_COUNT
+            .with_label_values(&[model_name, column, action])
+            .inc();
+        match self {
+            Self::Saturate => Ok(Some(clamped)),
+            Self::Skip => Ok(None),
+            Self::Error => anyhow::bail!(
+                "{}.{} is out of range at version {}: {}",
+                model_name,
+                column,
+                txn_version,
+                value
+            ),
+        }
+    }
+}
+
 pub fn get_entry_function_from_user_request(
     user_request: &UserTransactionRequest,
 ) -> Option<String> {
@@ -129,14 +257,16 @@ pub fn get_entry_function_from_user_request(
                 Some(payload.entry_function_id_str.clone())
             },
             Some(PayloadType::MultisigPayload(payload)) => {
-                if let Some(payload) = payload.transaction_payload.as_ref() {
-                    match payload.payload.as_ref().unwrap() {
-                        MultisigPayloadType::EntryFunctionPayload(payload) => {
-                            Some(payload.entry_function_id_str.clone())
-                        },
-                    }
-                } else {
-                    None
+                // Multisig transactions that only execute a payload stored on chain don't carry it
+                match payload
+                    .transaction_payload
+                    .as_ref()
+                    .and_then(|payload| payload.payload.as_ref())
+                {
+                    Some(MultisigPayloadType::EntryFunctionPayload(payload)) => {
+                        Some(payload.entry_function_id_str.clone())
+                    },
+                    None => None,
                 }
             },
             _ => return None,
@@ -287,6 +417,8 @@ pub fn naive_datetime_to_timestamp(ndt: NaiveDateTime) -> Timestamp {
     }
 }
 
+/// The transaction timestamp as a `NaiveDateTime` in UTC. Every timestamp the processor stores is
+/// UTC, in `timestamp` columns unless `store_timestamps_as_timestamptz` converts them.
 pub fn parse_timestamp(ts: &Timestamp, version: i64) -> chrono::NaiveDateTime {
     let final_ts = if ts.seconds >= MAX_TIMESTAMP_SECS {
         Timestamp {
@@ -301,6 +433,7 @@ pub fn parse_timestamp(ts: &Timestamp, version: i64) -> chrono::NaiveDateTime {
         .unwrap_or_else(|| panic!("Could not parse timestamp {:?} for version {}", ts, version))
 }
 
+/// Unix seconds as a `NaiveDateTime` in UTC, like `parse_timestamp`
 pub fn parse_timestamp_secs(ts: u64, version: i64) -> chrono::NaiveDateTime {
     #[allow(deprecated)]
     chrono::NaiveDateTime::from_timestamp_opt(
@@ -453,6 +586,20 @@ where
     s.parse::<T>().map_err(D::Error::custom)
 }
 
+/// Same as `deserialize_from_string` but a missing or null value is None
+pub fn deserialize_option_from_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    <T as FromStr>::Err: std::fmt::Display,
+{
+    use serde::de::Error;
+
+    <Option<String>>::deserialize(deserializer)?
+        .map(|s| s.parse::<T>().map_err(D::Error::custom))
+        .transpose()
+}
+
 /// Convert the protobuf Timestamp to epcoh time in seconds.
 pub fn time_diff_since_pb_timestamp_in_secs(timestamp: &Timestamp) -> f64 {
     let current_timestamp = std::time::SystemTime::now()
@@ -481,6 +628,26 @@ pub fn get_name_from_unnested_move_type(move_type: &str) -> &str {
     t.last().unwrap()
 }
 
+/// Number of events carried in the transaction data
+pub fn get_transaction_event_count(txn: &Transaction) -> usize {
+    match txn.txn_data.as_ref() {
+        Some(TxnData::BlockMetadata(inner)) => inner.events.len(),
+        Some(TxnData::Genesis(inner)) => inner.events.len(),
+        Some(TxnData::User(inner)) => inner.events.len(),
+        Some(TxnData::Validator(inner)) => inner.events.len(),
+        _ => 0,
+    }
+}
+
+/// Compares the events a transaction carries against the number of events in its size info,
+/// which has an entry per event. Returns (received, expected) if they don't match. Transactions
+/// without size info can't be checked and are treated as complete.
+pub fn get_event_count_mismatch(txn: &Transaction) -> Option<(usize, usize)> {
+    let expected = txn.size_info.as_ref()?.event_size_info.len();
+    let received = get_transaction_event_count(txn);
+    (received != expected).then_some((received, expected))
+}
+
 /* COMMON STRUCTS */
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Aggregator {
@@ -527,6 +694,76 @@ mod tests {
         pub default_properties: serde_json::Value,
     }
 
+    #[test]
+    fn test_standardized_address() {
+        let address = StandardizedAddress::new("0xa5");
+        let expected = "0x00000000000000000000000000000000000000000000000000000000000000a5";
+        assert_eq!(address.as_str(), expected);
+        // Standardizing is idempotent, so an already standardized address stays the same
+        assert_eq!(StandardizedAddress::new(expected), address);
+        // Serialized as the plain string, and standardized again when deserialized
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            format!("\"{}\"", expected)
+        );
+        assert_eq!(
+            serde_json::from_str::<StandardizedAddress>("\"a5\"").unwrap(),
+            address
+        );
+    }
+
+    #[test]
+    fn test_event_key_string() {
+        assert_eq!(
+            event_key_string(&StandardizedAddress::new("0xa5"), 3),
+            "0x030000000000000000000000000000000000000000000000000000000000000000000000000000a5"
+        );
+        // Creation numbers past one byte keep their little endian order
+        assert!(event_key_string(&StandardizedAddress::new("0x1"), 0x0102)
+            .starts_with("0x0201000000000000"));
+    }
+
+    #[test]
+    fn test_set_numeric_scale() {
+        let val = BigDecimal::from_str("12.345").unwrap();
+        assert_eq!(set_numeric_scale(&val, 2).to_string(), "12.35");
+        assert_eq!(set_numeric_scale(&val, 0).to_string(), "12");
+        assert_eq!(set_numeric_scale(&val, 5).to_string(), "12.34500");
+        let val = BigDecimal::from_str("-0.5").unwrap();
+        assert_eq!(set_numeric_scale(&val, 0).to_string(), "-1");
+    }
+
+    #[test]
+    fn test_numeric_overflow_policy() {
+        let too_large = BigDecimal::from_str("100000000000000000000000000000").unwrap();
+        let check = |policy: NumericOverflowPolicy, value: &BigDecimal| {
+            policy.check_u64(value.clone(), "Model", "amount", 1)
+        };
+        for policy in [
+            NumericOverflowPolicy::Saturate,
+            NumericOverflowPolicy::Skip,
+            NumericOverflowPolicy::Error,
+        ] {
+            assert_eq!(
+                check(policy, &BigDecimal::from(5)).unwrap(),
+                Some(BigDecimal::from(5))
+            );
+        }
+        assert_eq!(
+            check(NumericOverflowPolicy::Saturate, &too_large).unwrap(),
+            Some(BigDecimal::from(u64::MAX))
+        );
+        assert_eq!(
+            check(NumericOverflowPolicy::Saturate, &BigDecimal::from(-5)).unwrap(),
+            Some(BigDecimal::zero())
+        );
+        assert_eq!(
+            check(NumericOverflowPolicy::Skip, &too_large).unwrap(),
+            None
+        );
+        assert!(check(NumericOverflowPolicy::Error, &BigDecimal::from(-5)).is_err());
+    }
+
     #[test]
     fn test_parse_timestamp() {
         let ts = parse_timestamp(
@@ -676,4 +913,26 @@ mod tests {
         let d: TokenObjectDataMock = serde_json::from_str(val.as_str()).unwrap();
         assert_eq!(d.default_properties, Value::Object(serde_json::Map::new()));
     }
+
+    #[test]
+    fn test_event_count_mismatch() {
+        use aptos_protos::transaction::v1::{
+            Event, EventSizeInfo, TransactionSizeInfo, UserTransaction,
+        };
+
+        let txn = |num_events: usize, num_sizes: Option<usize>| Transaction {
+            txn_data: Some(TxnData::User(UserTransaction {
+                events: vec![Event::default(); num_events],
+                ..UserTransaction::default()
+            })),
+            size_info: num_sizes.map(|n| TransactionSizeInfo {
+                event_size_info: vec![EventSizeInfo::default(); n],
+                ..TransactionSizeInfo::default()
+            }),
+            ..Transaction::default()
+        };
+        assert_eq!(get_event_count_mismatch(&txn(3, Some(3))), None);
+        assert_eq!(get_event_count_mismatch(&txn(2, Some(3))), Some((2, 3)));
+        assert_eq!(get_event_count_mismatch(&txn(2, None)), None);
+    }
 }
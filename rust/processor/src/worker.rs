@@ -2,8 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    config::IndexerGrpcHttp2Config,
-    db::common::models::{ledger_info::LedgerInfo, processor_status::ProcessorStatusQuery},
+    config::{AdditionalProcessorConfig, IndexerGrpcHttp2Config, OnProcessorFailure},
+    db::common::models::{
+        failed_transactions::FailedTransaction,
+        ledger_info::LedgerInfo,
+        processor_status::ProcessorStatusQuery,
+        token_models::{token_activities::TokenActivity, token_utils::IdHashScheme},
+    },
     gap_detectors::{
         create_gap_detector_status_tracker_loop, gap_detector::DefaultGapDetector,
         parquet_gap_detector::ParquetFileGapDetectorInner, GapDetector, ProcessingResult,
@@ -13,6 +18,7 @@ use crate::{
         account_transactions_processor::AccountTransactionsProcessor,
         ans_processor::AnsProcessor,
         default_processor::DefaultProcessor,
+        event_index_backfill_processor::EventIndexBackfillProcessor,
         events_processor::EventsProcessor,
         fungible_asset_processor::FungibleAssetProcessor,
         monitoring_processor::MonitoringProcessor,
@@ -27,6 +33,7 @@ use crate::{
             parquet_transaction_metadata_processor::ParquetTransactionMetadataProcessor,
         },
         stake_processor::StakeProcessor,
+        token_activities_reprocessor::TokenActivitiesReprocessor,
         token_v2_processor::TokenV2Processor,
         transaction_metadata_processor::TransactionMetadataProcessor,
         user_transaction_processor::UserTransactionProcessor,
@@ -35,32 +42,56 @@ use crate::{
     schema::ledger_infos,
     transaction_filter::TransactionFilter,
     utils::{
+        circuit_breaker::{CircuitBreakerConfig, FailedTransactionsConfig, OnCircuitBreakerTrip},
+        collection_volume::CollectionVolumeTracker,
         counters::{
-            ProcessorStep, GRPC_LATENCY_BY_PROCESSOR_IN_SECS, LATEST_PROCESSED_VERSION,
+            ProcessorStep, BUFFERED_TRANSACTIONS_COMPRESSION_TIME_IN_SECS,
+            DB_CONNECTION_RETRY_COUNT, FAILED_TRANSACTIONS_COUNT,
+            GRPC_LATENCY_BY_PROCESSOR_IN_SECS, LATEST_PROCESSED_VERSION,
             NUM_TRANSACTIONS_PROCESSED_COUNT, PB_CHANNEL_FETCH_WAIT_TIME_SECS,
-            PROCESSED_BYTES_COUNT, PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS,
-            PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS, PROCESSOR_ERRORS_COUNT,
-            PROCESSOR_INVOCATIONS_COUNT, PROCESSOR_SUCCESSES_COUNT,
+            PROCESSED_BYTES_COUNT, PROCESSING_LATENCY_IN_SECS,
+            PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS, PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS,
+            PROCESSOR_ERRORS_COUNT, PROCESSOR_INVOCATIONS_COUNT, PROCESSOR_SUCCESSES_COUNT,
             SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS, SINGLE_BATCH_PARSING_TIME_IN_SECS,
             SINGLE_BATCH_PROCESSING_TIME_IN_SECS, TRANSACTION_UNIX_TIMESTAMP,
         },
         database::{
-            execute_with_better_error_conn, new_db_pool, run_pending_migrations, ArcDbPool,
+            add_search_path_to_db_url, add_utc_timezone_to_db_url, chain_schema_name,
+            create_schema, execute_with_better_error_conn, get_pool_connection,
+            is_connection_error, new_db_pool, run_pending_migrations, set_max_concurrent_db_writes,
+            set_statement_timeout_min_chunk_size, ArcDbPool, DEFAULT_MAX_POOL_SIZE,
+        },
+        derive_from_activities::{derive_from_token_activities, DerivedTable},
+        ownership_audit::{
+            audit_current_token_ownerships, create_ownership_audit_loop, OwnershipAuditConfig,
+        },
+        progress_file::{create_progress_file_loop, ProgressTracker},
+        prune_token_activities::{
+            create_token_activities_pruning_loop, TokenActivitiesPruningConfig,
+        },
+        rehash_token_ids::rehash_token_ids,
+        skipped_transactions::SkippedTransactions,
+        timestamptz::convert_timestamps_to_timestamptz,
+        util::{
+            naive_datetime_to_timestamp, time_diff_since_pb_timestamp_in_secs, timestamp_to_iso,
+            timestamp_to_unixtime,
         },
-        util::{time_diff_since_pb_timestamp_in_secs, timestamp_to_iso, timestamp_to_unixtime},
     },
 };
 use ahash::AHashMap;
 use anyhow::{Context, Result};
 use aptos_moving_average::MovingAverage;
+use aptos_protos::transaction::v1::Transaction;
 use bitflags::bitflags;
 use kanal::AsyncSender;
+use prost::Message;
 use std::{
     collections::HashSet,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, field, info, info_span, warn, Instrument};
 use url::Url;
 // this is how large the fetch queue should be. Each bucket should have a max of 80MB or so, so a batch
 // of 50 means that we could potentially have at least 4.8GB of data in memory at any given time and that we should provision
@@ -68,6 +99,8 @@ use url::Url;
 
 pub const BUFFER_SIZE: usize = 300;
 pub const PROCESSOR_SERVICE_TYPE: &str = "processor";
+// Wait before retrying a batch that lost its db connection, doubled on each retry
+const DB_CONNECTION_RETRY_DELAY_MS: u64 = 500;
 
 bitflags! {
     #[derive(Debug, Clone, Copy)]
@@ -113,6 +146,8 @@ bitflags! {
 
 pub struct Worker {
     pub db_pool: ArcDbPool,
+    // Set with postgres_schema_per_chain, the connection string's search_path already points at it
+    pub postgres_schema: Option<String>,
     pub processor_config: ProcessorConfig,
     pub postgres_connection_string: String,
     pub indexer_grpc_data_service_address: Url,
@@ -120,16 +155,37 @@ pub struct Worker {
     pub auth_token: String,
     pub starting_version: Option<u64>,
     pub ending_version: Option<u64>,
+    pub min_version: Option<u64>,
     pub number_concurrent_processing_tasks: usize,
     pub gap_detection_batch_size: u64,
     pub parquet_gap_detection_batch_size: u64,
     pub grpc_chain_id: Option<u64>,
+    pub expected_chain_id: Option<u64>,
     pub pb_channel_txn_chunk_size: usize,
+    pub compress_buffered_transactions: bool,
+    pub transaction_reorder_buffer_size: usize,
+    pub pb_channel_flush_interval_ms: Option<u64>,
     pub per_table_chunk_sizes: AHashMap<String, usize>,
     pub enable_verbose_logging: Option<bool>,
     pub transaction_filter: TransactionFilter,
     pub grpc_response_item_timeout_in_secs: u64,
     pub deprecated_tables: TableFlags,
+    pub derive_from_token_activities: Option<DerivedTable>,
+    pub rehash_token_ids_from: Option<IdHashScheme>,
+    pub processor_status_heartbeat_secs: Option<u64>,
+    pub json_transactions_file: Option<PathBuf>,
+    pub progress_file: Option<PathBuf>,
+    pub progress_file_interval_secs: u64,
+    pub db_connection_retries: u32,
+    pub prune_token_activities: Option<TokenActivitiesPruningConfig>,
+    pub additional_processors: Vec<AdditionalProcessorConfig>,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    pub failed_transactions: Option<FailedTransactionsConfig>,
+    pub indexer_grpc_fallback_addresses: Vec<Url>,
+    pub store_timestamps_as_timestamptz: bool,
+    pub audit_current_token_ownerships: Option<OwnershipAuditConfig>,
+    // Built once and handed to every processor instance, so the stats endpoint sees all batches
+    pub collection_volume: Option<Arc<Mutex<CollectionVolumeTracker>>>,
 }
 
 impl Worker {
@@ -142,35 +198,226 @@ impl Worker {
         auth_token: String,
         starting_version: Option<u64>,
         ending_version: Option<u64>,
+        min_version: Option<u64>,
         number_concurrent_processing_tasks: Option<usize>,
         db_pool_size: Option<u32>,
+        db_statement_timeout_in_secs: Option<u64>,
+        db_pool_connection_timeout_in_secs: Option<u64>,
+        expected_chain_id: Option<u64>,
+        postgres_schema_per_chain: bool,
         gap_detection_batch_size: u64,
         parquet_gap_detection_batch_size: u64,
         // The number of transactions per protobuf batch
         pb_channel_txn_chunk_size: usize,
+        compress_buffered_transactions: bool,
+        transaction_reorder_buffer_size: usize,
+        pb_channel_flush_interval_ms: Option<u64>,
         per_table_chunk_sizes: AHashMap<String, usize>,
         enable_verbose_logging: Option<bool>,
         transaction_filter: TransactionFilter,
         grpc_response_item_timeout_in_secs: u64,
         deprecated_tables: HashSet<String>,
+        derive_from_token_activities: Option<DerivedTable>,
+        rehash_token_ids_from: Option<IdHashScheme>,
+        processor_status_heartbeat_secs: Option<u64>,
+        json_transactions_file: Option<PathBuf>,
+        progress_file: Option<PathBuf>,
+        progress_file_interval_secs: u64,
+        db_connection_retries: u32,
+        prune_token_activities: Option<TokenActivitiesPruningConfig>,
+        additional_processors: Vec<AdditionalProcessorConfig>,
+        circuit_breaker: Option<CircuitBreakerConfig>,
+        failed_transactions: Option<FailedTransactionsConfig>,
+        indexer_grpc_fallback_addresses: Vec<Url>,
+        max_concurrent_db_writes: Option<usize>,
+        token_id_hash_scheme: Option<IdHashScheme>,
+        db_statement_timeout_min_chunk_size: Option<usize>,
+        store_timestamps_as_timestamptz: bool,
+        audit_current_token_ownerships: Option<OwnershipAuditConfig>,
     ) -> Result<Self> {
         let processor_name = processor_config.name();
         info!(processor_name = processor_name, "[Parser] Kicking off");
 
+        let postgres_schema = if postgres_schema_per_chain {
+            let chain_id = expected_chain_id
+                .context("postgres_schema_per_chain requires expected_chain_id")?;
+            Some(chain_schema_name(chain_id))
+        } else {
+            None
+        };
+        // Everything connecting to the DB goes through this url, so all of it uses the schema
+        let postgres_connection_string = match &postgres_schema {
+            Some(schema) => add_search_path_to_db_url(&postgres_connection_string, schema),
+            None => postgres_connection_string,
+        };
+        // timestamptz columns are written and read in the session's time zone
+        let postgres_connection_string = if store_timestamps_as_timestamptz {
+            add_utc_timezone_to_db_url(&postgres_connection_string)
+        } else {
+            postgres_connection_string
+        };
+
         info!(
             processor_name = processor_name,
             service_type = PROCESSOR_SERVICE_TYPE,
             "[Parser] Creating connection pool"
         );
-        let conn_pool = new_db_pool(&postgres_connection_string, db_pool_size)
-            .await
-            .context("Failed to create connection pool")?;
+        let conn_pool = new_db_pool(
+            &postgres_connection_string,
+            db_pool_size,
+            db_statement_timeout_in_secs,
+            db_pool_connection_timeout_in_secs,
+        )
+        .await
+        .context("Failed to create connection pool")?;
+        if let Some(schema) = &postgres_schema {
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                schema,
+                "[Parser] Using the chain's schema"
+            );
+            create_schema(&conn_pool, schema)
+                .await
+                .context("Failed to create the chain's schema")?;
+        }
         info!(
             processor_name = processor_name,
             service_type = PROCESSOR_SERVICE_TYPE,
             "[Parser] Finish creating the connection pool"
         );
         let number_concurrent_processing_tasks = number_concurrent_processing_tasks.unwrap_or(10);
+        anyhow::ensure!(
+            number_concurrent_processing_tasks > 0,
+            "number_concurrent_processing_tasks has to be positive"
+        );
+        // Each batch checks the watermark left by the one before it, so they have to commit in order
+        for config in std::iter::once(&processor_config).chain(
+            additional_processors
+                .iter()
+                .map(|additional_processor| &additional_processor.processor_config),
+        ) {
+            if let ProcessorConfig::TokenV2Processor(config) = config {
+                anyhow::ensure!(
+                    !config.commit_watermark_with_batch || number_concurrent_processing_tasks == 1,
+                    "commit_watermark_with_batch needs number_concurrent_processing_tasks: 1"
+                );
+            }
+        }
+        if let Some(max_concurrent_db_writes) = max_concurrent_db_writes {
+            anyhow::ensure!(
+                max_concurrent_db_writes > 0,
+                "max_concurrent_db_writes has to be positive"
+            );
+            set_max_concurrent_db_writes(max_concurrent_db_writes);
+        }
+        if let Some(min_chunk_size) = db_statement_timeout_min_chunk_size {
+            anyhow::ensure!(
+                min_chunk_size > 0,
+                "db_statement_timeout_min_chunk_size has to be positive"
+            );
+            set_statement_timeout_min_chunk_size(min_chunk_size);
+        }
+        anyhow::ensure!(
+            progress_file_interval_secs > 0,
+            "progress_file_interval_secs has to be positive"
+        );
+        // Each write statement holds a connection. Without max_concurrent_db_writes a batch runs
+        // its chunks and tables at once, so only the pool bounds them
+        let db_pool_size = db_pool_size.unwrap_or(DEFAULT_MAX_POOL_SIZE);
+        info!(
+            processor_name = processor_name,
+            service_type = PROCESSOR_SERVICE_TYPE,
+            processing_tasks = number_concurrent_processing_tasks,
+            max_concurrent_db_writes = ?max_concurrent_db_writes,
+            db_pool_size,
+            "[Parser] Effective concurrency"
+        );
+        if max_concurrent_db_writes.is_some_and(|db_writes| db_writes > db_pool_size as usize) {
+            warn!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                max_concurrent_db_writes = ?max_concurrent_db_writes,
+                db_pool_size,
+                "[Parser] More concurrent writes than DB connections, writes will wait on the pool"
+            );
+        }
+        if let Some(token_id_hash_scheme) = token_id_hash_scheme {
+            IdHashScheme::set_current(token_id_hash_scheme);
+        }
+        info!(
+            processor_name = processor_name,
+            service_type = PROCESSOR_SERVICE_TYPE,
+            token_id_hash_scheme = ?IdHashScheme::current(),
+            "[Parser] Token id hash scheme"
+        );
+        if let Some(pruning_config) = &prune_token_activities {
+            pruning_config.validate()?;
+            // Pruning is measured from this processor's checkpoint, so it has to be the one writing
+            // token_activities
+            anyhow::ensure!(
+                match processor_config {
+                    ProcessorConfig::TokenV2Processor(config) => config.token_activities.is_some(),
+                    ProcessorConfig::TokenActivitiesReprocessor(_) => true,
+                    _ => false,
+                },
+                "prune_token_activities needs processor_config to be token_activities_reprocessor \
+                 or token_v2_processor with token_activities set"
+            );
+        }
+        if let Some(audit_config) = &audit_current_token_ownerships {
+            audit_config.validate()?;
+            // The audit stops at this processor's checkpoint, so it has to be the one writing both
+            // tables
+            anyhow::ensure!(
+                matches!(processor_config, ProcessorConfig::TokenV2Processor(_)),
+                "audit_current_token_ownerships needs processor_config to be token_v2_processor"
+            );
+        }
+        if let Some(circuit_breaker) = &circuit_breaker {
+            circuit_breaker.validate(processor_config.is_parquet_processor())?;
+        }
+        if let Some(failed_transactions) = &failed_transactions {
+            failed_transactions.validate(processor_config.is_parquet_processor())?;
+        }
+        // Metrics and logs are labeled by processor name, so each one can only run once
+        let mut processor_names = HashSet::from([processor_name]);
+        for additional_processor in &additional_processors {
+            anyhow::ensure!(
+                processor_names.insert(additional_processor.processor_config.name()),
+                "Processor {} is configured more than once",
+                additional_processor.processor_config.name()
+            );
+            if let ProcessorConfig::TokenV2Processor(config) =
+                &additional_processor.processor_config
+            {
+                anyhow::ensure!(
+                    config.top_collections.is_none(),
+                    "top_collections can only be set on the main processor_config"
+                );
+            }
+        }
+        let collection_volume = match &processor_config {
+            ProcessorConfig::TokenV2Processor(config) => {
+                config
+                    .top_collections
+                    .clone()
+                    .map(|top_collections_config| {
+                        let tracker = Arc::new(Mutex::new(CollectionVolumeTracker::new(
+                            top_collections_config,
+                        )));
+                        let endpoint_tracker = tracker.clone();
+                        server_framework::register_stats_endpoint("top_collections", move || {
+                            serde_json::to_string(
+                                &endpoint_tracker.lock().unwrap().top_collections(),
+                            )
+                            .unwrap()
+                        });
+                        tracker
+                    })
+            },
+            _ => None,
+        };
 
         let mut deprecated_tables_flags = TableFlags::empty();
         for table in deprecated_tables.iter() {
@@ -181,26 +428,54 @@ impl Worker {
 
         Ok(Self {
             db_pool: conn_pool,
+            postgres_schema,
             processor_config,
             postgres_connection_string,
             indexer_grpc_data_service_address,
             grpc_http2_config,
             starting_version,
             ending_version,
+            min_version,
             auth_token,
             number_concurrent_processing_tasks,
             gap_detection_batch_size,
             parquet_gap_detection_batch_size,
             grpc_chain_id: None,
+            expected_chain_id,
             pb_channel_txn_chunk_size,
+            compress_buffered_transactions,
+            transaction_reorder_buffer_size,
+            pb_channel_flush_interval_ms,
             per_table_chunk_sizes,
             enable_verbose_logging,
             transaction_filter,
             grpc_response_item_timeout_in_secs,
             deprecated_tables: deprecated_tables_flags,
+            derive_from_token_activities,
+            rehash_token_ids_from,
+            processor_status_heartbeat_secs,
+            json_transactions_file,
+            progress_file,
+            progress_file_interval_secs,
+            db_connection_retries,
+            prune_token_activities,
+            additional_processors,
+            circuit_breaker,
+            failed_transactions,
+            indexer_grpc_fallback_addresses,
+            store_timestamps_as_timestamptz,
+            audit_current_token_ownerships,
+            collection_volume,
         })
     }
 
+    /// The primary data service endpoint followed by the ones to fail over to
+    fn indexer_grpc_data_service_addresses(&self) -> Vec<Url> {
+        std::iter::once(self.indexer_grpc_data_service_address.clone())
+            .chain(self.indexer_grpc_fallback_addresses.iter().cloned())
+            .collect()
+    }
+
     /// This is the main logic of the processor. We will do a few large parts:
     /// 1. Connect to GRPC and handling all the stuff before starting the stream such as diesel migration
     /// 2. Start a thread specifically to fetch data from GRPC. We will keep a buffer of X batches of transactions
@@ -223,6 +498,76 @@ impl Worker {
             "[Parser] Finished migrations"
         );
 
+        if self.store_timestamps_as_timestamptz {
+            let converted_tables = convert_timestamps_to_timestamptz(self.db_pool.clone())
+                .await
+                .expect("[Parser] Failed to convert timestamps to timestamptz");
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                converted_tables,
+                "[Parser] Timestamp columns are timestamptz"
+            );
+        }
+
+        self.check_schema()
+            .await
+            .expect("[Parser] Schema check failed");
+
+        if let Some(table) = self.derive_from_token_activities {
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                table = ?table,
+                "[Parser] Deriving table from token_activities instead of processing the stream"
+            );
+            derive_from_token_activities(self.db_pool.clone(), table, &self.per_table_chunk_sizes)
+                .await
+                .expect("[Parser] Failed to derive table from token_activities");
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                table = ?table,
+                "[Parser] Finished deriving table from token_activities"
+            );
+            return;
+        }
+
+        if let Some(audit_config) = self
+            .audit_current_token_ownerships
+            .as_ref()
+            .filter(|audit_config| audit_config.interval_secs.is_none())
+        {
+            audit_current_token_ownerships(
+                &self.db_pool,
+                &self.processor_config.processor_status_name(),
+                audit_config.sample_size,
+                self.audit_starting_version(),
+            )
+            .await
+            .expect("[Parser] Failed to audit current_token_ownerships_v2");
+            return;
+        }
+
+        if let Some(from) = self.rehash_token_ids_from {
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                from = ?from,
+                to = ?IdHashScheme::current(),
+                "[Parser] Rehashing token ids instead of processing the stream"
+            );
+            rehash_token_ids(self.db_pool.clone(), from)
+                .await
+                .expect("[Parser] Failed to rehash token ids");
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                "[Parser] Finished rehashing token ids"
+            );
+            return;
+        }
+
         let starting_version_from_db = self
             .get_start_version()
             .await
@@ -236,7 +581,22 @@ impl Worker {
                 0
             });
 
-        let starting_version = self.starting_version.unwrap_or(starting_version_from_db);
+        // Everything below min_version is skipped, so the checkpoint jumps forward to it
+        let starting_version = self
+            .starting_version
+            .unwrap_or(starting_version_from_db)
+            .max(self.min_version.unwrap_or(0));
+
+        // Transactions at the start of the stream that come without a timestamp share the
+        // checkpoint's block, until the next block metadata transaction
+        let previous_timestamp = if starting_version == starting_version_from_db {
+            self.get_checkpoint_timestamp()
+                .await
+                .expect("[Parser] Database error when getting the checkpoint's timestamp")
+                .map(naive_datetime_to_timestamp)
+        } else {
+            None
+        };
 
         info!(
             processor_name = processor_name,
@@ -245,21 +605,30 @@ impl Worker {
             final_start_version = starting_version,
             start_version_from_config = self.starting_version,
             start_version_from_db = starting_version_from_db,
+            min_version_from_config = self.min_version,
             "[Parser] Building processor",
         );
 
         let concurrent_tasks = self.number_concurrent_processing_tasks;
 
-        // get the chain id
-        let chain_id = crate::grpc_stream::get_chain_id(
-            self.indexer_grpc_data_service_address.clone(),
-            self.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
-            self.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
-            self.grpc_http2_config.grpc_connection_timeout_secs(),
-            self.auth_token.clone(),
-            processor_name.to_string(),
-        )
-        .await;
+        // get the chain id. A json file doesn't carry one so it has to come from the config
+        let chain_id = match self.json_transactions_file {
+            Some(_) => self
+                .expected_chain_id
+                .expect("[Parser] expected_chain_id is required with json_transactions_file"),
+            None => {
+                crate::grpc_stream::get_chain_id(
+                    self.indexer_grpc_data_service_addresses(),
+                    self.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
+                    self.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
+                    self.grpc_http2_config.grpc_connection_timeout_secs(),
+                    self.grpc_http2_config.grpc_max_decoding_message_size(),
+                    self.auth_token.clone(),
+                    processor_name.to_string(),
+                )
+                .await
+            },
+        };
         self.check_or_update_chain_id(chain_id as i64)
             .await
             .unwrap();
@@ -267,14 +636,20 @@ impl Worker {
         self.grpc_chain_id = Some(chain_id);
 
         let ending_version = self.ending_version;
-        let indexer_grpc_data_service_address = self.indexer_grpc_data_service_address.clone();
+        let indexer_grpc_data_service_addresses = self.indexer_grpc_data_service_addresses();
         let indexer_grpc_http2_ping_interval =
             self.grpc_http2_config.grpc_http2_ping_interval_in_secs();
         let indexer_grpc_http2_ping_timeout =
             self.grpc_http2_config.grpc_http2_ping_timeout_in_secs();
         let indexer_grpc_reconnection_timeout_secs =
             self.grpc_http2_config.grpc_connection_timeout_secs();
+        let max_decoding_message_size = self.grpc_http2_config.grpc_max_decoding_message_size();
         let pb_channel_txn_chunk_size = self.pb_channel_txn_chunk_size;
+        let compress_buffered_transactions = self.compress_buffered_transactions;
+        let transaction_reorder_buffer_size = self.transaction_reorder_buffer_size;
+        let pb_channel_flush_interval = self
+            .pb_channel_flush_interval_ms
+            .map(std::time::Duration::from_millis);
 
         // Create a transaction fetcher thread that will continuously fetch transactions from the GRPC stream
         // and write into a channel
@@ -285,6 +660,7 @@ impl Worker {
         let transaction_filter = self.transaction_filter.clone();
         let grpc_response_item_timeout =
             std::time::Duration::from_secs(self.grpc_response_item_timeout_in_secs);
+        let json_transactions_file = self.json_transactions_file.clone();
         let fetcher_task = tokio::spawn(async move {
             info!(
                 processor_name = processor_name,
@@ -294,19 +670,38 @@ impl Worker {
                 "[Parser] Starting fetcher thread"
             );
 
+            if let Some(json_transactions_file) = json_transactions_file {
+                return crate::json_stream::create_json_file_fetcher_loop(
+                    tx,
+                    &json_transactions_file,
+                    chain_id,
+                    starting_version,
+                    request_ending_version,
+                    processor_name.to_string(),
+                    transaction_filter,
+                    pb_channel_txn_chunk_size,
+                )
+                .await;
+            }
+
             crate::grpc_stream::create_fetcher_loop(
                 tx.clone(),
-                indexer_grpc_data_service_address.clone(),
+                indexer_grpc_data_service_addresses,
                 indexer_grpc_http2_ping_interval,
                 indexer_grpc_http2_ping_timeout,
                 indexer_grpc_reconnection_timeout_secs,
                 grpc_response_item_timeout,
+                max_decoding_message_size,
                 starting_version,
+                previous_timestamp,
                 request_ending_version,
                 auth_token.clone(),
                 processor_name.to_string(),
                 transaction_filter,
                 pb_channel_txn_chunk_size,
+                compress_buffered_transactions,
+                transaction_reorder_buffer_size,
+                pb_channel_flush_interval,
             )
             .await
         });
@@ -329,7 +724,9 @@ impl Worker {
             self.per_table_chunk_sizes.clone(),
             self.deprecated_tables,
             self.db_pool.clone(),
+            &self.postgres_connection_string,
             maybe_gap_detector_sender,
+            self.collection_volume.clone(),
         );
 
         let gap_detector = if is_parquet_processor {
@@ -340,6 +737,48 @@ impl Worker {
             GapDetector::DefaultGapDetector(DefaultGapDetector::new(starting_version))
         };
         let gap_detector_clone = gap_detector.clone();
+        let processor_status_heartbeat_secs = self.processor_status_heartbeat_secs;
+
+        let progress_tracker = self.progress_file.clone().map(|progress_file| {
+            let progress_tracker = ProgressTracker::default();
+            tokio::spawn(create_progress_file_loop(
+                processor_name.to_string(),
+                progress_file,
+                self.progress_file_interval_secs,
+                progress_tracker.clone(),
+            ));
+            progress_tracker
+        });
+
+        if let Some(pruning_config) = self.prune_token_activities.clone() {
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                "[Parser] Starting token_activities pruning"
+            );
+            tokio::spawn(create_token_activities_pruning_loop(
+                self.db_pool.clone(),
+                self.processor_config.processor_status_name(),
+                pruning_config,
+            ));
+        }
+
+        if let Some(audit_config) = self.audit_current_token_ownerships.clone() {
+            if let Some(interval_secs) = audit_config.interval_secs {
+                info!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    "[Parser] Starting current_token_ownerships_v2 audit"
+                );
+                tokio::spawn(create_ownership_audit_loop(
+                    self.db_pool.clone(),
+                    self.processor_config.processor_status_name(),
+                    audit_config.sample_size,
+                    self.audit_starting_version(),
+                    interval_secs,
+                ));
+            }
+        }
 
         tokio::spawn(async move {
             create_gap_detector_status_tracker_loop(
@@ -347,6 +786,9 @@ impl Worker {
                 gap_detector_receiver,
                 processor,
                 gap_detection_batch_size,
+                chain_id,
+                processor_status_heartbeat_secs,
+                progress_tracker,
             )
             .await;
         });
@@ -366,6 +808,12 @@ impl Worker {
             "[Parser] Spawning concurrent parallel processor tasks",
         );
 
+        // Only the main processor moves the checkpoint, so what additional parquet processors report
+        // about their uploads is dropped
+        let (additional_gap_detector_sender, additional_gap_detector_receiver) =
+            kanal::bounded_async::<ProcessingResult>(BUFFER_SIZE);
+        tokio::spawn(async move { while additional_gap_detector_receiver.recv().await.is_ok() {} });
+
         let mut processor_tasks = vec![fetcher_task];
         for task_index in 0..concurrent_tasks {
             let join_handle: JoinHandle<()> = self
@@ -374,6 +822,7 @@ impl Worker {
                     receiver.clone(),
                     gap_detector_sender.clone(),
                     gap_detector.clone(),
+                    additional_gap_detector_sender.clone(),
                 )
                 .await;
             processor_tasks.push(join_handle);
@@ -399,6 +848,7 @@ impl Worker {
         receiver: kanal::AsyncReceiver<TransactionsPBResponse>,
         gap_detector_sender: AsyncSender<ProcessingResult>,
         mut gap_detector: GapDetector,
+        additional_gap_detector_sender: AsyncSender<ProcessingResult>,
     ) -> JoinHandle<()> {
         let processor_name = self.processor_config.name();
         let stream_address = self.indexer_grpc_data_service_address.to_string();
@@ -412,7 +862,9 @@ impl Worker {
                 self.per_table_chunk_sizes.clone(),
                 self.deprecated_tables,
                 self.db_pool.clone(),
+                &self.postgres_connection_string,
                 Some(gap_detector_sender.clone()),
+                self.collection_volume.clone(),
             )
         } else {
             build_processor(
@@ -420,11 +872,37 @@ impl Worker {
                 self.per_table_chunk_sizes.clone(),
                 self.deprecated_tables,
                 self.db_pool.clone(),
+                &self.postgres_connection_string,
                 None,
+                self.collection_volume.clone(),
             )
         };
 
+        let additional_processors: Vec<(Processor, &'static str, OnProcessorFailure)> = self
+            .additional_processors
+            .iter()
+            .map(|additional_processor| {
+                let config = &additional_processor.processor_config;
+                let processor = build_processor(
+                    config,
+                    self.per_table_chunk_sizes.clone(),
+                    self.deprecated_tables,
+                    self.db_pool.clone(),
+                    &self.postgres_connection_string,
+                    config
+                        .is_parquet_processor()
+                        .then(|| additional_gap_detector_sender.clone()),
+                    None,
+                );
+                (processor, config.name(), additional_processor.on_failure)
+            })
+            .collect();
+
         let concurrent_tasks = self.number_concurrent_processing_tasks;
+        let min_version = self.min_version;
+        let db_connection_retries = self.db_connection_retries;
+        let circuit_breaker = self.circuit_breaker.clone();
+        let failed_transactions = self.failed_transactions.clone();
 
         let chain_id = self
             .grpc_chain_id
@@ -437,17 +915,57 @@ impl Worker {
             let mut ma = MovingAverage::new(3000);
 
             loop {
+                // Finish the batch in flight and hold before taking the next one while paused
+                server_framework::wait_while_paused().await;
                 let txn_channel_fetch_latency = std::time::Instant::now();
+                // Exported when otel_traces is configured, with one child span per step
+                let batch_span = info_span!(
+                    "batch",
+                    processor_name,
+                    task_index,
+                    start_version = field::Empty,
+                    end_version = field::Empty,
+                    num_transactions = field::Empty,
+                );
+                let fetch_span = info_span!(
+                    parent: &batch_span,
+                    "fetch",
+                    start_version = field::Empty,
+                    end_version = field::Empty,
+                    num_transactions = field::Empty,
+                );
                 match fetch_transactions(
                     processor_name,
                     &stream_address,
                     receiver_clone.clone(),
                     task_index,
                 )
+                .instrument(fetch_span.clone())
                 .await
                 {
                     // Fetched transactions from channel
-                    Ok(transactions_pb) => {
+                    Ok(mut transactions_pb) => {
+                        if transactions_pb.compressed_transactions.is_some() {
+                            let decompress_time = std::time::Instant::now();
+                            if let Err(e) = transactions_pb.decompress() {
+                                error!(
+                                    processor_name = processor_name,
+                                    service_type = PROCESSOR_SERVICE_TYPE,
+                                    start_version = transactions_pb.start_version,
+                                    end_version = transactions_pb.end_version,
+                                    error = ?e,
+                                    "[Parser][T#{}] Error decompressing transactions batch",
+                                    task_index
+                                );
+                                panic!(
+                                    "[Parser][T#{}] Error decompressing transactions batch",
+                                    task_index
+                                );
+                            }
+                            BUFFERED_TRANSACTIONS_COMPRESSION_TIME_IN_SECS
+                                .with_label_values(&[processor_name, "decompress"])
+                                .observe(decompress_time.elapsed().as_secs_f64());
+                        }
                         let size_in_bytes = transactions_pb.size_in_bytes as f64;
                         let first_txn_version = transactions_pb
                             .transactions
@@ -461,6 +979,13 @@ impl Worker {
                             .map(|t| t.version)
                             .unwrap_or_default();
                         let batch_last_txn_version = transactions_pb.end_version;
+                        let num_transactions = transactions_pb.transactions.len();
+                        for span in [&batch_span, &fetch_span] {
+                            span.record("start_version", batch_first_txn_version);
+                            span.record("end_version", batch_last_txn_version);
+                            span.record("num_transactions", num_transactions);
+                        }
+                        drop(fetch_span);
                         let start_txn_timestamp = transactions_pb.start_txn_timestamp.clone();
                         let end_txn_timestamp = transactions_pb.end_txn_timestamp.clone();
 
@@ -514,18 +1039,92 @@ impl Worker {
                             );
                         }
 
+                        SkippedTransactions::check_batch(
+                            &mut transactions_pb.transactions,
+                            min_version,
+                        )
+                        .report(
+                            processor_name,
+                            batch_first_txn_version,
+                            batch_last_txn_version,
+                        );
+
                         let processing_time = std::time::Instant::now();
 
-                        let res = do_processor(
-                            transactions_pb,
-                            &processor,
-                            chain_id,
-                            processor_name,
-                            &auth_token,
-                            false, // enable_verbose_logging
+                        // Moved out once and shared with the additional processors and any
+                        // retries, transactions_pb keeps the batch's metadata
+                        let transactions =
+                            Arc::new(std::mem::take(&mut transactions_pb.transactions));
+                        let (res, additional_results) = futures::future::join(
+                            process_with_circuit_breaker(
+                                transactions.clone(),
+                                &transactions_pb,
+                                &processor,
+                                chain_id,
+                                processor_name,
+                                &auth_token,
+                                db_connection_retries,
+                                circuit_breaker.as_ref(),
+                                failed_transactions.as_ref(),
+                            )
+                            .instrument(info_span!(
+                                parent: &batch_span,
+                                "process",
+                                start_version = batch_first_txn_version,
+                                end_version = batch_last_txn_version,
+                                num_transactions,
+                            )),
+                            futures::future::join_all(additional_processors.iter().map(
+                                |(additional_processor, additional_name, _)| {
+                                    do_processor(
+                                        transactions.clone(),
+                                        &transactions_pb,
+                                        additional_processor,
+                                        chain_id,
+                                        additional_name,
+                                        &auth_token,
+                                        false, // enable_verbose_logging
+                                        db_connection_retries,
+                                    )
+                                },
+                            )),
                         )
                         .await;
 
+                        // Before the main processor's result reaches the gap detector, so a failed
+                        // batch never moves the checkpoint
+                        for (&(_, additional_name, on_failure), result) in
+                            additional_processors.iter().zip(additional_results)
+                        {
+                            match result {
+                                Ok(_) => {
+                                    PROCESSOR_SUCCESSES_COUNT
+                                        .with_label_values(&[additional_name])
+                                        .inc();
+                                },
+                                Err(e) => {
+                                    error!(
+                                        processor_name = additional_name,
+                                        stream_address = stream_address.as_str(),
+                                        error = ?e,
+                                        task_index,
+                                        on_failure = ?on_failure,
+                                        "[Parser][T#{}] Error processing transactions in additional processor",
+                                        task_index
+                                    );
+                                    PROCESSOR_ERRORS_COUNT
+                                        .with_label_values(&[additional_name])
+                                        .inc();
+                                    if on_failure == OnProcessorFailure::Fail {
+                                        panic!(
+                                            "[Parser][T#{}] Error processing '{:}' transactions: {:?}",
+                                            task_index, additional_name, e
+                                        );
+                                    }
+                                },
+                            }
+                        }
+
                         let processing_result = match res {
                             Ok(versions) => {
                                 PROCESSOR_SUCCESSES_COUNT
@@ -592,6 +1191,11 @@ impl Worker {
                                     .observe(time_diff_since_pb_timestamp_in_secs(
                                         end_txn_timestamp.as_ref().unwrap(),
                                     ));
+                                PROCESSING_LATENCY_IN_SECS
+                                    .with_label_values(&[processor_name])
+                                    .set(time_diff_since_pb_timestamp_in_secs(
+                                        end_txn_timestamp.as_ref().unwrap(),
+                                    ));
                                 LATEST_PROCESSED_VERSION
                                     .with_label_values(&[
                                         processor_name,
@@ -705,7 +1309,7 @@ impl Worker {
         info!("Running migrations: {:?}", self.postgres_connection_string);
         let mut conn =
             PgConnection::establish(&self.postgres_connection_string).expect("migrations failed!");
-        run_pending_migrations(&mut conn);
+        run_pending_migrations(&mut conn, self.postgres_schema.is_some());
     }
 
     // If the libpq feature isn't enabled, we use diesel async instead. This is used by
@@ -715,6 +1319,7 @@ impl Worker {
         use diesel_async::async_connection_wrapper::AsyncConnectionWrapper;
 
         info!("Running migrations: {:?}", self.postgres_connection_string);
+        let per_chain_schema = self.postgres_schema.is_some();
         let conn = self
             .db_pool
             // We need to use this since AsyncConnectionWrapper doesn't know how to
@@ -728,32 +1333,86 @@ impl Worker {
             // https://docs.rs/diesel-async/latest/diesel_async/async_connection_wrapper/type.AsyncConnectionWrapper.html
             let mut conn: AsyncConnectionWrapper<diesel_async::AsyncPgConnection> =
                 AsyncConnectionWrapper::from(conn);
-            run_pending_migrations(&mut conn);
+            run_pending_migrations(&mut conn, per_chain_schema);
         })
         .await
         .expect("[Parser] Failed to run migrations");
     }
 
+    /// Fail fast if a table has drifted from the struct we insert into it, rather than erroring
+    /// on the first insert deep into a run.
+    async fn check_schema(&self) -> Result<()> {
+        let mut conn = get_pool_connection(&self.db_pool).await?;
+        TokenActivity::check_schema(&mut conn).await
+    }
+
+    /// First version the audit of current_token_ownerships_v2 assumes was indexed. The checkpoint
+    /// only says where the processor resumes, so this is the configured start.
+    fn audit_starting_version(&self) -> u64 {
+        self.starting_version
+            .unwrap_or(0)
+            .max(self.min_version.unwrap_or(0))
+    }
+
     /// Gets the start version for the processor. If not found, start from 0.
     pub async fn get_start_version(&self) -> Result<Option<u64>> {
-        let mut conn = self.db_pool.get().await?;
+        let mut conn = get_pool_connection(&self.db_pool).await?;
 
-        match ProcessorStatusQuery::get_by_processor(self.processor_config.name(), &mut conn)
-            .await?
+        match ProcessorStatusQuery::get_by_processor(
+            &self.processor_config.processor_status_name(),
+            &mut conn,
+        )
+        .await?
         {
             Some(status) => Ok(Some(status.last_success_version as u64 + 1)),
             None => Ok(None),
         }
     }
 
-    /// Verify the chain id from GRPC against the database.
+    /// Timestamp of the last processed transaction, the one right before `get_start_version`
+    pub async fn get_checkpoint_timestamp(&self) -> Result<Option<chrono::NaiveDateTime>> {
+        let mut conn = get_pool_connection(&self.db_pool).await?;
+
+        Ok(ProcessorStatusQuery::get_by_processor(
+            &self.processor_config.processor_status_name(),
+            &mut conn,
+        )
+        .await?
+        .and_then(|status| status.last_transaction_timestamp))
+    }
+
+    /// Verify the chain id from GRPC against the config and the database.
     pub async fn check_or_update_chain_id(&self, grpc_chain_id: i64) -> Result<u64> {
         let processor_name = self.processor_config.name();
         info!(
             processor_name = processor_name,
             "[Parser] Checking if chain id is correct"
         );
-        let mut conn = self.db_pool.get().await?;
+        if let Some(expected_chain_id) = self.expected_chain_id {
+            anyhow::ensure!(
+                expected_chain_id as i64 == grpc_chain_id,
+                "[Parser] Wrong chain detected! Stream is serving chain {} but the processor is configured for chain {}",
+                grpc_chain_id,
+                expected_chain_id
+            );
+        }
+        let mut conn = get_pool_connection(&self.db_pool).await?;
+
+        // Catches a processor being pointed at another chain even if ledger_infos was reset
+        if let Some(status_chain_id) = ProcessorStatusQuery::get_by_processor(
+            &self.processor_config.processor_status_name(),
+            &mut conn,
+        )
+        .await?
+        .and_then(|status| status.chain_id)
+        {
+            anyhow::ensure!(
+                status_chain_id == grpc_chain_id,
+                "[Parser] Wrong chain detected! Trying to index chain {} now but processor status is for chain {}",
+                grpc_chain_id,
+                status_chain_id
+            );
+        }
 
         let maybe_existing_chain_id = LedgerInfo::get(&mut conn).await?.map(|li| li.chain_id);
 
@@ -821,32 +1480,228 @@ async fn fetch_transactions(
     }
 }
 
+/// Same as `do_processor`, but with a circuit breaker configured a failing batch is retried until
+/// it has failed `max_consecutive_failures` times in a row, then halted on or skipped around. With
+/// failed_transactions configured, a batch that stops the processor first has its offending
+/// transaction recorded.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_with_circuit_breaker(
+    transactions: Arc<Vec<Transaction>>,
+    transactions_pb: &TransactionsPBResponse,
+    processor: &Processor,
+    db_chain_id: u64,
+    processor_name: &str,
+    auth_token: &str,
+    db_connection_retries: u32,
+    circuit_breaker: Option<&CircuitBreakerConfig>,
+    failed_transactions: Option<&FailedTransactionsConfig>,
+) -> Result<ProcessingResult> {
+    if circuit_breaker.is_none() && failed_transactions.is_none() {
+        return do_processor(
+            transactions,
+            transactions_pb,
+            processor,
+            db_chain_id,
+            processor_name,
+            auth_token,
+            false, // enable_verbose_logging
+            db_connection_retries,
+        )
+        .await;
+    }
+
+    let start_version = transactions_pb.start_version;
+    let end_version = transactions_pb.end_version;
+    let max_consecutive_failures = circuit_breaker.map_or(1, |circuit_breaker| {
+        circuit_breaker.max_consecutive_failures
+    });
+    let mut consecutive_failures = 0;
+    let error = loop {
+        match do_processor(
+            transactions.clone(),
+            transactions_pb,
+            processor,
+            db_chain_id,
+            processor_name,
+            auth_token,
+            false, // enable_verbose_logging
+            db_connection_retries,
+        )
+        .await
+        {
+            Ok(processing_result) => return Ok(processing_result),
+            Err(e) => {
+                consecutive_failures += 1;
+                if consecutive_failures >= max_consecutive_failures {
+                    break e;
+                }
+                warn!(
+                    processor_name = processor_name,
+                    start_version,
+                    end_version,
+                    consecutive_failures,
+                    error = ?e,
+                    "[Parser] Error processing transactions, retrying the batch"
+                );
+                tokio::time::sleep(std::time::Duration::from_millis(
+                    circuit_breaker.map_or(0, |circuit_breaker| circuit_breaker.retry_delay_ms),
+                ))
+                .await;
+            },
+        }
+    };
+
+    let error = match circuit_breaker {
+        Some(circuit_breaker) => {
+            error!(
+                processor_name = processor_name,
+                start_version,
+                end_version,
+                consecutive_failures,
+                on_trip = ?circuit_breaker.on_trip,
+                error = ?error,
+                "[Parser] Circuit breaker tripped"
+            );
+            if circuit_breaker.on_trip == OnCircuitBreakerTrip::Skip {
+                let processing_start = std::time::Instant::now();
+                record_failed_transactions(
+                    &transactions,
+                    processor,
+                    db_chain_id,
+                    processor_name,
+                    failed_transactions,
+                    true,
+                )
+                .await?;
+                return Ok(ProcessingResult::DefaultProcessingResult(
+                    DefaultProcessingResult {
+                        start_version,
+                        end_version,
+                        processing_duration_in_secs: processing_start.elapsed().as_secs_f64(),
+                        db_insertion_duration_in_secs: 0.0,
+                        last_transaction_timestamp: transactions_pb.end_txn_timestamp.clone(),
+                    },
+                ));
+            }
+            error.context(format!(
+                "Circuit breaker tripped: versions [{}, {}] failed {} times in a row",
+                start_version, end_version, consecutive_failures
+            ))
+        },
+        None => error,
+    };
+    if failed_transactions.is_some() {
+        // Best effort, the batch's own error is the one to stop with
+        if let Err(e) = record_failed_transactions(
+            &transactions,
+            processor,
+            db_chain_id,
+            processor_name,
+            failed_transactions,
+            false,
+        )
+        .await
+        {
+            error!(
+                processor_name = processor_name,
+                start_version,
+                end_version,
+                error = ?e,
+                "[Parser] Error recording the failed transaction"
+            );
+        }
+    }
+    Err(error)
+}
+
+/// Processes the transactions one at a time and records the ones that fail in
+/// failed_transactions. With `skip` the failing transactions are left out and the rest of the
+/// batch is processed. Otherwise it stops at the first failing one, so that nothing after it is
+/// written before the processor stops and the checkpoint can't move past it. Fails only if a
+/// failed transaction can't be recorded.
+async fn record_failed_transactions(
+    transactions: &[Transaction],
+    processor: &Processor,
+    db_chain_id: u64,
+    processor_name: &str,
+    failed_transactions: Option<&FailedTransactionsConfig>,
+    skip: bool,
+) -> Result<()> {
+    let store_raw_transactions =
+        failed_transactions.is_some_and(|config| config.store_raw_transactions);
+    let outcome = if skip { "skipped" } else { "halted" };
+    for transaction in transactions {
+        let version = transaction.version;
+        let raw_transaction = store_raw_transactions.then(|| transaction.encode_to_vec());
+        if let Err(e) = processor
+            .process_transactions(
+                Arc::new(vec![transaction.clone()]),
+                version,
+                version,
+                Some(db_chain_id),
+            )
+            .await
+        {
+            error!(
+                processor_name = processor_name,
+                transaction_version = version,
+                outcome,
+                error = ?e,
+                "[Parser] Recording failed transaction"
+            );
+            FailedTransaction {
+                processor: processor.processor_status_name(),
+                transaction_version: version as i64,
+                error: format!("{:?}", e),
+                raw_transaction,
+            }
+            .upsert(processor.get_pool())
+            .await
+            .context(format!(
+                "Failed to record transaction {} in failed_transactions",
+                version
+            ))?;
+            FAILED_TRANSACTIONS_COUNT
+                .with_label_values(&[processor_name, outcome])
+                .inc();
+            if !skip {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Processes `transactions`, the batch `transactions_pb` describes. They're passed separately so
+/// that the batch can be shared rather than copied between processors and retries.
 pub async fn do_processor(
-    transactions_pb: TransactionsPBResponse,
+    transactions: Arc<Vec<Transaction>>,
+    transactions_pb: &TransactionsPBResponse,
     processor: &Processor,
     db_chain_id: u64,
     processor_name: &str,
     auth_token: &str,
     enable_verbose_logging: bool,
+    db_connection_retries: u32,
 ) -> Result<ProcessingResult> {
     // We use the value passed from the `transactions_pb` as it may have been filtered
     let start_version = transactions_pb.start_version;
     let end_version = transactions_pb.end_version;
 
     // Fake this as it's possible we have filtered out all of the txns in this batch
-    if transactions_pb.transactions.is_empty() {
+    if transactions.is_empty() {
         return Ok(ProcessingResult::DefaultProcessingResult(
             DefaultProcessingResult {
                 start_version,
                 end_version,
                 processing_duration_in_secs: 0.0,
                 db_insertion_duration_in_secs: 0.0,
-                last_transaction_timestamp: transactions_pb.end_txn_timestamp,
+                last_transaction_timestamp: transactions_pb.end_txn_timestamp.clone(),
             },
         ));
     }
 
-    let txn_time = transactions_pb.start_txn_timestamp;
+    let txn_time = transactions_pb.start_txn_timestamp.clone();
 
     if let Some(ref t) = txn_time {
         PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS
@@ -868,14 +1723,40 @@ pub async fn do_processor(
         );
     }
 
-    let processed_result = processor
-        .process_transactions(
-            transactions_pb.transactions,
-            start_version,
-            end_version,
-            Some(db_chain_id),
-        )
-        .await;
+    // Writes are idempotent, so a batch that lost its connection part way is run again as a whole.
+    // Only a batch that committed returns Ok, which is what moves the checkpoint forward.
+    let mut retries = 0;
+    let processed_result = loop {
+        match processor
+            .process_transactions(
+                transactions.clone(),
+                start_version,
+                end_version,
+                Some(db_chain_id),
+            )
+            .await
+        {
+            Err(e) if retries < db_connection_retries && is_connection_error(&e) => {
+                retries += 1;
+                DB_CONNECTION_RETRY_COUNT
+                    .with_label_values(&[processor_name])
+                    .inc();
+                let delay =
+                    std::time::Duration::from_millis(DB_CONNECTION_RETRY_DELAY_MS << (retries - 1));
+                warn!(
+                    processor_name = processor_name,
+                    start_version,
+                    end_version,
+                    retries,
+                    error = ?e,
+                    "[Parser] Lost the db connection, retrying the batch in {:?}",
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            },
+            res => break res,
+        }
+    };
 
     if let Some(ref t) = txn_time {
         PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS
@@ -896,7 +1777,9 @@ pub fn build_processor(
     per_table_chunk_sizes: AHashMap<String, usize>,
     deprecated_tables: TableFlags,
     db_pool: ArcDbPool,
+    postgres_connection_string: &str,
     gap_detector_sender: Option<AsyncSender<ProcessingResult>>, // Parquet only
+    collection_volume: Option<Arc<Mutex<CollectionVolumeTracker>>>, // TokenV2 only
 ) -> Processor {
     match config {
         ProcessorConfig::AccountTransactionsProcessor => Processor::from(
@@ -913,14 +1796,22 @@ pub fn build_processor(
             per_table_chunk_sizes,
             deprecated_tables,
         )),
-        ProcessorConfig::EventsProcessor => {
-            Processor::from(EventsProcessor::new(db_pool, per_table_chunk_sizes))
+        ProcessorConfig::EventIndexBackfillProcessor => {
+            Processor::from(EventIndexBackfillProcessor::new(db_pool))
         },
-        ProcessorConfig::FungibleAssetProcessor => Processor::from(FungibleAssetProcessor::new(
+        ProcessorConfig::EventsProcessor(config) => Processor::from(EventsProcessor::new(
             db_pool,
+            config.clone(),
             per_table_chunk_sizes,
-            deprecated_tables,
         )),
+        ProcessorConfig::FungibleAssetProcessor(config) => {
+            Processor::from(FungibleAssetProcessor::new(
+                db_pool,
+                config.clone(),
+                per_table_chunk_sizes,
+                deprecated_tables,
+            ))
+        },
         ProcessorConfig::MonitoringProcessor => Processor::from(MonitoringProcessor::new(db_pool)),
         ProcessorConfig::NftMetadataProcessor(config) => {
             Processor::from(NftMetadataProcessor::new(db_pool, config.clone()))
@@ -936,11 +1827,16 @@ pub fn build_processor(
             config.clone(),
             per_table_chunk_sizes,
         )),
+        ProcessorConfig::TokenActivitiesReprocessor(config) => Processor::from(
+            TokenActivitiesReprocessor::new(db_pool, config.clone(), per_table_chunk_sizes),
+        ),
         ProcessorConfig::TokenV2Processor(config) => Processor::from(TokenV2Processor::new(
             db_pool,
+            postgres_connection_string.to_string(),
             config.clone(),
             per_table_chunk_sizes,
             deprecated_tables,
+            collection_volume,
         )),
         ProcessorConfig::TransactionMetadataProcessor => Processor::from(
             TransactionMetadataProcessor::new(db_pool, per_table_chunk_sizes),
@@ -0,0 +1,211 @@
+#!/usr/bin/env python3
+# Copyright © Aptos Foundation
+# SPDX-License-Identifier: Apache-2.0
+
+"""Writes transactions.json.gz, the input of tests/token_v2_replay.rs.
+
+The transactions are built by hand in the REST API json shape the json file source reads, one per
+line, so the fixture needs no data service. They cover v1 token mints, transfers and burns, coin
+transfers with no token events, and failed transactions, whose events must not be written.
+
+The output is deterministic. After changing this script, rerun it and then the test with
+INDEXER_REPLAY_UPDATE_EXPECTED=1 to refresh expected.json, and commit all three files.
+"""
+
+import gzip
+import json
+import os
+import random
+
+FIRST_VERSION = 5_000_000
+NUM_TRANSACTIONS = 300
+FIRST_TIMESTAMP_MICROS = 1_700_000_000_000_000
+
+CREATORS = ["0xc1", "0xc2", "0xc3"]
+COLLECTIONS = ["Aptos Monkeys", "Pixel Pets", "Zero Art"]
+COLLECTORS = ["0xa1", "0xa2", "0xa3", "0xa4", "0xa5", "0xa6"]
+
+OUTPUT = os.path.join(os.path.dirname(os.path.abspath(__file__)), "transactions.json.gz")
+
+
+def token_data_id(token):
+    creator, collection, name = token
+    return {"creator": creator, "collection": collection, "name": name}
+
+
+def token_id(token):
+    return {"token_data_id": token_data_id(token), "property_version": "0"}
+
+
+class Generator:
+    def __init__(self):
+        self.rng = random.Random(146)
+        self.sequence_numbers = {}
+        self.event_sequence_numbers = {}
+        # Amount of each token each account holds
+        self.balances = {}
+        self.minted = []
+
+    def event(self, account, creation_number, type_str, data):
+        key = (account, creation_number)
+        sequence_number = self.event_sequence_numbers.get(key, 0)
+        self.event_sequence_numbers[key] = sequence_number + 1
+        return {
+            "guid": {"creation_number": str(creation_number), "account_address": account},
+            "sequence_number": str(sequence_number),
+            "type": type_str,
+            "data": data,
+        }
+
+    def mint_event(self, token, amount):
+        return self.event(
+            token[0],
+            5,
+            "0x3::token::MintTokenEvent",
+            {"id": token_data_id(token), "amount": str(amount)},
+        )
+
+    def withdraw_event(self, account, token, amount):
+        return self.event(
+            account,
+            2,
+            "0x3::token::WithdrawEvent",
+            {"id": token_id(token), "amount": str(amount)},
+        )
+
+    def deposit_event(self, account, token, amount):
+        return self.event(
+            account,
+            3,
+            "0x3::token::DepositEvent",
+            {"id": token_id(token), "amount": str(amount)},
+        )
+
+    def burn_event(self, account, token, amount):
+        return self.event(
+            account,
+            7,
+            "0x3::token::BurnTokenEvent",
+            {"id": token_id(token), "amount": str(amount)},
+        )
+
+    def move_tokens(self, token, from_account, to_account, amount):
+        if from_account is not None:
+            self.balances[(from_account, token)] -= amount
+        if to_account is not None:
+            key = (to_account, token)
+            self.balances[key] = self.balances.get(key, 0) + amount
+
+    def mint(self):
+        creator = self.rng.choice(CREATORS)
+        collection = self.rng.choice(COLLECTIONS)
+        token = (creator, collection, "%s #%d" % (collection, len(self.minted) + 1))
+        amount = self.rng.choice([1, 1, 1, 5])
+        self.minted.append(token)
+        self.move_tokens(token, None, creator, amount)
+        events = [
+            self.mint_event(token, amount),
+            self.deposit_event(creator, token, amount),
+        ]
+        return creator, "0x3::token::create_token_script", [creator, collection], events
+
+    def transfer(self):
+        holdings = sorted(key for key, amount in self.balances.items() if amount > 0)
+        if not holdings:
+            return self.mint()
+        sender, token = self.rng.choice(holdings)
+        receiver = self.rng.choice([c for c in COLLECTORS + CREATORS if c != sender])
+        amount = self.rng.randint(1, self.balances[(sender, token)])
+        self.move_tokens(token, sender, receiver, amount)
+        events = [
+            self.withdraw_event(sender, token, amount),
+            self.deposit_event(receiver, token, amount),
+        ]
+        return sender, "0x3::token::direct_transfer_script", [receiver, token[2]], events
+
+    def burn(self):
+        holdings = sorted(key for key, amount in self.balances.items() if amount > 0)
+        if not holdings:
+            return self.mint()
+        owner, token = self.rng.choice(holdings)
+        amount = self.balances[(owner, token)]
+        self.move_tokens(token, owner, None, amount)
+        return owner, "0x3::token::burn", [token[2]], [self.burn_event(owner, token, amount)]
+
+    def coin_transfer(self):
+        sender, receiver = self.rng.sample(COLLECTORS, 2)
+        amount = str(self.rng.randint(1, 10_000))
+        events = [
+            self.event(sender, 3, "0x1::coin::WithdrawEvent", {"amount": amount}),
+            self.event(receiver, 2, "0x1::coin::DepositEvent", {"amount": amount}),
+        ]
+        return sender, "0x1::coin::transfer", [receiver, amount], events
+
+    def transaction(self, index):
+        version = FIRST_VERSION + index
+        roll = self.rng.random()
+        success = True
+        if index % 25 == 24:
+            # A failed transfer: its events are in the transaction but nothing moved
+            holdings = sorted(key for key, amount in self.balances.items() if amount > 0)
+            sender, token = self.rng.choice(holdings)
+            receiver = self.rng.choice(COLLECTORS)
+            function, arguments = "0x3::token::direct_transfer_script", [receiver, token[2]]
+            events = [
+                self.withdraw_event(sender, token, 1),
+                self.deposit_event(receiver, token, 1),
+            ]
+            success = False
+        elif roll < 0.35:
+            sender, function, arguments, events = self.mint()
+        elif roll < 0.75:
+            sender, function, arguments, events = self.transfer()
+        elif roll < 0.85:
+            sender, function, arguments, events = self.burn()
+        else:
+            sender, function, arguments, events = self.coin_transfer()
+
+        sequence_number = self.sequence_numbers.get(sender, 0)
+        self.sequence_numbers[sender] = sequence_number + 1
+        timestamp = FIRST_TIMESTAMP_MICROS + index * 250_000
+        return {
+            "type": "user_transaction",
+            "version": str(version),
+            "hash": "0x%064x" % version,
+            "state_change_hash": "0x%064x" % (version + 1),
+            "event_root_hash": "0x%064x" % (version + 2),
+            "state_checkpoint_hash": None,
+            "gas_used": str(self.rng.randint(5, 1_000)),
+            "success": success,
+            "vm_status": "Executed successfully" if success else "Move abort",
+            "accumulator_root_hash": "0x%064x" % (version + 3),
+            "timestamp": str(timestamp),
+            "sender": sender,
+            "sequence_number": str(sequence_number),
+            "max_gas_amount": "200000",
+            "gas_unit_price": "100",
+            "expiration_timestamp_secs": str(timestamp // 1_000_000 + 600),
+            "payload": {
+                "type": "entry_function_payload",
+                "function": function,
+                "type_arguments": [],
+                "arguments": arguments,
+            },
+            "events": events,
+            "changes": [],
+        }
+
+
+def main():
+    generator = Generator()
+    lines = [
+        json.dumps(generator.transaction(index), separators=(",", ":"))
+        for index in range(NUM_TRANSACTIONS)
+    ]
+    with open(OUTPUT, "wb") as file:
+        with gzip.GzipFile(filename="", fileobj=file, mode="wb", mtime=0) as gz:
+            gz.write(("\n".join(lines) + "\n").encode())
+
+
+if __name__ == "__main__":
+    main()
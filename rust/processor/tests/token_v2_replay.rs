@@ -0,0 +1,137 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replays a fixed set of transactions through the full worker, token_v2_processor writing into a
+//! scratch database, and checks the token_activities_v2 rows it ends up with. This is the end to
+//! end regression guard, so a change in the count or checksum means the processor output changed
+//! and needs explaining.
+//!
+//! Needs a postgres server, the test creates and drops its own database on it:
+//!
+//! ```sh
+//! INDEXER_REPLAY_POSTGRES_URL=postgresql://postgres@localhost:5432/postgres \
+//!     cargo test -p processor --test token_v2_replay -- --ignored
+//! ```
+//!
+//! The fixture is REST API json, one transaction per line, read through the json file source, so
+//! nothing is fetched from a data service. `generate_transactions.py` next to it builds the
+//! transactions by hand: v1 token mints, transfers and burns, coin transfers and failed
+//! transactions. After changing the generator, or after an intended change to the output, rerun
+//! with `INDEXER_REPLAY_UPDATE_EXPECTED=1` to rewrite `expected.json` and commit the files.
+
+use diesel::{
+    pg::PgConnection,
+    sql_query,
+    sql_types::{BigInt, Text},
+    Connection, QueryableByName, RunQueryDsl,
+};
+use processor::IndexerGrpcProcessorConfig;
+use serde::{Deserialize, Serialize};
+use server_framework::RunnableConfig;
+use std::path::PathBuf;
+use url::Url;
+
+const FIXTURE_CHAIN_ID: u64 = 1;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Expected {
+    token_activities_count: i64,
+    token_activities_checksum: String,
+}
+
+#[derive(QueryableByName)]
+struct Summary {
+    #[diesel(sql_type = BigInt)]
+    token_activities_count: i64,
+    #[diesel(sql_type = Text)]
+    token_activities_checksum: String,
+}
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/token_v2_replay")
+}
+
+/// Count and md5 of the key columns of every token_activities_v2 row, in primary key order
+fn summarize(conn: &mut PgConnection) -> Expected {
+    let summary: Summary = sql_query(
+        "SELECT COUNT(*) AS token_activities_count,
+            md5(COALESCE(string_agg(
+                concat_ws('|', transaction_version, event_index, event_account_address,
+                    token_data_id, property_version_v1, type, from_address, to_address,
+                    token_amount, token_standard, is_fungible_v2),
+                E'\\n' ORDER BY transaction_version, event_index
+            ), '')) AS token_activities_checksum
+        FROM token_activities_v2",
+    )
+    .get_result(conn)
+    .expect("Failed to summarize token_activities_v2");
+    Expected {
+        token_activities_count: summary.token_activities_count,
+        token_activities_checksum: summary.token_activities_checksum,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs a postgres server in INDEXER_REPLAY_POSTGRES_URL"]
+async fn test_token_v2_replay() {
+    let server_url = std::env::var("INDEXER_REPLAY_POSTGRES_URL")
+        .expect("INDEXER_REPLAY_POSTGRES_URL must point at a postgres server");
+    let transactions_file = fixture_dir().join("transactions.json.gz");
+    assert!(
+        transactions_file.exists(),
+        "Missing {}, see the top of this file for how to generate it",
+        transactions_file.display()
+    );
+
+    let db_name = format!("token_v2_replay_{}", std::process::id());
+    let mut admin_conn =
+        PgConnection::establish(&server_url).expect("Failed to connect to postgres");
+    sql_query(format!("CREATE DATABASE {}", db_name))
+        .execute(&mut admin_conn)
+        .expect("Failed to create the replay database");
+    let mut db_url = Url::parse(&server_url).expect("Invalid INDEXER_REPLAY_POSTGRES_URL");
+    db_url.set_path(&db_name);
+
+    let config: IndexerGrpcProcessorConfig = serde_json::from_value(serde_json::json!({
+        "processor_config": { "type": "token_v2_processor" },
+        "postgres_connection_string": db_url.as_str(),
+        "indexer_grpc_data_service_address": "http://127.0.0.1:50051",
+        "auth_token": "",
+        "expected_chain_id": FIXTURE_CHAIN_ID,
+        "number_concurrent_processing_tasks": 1,
+        "pb_channel_txn_chunk_size": 100,
+        "json_transactions_file": transactions_file,
+    }))
+    .unwrap();
+    let result = config.run().await;
+
+    let actual = result.map(|_| {
+        let mut conn = PgConnection::establish(db_url.as_str())
+            .expect("Failed to connect to the replay database");
+        summarize(&mut conn)
+    });
+    sql_query(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", db_name))
+        .execute(&mut admin_conn)
+        .expect("Failed to drop the replay database");
+    let actual = actual.expect("Replay failed");
+
+    let expected_file = fixture_dir().join("expected.json");
+    if std::env::var("INDEXER_REPLAY_UPDATE_EXPECTED").is_ok() {
+        std::fs::write(
+            &expected_file,
+            serde_json::to_string_pretty(&actual).unwrap() + "\n",
+        )
+        .unwrap();
+        return;
+    }
+    let expected: Expected = serde_json::from_str(
+        &std::fs::read_to_string(&expected_file)
+            .unwrap_or_else(|_| panic!("Missing {}", expected_file.display())),
+    )
+    .unwrap();
+    assert!(
+        actual.token_activities_count > 0,
+        "Replay wrote no token activities"
+    );
+    assert_eq!(actual, expected);
+}
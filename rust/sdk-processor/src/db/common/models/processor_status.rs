@@ -15,6 +15,7 @@ pub struct ProcessorStatus {
     pub processor: String,
     pub last_success_version: i64,
     pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub chain_id: Option<i64>,
 }
 
 #[derive(AsChangeset, Debug, Queryable)]
@@ -25,6 +26,7 @@ pub struct ProcessorStatusQuery {
     pub last_success_version: i64,
     pub last_updated: chrono::NaiveDateTime,
     pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub chain_id: Option<i64>,
 }
 
 impl ProcessorStatusQuery {
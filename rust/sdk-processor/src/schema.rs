@@ -912,6 +912,7 @@ diesel::table! {
         last_success_version -> Int8,
         last_updated -> Timestamp,
         last_transaction_timestamp -> Nullable<Timestamp>,
+        chain_id -> Nullable<Int8>,
     }
 }
 
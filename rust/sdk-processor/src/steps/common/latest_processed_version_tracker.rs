@@ -68,6 +68,7 @@ where
                 processor: self.tracker_name.clone(),
                 last_success_version: last_success_batch.end_version as i64,
                 last_transaction_timestamp: end_timestamp,
+                chain_id: None,
             };
             execute_with_better_error(
                 self.conn_pool.clone(),
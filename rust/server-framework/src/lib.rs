@@ -12,9 +12,16 @@ use std::convert::Infallible;
 // TODO: remove deprecated lint when new clippy nightly is released
 #[allow(deprecated)]
 use std::{fs::File, io::Read, panic::PanicInfo, path::PathBuf, process};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
 use tokio::runtime::Handle;
 use tracing::error;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use warp::{http::Response, Filter};
 
 /// ServerArgs bootstraps a server with all common pieces. And then triggers the run method for
@@ -31,9 +38,9 @@ impl ServerArgs {
         C: RunnableConfig,
     {
         // Set up the server.
-        setup_logging();
-        setup_panic_handler();
         let config = load::<GenericConfig<C>>(&self.config_path)?;
+        setup_logging_with_otel_traces(config.otel_traces.as_ref(), config.get_server_name());
+        setup_panic_handler();
         run_server_with_config(config, handle).await
     }
 }
@@ -65,6 +72,9 @@ where
 pub struct GenericConfig<T> {
     // Shared configuration among all services.
     pub health_check_port: u16,
+    // Export tracing spans over OTLP. Off if not set.
+    #[serde(default)]
+    pub otel_traces: Option<OtelTracesConfig>,
 
     // Specific configuration for each service.
     pub server_config: T,
@@ -138,26 +148,127 @@ fn handle_panic(panic_info: &PanicInfo<'_>) {
     process::exit(12);
 }
 
+/// Where to send tracing spans, e.g. the processor's fetch, parse and write spans for each batch
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtelTracesConfig {
+    /// OTLP gRPC endpoint of the collector, e.g. `http://localhost:4317`
+    pub endpoint: String,
+    /// `service.name` of the exported spans. Defaults to the server name.
+    pub service_name: Option<String>,
+}
+
 /// Set up logging for the server.
 pub fn setup_logging() {
+    setup_logging_with_otel_traces(None, String::new());
+}
+
+/// Set up logging for the server, and if `otel_traces` is set also export spans to it. Spans go
+/// through the same env filter as logs. Must be called from within a tokio runtime.
+pub fn setup_logging_with_otel_traces(otel_traces: Option<&OtelTracesConfig>, server_name: String) {
     let env_filter = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new("info"))
         .unwrap();
-    tracing_subscriber::fmt()
+    let fmt_layer = tracing_subscriber::fmt::layer()
         .json()
         .with_file(true)
         .with_line_number(true)
         .with_thread_ids(true)
         .with_target(false)
-        .with_thread_names(true)
-        .with_env_filter(env_filter)
+        .with_thread_names(true);
+    let otel_layer = otel_traces.map(|config| {
+        let service_name = config.service_name.clone().unwrap_or(server_name);
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(&config.endpoint),
+            )
+            .with_trace_config(opentelemetry_sdk::trace::config().with_resource(
+                opentelemetry_sdk::Resource::new(vec![opentelemetry::KeyValue::new(
+                    "service.name",
+                    service_name,
+                )]),
+            ))
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .expect("Failed to set up the OTLP trace exporter");
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    });
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(fmt_layer)
+        .with(otel_layer)
         .init();
 }
 
+/// Set through `POST /pause` and `POST /resume` on the health check port.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+pub fn set_paused(paused: bool) {
+    PAUSED.store(paused, Ordering::SeqCst);
+}
+
+/// Holds until the service is resumed. Services call this between units of work, e.g. before
+/// pulling the next batch, so that pausing lets in-flight work finish first.
+pub async fn wait_while_paused() {
+    while is_paused() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+type StatsFn = Box<dyn Fn() -> String + Send + Sync>;
+
+/// Served at `GET /stats/<name>`, see `register_stats_endpoint`
+static STATS_ENDPOINTS: RwLock<Vec<(String, StatsFn)>> = RwLock::new(Vec::new());
+
+/// Serves the JSON returned by `stats_fn` at `GET /stats/<name>` on the health check port. The
+/// function is called on every request, so it should only read state the service already keeps.
+pub fn register_stats_endpoint(name: &str, stats_fn: impl Fn() -> String + Send + Sync + 'static) {
+    STATS_ENDPOINTS
+        .write()
+        .unwrap()
+        .push((name.to_string(), Box::new(stats_fn)));
+}
+
+fn get_stats(name: &str) -> Option<String> {
+    STATS_ENDPOINTS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(endpoint_name, _)| endpoint_name == name)
+        .map(|(_, stats_fn)| stats_fn())
+}
+
 /// Register readiness and liveness probes and set up metrics endpoint.
 async fn register_probes_and_metrics_handler(port: u16) {
-    let readiness = warp::path("readiness")
-        .map(move || warp::reply::with_status("ready", warp::http::StatusCode::OK));
+    let readiness = warp::path("readiness").map(move || {
+        let status = if is_paused() { "paused" } else { "ready" };
+        warp::reply::with_status(status, warp::http::StatusCode::OK)
+    });
+    let pause = warp::post().and(warp::path("pause")).map(|| {
+        set_paused(true);
+        warp::reply::with_status("paused", warp::http::StatusCode::OK)
+    });
+    let resume = warp::post().and(warp::path("resume")).map(|| {
+        set_paused(false);
+        warp::reply::with_status("resumed", warp::http::StatusCode::OK)
+    });
+    let stats = warp::get()
+        .and(warp::path!("stats" / String))
+        .map(|name: String| match get_stats(&name) {
+            Some(body) => Response::builder()
+                .header("Content-Type", "application/json")
+                .status(warp::http::StatusCode::OK)
+                .body(body),
+            None => Response::builder()
+                .status(warp::http::StatusCode::NOT_FOUND)
+                .body(format!("No stats named {name}")),
+        });
     let metrics_endpoint = warp::path("metrics").map(|| {
         // Metrics encoding.
         let metrics = prometheus::gather();
@@ -201,13 +312,26 @@ async fn register_probes_and_metrics_handler(port: u16) {
             })
         });
         #[cfg(target_os = "linux")]
-        warp::serve(readiness.or(metrics_endpoint).or(profilez))
-            .run(([0, 0, 0, 0], port))
-            .await;
+        warp::serve(
+            readiness
+                .or(pause)
+                .or(resume)
+                .or(stats)
+                .or(metrics_endpoint)
+                .or(profilez),
+        )
+        .run(([0, 0, 0, 0], port))
+        .await;
     } else {
-        warp::serve(readiness.or(metrics_endpoint))
-            .run(([0, 0, 0, 0], port))
-            .await;
+        warp::serve(
+            readiness
+                .or(pause)
+                .or(resume)
+                .or(stats)
+                .or(metrics_endpoint),
+        )
+        .run(([0, 0, 0, 0], port))
+        .await;
     }
 }
 